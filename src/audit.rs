@@ -0,0 +1,325 @@
+use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
+use std::cell::Cell;
+use std::time::Duration;
+
+/// Tracing target of audit records. Events with this target are written, one
+/// JSON object per line, to the audit log.
+pub const AUDIT_TARGET: &str = "audit";
+
+tokio::task_local! {
+    /// Set when the judge was unavailable and the call was allowed anyway
+    static UNJUDGED: Cell<bool>;
+}
+
+/// Mark the tool call being handled as allowed without a judgment, so that
+/// its audit record says so. Does nothing outside [`track_unjudged`].
+pub fn mark_unjudged() {
+    let _ = UNJUDGED.try_with(|unjudged| unjudged.set(true));
+}
+
+/// Run a tool call, returning its result and whether it was marked with
+/// [`mark_unjudged`]
+pub async fn track_unjudged<F: Future>(call: F) -> (F::Output, bool) {
+    UNJUDGED
+        .scope(Cell::new(false), async {
+            let output = call.await;
+            (output, UNJUDGED.with(Cell::get))
+        })
+        .await
+}
+
+/// The outcome of a tool call, as recorded in the audit log
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AuditRecord<'a> {
+    tool: &'a str,
+    remote_host: Option<&'a str>,
+    /// The hosts of a call run on several, comma-separated
+    remote_hosts: Option<String>,
+    remote_user: Option<String>,
+    status_code: Option<i64>,
+    duration_ms: u64,
+    success: bool,
+    error: Option<String>,
+    judge_unavailable: bool,
+}
+
+/// Record the outcome of a tool call in the audit log
+pub fn record_tool_call(
+    tool: &str,
+    params: &serde_json::Value,
+    result: &Result<CallToolResult, CallToolError>,
+    duration: Duration,
+    judge_unavailable: bool,
+) {
+    let record = audit_record(tool, params, result, duration, judge_unavailable);
+    tracing::info!(
+        target: AUDIT_TARGET,
+        tool = record.tool,
+        remote_host = record.remote_host,
+        remote_hosts = record.remote_hosts.as_deref(),
+        remote_user = record.remote_user.as_deref(),
+        status_code = record.status_code,
+        duration_ms = record.duration_ms,
+        success = record.success,
+        error = record.error.as_deref(),
+        judge_unavailable = record.judge_unavailable.then_some(true),
+        "tool call"
+    );
+}
+
+fn audit_record<'a>(
+    tool: &'a str,
+    params: &'a serde_json::Value,
+    result: &Result<CallToolResult, CallToolError>,
+    duration: Duration,
+    judge_unavailable: bool,
+) -> AuditRecord<'a> {
+    let remote_host = params.get("remote_host").and_then(|v| v.as_str());
+    let hosts: Vec<&str> = crate::policy::target_hosts(params).collect();
+    let (status_code, success, error) = match result {
+        Ok(result) => {
+            let status_code = result
+                .structured_content
+                .as_ref()
+                .and_then(|content| content.get("status_code"))
+                .and_then(|v| v.as_i64());
+            (status_code, succeeded(result), None)
+        }
+        Err(err) => (None, false, Some(err.to_string())),
+    };
+
+    AuditRecord {
+        tool,
+        remote_host: remote_host.map(without_user),
+        remote_hosts: (remote_host.is_none() && !hosts.is_empty()).then(|| {
+            hosts
+                .iter()
+                .map(|host| without_user(host))
+                .collect::<Vec<&str>>()
+                .join(",")
+        }),
+        remote_user: common_remote_user(&hosts, params),
+        status_code,
+        duration_ms: duration.as_millis().try_into().unwrap_or(u64::MAX),
+        success,
+        error,
+        judge_unavailable,
+    }
+}
+
+/// Whether a tool call's result is a success: not an error result, and for
+/// commands, one that exited with status 0 without timing out. Results
+/// covering several commands, such as a sequence's, say whether they all
+/// succeeded in `succeeded`.
+pub fn succeeded(result: &CallToolResult) -> bool {
+    let content = result.structured_content.as_ref();
+    let field = |key: &str| content.and_then(|content| content.get(key));
+    if let Some(succeeded) = field("succeeded").and_then(|v| v.as_bool()) {
+        return result.is_error != Some(true) && succeeded;
+    }
+    let status_code = field("status_code").and_then(|v| v.as_i64());
+    let timed_out = field("timed_out").and_then(|v| v.as_bool()) == Some(true);
+    result.is_error != Some(true) && matches!(status_code, None | Some(0)) && !timed_out
+}
+
+/// `host` without the user in `user@host`
+fn without_user(host: &str) -> &str {
+    host.rsplit_once('@').map_or(host, |(_, host)| host)
+}
+
+/// The user a call logs in to each of `hosts` as, if it names the same one
+/// for all of them
+fn common_remote_user(hosts: &[&str], params: &serde_json::Value) -> Option<String> {
+    let mut users = hosts.iter().map(|host| remote_user(host, params));
+    let user = users.next()??;
+    users
+        .all(|other| other.as_ref() == Some(&user))
+        .then_some(user)
+}
+
+/// The user a call logs in as, if it names one: from `user@host`, a User
+/// option, or MCP_LINUX_SSH_DEFAULT_USER. Otherwise ssh picks the user.
+fn remote_user(remote_host: &str, params: &serde_json::Value) -> Option<String> {
+    if let Some((user, _)) = remote_host.rsplit_once('@') {
+        return Some(user.to_string());
+    }
+    let option_user = params
+        .get("options")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str())
+        .find_map(|option| {
+            let (key, value) = option.split_once('=')?;
+            key.trim()
+                .eq_ignore_ascii_case("user")
+                .then(|| value.trim().to_string())
+        });
+    if option_user.is_some() {
+        return option_user;
+    }
+    let use_ssh_config = params.get("use_ssh_config").and_then(|v| v.as_bool()) == Some(true);
+    let default_user = &crate::config::connection_defaults().user;
+    (!use_ssh_config && !default_user.is_empty()).then(|| default_user.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_mcp_sdk::schema::TextContent;
+
+    fn command_result(status_code: i64) -> CallToolResult {
+        let mut content = serde_json::Map::new();
+        content.insert("status_code".to_string(), status_code.into());
+        CallToolResult::text_content(vec![TextContent::from("")]).with_structured_content(content)
+    }
+
+    #[test]
+    fn test_audit_record_success() {
+        let params = serde_json::json!({"remote_host": "deploy@prod-db", "cmd": "uptime"});
+        let record = audit_record(
+            "run_ssh_command",
+            &params,
+            &Ok(command_result(0)),
+            Duration::from_millis(42),
+            false,
+        );
+        assert_eq!(
+            record,
+            AuditRecord {
+                tool: "run_ssh_command",
+                remote_host: Some("prod-db"),
+                remote_hosts: None,
+                remote_user: Some("deploy".to_string()),
+                status_code: Some(0),
+                duration_ms: 42,
+                success: true,
+                error: None,
+                judge_unavailable: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_audit_record_failure() {
+        let params = serde_json::json!({
+            "remote_host": "prod-db",
+            "options": ["User=admin"],
+        });
+        let record = audit_record(
+            "run_ssh_command",
+            &params,
+            &Ok(command_result(1)),
+            Duration::ZERO,
+            false,
+        );
+        assert_eq!(record.remote_user.as_deref(), Some("admin"));
+        assert_eq!(record.status_code, Some(1));
+        assert!(!record.success);
+
+        let record = audit_record(
+            "copy_file",
+            &params,
+            &Err(CallToolError::from_message("rsync failed")),
+            Duration::ZERO,
+            false,
+        );
+        assert!(!record.success);
+        assert_eq!(record.error.as_deref(), Some("rsync failed"));
+    }
+
+    #[test]
+    fn test_audit_record_failed_sequence() {
+        // A sequence has no status code of its own, only one per step
+        let mut content = serde_json::Map::new();
+        content.insert("succeeded".to_string(), false.into());
+        content.insert(
+            "steps".to_string(),
+            serde_json::json!([{"step": "false", "status_code": 1}, {"step": "uptime", "skipped": true}]),
+        );
+        let result = CallToolResult::text_content(vec![TextContent::from("")])
+            .with_structured_content(content);
+        let params = serde_json::json!({"remote_host": "prod-db"});
+        let record = audit_record(
+            "run_sequence",
+            &params,
+            &Ok(result.clone()),
+            Duration::ZERO,
+            false,
+        );
+        assert_eq!(record.status_code, None);
+        assert!(!record.success);
+
+        let mut result = result;
+        result
+            .structured_content
+            .as_mut()
+            .unwrap()
+            .insert("succeeded".to_string(), true.into());
+        assert!(succeeded(&result));
+    }
+
+    #[test]
+    fn test_audit_record_fan_out() {
+        let params = serde_json::json!({
+            "remote_hosts": ["deploy@web1", "deploy@web2"],
+            "cmd": "uptime",
+        });
+        let mut content = serde_json::Map::new();
+        content.insert("succeeded".to_string(), false.into());
+        content.insert("failed_hosts".to_string(), 1.into());
+        content.insert("results".to_string(), serde_json::json!([]));
+        let result = CallToolResult::text_content(vec![TextContent::from("")])
+            .with_structured_content(content);
+        let record = audit_record(
+            "run_ssh_command",
+            &params,
+            &Ok(result),
+            Duration::ZERO,
+            false,
+        );
+        assert_eq!(record.remote_host, None);
+        assert_eq!(record.remote_hosts.as_deref(), Some("web1,web2"));
+        assert_eq!(record.remote_user.as_deref(), Some("deploy"));
+        assert!(!record.success);
+
+        // Hosts logged in to as different users have no common user
+        let params = serde_json::json!({"remote_hosts": ["deploy@web1", "admin@web2"]});
+        let record = audit_record(
+            "run_ssh_command",
+            &params,
+            &Ok(command_result(0)),
+            Duration::ZERO,
+            false,
+        );
+        assert_eq!(record.remote_user, None);
+    }
+
+    #[test]
+    fn test_audit_record_local_command() {
+        let params = serde_json::json!({"cmd": "ping"});
+        let record = audit_record(
+            "run_local_command",
+            &params,
+            &Ok(command_result(0)),
+            Duration::ZERO,
+            false,
+        );
+        assert_eq!(record.remote_host, None);
+        assert_eq!(record.remote_hosts, None);
+        assert_eq!(record.remote_user, None);
+        assert!(record.success);
+    }
+
+    #[tokio::test]
+    async fn test_track_unjudged() {
+        let (_, unjudged) = track_unjudged(async {}).await;
+        assert!(!unjudged);
+
+        let (_, unjudged) = track_unjudged(async { mark_unjudged() }).await;
+        assert!(unjudged);
+
+        // Outside a tracked call, marking does nothing
+        mark_unjudged();
+    }
+}
@@ -1,12 +1,15 @@
 use async_trait::async_trait;
 use rust_mcp_sdk::schema::schema_utils::CallToolError;
 use rust_mcp_sdk::schema::{
-    CallToolRequestParams, CallToolResult, ListToolsResult, PaginatedRequestParams, RpcError,
+    CallToolRequestParams, CallToolResult, GetPromptRequestParams, GetPromptResult,
+    ListPromptsResult, ListResourcesResult, ListToolsResult, PaginatedRequestParams,
+    ReadResourceRequestParams, ReadResourceResult, RpcError,
 };
 use rust_mcp_sdk::{McpServer, mcp_server::ServerHandler};
 use std::sync::Arc;
+use std::time::Instant;
 
-use crate::judge::JudgeService;
+use crate::judge::{JudgeResult, JudgeService};
 use crate::tools::POSIXSSHTools;
 
 pub struct POSIXSSHHandler {
@@ -14,34 +17,115 @@ pub struct POSIXSSHHandler {
 }
 
 impl POSIXSSHHandler {
-    pub async fn new() -> Self {
-        let judge_service = match crate::config::load_judge_service().await {
-            Ok(Some(service)) => Some(service),
-            Ok(None) => {
-                tracing::debug!("Judge service not configured, judge disabled");
-                None
-            }
-            Err(e) => {
-                tracing::warn!("Failed to load judge service: {}", e);
-                None
-            }
-        };
-
+    /// Create a handler, consulting `judge_service` (if any) before each tool call
+    pub fn new(judge_service: Option<Arc<JudgeService>>) -> Self {
         Self { judge_service }
     }
 
-    /// Check if a tool call should be judged and validate it
+    /// Check if a tool call should be judged and validate it. `description`
+    /// describes the command that will really run, and `commands` each of the
+    /// commands it runs. A call on several hosts is judged if any of them
+    /// should be. Returns the judge's judgment of an allowed call, if the
+    /// judge made one.
     async fn check_tool_call(
         &self,
         tool_name: &str,
         params: &serde_json::Value,
-    ) -> Result<(), CallToolError> {
-        if let Some(judge) = &self.judge_service
-            && judge.should_judge(tool_name)
-        {
-            judge.check_tool_call(tool_name, params).await?;
+        description: &str,
+        commands: &[String],
+    ) -> Result<Option<JudgeResult>, CallToolError> {
+        let should_judge = |judge: &JudgeService| {
+            let mut hosts = crate::policy::target_hosts(params).peekable();
+            if hosts.peek().is_none() {
+                judge.should_judge(tool_name, None)
+            } else {
+                hosts.any(|host| judge.should_judge(tool_name, Some(host)))
+            }
+        };
+        match &self.judge_service {
+            Some(judge) if should_judge(judge) => {
+                judge
+                    .check_tool_call(tool_name, params, description, commands)
+                    .await
+            }
+            _ => Ok(None),
         }
-        Ok(())
+    }
+
+    /// Check a tool call against the policy and the judge, then run it
+    async fn call_tool(
+        &self,
+        params: CallToolRequestParams,
+        tool_params_json: &serde_json::Value,
+        runtime: Arc<dyn McpServer>,
+    ) -> Result<CallToolResult, CallToolError> {
+        let tool_name = params.name.clone();
+        let progress_token = crate::progress::progress_token(&params).filter(|_| {
+            tool_params_json.get("report_progress") == Some(&serde_json::Value::Bool(true))
+        });
+
+        // Enforce the enabled tools, host and command lists regardless of the
+        // judge
+        crate::policy::check_tool_enabled(&tool_name)?;
+        crate::policy::check_hosts(tool_params_json)?;
+        crate::policy::check_command(tool_params_json)?;
+
+        // Convert to tool enum, so the judge is told what will really run
+        let tool_params = POSIXSSHTools::try_from(params).map_err(CallToolError::new)?;
+
+        // Check with judge before executing
+        let judgment = self
+            .check_tool_call(
+                &tool_name,
+                tool_params_json,
+                &tool_params.describe_effective_command(),
+                &tool_params.describe_effective_commands(),
+            )
+            .await?;
+
+        let run = async {
+            let started = Instant::now();
+            let result = match tool_params {
+                POSIXSSHTools::RunLocalCommand(tool) => tool.call_tool().await,
+                POSIXSSHTools::RunSSHCommand(tool) => tool.call_tool().await,
+                POSIXSSHTools::RunSSHSudoCommand(tool) => tool.call_tool().await,
+                POSIXSSHTools::RunWithStdin(tool) => tool.call_tool().await,
+                POSIXSSHTools::RunSequence(tool) => tool.call_tool().await,
+                POSIXSSHTools::CheckConnection(tool) => tool.call_tool().await,
+                POSIXSSHTools::CopyFile(tool) => tool.call_tool().await,
+                POSIXSSHTools::FetchFile(tool) => tool.call_tool().await,
+                POSIXSSHTools::KillProcess(tool) => tool.call_tool().await,
+                POSIXSSHTools::ListDirectory(tool) => tool.call_tool().await,
+                POSIXSSHTools::Package(tool) => tool.call_tool().await,
+                POSIXSSHTools::PatchFile(tool) => tool.call_tool().await,
+                POSIXSSHTools::ReadRemoteFile(tool) => tool.call_tool().await,
+                POSIXSSHTools::Systemctl(tool) => tool.call_tool().await,
+                POSIXSSHTools::SystemSnapshot(tool) => tool.call_tool().await,
+                POSIXSSHTools::RemoteIdentity(tool) => tool.call_tool().await,
+                POSIXSSHTools::TailFile(tool) => tool.call_tool().await,
+                POSIXSSHTools::VerifyChecksum(tool) => tool.call_tool().await,
+                POSIXSSHTools::WriteFile(tool) => tool.call_tool().await,
+                POSIXSSHTools::AppendFile(tool) => tool.call_tool().await,
+                POSIXSSHTools::RenderAndWrite(tool) => tool.call_tool().await,
+                POSIXSSHTools::MoveFile(tool) => tool.call_tool().await,
+            };
+            crate::metrics::record_command_duration(&tool_name, started.elapsed());
+            result
+        };
+        // Tell the client that long commands are still running, if asked to
+        let mut result = match progress_token {
+            Some(token) => crate::progress::with_progress(run, runtime, token).await,
+            None => run.await,
+        }?;
+
+        // Pass on the judge's risk level and suggestions for allowed calls
+        if let Some(judgment) = judgment.filter(JudgeResult::has_feedback) {
+            result.meta.get_or_insert_default().insert(
+                "judge".to_string(),
+                serde_json::to_value(judgment).unwrap_or_default(),
+            );
+        }
+        Ok(result)
     }
 }
 
@@ -56,35 +140,308 @@ impl ServerHandler for POSIXSSHHandler {
         Ok(ListToolsResult {
             meta: None,
             next_cursor: None,
-            tools: POSIXSSHTools::tools(),
+            tools: crate::policy::filter_tools(
+                POSIXSSHTools::tools(),
+                crate::policy::enabled_tools(),
+            ),
+        })
+    }
+
+    /// Handle list resource requests
+    async fn handle_list_resources_request(
+        &self,
+        _: Option<PaginatedRequestParams>,
+        _: Arc<dyn McpServer>,
+    ) -> std::result::Result<ListResourcesResult, RpcError> {
+        Ok(ListResourcesResult {
+            meta: None,
+            next_cursor: None,
+            resources: crate::resources::list_resources(),
         })
     }
 
+    /// Handle read resource requests
+    async fn handle_read_resource_request(
+        &self,
+        params: ReadResourceRequestParams,
+        _: Arc<dyn McpServer>,
+    ) -> std::result::Result<ReadResourceResult, RpcError> {
+        crate::resources::read_resource(&params.uri, self.judge_service.is_some()).await
+    }
+
+    /// Handle list prompt requests
+    async fn handle_list_prompts_request(
+        &self,
+        _: Option<PaginatedRequestParams>,
+        _: Arc<dyn McpServer>,
+    ) -> std::result::Result<ListPromptsResult, RpcError> {
+        Ok(ListPromptsResult {
+            meta: None,
+            next_cursor: None,
+            prompts: crate::prompts::list_prompts(),
+        })
+    }
+
+    /// Handle get prompt requests
+    async fn handle_get_prompt_request(
+        &self,
+        params: GetPromptRequestParams,
+        _: Arc<dyn McpServer>,
+    ) -> std::result::Result<GetPromptResult, RpcError> {
+        crate::prompts::get_prompt(&params.name, params.arguments.as_ref())
+    }
+
     /// Handle tool call requests
     async fn handle_call_tool_request(
         &self,
-        params: CallToolRequestParams,
-        _: Arc<dyn McpServer>,
+        mut params: CallToolRequestParams,
+        runtime: Arc<dyn McpServer>,
     ) -> std::result::Result<CallToolResult, CallToolError> {
-        // Get tool name from params
-        let tool_name = params.name.as_str();
+        // Fill in the parameters configured in MCP_LINUX_SSH_DEFAULTS first,
+        // so that the policy, the judge and the audit log see what will run
+        crate::config::apply_tool_defaults(&params.name, params.arguments.get_or_insert_default());
 
-        // Get parameters as JSON for judge
-        let tool_params_json =
+        // Get parameters as JSON for judge, without secrets such as passwords
+        let mut tool_params_json =
             serde_json::Value::Object(params.arguments.clone().unwrap_or_default());
+        crate::tools::redact(&mut tool_params_json);
 
-        // Check with judge before executing
-        self.check_tool_call(tool_name, &tool_params_json).await?;
+        // Record the outcome of every call, including those that are refused
+        let tool_name = params.name.clone();
+        let started = Instant::now();
+        let (result, unjudged) =
+            crate::audit::track_unjudged(self.call_tool(params, &tool_params_json, runtime)).await;
+        crate::audit::record_tool_call(
+            &tool_name,
+            &tool_params_json,
+            &result,
+            started.elapsed(),
+            unjudged,
+        );
+        crate::metrics::record_tool_call(&tool_name, &result);
+        // Report failures as error results carrying their error_kind
+        Ok(result.unwrap_or_else(|e| crate::tools::error_result(&e)))
+    }
+}
 
-        // Convert to tool enum and execute
-        let tool_params = POSIXSSHTools::try_from(params).map_err(CallToolError::new)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::JudgeConfig;
+    use std::collections::HashMap;
+
+    /// Build a judge that rejects `rm` and allows `uptime` without calling the
+    /// LLM, so no provider needs to be reachable.
+    async fn stub_judge() -> Arc<JudgeService> {
+        stub_judge_for_hosts("").await
+    }
+
+    /// Build the stub judge, only judging calls on hosts matching
+    /// `host_patterns`
+    async fn stub_judge_for_hosts(host_patterns: &str) -> Arc<JudgeService> {
+        let mut config = JudgeConfig::for_test("ollama", "stub", "http://127.0.0.1:1");
+        config.allow_patterns = "^uptime on ".to_string();
+        config.deny_patterns = "^rm ".to_string();
+        config.host_patterns = host_patterns.to_string();
+        config.tools =
+            "run_ssh_command,run_ssh_sudo_command,run_with_stdin,run_sequence".to_string();
+        Arc::new(
+            JudgeService::from_config(config, HashMap::new())
+                .await
+                .unwrap(),
+        )
+    }
+
+    /// Convert a call to its tool, as the handler does before judging it
+    fn tool(tool_name: &str, arguments: &serde_json::Value) -> POSIXSSHTools {
+        let params: CallToolRequestParams = serde_json::from_value(serde_json::json!({
+            "name": tool_name,
+            "arguments": arguments,
+        }))
+        .unwrap();
+        POSIXSSHTools::try_from(params).unwrap()
+    }
+
+    /// Describe a call the way the handler does before judging it
+    fn describe(tool_name: &str, arguments: &serde_json::Value) -> String {
+        tool(tool_name, arguments).describe_effective_command()
+    }
+
+    /// Judge a call the way the handler does
+    async fn judge(
+        handler: &POSIXSSHHandler,
+        tool_name: &str,
+        params: &serde_json::Value,
+    ) -> Result<Option<JudgeResult>, CallToolError> {
+        let tool = tool(tool_name, params);
+        handler
+            .check_tool_call(
+                tool_name,
+                params,
+                &tool.describe_effective_command(),
+                &tool.describe_effective_commands(),
+            )
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_check_tool_call_rejected_by_judge() {
+        let handler = POSIXSSHHandler::new(Some(stub_judge().await));
+        let params = serde_json::json!({
+            "remote_host": "localhost",
+            "cmd": "rm",
+            "args": ["-rf", "/tmp/data"]
+        });
 
-        match tool_params {
-            POSIXSSHTools::RunLocalCommand(tool) => tool.call_tool().await,
-            POSIXSSHTools::RunSSHCommand(tool) => tool.call_tool().await,
-            POSIXSSHTools::RunSSHSudoCommand(tool) => tool.call_tool().await,
-            POSIXSSHTools::CopyFile(tool) => tool.call_tool().await,
-            POSIXSSHTools::PatchFile(tool) => tool.call_tool().await,
+        let error = judge(&handler, "run_ssh_command", &params)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("matched deny pattern ^rm"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_check_tool_call_allowed_by_judge() {
+        let handler = POSIXSSHHandler::new(Some(stub_judge().await));
+        let params = serde_json::json!({"remote_host": "localhost", "cmd": "uptime", "args": []});
+
+        assert!(
+            judge(&handler, "run_ssh_command", &params)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_tool_call_patterns_see_wrappers() {
+        // The stub LLM can't be reached, so anything the allow pattern
+        // doesn't cover is rejected
+        let handler = POSIXSSHHandler::new(Some(stub_judge().await));
+        let calls = [
+            (
+                "run_ssh_sudo_command",
+                serde_json::json!({"remote_host": "localhost", "cmd": "uptime", "args": []}),
+            ),
+            (
+                "run_ssh_command",
+                serde_json::json!({
+                    "remote_host": "localhost",
+                    "cmd": "uptime",
+                    "args": [],
+                    "remote_shell": "/bin/bash"
+                }),
+            ),
+            (
+                "run_with_stdin",
+                serde_json::json!({
+                    "remote_host": "localhost",
+                    "cmd": "sh",
+                    "args": [],
+                    "stdin": "uptime; rm -rf /"
+                }),
+            ),
+            (
+                "run_ssh_command",
+                serde_json::json!({
+                    "remote_host": "localhost",
+                    "cmd": "env",
+                    "args": ["uptime"],
+                }),
+            ),
+        ];
+        for (tool_name, params) in &calls {
+            let error = judge(&handler, tool_name, params).await.unwrap_err();
+            assert!(
+                !error.to_string().contains("pattern"),
+                "{}: {}",
+                describe(tool_name, params),
+                error
+            );
         }
     }
+
+    #[tokio::test]
+    async fn test_check_tool_call_patterns_see_each_step() {
+        let handler = POSIXSSHHandler::new(Some(stub_judge().await));
+        let sequence = |steps: serde_json::Value| serde_json::json!({"remote_host": "localhost", "steps": steps});
+
+        let params = sequence(serde_json::json!([
+            {"cmd": "uptime", "args": []},
+            {"cmd": "uptime", "args": []}
+        ]));
+        assert!(
+            judge(&handler, "run_sequence", &params)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // An allowed first step doesn't approve the rest of the sequence, so
+        // the unreachable stub LLM rejects it
+        let params = sequence(serde_json::json!([
+            {"cmd": "uptime", "args": []},
+            {"cmd": "reboot", "args": []}
+        ]));
+        let error = judge(&handler, "run_sequence", &params).await.unwrap_err();
+        assert!(!error.to_string().contains("pattern"), "{}", error);
+
+        // A denied step rejects the sequence, even after an allowed one
+        let params = sequence(serde_json::json!([
+            {"cmd": "uptime", "args": []},
+            {"cmd": "rm", "args": ["-rf", "/"]}
+        ]));
+        let error = judge(&handler, "run_sequence", &params).await.unwrap_err();
+        assert!(
+            error.to_string().contains("matched deny pattern ^rm"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_tool_call_skips_unjudged_tools() {
+        let handler = POSIXSSHHandler::new(Some(stub_judge().await));
+        let params = serde_json::json!({"cmd": "rm", "args": ["-rf", "/tmp/data"]});
+
+        assert!(judge(&handler, "run_local_command", &params).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_check_tool_call_judges_matching_hosts() {
+        let handler = POSIXSSHHandler::new(Some(stub_judge_for_hosts("prod-*").await));
+        let params = |remote_host: &str| serde_json::json!({"remote_host": remote_host, "cmd": "rm", "args": ["-rf", "/tmp/data"]});
+
+        assert!(
+            judge(&handler, "run_ssh_command", &params("prod-db"))
+                .await
+                .is_err()
+        );
+        assert!(
+            judge(&handler, "run_ssh_command", &params("dev-db"))
+                .await
+                .is_ok()
+        );
+
+        // A fan-out is judged if any of its hosts matches
+        let params = serde_json::json!({
+            "remote_hosts": ["dev-db", "prod-db"],
+            "cmd": "rm",
+            "args": ["-rf", "/tmp/data"]
+        });
+        assert!(judge(&handler, "run_ssh_command", &params).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_check_tool_call_without_judge() {
+        let handler = POSIXSSHHandler::new(None);
+        let params = serde_json::json!({"cmd": "rm", "args": ["-rf", "/"]});
+
+        assert!(
+            handler
+                .check_tool_call("run_ssh_command", &params, "", &[])
+                .await
+                .is_ok()
+        );
+    }
 }
@@ -0,0 +1,143 @@
+use crate::judge::JudgeService;
+use axum::Json;
+use axum::http::StatusCode;
+use axum::routing::{MethodRouter, get};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// How long a judge ping answers readiness probes for, so that frequent
+/// probes don't each cost an LLM call
+const JUDGE_PING_TTL: Duration = Duration::from_secs(30);
+
+/// A health probe, answered by the server itself rather than an MCP tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Probe {
+    /// `GET /healthz`: the server is up
+    Live,
+    /// `GET /readyz`: the server is up and the judge, if configured, answers
+    Ready,
+}
+
+/// Readiness state shared by the probe routes
+struct Health {
+    judge: Option<Arc<JudgeService>>,
+    /// When the judge was last pinged and whether it answered. Held while
+    /// pinging, so that concurrent probes share one ping.
+    last_ping: Mutex<Option<(Instant, bool)>>,
+}
+
+impl Health {
+    /// The status code and JSON body answering `probe`
+    async fn answer(&self, probe: Probe) -> (StatusCode, serde_json::Value) {
+        if probe == Probe::Live {
+            return (StatusCode::OK, serde_json::json!({"status": "ok"}));
+        }
+        let Some(judge) = &self.judge else {
+            return (
+                StatusCode::OK,
+                serde_json::json!({"status": "ok", "judge": "disabled"}),
+            );
+        };
+
+        let mut last_ping = self.last_ping.lock().await;
+        let ok = match *last_ping {
+            Some((pinged, ok)) if pinged.elapsed() < JUDGE_PING_TTL => ok,
+            _ => {
+                let ok = match judge.ping().await {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::warn!("Readiness check failed to reach the judge: {}", e);
+                        false
+                    }
+                };
+                *last_ping = Some((Instant::now(), ok));
+                ok
+            }
+        };
+        if ok {
+            (
+                StatusCode::OK,
+                serde_json::json!({"status": "ok", "judge": "ok"}),
+            )
+        } else {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                serde_json::json!({"status": "unavailable", "judge": "unavailable"}),
+            )
+        }
+    }
+}
+
+/// The `/healthz` and `/readyz` routes, served next to the MCP transport on
+/// the same listener. Probes need no MCP session and stay cheap.
+pub fn routes(judge: Option<Arc<JudgeService>>) -> [(&'static str, MethodRouter); 2] {
+    let health = Arc::new(Health {
+        judge,
+        last_ping: Mutex::new(None),
+    });
+    [
+        ("/healthz", route(health.clone(), Probe::Live)),
+        ("/readyz", route(health, Probe::Ready)),
+    ]
+}
+
+/// A `GET` route answering `probe`
+fn route(health: Arc<Health>, probe: Probe) -> MethodRouter {
+    get(move || async move {
+        let (status, body) = health.answer(probe).await;
+        (status, Json(body))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Send `request` to `addr` and return the whole response
+    async fn request(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_routes() {
+        let router = routes(None)
+            .into_iter()
+            .fold(axum::Router::new(), |router, (path, route)| {
+                router.route(path, route)
+            });
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let response = request(
+            addr,
+            "GET /healthz HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("{\"status\":\"ok\"}"));
+
+        let response = request(
+            addr,
+            "GET /readyz HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("{\"judge\":\"disabled\",\"status\":\"ok\"}"));
+
+        let response = request(
+            addr,
+            "POST /healthz HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 405 "));
+    }
+}
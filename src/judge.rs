@@ -1,9 +1,11 @@
+use crate::audit::AUDIT_TARGET;
 use crate::config::FailMode;
 use anyhow::{Context, Result};
+use regex::Regex;
 use rust_mcp_sdk::schema::schema_utils::CallToolError;
 use serde::{Deserialize, Serialize};
 use siumai::prelude::*;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::timeout as tokio_timeout;
@@ -19,13 +21,59 @@ Consider: \
 - Network operations that could affect connectivity \
 - File operations that could overwrite critical files \
 \n\
-Return JSON: {\"allowed\": true/false, \"reason\": \"brief explanation\"}";
+Return JSON: {\"allowed\": true/false, \"reason\": \"brief explanation\", \
+\"risk_level\": \"low/medium/high\", \"suggested_modification\": \"optional safer alternative\"} \
+When a call is risky but would be acceptable with a change, such as adding --dry-run or \
+narrowing a path or glob, describe the change in suggested_modification.";
+
+/// Delay before the first retry of a failed LLM call, doubled for each
+/// further retry
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
 
 /// Result of a judgment
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JudgeResult {
     pub allowed: bool,
     pub reason: String,
+    /// How risky the judge considers the call: low, medium or high
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub risk_level: Option<String>,
+    /// A change that would make the call acceptable or safer, such as adding
+    /// --dry-run or narrowing a glob
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_modification: Option<String>,
+}
+
+impl JudgeResult {
+    /// The error message for a rejected call, including the risk level and
+    /// suggested modification so the caller knows what to change
+    fn rejection_message(&self) -> String {
+        let mut message = format!("Tool call rejected by judge: {}", self.reason);
+        if let Some(risk_level) = &self.risk_level {
+            message.push_str(&format!(" (risk level: {})", risk_level));
+        }
+        if let Some(suggestion) = &self.suggested_modification {
+            message.push_str(&format!("\nSuggested modification: {}", suggestion));
+        }
+        message
+    }
+
+    /// Whether the judgment has anything to tell the caller besides allowing
+    /// the call
+    pub fn has_feedback(&self) -> bool {
+        self.risk_level.is_some() || self.suggested_modification.is_some()
+    }
+}
+
+/// Outcome of matching a tool call against the allow and deny patterns
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PatternDecision {
+    /// A deny pattern matched; the call is rejected without asking the LLM
+    Deny(String),
+    /// An allow pattern matched; the call is approved without asking the LLM
+    Allow(String),
+    /// No pattern matched; the LLM decides
+    Unmatched,
 }
 
 /// Judge service that evaluates tool calls using an LLM
@@ -33,26 +81,49 @@ pub struct JudgeResult {
 pub struct JudgeService {
     #[serde(skip)]
     client: Arc<dyn ChatCapability + Send + Sync>,
+    #[serde(skip)]
+    allow_patterns: Vec<Regex>,
+    #[serde(skip)]
+    deny_patterns: Vec<Regex>,
     fail_mode: FailMode,
     judge_tools: HashSet<String>,
+    /// Host globs limiting judging to the calls on matching hosts. Empty to
+    /// judge calls on every host.
+    judge_hosts: Vec<String>,
     system_prompt: String,
+    tool_prompts: HashMap<String, String>,
     timeout: Duration,
+    /// Number of times to retry an LLM call failing with a transient error
+    retries: u32,
+    /// Whether the provider was asked for a JSON object, so the response is
+    /// parsed as is rather than searched for one
+    json_mode: bool,
 }
 
 impl std::fmt::Debug for JudgeService {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("JudgeService")
+            .field("allow_patterns", &self.allow_patterns)
+            .field("deny_patterns", &self.deny_patterns)
             .field("fail_mode", &self.fail_mode)
             .field("judge_tools", &self.judge_tools)
+            .field("judge_hosts", &self.judge_hosts)
             .field("system_prompt", &self.system_prompt)
+            .field("tool_prompts", &self.tool_prompts)
             .field("timeout", &self.timeout)
+            .field("retries", &self.retries)
+            .field("json_mode", &self.json_mode)
             .finish_non_exhaustive()
     }
 }
 
 impl JudgeService {
-    /// Create a new judge service from configuration
-    pub async fn from_config(config: crate::config::JudgeConfig) -> Result<Self> {
+    /// Create a new judge service from configuration. `tool_prompts` maps tool
+    /// names to system prompts used instead of the default for those tools.
+    pub async fn from_config(
+        config: crate::config::JudgeConfig,
+        tool_prompts: HashMap<String, String>,
+    ) -> Result<Self> {
         let timeout = Duration::from_secs(config.timeout_seconds);
 
         // Parse judge tools from comma-separated string
@@ -63,27 +134,96 @@ impl JudgeService {
             .filter(|s| !s.is_empty())
             .collect();
 
+        // Host globs are matched case-insensitively, as by the host allowlist
+        let judge_hosts: Vec<String> = config
+            .host_patterns
+            .split(',')
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        // Compile allow and deny patterns up front so invalid patterns are
+        // reported at startup
+        let allow_patterns = compile_patterns(&config.allow_patterns)
+            .context("Invalid MCP_LINUX_SSH_JUDGE_ALLOW_PATTERNS")?;
+        let deny_patterns = compile_patterns(&config.deny_patterns)
+            .context("Invalid MCP_LINUX_SSH_JUDGE_DENY_PATTERNS")?;
+
+        // Only the OpenAI chat completions API has a JSON mode the client
+        // can request. Other providers fall back to finding the JSON object
+        // in the response text.
+        let json_mode = config.json_mode && supports_json_mode(&config.service);
+        if config.json_mode && !json_mode {
+            warn!(
+                "MCP_LINUX_SSH_JUDGE_JSON_MODE is not supported by {}, extracting JSON from the response text",
+                config.service
+            );
+        }
+
         // Build LLM client based on provider type
         let client: Arc<dyn ChatCapability + Send + Sync> = match config.service.as_str() {
             "openai" => {
                 if config.api_key.is_empty() {
                     anyhow::bail!("MCP_LINUX_SSH_JUDGE_API_KEY is required for OpenAI");
                 }
-                let mut builder = Siumai::builder()
-                    .openai()
-                    .api_key(&config.api_key)
-                    .model(&config.model);
+                if json_mode {
+                    Arc::new(
+                        openai_json_client(&config.api_key, &config.base_url, &config.model)
+                            .await
+                            .context("Failed to create OpenAI client")?,
+                    )
+                } else {
+                    let mut builder = Siumai::builder()
+                        .openai()
+                        .api_key(&config.api_key)
+                        .model(&config.model);
 
-                if !config.base_url.is_empty() {
-                    builder = builder.base_url(&config.base_url);
+                    if !config.base_url.is_empty() {
+                        builder = builder.base_url(&config.base_url);
+                    }
+
+                    Arc::new(
+                        builder
+                            .build()
+                            .await
+                            .context("Failed to create OpenAI client")?,
+                    )
+                }
+            }
+            "openai_compatible" => {
+                // Any server speaking the OpenAI chat completions API, such as
+                // Azure OpenAI's v1 endpoint, vLLM or LiteLLM. The model is
+                // passed through as is, so deployment names work too.
+                if config.base_url.is_empty() {
+                    anyhow::bail!(
+                        "MCP_LINUX_SSH_JUDGE_BASE_URL is required for OpenAI-compatible endpoints"
+                    );
                 }
+                // Local servers often need no key, but the client always sends one
+                let api_key = if config.api_key.is_empty() {
+                    "none"
+                } else {
+                    config.api_key.as_str()
+                };
 
-                Arc::new(
-                    builder
-                        .build()
-                        .await
-                        .context("Failed to create OpenAI client")?,
-                )
+                if json_mode {
+                    Arc::new(
+                        openai_json_client(api_key, &config.base_url, &config.model)
+                            .await
+                            .context("Failed to create OpenAI-compatible client")?,
+                    )
+                } else {
+                    Arc::new(
+                        Siumai::builder()
+                            .openai()
+                            .api_key(api_key)
+                            .base_url(&config.base_url)
+                            .model(&config.model)
+                            .build()
+                            .await
+                            .context("Failed to create OpenAI-compatible client")?,
+                    )
+                }
             }
             "anthropic" => {
                 if config.api_key.is_empty() {
@@ -143,7 +283,7 @@ impl JudgeService {
             }
             _ => {
                 anyhow::bail!(
-                    "Unsupported provider type: {}. Supported: openai, anthropic, ollama, gemini",
+                    "Unsupported provider type: {}. Supported: openai, openai_compatible, anthropic, ollama, gemini",
                     config.service
                 );
             }
@@ -151,48 +291,127 @@ impl JudgeService {
 
         Ok(Self {
             client,
+            allow_patterns,
+            deny_patterns,
             fail_mode: config.fail_mode,
             judge_tools,
+            judge_hosts,
             system_prompt: SYSTEM_PROMPT.to_string(),
+            tool_prompts,
             timeout,
+            retries: config.retries,
+            json_mode,
         })
     }
 
-    /// Check if a tool should be judged
-    pub fn should_judge(&self, tool_name: &str) -> bool {
-        self.judge_tools.contains(tool_name)
+    /// Check if a tool call on `host` should be judged. With host patterns,
+    /// only calls on matching hosts are, while calls without a host (local
+    /// commands) are always judged.
+    pub fn should_judge(&self, tool_name: &str, host: Option<&str>) -> bool {
+        if !self.judge_tools.contains(tool_name) {
+            return false;
+        }
+        match host {
+            Some(host) if !self.judge_hosts.is_empty() => {
+                let host = crate::policy::host_name(host).to_lowercase();
+                self.judge_hosts
+                    .iter()
+                    .any(|pattern| crate::policy::glob_match(pattern, &host))
+            }
+            _ => true,
+        }
+    }
+
+    /// Get the system prompt for a tool, falling back to the default prompt
+    fn system_prompt_for(&self, tool_name: &str) -> &str {
+        select_system_prompt(&self.tool_prompts, &self.system_prompt, tool_name)
     }
 
-    /// Judge a tool call and return an error if rejected
+    /// Ask the LLM, retrying up to `retries` times with exponential backoff
+    /// when the call fails with a transient error such as a rate limit or a
+    /// server error. Each attempt has its own timeout, and attempts that time
+    /// out aren't retried so that a hung provider doesn't hold up the call for
+    /// several timeouts. Returns why the LLM couldn't be asked otherwise.
+    async fn chat(&self, messages: Vec<ChatMessage>) -> Result<ChatResponse, &'static str> {
+        let mut attempt = 0;
+        loop {
+            match tokio_timeout(self.timeout, self.client.chat(messages.clone())).await {
+                Ok(Ok(response)) => return Ok(response),
+                Ok(Err(e)) if attempt < self.retries && is_transient(&e) => {
+                    attempt += 1;
+                    let delay = RETRY_BACKOFF * 2u32.pow((attempt - 1).min(8));
+                    warn!(
+                        attempt,
+                        retries = self.retries,
+                        delay_ms = delay.as_millis() as u64,
+                        error = %e,
+                        "Retrying LLM judge call"
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Ok(Err(e)) => {
+                    error!("LLM execution error: {}", e);
+                    return Err("LLM execution failed");
+                }
+                Err(_) => {
+                    warn!("LLM judge timeout after {:?}", self.timeout);
+                    return Err("LLM judge timeout");
+                }
+            }
+        }
+    }
+
+    /// Check that the LLM answers, for readiness probes, with a one-word
+    /// request subject to the same timeout and retries as judging a call.
+    /// Returns why the LLM couldn't be asked otherwise.
+    pub async fn ping(&self) -> Result<(), &'static str> {
+        self.chat(vec![user!("Reply with OK.")]).await.map(|_| ())
+    }
+
+    /// Judge a tool call and return an error if rejected. `description` is a
+    /// plain description of the command that will run, such as "sudo apt
+    /// remove --purge nginx on prod-db", which the LLM is shown along with the
+    /// parameters. `commands` describes each command the call runs in the
+    /// same way, such as each step of a sequence, for matching the allow and
+    /// deny patterns. Returns the LLM's judgment of an allowed call, or None
+    /// if the call was allowed without one.
     pub async fn check_tool_call(
         &self,
         tool_name: &str,
         tool_params: &serde_json::Value,
-    ) -> Result<(), CallToolError> {
+        description: &str,
+        commands: &[String],
+    ) -> Result<Option<JudgeResult>, CallToolError> {
+        // Deterministic patterns short-circuit the LLM. They are matched
+        // against the descriptions the LLM would be shown, which include sudo,
+        // remote shells and standard input, so that e.g. an allow pattern of
+        // ^uptime doesn't approve sudo uptime. Each command is matched on its
+        // own, so that an allowed first step doesn't approve the ones after it.
+        match evaluate_patterns(&self.allow_patterns, &self.deny_patterns, commands) {
+            PatternDecision::Deny(pattern) => {
+                crate::metrics::record_judge_decision("deny");
+                return Err(CallToolError::from_message(format!(
+                    "Tool call rejected by judge: matched deny pattern {}",
+                    pattern
+                )));
+            }
+            PatternDecision::Allow(pattern) => {
+                crate::metrics::record_judge_decision("allow");
+                tracing::debug!("Tool call allowed by pattern {}", pattern);
+                return Ok(None);
+            }
+            PatternDecision::Unmatched => {}
+        }
+
         // Build the prompt
-        let prompt = format!(
-            "Tool: {}\nParameters:\n{}\n\nEvaluate if this tool call should be allowed. Return JSON: {{\"allowed\": true/false, \"reason\": \"brief explanation\"}}",
-            tool_name,
-            serde_json::to_string_pretty(tool_params)
-                .unwrap_or_else(|_| format!("{:?}", tool_params))
-        );
+        let prompt = build_prompt(tool_name, tool_params, description);
 
         // Create the messages
-        let messages = vec![system!(&self.system_prompt), user!(&prompt)];
+        let messages = vec![system!(self.system_prompt_for(tool_name)), user!(&prompt)];
 
-        // Execute with timeout
-        let result = tokio_timeout(self.timeout, async { self.client.chat(messages).await }).await;
-
-        let response = match result {
-            Ok(Ok(response)) => response,
-            Ok(Err(e)) => {
-                error!("LLM execution error: {}", e);
-                return handle_llm_error(&self.fail_mode, "LLM execution failed");
-            }
-            Err(_) => {
-                warn!("LLM judge timeout after {:?}", self.timeout);
-                return handle_llm_error(&self.fail_mode, "LLM judge timeout");
-            }
+        let response = match self.chat(messages).await {
+            Ok(response) => response,
+            Err(message) => return handle_llm_error(&self.fail_mode, tool_name, message),
         };
 
         // Get response text
@@ -202,48 +421,147 @@ impl JudgeService {
             .unwrap_or_else(|| response.content.all_text());
 
         // Parse the JSON response
-        let judgment: JudgeResult = match serde_json::from_str(&response_text) {
-            Ok(j) => j,
+        let judgment = match parse_judgment(&response_text, self.json_mode) {
+            Ok(judgment) => judgment,
             Err(e) => {
-                // Try to extract JSON from the response if it's embedded in text
-                if let Some(json_start) = response_text.find('{') {
-                    if let Some(json_end) = response_text.rfind('}') {
-                        let json_str = &response_text[json_start..=json_end];
-                        match serde_json::from_str(json_str) {
-                            Ok(j) => j,
-                            Err(_) => {
-                                error!("Failed to parse LLM response as JSON: {}", e);
-                                return handle_llm_error(
-                                    &self.fail_mode,
-                                    "Failed to parse judge response",
-                                );
-                            }
-                        }
-                    } else {
-                        error!("Failed to parse LLM response as JSON: {}", e);
-                        return handle_llm_error(&self.fail_mode, "Failed to parse judge response");
-                    }
-                } else {
-                    error!("Failed to parse LLM response as JSON: {}", e);
-                    return handle_llm_error(&self.fail_mode, "Failed to parse judge response");
-                }
+                error!("Failed to parse LLM response as JSON: {}", e);
+                return handle_llm_error(
+                    &self.fail_mode,
+                    tool_name,
+                    "Failed to parse judge response",
+                );
             }
         };
 
         // Check the judgment
         if !judgment.allowed {
-            return Err(CallToolError::from_message(format!(
-                "Tool call rejected by judge: {}",
-                judgment.reason
-            )));
+            crate::metrics::record_judge_decision("deny");
+            return Err(CallToolError::from_message(judgment.rejection_message()));
         }
+        crate::metrics::record_judge_decision("allow");
+
+        Ok(Some(judgment))
+    }
+}
+
+/// Whether an LLM error may go away if the call is retried: rate limits,
+/// server errors and network failures, but not authentication or invalid
+/// requests
+fn is_transient(error: &LlmError) -> bool {
+    match error {
+        LlmError::ApiError { code, .. } => *code == 429 || *code >= 500,
+        LlmError::RateLimitError(_) | LlmError::HttpError(_) => true,
+        _ => false,
+    }
+}
+
+/// Build the prompt asking the LLM to judge a tool call
+fn build_prompt(tool_name: &str, tool_params: &serde_json::Value, description: &str) -> String {
+    format!(
+        "Tool: {}\nEffective command: {}\nParameters:\n{}\n\nEvaluate if this tool call should be allowed. Return JSON: {{\"allowed\": true/false, \"reason\": \"brief explanation\", \"risk_level\": \"low/medium/high\", \"suggested_modification\": \"optional safer alternative\"}}",
+        tool_name,
+        description,
+        serde_json::to_string_pretty(tool_params).unwrap_or_else(|_| format!("{:?}", tool_params))
+    )
+}
+
+/// An OpenAI chat completions client asking for a JSON object in every
+/// response. The unified Siumai builder can't set the response format, so the
+/// OpenAI-specific builder is used instead.
+async fn openai_json_client(
+    api_key: &str,
+    base_url: &str,
+    model: &str,
+) -> Result<siumai::providers::openai::OpenAiClient, LlmError> {
+    let mut builder = LlmBuilder::new()
+        .openai()
+        .api_key(api_key)
+        .model(model)
+        .response_format(siumai::params::ResponseFormat::JsonObject);
+    if !base_url.is_empty() {
+        builder = builder.base_url(base_url);
+    }
+    builder.build().await
+}
+
+/// Whether the provider can be asked to return a JSON object
+fn supports_json_mode(service: &str) -> bool {
+    matches!(service, "openai" | "openai_compatible")
+}
 
-        Ok(())
+/// Parse the LLM's judgment. In JSON mode the provider returns a bare JSON
+/// object, which is parsed as is. Otherwise models often wrap it in prose or
+/// markdown fences, so the outermost braces are parsed if the whole response
+/// isn't JSON.
+fn parse_judgment(response_text: &str, json_mode: bool) -> serde_json::Result<JudgeResult> {
+    let response_text = response_text.trim();
+    match serde_json::from_str(response_text) {
+        Err(e) if !json_mode => match (response_text.find('{'), response_text.rfind('}')) {
+            (Some(start), Some(end)) if start < end => {
+                serde_json::from_str(&response_text[start..=end])
+            }
+            _ => Err(e),
+        },
+        result => result,
     }
 }
 
+/// Compile a comma-separated list of regular expressions
+fn compile_patterns(patterns: &str) -> Result<Vec<Regex>> {
+    patterns
+        .split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(|pattern| Regex::new(pattern).with_context(|| format!("Invalid pattern: {}", pattern)))
+        .collect()
+}
+
+/// Match each command against the deny patterns, then the allow patterns.
+/// Deny patterns take precedence: the call is denied if any command matches
+/// one, and only allowed if every command matches an allow pattern.
+fn evaluate_patterns(allow: &[Regex], deny: &[Regex], commands: &[String]) -> PatternDecision {
+    for command in commands {
+        if let Some(pattern) = deny.iter().find(|pattern| pattern.is_match(command)) {
+            return PatternDecision::Deny(pattern.to_string());
+        }
+    }
+    let mut allowed_by: Vec<String> = Vec::new();
+    for command in commands {
+        match allow.iter().find(|pattern| pattern.is_match(command)) {
+            Some(pattern) => {
+                if !allowed_by.contains(&pattern.to_string()) {
+                    allowed_by.push(pattern.to_string());
+                }
+            }
+            None => return PatternDecision::Unmatched,
+        }
+    }
+    if allowed_by.is_empty() {
+        return PatternDecision::Unmatched;
+    }
+    PatternDecision::Allow(allowed_by.join(", "))
+}
+
+/// Select the tool-specific system prompt if one is configured, otherwise the
+/// default prompt
+fn select_system_prompt<'a>(
+    tool_prompts: &'a HashMap<String, String>,
+    default_prompt: &'a str,
+    tool_name: &str,
+) -> &'a str {
+    tool_prompts
+        .get(tool_name)
+        .map(String::as_str)
+        .unwrap_or(default_prompt)
+}
+
 /// Handle LLM errors based on fail mode
-fn handle_llm_error(fail_mode: &FailMode, message: &str) -> Result<(), CallToolError> {
+fn handle_llm_error(
+    fail_mode: &FailMode,
+    tool_name: &str,
+    message: &str,
+) -> Result<Option<JudgeResult>, CallToolError> {
+    crate::metrics::record_judge_decision("unavailable");
     match fail_mode {
         FailMode::Closed => Err(CallToolError::from_message(format!(
             "Judge unavailable: {}",
@@ -254,7 +572,360 @@ fn handle_llm_error(fail_mode: &FailMode, message: &str) -> Result<(), CallToolE
                 "Judge unavailable (fail_mode=open), allowing tool call: {}",
                 message
             );
-            Ok(())
+            Ok(None)
+        }
+        FailMode::Log => {
+            // Recorded in the audit log, so that calls allowed without a
+            // judgment can be found and reviewed
+            warn!(
+                target: AUDIT_TARGET,
+                tool = tool_name,
+                judge_unavailable = true,
+                reason = message,
+                "Judge unavailable (fail_mode=log), allowing tool call"
+            );
+            crate::audit::mark_unjudged();
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_system_prompt() {
+        let tool_prompts =
+            HashMap::from([("patch_file".to_string(), "Review the patch".to_string())]);
+
+        assert_eq!(
+            select_system_prompt(&tool_prompts, SYSTEM_PROMPT, "patch_file"),
+            "Review the patch"
+        );
+        assert_eq!(
+            select_system_prompt(&tool_prompts, SYSTEM_PROMPT, "run_local_command"),
+            SYSTEM_PROMPT
+        );
+    }
+
+    #[test]
+    fn test_build_prompt() {
+        let params = serde_json::json!({"remote_host": "prod-db", "cmd": "apt", "args": ["remove", "nginx"]});
+        let prompt = build_prompt(
+            "run_ssh_sudo_command",
+            &params,
+            "sudo apt remove nginx on prod-db",
+        );
+        assert!(prompt.starts_with(
+            "Tool: run_ssh_sudo_command\nEffective command: sudo apt remove nginx on prod-db\n"
+        ));
+        assert!(prompt.contains("\"cmd\": \"apt\""));
+    }
+
+    #[test]
+    fn test_handle_llm_error_closed() {
+        let err = handle_llm_error(&FailMode::Closed, "run_ssh_command", "LLM judge timeout")
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Judge unavailable: LLM judge timeout")
+        );
+    }
+
+    #[test]
+    fn test_handle_llm_error_open() {
+        assert!(handle_llm_error(&FailMode::Open, "run_ssh_command", "LLM judge timeout").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_handle_llm_error_log() {
+        let (result, unjudged) = crate::audit::track_unjudged(async {
+            handle_llm_error(&FailMode::Log, "run_ssh_command", "LLM judge timeout")
+        })
+        .await;
+        assert!(result.is_ok());
+        assert!(unjudged);
+    }
+
+    #[test]
+    fn test_judge_result_optional_fields() {
+        let judgment: JudgeResult =
+            serde_json::from_str(r#"{"allowed": true, "reason": "read-only"}"#).unwrap();
+        assert!(judgment.allowed);
+        assert!(!judgment.has_feedback());
+
+        let judgment: JudgeResult = serde_json::from_str(
+            r#"{"allowed": false, "reason": "deletes every log", "risk_level": "high",
+                "suggested_modification": "restrict the glob to /var/log/app/*.log"}"#,
+        )
+        .unwrap();
+        assert!(judgment.has_feedback());
+        assert_eq!(
+            judgment.rejection_message(),
+            "Tool call rejected by judge: deletes every log (risk level: high)\n\
+            Suggested modification: restrict the glob to /var/log/app/*.log"
+        );
+    }
+
+    #[test]
+    fn test_parse_judgment() {
+        let bare = r#"{"allowed": false, "reason": "deletes data", "risk_level": "high"}"#;
+        let fenced = format!("Here is my judgment:\n```json\n{}\n```", bare);
+
+        for json_mode in [false, true] {
+            let judgment = parse_judgment(bare, json_mode).unwrap();
+            assert!(!judgment.allowed);
+            assert_eq!(judgment.risk_level.as_deref(), Some("high"));
+        }
+        assert_eq!(
+            parse_judgment(&fenced, false).unwrap().reason,
+            "deletes data"
+        );
+        // JSON mode responses aren't searched for JSON
+        assert!(parse_judgment(&fenced, true).is_err());
+        assert!(parse_judgment("I can't judge this", false).is_err());
+        assert!(parse_judgment("} not json {", false).is_err());
+    }
+
+    #[test]
+    fn test_supports_json_mode() {
+        assert!(supports_json_mode("openai"));
+        assert!(supports_json_mode("openai_compatible"));
+        assert!(!supports_json_mode("anthropic"));
+        assert!(!supports_json_mode("ollama"));
+    }
+
+    /// A client that fails with a 503 error `failures` times, then allows the
+    /// call
+    struct FlakyClient {
+        failures: std::sync::atomic::AtomicU32,
+        calls: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait::async_trait]
+    impl ChatCapability for FlakyClient {
+        async fn chat_with_tools(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _tools: Option<Vec<Tool>>,
+        ) -> Result<ChatResponse, LlmError> {
+            use std::sync::atomic::Ordering;
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self
+                .failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+            {
+                return Err(LlmError::ApiError {
+                    code: 503,
+                    message: "overloaded".to_string(),
+                    details: None,
+                });
+            }
+            Ok(ChatResponse::new(MessageContent::Text(
+                r#"{"allowed": true, "reason": "read-only"}"#.to_string(),
+            )))
+        }
+
+        async fn chat_stream(
+            &self,
+            _messages: Vec<ChatMessage>,
+            _tools: Option<Vec<Tool>>,
+        ) -> Result<ChatStream, LlmError> {
+            Err(LlmError::UnsupportedOperation(
+                "the judge doesn't stream".to_string(),
+            ))
         }
     }
+
+    /// Build a judge asking `client`, failing closed so that an LLM failure
+    /// rejects the call
+    fn judge_with_client(client: Arc<FlakyClient>, retries: u32) -> JudgeService {
+        JudgeService {
+            client,
+            allow_patterns: Vec::new(),
+            deny_patterns: Vec::new(),
+            fail_mode: FailMode::Closed,
+            judge_tools: HashSet::from(["run_ssh_command".to_string()]),
+            judge_hosts: Vec::new(),
+            system_prompt: SYSTEM_PROMPT.to_string(),
+            tool_prompts: HashMap::new(),
+            timeout: Duration::from_secs(5),
+            retries,
+            json_mode: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_tool_call_retries_transient_errors() {
+        let params = serde_json::json!({"remote_host": "prod-db", "cmd": "uptime"});
+        let commands = vec!["uptime on prod-db".to_string()];
+        let flaky = || {
+            Arc::new(FlakyClient {
+                failures: 1.into(),
+                calls: 0.into(),
+            })
+        };
+
+        let client = flaky();
+        let judge = judge_with_client(client.clone(), 1);
+        let judgment = judge
+            .check_tool_call("run_ssh_command", &params, "uptime on prod-db", &commands)
+            .await
+            .unwrap();
+        assert!(judgment.unwrap().allowed);
+        assert_eq!(client.calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // Without retries, the failure reaches the fail mode
+        let client = flaky();
+        let judge = judge_with_client(client.clone(), 0);
+        let err = judge
+            .check_tool_call("run_ssh_command", &params, "uptime on prod-db", &commands)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Judge unavailable"));
+        assert_eq!(client.calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ping() {
+        let client = Arc::new(FlakyClient {
+            failures: 1.into(),
+            calls: 0.into(),
+        });
+        assert!(judge_with_client(client.clone(), 0).ping().await.is_err());
+        assert!(judge_with_client(client.clone(), 0).ping().await.is_ok());
+        assert_eq!(client.calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let api_error = |code| LlmError::ApiError {
+            code,
+            message: String::new(),
+            details: None,
+        };
+        assert!(is_transient(&api_error(429)));
+        assert!(is_transient(&api_error(503)));
+        assert!(!is_transient(&api_error(401)));
+        assert!(!is_transient(&api_error(400)));
+    }
+
+    fn openai_compatible_config(base_url: &str) -> crate::config::JudgeConfig {
+        crate::config::JudgeConfig::for_test("openai_compatible", "my-gpt-4o-deployment", base_url)
+    }
+
+    #[tokio::test]
+    async fn test_from_config_openai_compatible() {
+        let config = openai_compatible_config("http://127.0.0.1:8000/v1");
+        let judge = JudgeService::from_config(config, HashMap::new())
+            .await
+            .unwrap();
+        assert!(judge.should_judge("run_ssh_command", None));
+        assert!(judge.should_judge("run_ssh_command", Some("dev-1")));
+        assert!(!judge.should_judge("copy_file", Some("dev-1")));
+    }
+
+    #[tokio::test]
+    async fn test_should_judge_host_patterns() {
+        let mut config = openai_compatible_config("http://127.0.0.1:8000/v1");
+        config.host_patterns = "prod-*, *.Prod.Internal".to_string();
+        let judge = JudgeService::from_config(config, HashMap::new())
+            .await
+            .unwrap();
+
+        assert!(judge.should_judge("run_ssh_command", Some("prod-db")));
+        assert!(judge.should_judge("run_ssh_command", Some("admin@db1.prod.internal:2222")));
+        assert!(!judge.should_judge("run_ssh_command", Some("dev-db")));
+        assert!(!judge.should_judge("run_ssh_command", Some("db1.staging.internal")));
+        // Calls without a host, such as local commands, are still judged
+        assert!(judge.should_judge("run_ssh_command", None));
+        // Host patterns don't bring in tools that aren't judged
+        assert!(!judge.should_judge("copy_file", Some("prod-db")));
+    }
+
+    #[tokio::test]
+    async fn test_from_config_openai_compatible_requires_base_url() {
+        let err = JudgeService::from_config(openai_compatible_config(""), HashMap::new())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("MCP_LINUX_SSH_JUDGE_BASE_URL"));
+    }
+
+    #[test]
+    fn test_compile_patterns() {
+        assert!(compile_patterns("").unwrap().is_empty());
+        assert_eq!(compile_patterns("^uptime$, ^df -h$").unwrap().len(), 2);
+        assert!(compile_patterns("([unclosed").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_patterns_deny() {
+        let allow = compile_patterns("^rm").unwrap();
+        let deny = compile_patterns("^rm -rf").unwrap();
+        assert_eq!(
+            evaluate_patterns(&allow, &deny, &["rm -rf /".to_string()]),
+            PatternDecision::Deny("^rm -rf".to_string())
+        );
+    }
+
+    #[test]
+    fn test_evaluate_patterns_allow() {
+        let allow = compile_patterns("^uptime$,^df -h$").unwrap();
+        let deny = compile_patterns("^rm -rf").unwrap();
+        assert_eq!(
+            evaluate_patterns(&allow, &deny, &["df -h".to_string()]),
+            PatternDecision::Allow("^df -h$".to_string())
+        );
+    }
+
+    #[test]
+    fn test_evaluate_patterns_unmatched() {
+        let allow = compile_patterns("^uptime$").unwrap();
+        let deny = compile_patterns("^rm -rf").unwrap();
+        assert_eq!(
+            evaluate_patterns(&allow, &deny, &["systemctl restart nginx".to_string()]),
+            PatternDecision::Unmatched
+        );
+        assert_eq!(
+            evaluate_patterns(&allow, &deny, &[]),
+            PatternDecision::Unmatched
+        );
+    }
+
+    #[test]
+    fn test_evaluate_patterns_every_command() {
+        let allow = compile_patterns("^systemctl status ,^uptime on ").unwrap();
+        let deny = compile_patterns("^rm").unwrap();
+        let commands = |commands: &[&str]| -> Vec<String> {
+            commands.iter().map(|command| command.to_string()).collect()
+        };
+
+        // An allowed first step doesn't approve the steps after it
+        assert_eq!(
+            evaluate_patterns(
+                &allow,
+                &deny,
+                &commands(&["systemctl status nginx on prod-db", "reboot on prod-db"])
+            ),
+            PatternDecision::Unmatched
+        );
+        assert_eq!(
+            evaluate_patterns(
+                &allow,
+                &deny,
+                &commands(&["systemctl status nginx on prod-db", "rm -rf / on prod-db"])
+            ),
+            PatternDecision::Deny("^rm".to_string())
+        );
+        assert_eq!(
+            evaluate_patterns(
+                &allow,
+                &deny,
+                &commands(&["uptime on prod-db", "systemctl status nginx on prod-db"])
+            ),
+            PatternDecision::Allow("^uptime on, ^systemctl status".to_string())
+        );
+    }
 }
@@ -1,4 +1,12 @@
+pub mod audit;
 pub mod config;
 pub mod handler;
+pub mod health;
 pub mod judge;
+pub mod logs;
+pub mod metrics;
+pub mod policy;
+pub mod progress;
+pub mod prompts;
+pub mod resources;
 pub mod tools;
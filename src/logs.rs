@@ -0,0 +1,59 @@
+use anyhow::Error;
+use std::path::Path;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+
+/// Environment variable limiting how many daily files of each log are kept.
+/// Older ones are deleted as the logs rotate.
+pub const LOG_MAX_FILES_VAR: &str = "MCP_LINUX_SSH_LOG_MAX_FILES";
+
+/// The number of files of each log to keep, if limited. Unset, empty or 0
+/// keep every file; an invalid value is an error, for the caller to report
+/// once logging is set up.
+pub fn log_max_files() -> Result<Option<usize>, Error> {
+    parse_log_max_files(&std::env::var(LOG_MAX_FILES_VAR).unwrap_or_default())
+}
+
+fn parse_log_max_files(value: &str) -> Result<Option<usize>, Error> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    match value.trim().parse::<usize>() {
+        Ok(max_files) => Ok(Some(max_files).filter(|max_files| *max_files > 0)),
+        Err(_) => Err(Error::msg(format!(
+            "Invalid {}: {:?}",
+            LOG_MAX_FILES_VAR, value
+        ))),
+    }
+}
+
+/// A log file in `dir` rotated daily as `<prefix>.<date>`, keeping at most
+/// `max_files` of them, today's included.
+pub fn daily_appender(
+    dir: &Path,
+    prefix: &str,
+    max_files: Option<usize>,
+) -> Result<RollingFileAppender, Error> {
+    let mut builder = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(prefix);
+    if let Some(max_files) = max_files {
+        builder = builder.max_log_files(max_files);
+    }
+    builder
+        .build(dir)
+        .map_err(|e| Error::msg(format!("Failed to open log file {}: {}", prefix, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_max_files() {
+        assert_eq!(parse_log_max_files("").unwrap(), None);
+        assert_eq!(parse_log_max_files(" 7 ").unwrap(), Some(7));
+        assert_eq!(parse_log_max_files("0").unwrap(), None);
+        assert!(parse_log_max_files("seven").is_err());
+        assert!(parse_log_max_files("-1").is_err());
+    }
+}
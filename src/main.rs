@@ -1,19 +1,29 @@
 use anyhow::Error;
 use directories::ProjectDirs;
-use mcp_linux_ssh::handler::POSIXSSHHandler;
+use mcp_linux_ssh::{
+    audit::AUDIT_TARGET,
+    config::{Transport, load_judge_service, load_transport},
+    handler::POSIXSSHHandler,
+    health,
+    logs::{daily_appender, log_max_files},
+    metrics,
+};
 use rust_mcp_sdk::{
     McpServer, StdioTransport, TransportOptions,
-    mcp_server::{McpServerOptions, ToMcpServerHandler, server_runtime},
+    mcp_server::{
+        HyperServerOptions, McpServerOptions, ToMcpServerHandler, hyper_server, server_runtime,
+    },
     schema::{
         Implementation, InitializeResult, LATEST_PROTOCOL_VERSION, ServerCapabilities,
-        ServerCapabilitiesTools,
+        ServerCapabilitiesPrompts, ServerCapabilitiesResources, ServerCapabilitiesTools,
     },
 };
 use std::fs::create_dir_all;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tracing_subscriber::{
-    EnvFilter, fmt, fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt,
+    EnvFilter, Layer, filter::filter_fn, fmt, fmt::format::FmtSpan, layer::SubscriberExt,
+    util::SubscriberInitExt,
 };
 
 #[tokio::main]
@@ -42,28 +52,55 @@ async fn main() -> Result<(), Error> {
     create_dir_all(log_parent)
         .map_err(|e| Error::msg(format!("Failed to create log directory: {}", e)))?;
 
-    let file_appender = tracing_appender::rolling::daily(log_parent, "tool_calls.jsonl");
-    let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
+    let max_files = log_max_files();
+    let max_files_kept = max_files.as_ref().ok().copied().flatten();
+    let file_appender = daily_appender(log_parent, "tool_calls.jsonl", max_files_kept)?;
+    let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+
+    // Tool call outcomes are also written on their own to audit.jsonl, one
+    // flat JSON object per call
+    let audit_appender = daily_appender(log_parent, "audit.jsonl", max_files_kept)?;
+    let (audit_writer, audit_guard) = tracing_appender::non_blocking(audit_appender);
+
+    // RUST_LOG applies to the stderr and tool call logs but not to the audit
+    // log, which always records every call
+    let env_filter =
+        || EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
 
     tracing_subscriber::registry()
-        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
         .with(
             fmt::layer()
                 .with_writer(std::io::stderr)
                 .with_ansi(false)
-                .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE),
+                .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+                .with_filter(env_filter()),
         )
         .with(
             fmt::layer()
                 .with_writer(non_blocking)
                 .json()
                 .with_ansi(false)
-                .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE),
+                .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
+                .with_filter(env_filter()),
+        )
+        .with(
+            fmt::layer()
+                .with_writer(audit_writer)
+                .json()
+                .flatten_event(true)
+                .with_current_span(false)
+                .with_span_list(false)
+                .with_ansi(false)
+                .with_filter(filter_fn(|metadata| metadata.target() == AUDIT_TARGET)),
         )
         .init();
 
     tracing::info!("starting");
 
+    if let Err(e) = max_files {
+        tracing::warn!("{}; keeping every log file", e);
+    }
+
     // Define server details & capabilities
     let server_details = InitializeResult {
         server_info: Implementation {
@@ -76,6 +113,11 @@ async fn main() -> Result<(), Error> {
         },
         capabilities: ServerCapabilities {
             tools: Some(ServerCapabilitiesTools { list_changed: None }),
+            prompts: Some(ServerCapabilitiesPrompts { list_changed: None }),
+            resources: Some(ServerCapabilitiesResources {
+                list_changed: None,
+                subscribe: None,
+            }),
             ..Default::default()
         },
         instructions: Some(String::from(
@@ -88,30 +130,129 @@ async fn main() -> Result<(), Error> {
         protocol_version: LATEST_PROTOCOL_VERSION.to_string(),
     };
 
-    // Create transport with default options
-    let transport = StdioTransport::new(TransportOptions::default())
-        .map_err(|e| Error::msg(format!("{}", e)))?;
+    let (transport, bind_addr) = load_transport()?;
+
+    // Serve metrics, if asked to
+    if let Some(metrics_addr) = metrics::start().await? {
+        tracing::info!("Serving metrics on http://{}/metrics", metrics_addr);
+    }
+
+    // Load the judge, if configured
+    let judge_service = match load_judge_service().await {
+        Ok(service) => service,
+        Err(e) => {
+            tracing::warn!("Failed to load judge service: {}", e);
+            None
+        }
+    };
 
     // Create custom handler with judge initialization
-    let handler = POSIXSSHHandler::new().await;
+    let handler = POSIXSSHHandler::new(judge_service.clone());
     let handler_arc: Arc<dyn rust_mcp_sdk::mcp_server::McpServerHandler> =
         handler.to_mcp_server_handler();
 
-    // Create server options
-    let server_options = McpServerOptions {
-        server_details,
-        transport,
-        handler: handler_arc,
-        task_store: None,
-        client_task_store: None,
+    let serve = async {
+        match transport {
+            Transport::Stdio => {
+                // Create transport with default options
+                let transport = StdioTransport::new(TransportOptions::default())
+                    .map_err(|e| Error::msg(format!("{}", e)))?;
+
+                // Create server options
+                let server_options = McpServerOptions {
+                    server_details,
+                    transport,
+                    handler: handler_arc,
+                    task_store: None,
+                    client_task_store: None,
+                };
+
+                // Create Server
+                let server = server_runtime::create_server(server_options);
+
+                // Start!
+                server
+                    .start()
+                    .await
+                    .map_err(|e| Error::msg(format!("{}", e)))
+            }
+            Transport::Http | Transport::Sse => {
+                if !bind_addr.ip().is_loopback() {
+                    // The server has no authentication of its own
+                    tracing::warn!(
+                        "Listening on {}, which is reachable from other machines; \
+                    anyone who can connect can run commands through this server",
+                        bind_addr
+                    );
+                }
+                tracing::info!("Listening on {} ({:?} transport)", bind_addr, transport);
+
+                let mut server = hyper_server::create_server(
+                    server_details,
+                    handler_arc,
+                    HyperServerOptions {
+                        host: bind_addr.ip().to_string(),
+                        port: bind_addr.port(),
+                        sse_support: transport == Transport::Sse,
+                        ..Default::default()
+                    },
+                );
+                // Health probes are served on the same listener as the transport
+                for (path, route) in health::routes(judge_service) {
+                    server = server.with_route(path, route);
+                }
+
+                server
+                    .start()
+                    .await
+                    .map_err(|e| Error::msg(format!("{}", e)))
+            }
+        }
+    };
+
+    // Stop serving on SIGINT or SIGTERM, as well as when the server ends on
+    // its own, e.g. when the client closes stdin
+    let result = tokio::select! {
+        result = serve => result,
+        () = shutdown_signal() => Ok(()),
     };
 
-    // Create Server
-    let server = server_runtime::create_server(server_options);
+    tracing::info!("shutting down");
+    mcp_linux_ssh::tools::remove_control_dir();
+    // Dropping the guards flushes the log writers, so the last tool calls
+    // aren't lost
+    drop(audit_guard);
+    drop(guard);
+    result
+}
+
+/// Wait for SIGINT (ctrl-c) or, on Unix, SIGTERM, as sent by container
+/// runtimes and service managers to stop the server.
+async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Failed to listen for SIGINT: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    // Start!
-    server
-        .start()
-        .await
-        .map_err(|e| Error::msg(format!("{}", e)))
+    tokio::select! {
+        () = interrupt => tracing::info!("Received SIGINT"),
+        () = terminate => tracing::info!("Received SIGTERM"),
+    }
 }
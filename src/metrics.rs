@@ -0,0 +1,340 @@
+use axum::Router;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use hyper::server::conn::http1;
+use hyper_util::rt::TokioIo;
+use hyper_util::service::TowerToHyperService;
+use rust_mcp_sdk::schema::{CallToolResult, schema_utils::CallToolError};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::time::timeout;
+
+/// Upper bounds in seconds of the command duration histogram's buckets
+const DURATION_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0];
+
+/// How long a scraper's connection may stay open, from accepting it to
+/// sending the response, so that idle or slow clients can't hold it forever
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The metrics, only set once the metrics listener has started, so that
+/// recording them costs nothing when MCP_LINUX_SSH_METRICS_ADDR is unset
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// A histogram of durations, with a count per bucket of [`DURATION_BUCKETS`]
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [u64; DURATION_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if seconds <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+/// Counters and histograms of the tool calls handled since the server started
+#[derive(Debug, Default)]
+struct Metrics {
+    /// The tool names, so that calls to unknown tools share one label
+    /// instead of adding a series per name
+    tools: HashSet<String>,
+    tool_calls: Mutex<BTreeMap<(String, &'static str), u64>>,
+    command_durations: Mutex<BTreeMap<String, Histogram>>,
+    judge_decisions: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            tools: crate::tools::POSIXSSHTools::tools()
+                .into_iter()
+                .map(|tool| tool.name)
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// The label for `tool`: its name, or `unknown` for a tool that doesn't
+    /// exist
+    fn tool_label(&self, tool: &str) -> String {
+        if self.tools.contains(tool) {
+            tool.to_string()
+        } else {
+            "unknown".to_string()
+        }
+    }
+
+    /// Render the metrics in the Prometheus text exposition format
+    fn render(&self) -> String {
+        let mut text = String::new();
+
+        text.push_str("# HELP tool_calls_total Tool calls handled, by tool and outcome.\n");
+        text.push_str("# TYPE tool_calls_total counter\n");
+        for ((tool, outcome), count) in lock(&self.tool_calls).iter() {
+            let _ = writeln!(
+                text,
+                "tool_calls_total{{tool=\"{}\",outcome=\"{}\"}} {}",
+                escape_label(tool),
+                outcome,
+                count
+            );
+        }
+
+        text.push_str(
+            "# HELP ssh_command_duration_seconds Time taken to run the commands of allowed tool calls, by tool.\n",
+        );
+        text.push_str("# TYPE ssh_command_duration_seconds histogram\n");
+        for (tool, histogram) in lock(&self.command_durations).iter() {
+            let tool = escape_label(tool);
+            for (count, bound) in histogram.buckets.iter().zip(DURATION_BUCKETS) {
+                let _ = writeln!(
+                    text,
+                    "ssh_command_duration_seconds_bucket{{tool=\"{}\",le=\"{}\"}} {}",
+                    tool, bound, count
+                );
+            }
+            let _ = writeln!(
+                text,
+                "ssh_command_duration_seconds_bucket{{tool=\"{}\",le=\"+Inf\"}} {}",
+                tool, histogram.count
+            );
+            let _ = writeln!(
+                text,
+                "ssh_command_duration_seconds_sum{{tool=\"{}\"}} {}",
+                tool, histogram.sum
+            );
+            let _ = writeln!(
+                text,
+                "ssh_command_duration_seconds_count{{tool=\"{}\"}} {}",
+                tool, histogram.count
+            );
+        }
+
+        text.push_str("# HELP judge_decisions_total Decisions made by the judge, by decision.\n");
+        text.push_str("# TYPE judge_decisions_total counter\n");
+        for (decision, count) in lock(&self.judge_decisions).iter() {
+            let _ = writeln!(
+                text,
+                "judge_decisions_total{{decision=\"{}\"}} {}",
+                decision, count
+            );
+        }
+        text
+    }
+}
+
+/// Lock a metric, carrying on with the counts of a thread that panicked
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Escape a label value for the Prometheus text format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+/// The outcome of a tool call: `success`, `rejected` when the policy or the
+/// judge refused it, or `error`
+fn outcome(result: &Result<CallToolResult, CallToolError>) -> &'static str {
+    match result {
+        Ok(result) if crate::audit::succeeded(result) => "success",
+        Ok(_) => "error",
+        Err(e) if crate::tools::error_kind(&e.to_string()) == "rejected" => "rejected",
+        Err(_) => "error",
+    }
+}
+
+/// Count a tool call in `tool_calls_total` by its outcome
+pub fn record_tool_call(tool: &str, result: &Result<CallToolResult, CallToolError>) {
+    if let Some(metrics) = METRICS.get() {
+        *lock(&metrics.tool_calls)
+            .entry((metrics.tool_label(tool), outcome(result)))
+            .or_default() += 1;
+    }
+}
+
+/// Record how long an allowed tool call took to run its command in
+/// `ssh_command_duration_seconds`
+pub fn record_command_duration(tool: &str, duration: Duration) {
+    if let Some(metrics) = METRICS.get() {
+        lock(&metrics.command_durations)
+            .entry(metrics.tool_label(tool))
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+}
+
+/// Count a judge decision in `judge_decisions_total`: `allow`, `deny`, or
+/// `unavailable` when the LLM couldn't be asked or answered nonsense
+pub fn record_judge_decision(decision: &'static str) {
+    if let Some(metrics) = METRICS.get() {
+        *lock(&metrics.judge_decisions).entry(decision).or_default() += 1;
+    }
+}
+
+/// Start serving metrics at `/metrics` on the address in
+/// MCP_LINUX_SSH_METRICS_ADDR, if set. Metrics are only recorded once it is.
+pub async fn start() -> anyhow::Result<Option<SocketAddr>> {
+    let Some(addr) = crate::config::load_metrics_addr()? else {
+        return Ok(None);
+    };
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to listen for metrics on {}: {}", addr, e))?;
+    METRICS.get_or_init(Metrics::new);
+    tokio::spawn(serve(listener));
+    Ok(Some(addr))
+}
+
+/// Answer scrapes on `listener` until the server exits. Each connection
+/// answers a single request and is closed after [`CONNECTION_TIMEOUT`].
+async fn serve(listener: TcpListener) {
+    let router = Router::new().route("/metrics", get(scrape));
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let service = TowerToHyperService::new(router.clone());
+                tokio::spawn(async move {
+                    let connection = http1::Builder::new()
+                        .keep_alive(false)
+                        .serve_connection(TokioIo::new(stream), service);
+                    match timeout(CONNECTION_TIMEOUT, connection).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => tracing::debug!("Failed to answer metrics request: {}", e),
+                        Err(_) => tracing::debug!("Metrics connection timed out"),
+                    }
+                });
+            }
+            Err(e) => tracing::warn!("Failed to accept metrics connection: {}", e),
+        }
+    }
+}
+
+/// Answer `GET /metrics` with the metrics in the Prometheus text format
+async fn scrape() -> Response {
+    match METRICS.get() {
+        Some(metrics) => (
+            [(
+                header::CONTENT_TYPE,
+                "text/plain; version=0.0.4; charset=utf-8",
+            )],
+            metrics.render(),
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        *lock(&metrics.tool_calls)
+            .entry((metrics.tool_label("run_ssh_command"), "success"))
+            .or_default() += 2;
+        *lock(&metrics.tool_calls)
+            .entry((metrics.tool_label("no_such_tool"), "error"))
+            .or_default() += 1;
+        lock(&metrics.command_durations)
+            .entry("run_ssh_command".to_string())
+            .or_default()
+            .observe(0.75);
+        *lock(&metrics.judge_decisions).entry("deny").or_default() += 1;
+
+        let text = metrics.render();
+        assert!(
+            text.contains("tool_calls_total{tool=\"run_ssh_command\",outcome=\"success\"} 2\n")
+        );
+        assert!(text.contains("tool_calls_total{tool=\"unknown\",outcome=\"error\"} 1\n"));
+        assert!(text.contains(
+            "ssh_command_duration_seconds_bucket{tool=\"run_ssh_command\",le=\"0.5\"} 0\n"
+        ));
+        assert!(text.contains(
+            "ssh_command_duration_seconds_bucket{tool=\"run_ssh_command\",le=\"1\"} 1\n"
+        ));
+        assert!(text.contains(
+            "ssh_command_duration_seconds_bucket{tool=\"run_ssh_command\",le=\"+Inf\"} 1\n"
+        ));
+        assert!(text.contains("ssh_command_duration_seconds_sum{tool=\"run_ssh_command\"} 0.75\n"));
+        assert!(text.contains("judge_decisions_total{decision=\"deny\"} 1\n"));
+    }
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(
+            outcome(&Err(CallToolError::from_message(
+                "Tool call rejected by judge: deletes data"
+            ))),
+            "rejected"
+        );
+        assert_eq!(
+            outcome(&Err(CallToolError::from_message(
+                "SSH command timed out after 30 seconds"
+            ))),
+            "error"
+        );
+        let result = CallToolResult::text_content(vec![]);
+        assert_eq!(outcome(&Ok(result)), "success");
+
+        // A sequence that stopped at a failing step
+        let mut content = serde_json::Map::new();
+        content.insert("succeeded".to_string(), false.into());
+        content.insert("steps".to_string(), serde_json::json!([]));
+        let result = CallToolResult::text_content(vec![]).with_structured_content(content);
+        assert_eq!(outcome(&Ok(result)), "error");
+    }
+
+    /// Send `request` to `addr` and return the whole response
+    async fn request(addr: SocketAddr, request: &str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        METRICS.get_or_init(Metrics::new);
+        let listener = TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener));
+
+        let response = request(addr, "GET /metrics?x=1 HTTP/1.1\r\nHost: x\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("text/plain; version=0.0.4"));
+        assert!(response.contains("# TYPE tool_calls_total counter\n"));
+
+        let response = request(addr, "GET / HTTP/1.1\r\nHost: x\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404 "));
+
+        // A client that never finishes its request is disconnected
+        let started = std::time::Instant::now();
+        let response = request(addr, "GET /metrics HTTP/1.1\r\n").await;
+        assert!(response.is_empty());
+        assert!(started.elapsed() < CONNECTION_TIMEOUT + Duration::from_secs(5));
+    }
+}
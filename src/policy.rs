@@ -0,0 +1,981 @@
+use rust_mcp_sdk::schema::{RpcError, Tool, schema_utils::CallToolError};
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+/// Environment variable restricting the hosts the SSH tools may connect to:
+/// a comma-separated list of hostname globs and IP CIDR ranges.
+const ALLOWED_HOSTS_VAR: &str = "MCP_LINUX_SSH_ALLOWED_HOSTS";
+
+/// Environment variables holding comma-separated command basenames that the
+/// command tools refuse to run, or that are the only ones they will run.
+const DENY_COMMANDS_VAR: &str = "MCP_LINUX_SSH_DENY_COMMANDS";
+const ALLOW_COMMANDS_VAR: &str = "MCP_LINUX_SSH_ALLOW_COMMANDS";
+
+/// Environment variable holding the comma-separated names of the only tools
+/// the server advertises and runs.
+const ENABLED_TOOLS_VAR: &str = "MCP_LINUX_SSH_ENABLED_TOOLS";
+
+/// Environment variable holding the comma-separated paths of the ssh programs
+/// a tool call's `ssh_binary` may select, besides MCP_LINUX_SSH_SSH_BINARY.
+pub(crate) const ALLOWED_SSH_BINARIES_VAR: &str = "MCP_LINUX_SSH_ALLOWED_SSH_BINARIES";
+
+/// ssh options that change where a connection goes, reuse another host's
+/// connection or run local commands, rejected when the allowlist is set.
+const REDIRECTING_OPTIONS: &[&str] = &[
+    "HostName",
+    "ProxyJump",
+    "ProxyCommand",
+    "ProxyUseFdpass",
+    "ControlPath",
+    "LocalCommand",
+    "PermitLocalCommand",
+    "KnownHostsCommand",
+    "Match",
+];
+
+/// A pattern from the host allowlist
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum HostPattern {
+    /// A hostname glob where `*` matches any run of characters and `?` any
+    /// single character, compared case-insensitively.
+    Glob(String),
+    /// An IP address range in CIDR notation
+    Cidr(IpAddr, u8),
+    /// An entry that failed to parse. It never matches, so a typo narrows the
+    /// allowlist rather than widening it.
+    Invalid,
+}
+
+impl HostPattern {
+    fn parse(pattern: &str) -> Self {
+        match pattern.split_once('/') {
+            Some((address, prefix)) => {
+                let (Ok(address), Ok(prefix)) = (address.parse::<IpAddr>(), prefix.parse::<u8>())
+                else {
+                    tracing::warn!(
+                        "Ignoring invalid CIDR in {}: {}",
+                        ALLOWED_HOSTS_VAR,
+                        pattern
+                    );
+                    return HostPattern::Invalid;
+                };
+                let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+                if prefix > max_prefix {
+                    tracing::warn!(
+                        "Ignoring invalid CIDR in {}: {}",
+                        ALLOWED_HOSTS_VAR,
+                        pattern
+                    );
+                    return HostPattern::Invalid;
+                }
+                HostPattern::Cidr(address, prefix)
+            }
+            None => HostPattern::Glob(pattern.to_lowercase()),
+        }
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            HostPattern::Glob(pattern) => glob_match(pattern, &host.to_lowercase()),
+            HostPattern::Cidr(network, prefix) => host
+                .parse::<IpAddr>()
+                .is_ok_and(|address| in_network(address, *network, *prefix)),
+            HostPattern::Invalid => false,
+        }
+    }
+}
+
+/// Parse a comma-separated allowlist, skipping empty entries
+fn parse_allowed_hosts(value: &str) -> Vec<HostPattern> {
+    split_allowed_hosts(value).map(HostPattern::parse).collect()
+}
+
+fn split_allowed_hosts(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+}
+
+/// The host allowlist, or None when every host is allowed
+fn allowed_hosts() -> Option<&'static [HostPattern]> {
+    static ALLOWED_HOSTS: OnceLock<Option<Vec<HostPattern>>> = OnceLock::new();
+    ALLOWED_HOSTS
+        .get_or_init(|| {
+            std::env::var(ALLOWED_HOSTS_VAR)
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+                .map(|value| parse_allowed_hosts(&value))
+        })
+        .as_deref()
+}
+
+/// The entries of the host allowlist as configured, or None when every host
+/// is allowed
+pub(crate) fn allowed_host_patterns() -> Option<Vec<String>> {
+    std::env::var(ALLOWED_HOSTS_VAR)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| split_allowed_hosts(&value).map(str::to_string).collect())
+}
+
+/// Check the hosts a tool call would connect to, `remote_host` or each of
+/// `remote_hosts` and every hop of `jump_host`, against the allowlist in
+/// MCP_LINUX_SSH_ALLOWED_HOSTS.
+///
+/// Hosts are matched as given, without resolving names, so a CIDR range only
+/// matches calls that pass an IP address.
+pub fn check_hosts(params: &serde_json::Value) -> Result<(), CallToolError> {
+    match allowed_hosts() {
+        Some(patterns) => check_hosts_against(patterns, params),
+        None => Ok(()),
+    }
+}
+
+fn check_hosts_against(
+    patterns: &[HostPattern],
+    params: &serde_json::Value,
+) -> Result<(), CallToolError> {
+    let remote_hosts = target_hosts(params);
+    let jump_hosts = params
+        .get("jump_host")
+        .and_then(|v| v.as_str())
+        .into_iter()
+        .flat_map(|jump_host| jump_host.split(','));
+
+    // These options would connect somewhere other than the checked hosts
+    let options = params
+        .get("options")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str());
+    for option in options {
+        let key = option.split_once('=').map_or(option, |(key, _)| key).trim();
+        if REDIRECTING_OPTIONS
+            .iter()
+            .any(|redirecting| key.eq_ignore_ascii_case(redirecting))
+        {
+            return Err(CallToolError::new(
+                RpcError::invalid_request().with_message(format!(
+                    "Option {} is not allowed with {}",
+                    key, ALLOWED_HOSTS_VAR
+                )),
+            ));
+        }
+    }
+
+    for destination in remote_hosts.chain(jump_hosts) {
+        let host = host_name(destination);
+        if !patterns.iter().any(|pattern| pattern.matches(host)) {
+            return Err(CallToolError::new(
+                RpcError::invalid_request()
+                    .with_message(format!("Host {} is not in {}", host, ALLOWED_HOSTS_VAR)),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The hosts a tool call runs on: its `remote_host` or each of its
+/// `remote_hosts`, as given. Empty for local tools.
+pub(crate) fn target_hosts(params: &serde_json::Value) -> impl Iterator<Item = &str> {
+    params
+        .get("remote_host")
+        .and_then(|v| v.as_str())
+        .into_iter()
+        .chain(
+            params
+                .get("remote_hosts")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v.as_str()),
+        )
+}
+
+/// Command basenames the command tools may or may not run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct CommandPolicy {
+    pub deny: Vec<String>,
+    /// When set, only these commands may run
+    pub allow: Option<Vec<String>>,
+}
+
+impl CommandPolicy {
+    fn from_env() -> Self {
+        let list = |var| {
+            std::env::var(var)
+                .ok()
+                .map(|value| parse_command_list(&value))
+                .filter(|commands| !commands.is_empty())
+        };
+        CommandPolicy {
+            deny: list(DENY_COMMANDS_VAR).unwrap_or_default(),
+            allow: list(ALLOW_COMMANDS_VAR),
+        }
+    }
+
+    /// Check the program a command runs, resolved through wrappers such as
+    /// `sudo` and `sh -c`, against the lists. A command whose program can't be
+    /// resolved is rejected whenever either list is set.
+    fn check_command(&self, command: &TargetCommand) -> Result<(), CallToolError> {
+        if self.deny.is_empty() && self.allow.is_none() {
+            return Ok(());
+        }
+        match command.resolve() {
+            Some(name) => self.check(&name),
+            None => Err(CallToolError::from_message(format!(
+                "Command {} is not allowed with {} or {} set: the program it runs can't be determined",
+                command.cmd, DENY_COMMANDS_VAR, ALLOW_COMMANDS_VAR
+            ))),
+        }
+    }
+
+    /// Check a command against the deny list, then the allow list
+    fn check(&self, cmd: &str) -> Result<(), CallToolError> {
+        let name = command_name(cmd);
+        if self.deny.iter().any(|denied| denied == name) {
+            return Err(CallToolError::from_message(format!(
+                "Command {} is denied by {}",
+                name, DENY_COMMANDS_VAR
+            )));
+        }
+        if let Some(allow) = &self.allow
+            && !allow.iter().any(|allowed| allowed == name)
+        {
+            return Err(CallToolError::from_message(format!(
+                "Command {} is not in {}",
+                name, ALLOW_COMMANDS_VAR
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn parse_command_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|command| command_name(command.trim()).to_string())
+        .filter(|command| !command.is_empty())
+        .collect()
+}
+
+/// The basename of a command, so that /usr/bin/rm and rm are treated alike
+fn command_name(cmd: &str) -> &str {
+    cmd.rsplit('/').next().unwrap_or(cmd)
+}
+
+/// Check the command a command tool call (run_ssh_command,
+/// run_ssh_sudo_command, run_with_stdin or run_local_command) runs, or each of
+/// run_sequence's `steps`, against MCP_LINUX_SSH_DENY_COMMANDS and
+/// MCP_LINUX_SSH_ALLOW_COMMANDS. Calls to other tools have no `cmd` and always
+/// pass.
+pub fn check_command(params: &serde_json::Value) -> Result<(), CallToolError> {
+    target_commands(params)
+        .iter()
+        .try_for_each(|command| command_policy().check_command(command))
+}
+
+/// The command allow and deny lists, loaded from environment variables on
+/// first use
+pub(crate) fn command_policy() -> &'static CommandPolicy {
+    static POLICY: OnceLock<CommandPolicy> = OnceLock::new();
+    POLICY.get_or_init(CommandPolicy::from_env)
+}
+
+/// The names of the only tools that are enabled, loaded from
+/// MCP_LINUX_SSH_ENABLED_TOOLS on first use. None when every tool is enabled.
+pub(crate) fn enabled_tools() -> Option<&'static [String]> {
+    static ENABLED_TOOLS: OnceLock<Option<Vec<String>>> = OnceLock::new();
+    ENABLED_TOOLS
+        .get_or_init(|| {
+            let names = parse_tool_list(&std::env::var(ENABLED_TOOLS_VAR).ok()?);
+            let known = crate::tools::POSIXSSHTools::tools();
+            for name in &names {
+                if !known.iter().any(|tool| &tool.name == name) {
+                    tracing::warn!("Ignoring unknown tool in {}: {}", ENABLED_TOOLS_VAR, name);
+                }
+            }
+            Some(names).filter(|names| !names.is_empty())
+        })
+        .as_deref()
+}
+
+/// The ssh programs a tool call may select with `ssh_binary`, loaded from
+/// MCP_LINUX_SSH_ALLOWED_SSH_BINARIES on first use
+pub(crate) fn allowed_ssh_binaries() -> &'static [String] {
+    static ALLOWED_SSH_BINARIES: OnceLock<Vec<String>> = OnceLock::new();
+    ALLOWED_SSH_BINARIES.get_or_init(|| {
+        std::env::var(ALLOWED_SSH_BINARIES_VAR)
+            .map(|value| parse_tool_list(&value))
+            .unwrap_or_default()
+    })
+}
+
+fn parse_tool_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Keep the tools that `enabled` lists, or all of them when it is None
+pub(crate) fn filter_tools(tools: Vec<Tool>, enabled: Option<&[String]>) -> Vec<Tool> {
+    match enabled {
+        Some(enabled) => tools
+            .into_iter()
+            .filter(|tool| enabled.contains(&tool.name))
+            .collect(),
+        None => tools,
+    }
+}
+
+/// Reject calls to tools left out of MCP_LINUX_SSH_ENABLED_TOOLS
+pub fn check_tool_enabled(tool_name: &str) -> Result<(), CallToolError> {
+    check_tool_enabled_in(tool_name, enabled_tools())
+}
+
+fn check_tool_enabled_in(tool_name: &str, enabled: Option<&[String]>) -> Result<(), CallToolError> {
+    match enabled {
+        Some(enabled) if !enabled.iter().any(|name| name == tool_name) => {
+            Err(CallToolError::from_message(format!(
+                "Tool {} is disabled: it is not in {}",
+                tool_name, ENABLED_TOOLS_VAR
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A command a tool call runs
+#[derive(Debug, PartialEq, Eq)]
+struct TargetCommand<'a> {
+    cmd: &'a str,
+    args: Vec<&'a str>,
+    /// Whether the command is run through `remote_shell`, which may be any
+    /// program interpreting the command line
+    remote_shell: bool,
+}
+
+impl TargetCommand<'_> {
+    fn from_json(params: &serde_json::Value) -> Option<TargetCommand<'_>> {
+        Some(TargetCommand {
+            cmd: params.get("cmd")?.as_str()?,
+            args: params
+                .get("args")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|arg| arg.as_str())
+                .collect(),
+            remote_shell: params
+                .get("remote_shell")
+                .is_some_and(|shell| !shell.is_null()),
+        })
+    }
+
+    /// The basename of the program the command runs, or None when it can't be
+    /// told
+    fn resolve(&self) -> Option<String> {
+        if self.remote_shell {
+            return None;
+        }
+        let words = std::iter::once(self.cmd)
+            .chain(self.args.iter().copied())
+            .map(str::to_string)
+            .collect::<Vec<String>>();
+        resolve_program(&words)
+    }
+}
+
+/// The commands a tool call runs: its own and the one of each of its `steps`
+fn target_commands(params: &serde_json::Value) -> Vec<TargetCommand<'_>> {
+    TargetCommand::from_json(params)
+        .into_iter()
+        .chain(
+            params
+                .get("steps")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(TargetCommand::from_json),
+        )
+        .collect()
+}
+
+/// A program that runs the command given in its arguments
+struct Wrapper {
+    name: &'static str,
+    /// Options that take the next word as their value
+    value_options: &'static [&'static str],
+    /// Options that make it run something other than its arguments, such as
+    /// a login shell
+    unresolvable_options: &'static [&'static str],
+    /// Words preceding the command that aren't options, such as timeout's
+    /// duration
+    operands: usize,
+}
+
+const WRAPPERS: &[Wrapper] = &[
+    Wrapper {
+        name: "env",
+        value_options: &["-u", "--unset", "-C", "--chdir"],
+        unresolvable_options: &["-S", "--split-string"],
+        operands: 0,
+    },
+    Wrapper {
+        name: "sudo",
+        value_options: &[
+            "-C",
+            "--close-from",
+            "-D",
+            "--chdir",
+            "-g",
+            "--group",
+            "-h",
+            "--host",
+            "-p",
+            "--prompt",
+            "-R",
+            "--chroot",
+            "-r",
+            "--role",
+            "-T",
+            "--command-timeout",
+            "-t",
+            "--type",
+            "-U",
+            "--other-user",
+            "-u",
+            "--user",
+        ],
+        unresolvable_options: &["-e", "--edit", "-i", "--login", "-s", "--shell"],
+        operands: 0,
+    },
+    Wrapper {
+        name: "doas",
+        value_options: &["-C", "-u"],
+        unresolvable_options: &["-s"],
+        operands: 0,
+    },
+    Wrapper {
+        name: "nice",
+        value_options: &["-n", "--adjustment"],
+        unresolvable_options: &[],
+        operands: 0,
+    },
+    Wrapper {
+        name: "ionice",
+        value_options: &["-c", "--class", "-n", "--classdata"],
+        unresolvable_options: &["-p", "--pid", "-P", "--pgid", "-u", "--uid"],
+        operands: 0,
+    },
+    Wrapper {
+        name: "nohup",
+        value_options: &[],
+        unresolvable_options: &[],
+        operands: 0,
+    },
+    Wrapper {
+        name: "timeout",
+        value_options: &["-k", "--kill-after", "-s", "--signal"],
+        unresolvable_options: &[],
+        operands: 1,
+    },
+    Wrapper {
+        name: "stdbuf",
+        value_options: &["-i", "--input", "-o", "--output", "-e", "--error"],
+        unresolvable_options: &[],
+        operands: 0,
+    },
+    Wrapper {
+        name: "time",
+        value_options: &["-f", "--format", "-o", "--output"],
+        unresolvable_options: &[],
+        operands: 0,
+    },
+    Wrapper {
+        name: "xargs",
+        value_options: &[
+            "-a",
+            "--arg-file",
+            "-d",
+            "--delimiter",
+            "-E",
+            "-I",
+            "-L",
+            "--max-lines",
+            "-n",
+            "--max-args",
+            "-P",
+            "--max-procs",
+            "-s",
+            "--max-chars",
+        ],
+        unresolvable_options: &[],
+        operands: 0,
+    },
+    Wrapper {
+        name: "command",
+        value_options: &[],
+        unresolvable_options: &[],
+        operands: 0,
+    },
+    Wrapper {
+        name: "exec",
+        value_options: &["-a"],
+        unresolvable_options: &[],
+        operands: 0,
+    },
+    Wrapper {
+        name: "busybox",
+        value_options: &[],
+        unresolvable_options: &[],
+        operands: 0,
+    },
+];
+
+/// Shells, which run the script given with `-c` or else read one from stdin
+/// or a file
+const SHELLS: &[&str] = &["sh", "bash", "dash", "zsh", "ksh", "mksh", "ash", "fish"];
+
+/// Shell builtins that run a command built at runtime
+const SHELL_EVALUATORS: &[&str] = &["eval", ".", "source"];
+
+/// Characters that make a shell script more than a single simple command:
+/// lists, pipes, redirections, substitutions, grouping and globs
+const SHELL_METACHARACTERS: &[char] = &[
+    ';', '&', '|', '<', '>', '$', '`', '(', ')', '{', '}', '\n', '*', '?', '[',
+];
+
+/// Resolve the basename of the program `words` runs through wrappers such as
+/// `env`, `sudo`, `timeout` and `sh -c`. None when it can't be told, such as
+/// for a shell reading its script from stdin or a script with pipes or
+/// substitutions.
+fn resolve_program(words: &[String]) -> Option<String> {
+    let (cmd, args) = words.split_first()?;
+    let name = command_name(cmd);
+    if let Some(wrapper) = WRAPPERS.iter().find(|wrapper| wrapper.name == name) {
+        let command = wrapped_command(wrapper, args)?;
+        if command.is_empty() {
+            return Some(name.to_string());
+        }
+        return resolve_program(command);
+    }
+    if SHELLS.contains(&name) {
+        let script = shell_script(args)?;
+        if script.contains(SHELL_METACHARACTERS) {
+            return None;
+        }
+        let words = shlex::split(script)?;
+        let command = words
+            .iter()
+            .position(|word| !is_assignment(word))
+            .map_or(&[][..], |start| &words[start..]);
+        return resolve_program(command);
+    }
+    if SHELL_EVALUATORS.contains(&name) {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// The command a wrapper runs, after its options, assignments and operands.
+/// None when an option makes it run something else.
+fn wrapped_command<'a>(wrapper: &Wrapper, args: &'a [String]) -> Option<&'a [String]> {
+    let mut rest = args;
+    while let Some((word, tail)) = rest.split_first() {
+        if word == "--" {
+            rest = tail;
+            break;
+        }
+        if !word.starts_with('-') {
+            break;
+        }
+        rest = tail;
+        let takes_value = if let Some(long) = word.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, _)) => (name, true),
+                None => (long, false),
+            };
+            let option = format!("--{}", name);
+            if wrapper.unresolvable_options.contains(&option.as_str()) {
+                return None;
+            }
+            !value && wrapper.value_options.contains(&option.as_str())
+        } else {
+            // A cluster of short options, where an option taking a value
+            // ends it and takes the rest of the word or the next one
+            let mut takes_value = false;
+            for (index, flag) in word.char_indices().skip(1) {
+                let option = format!("-{}", flag);
+                if wrapper.unresolvable_options.contains(&option.as_str()) {
+                    return None;
+                }
+                if wrapper.value_options.contains(&option.as_str()) {
+                    takes_value = index + flag.len_utf8() == word.len();
+                    break;
+                }
+            }
+            takes_value
+        };
+        if takes_value {
+            rest = rest.get(1..)?;
+        }
+    }
+    if wrapper.name == "env" {
+        let start = rest
+            .iter()
+            .position(|word| !word.contains('='))
+            .unwrap_or(rest.len());
+        rest = &rest[start..];
+    }
+    rest.get(wrapper.operands..).or(Some(&[]))
+}
+
+/// The script a shell runs with `-c`, or None when it reads one from stdin or
+/// a file
+fn shell_script(args: &[String]) -> Option<&str> {
+    let mut command = false;
+    let mut rest = args;
+    while let Some((word, tail)) = rest.split_first() {
+        if !(word.starts_with('-') || word.starts_with('+')) || word == "-" || word == "--" {
+            break;
+        }
+        rest = tail;
+        if word.starts_with("--") {
+            if matches!(word.as_str(), "--rcfile" | "--init-file") {
+                rest = rest.get(1..)?;
+            }
+            continue;
+        }
+        command |= word.starts_with('-') && word.contains('c');
+        if word.ends_with(['o', 'O']) {
+            rest = rest.get(1..)?;
+        }
+    }
+    if !command {
+        return None;
+    }
+    let script = match rest.split_first()? {
+        (word, tail) if word == "--" => tail.first()?,
+        (word, _) => word,
+    };
+    Some(script)
+}
+
+/// Whether a word of a shell command sets a variable, e.g. `LANG=C`
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Strip the user and port from a `[user@]host[:port]` destination. Bare IPv6
+/// addresses are returned whole; with a port they must be in brackets.
+pub(crate) fn host_name(destination: &str) -> &str {
+    let host = destination
+        .trim()
+        .rsplit_once('@')
+        .map_or(destination.trim(), |(_, host)| host);
+    if let Some(rest) = host.strip_prefix('[') {
+        return rest.split_once(']').map_or(rest, |(host, _)| host);
+    }
+    match host.split_once(':') {
+        Some((name, port)) if !port.contains(':') => name,
+        _ => host,
+    }
+}
+
+/// Match `text` against a glob where `*` matches any run of characters and `?`
+/// any single character.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at
+    let mut backtrack = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, star_t)) = backtrack {
+            // Let the `*` absorb one more character and try again
+            p = star + 1;
+            t = star_t + 1;
+            backtrack = Some((star, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether `address` is in the `network`/`prefix` range
+fn in_network(address: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (address, network) {
+        (IpAddr::V4(address), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(address) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(address), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(address) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.example.com", "web1.example.com"));
+        assert!(glob_match("web?", "web1"));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("db*prod", "db-eu-prod"));
+        assert!(!glob_match("*.example.com", "example.com"));
+        assert!(!glob_match("web?", "web10"));
+        assert!(!glob_match("web1", "web1.evil.com"));
+    }
+
+    #[test]
+    fn test_cidr_match() {
+        let pattern = HostPattern::parse("10.0.0.0/8");
+        assert!(pattern.matches("10.1.2.3"));
+        assert!(!pattern.matches("11.0.0.1"));
+        assert!(!pattern.matches("web1"));
+        assert!(HostPattern::parse("0.0.0.0/0").matches("192.168.1.1"));
+        assert!(HostPattern::parse("fd00::/8").matches("fd12::1"));
+        assert!(!HostPattern::parse("fd00::/8").matches("10.1.2.3"));
+        assert_eq!(HostPattern::parse("10.0.0.0/33"), HostPattern::Invalid);
+        assert_eq!(HostPattern::parse("web/8"), HostPattern::Invalid);
+    }
+
+    #[test]
+    fn test_command_policy_deny() {
+        let policy = CommandPolicy {
+            deny: parse_command_list("rm, /sbin/reboot,"),
+            allow: None,
+        };
+        assert!(policy.check("rm").is_err());
+        assert!(policy.check("/bin/rm").is_err());
+        assert!(policy.check("reboot").is_err());
+        assert!(policy.check("ls").is_ok());
+    }
+
+    #[test]
+    fn test_command_policy_allow() {
+        let policy = CommandPolicy {
+            deny: parse_command_list("journalctl"),
+            allow: Some(parse_command_list("uptime,df,journalctl")),
+        };
+        assert!(policy.check("uptime").is_ok());
+        assert!(policy.check("/usr/bin/df").is_ok());
+        assert!(policy.check("rm").is_err());
+        // The deny list wins over the allow list
+        assert!(policy.check("journalctl").is_err());
+    }
+
+    #[test]
+    fn test_target_commands() {
+        let params = serde_json::json!({"cmd": "rm", "args": ["-rf", "/tmp/x"]});
+        assert_eq!(
+            target_commands(&params),
+            vec![TargetCommand {
+                cmd: "rm",
+                args: vec!["-rf", "/tmp/x"],
+                remote_shell: false,
+            }]
+        );
+
+        let params = serde_json::json!({
+            "steps": [{"cmd": "systemctl", "args": ["stop", "app"]}, {"cmd": "/bin/rm"}]
+        });
+        let commands = target_commands(&params);
+        assert_eq!(
+            commands.iter().map(|c| c.cmd).collect::<Vec<_>>(),
+            vec!["systemctl", "/bin/rm"]
+        );
+
+        let params = serde_json::json!({"remote_host": "web1", "path": "/etc"});
+        assert!(target_commands(&params).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_program() {
+        let resolve = |words: &[&str]| {
+            resolve_program(&words.iter().map(|w| w.to_string()).collect::<Vec<_>>())
+        };
+        let cases: &[(&[&str], Option<&str>)] = &[
+            (&["/bin/rm", "-rf", "/"], Some("rm")),
+            (
+                &["env", "-u", "HOME", "LANG=C", "rm", "-rf", "/"],
+                Some("rm"),
+            ),
+            (&["env", "-i", "LANG=C", "/usr/bin/rm"], Some("rm")),
+            (&["env"], Some("env")),
+            (&["sudo", "-u", "root", "rm"], Some("rm")),
+            (&["sudo", "-Eu", "root", "rm"], Some("rm")),
+            (&["sudo", "--user=root", "--", "rm"], Some("rm")),
+            (&["sudo", "-i"], None),
+            (&["sudo", "-s", "rm"], None),
+            (&["nice", "-n", "10", "ionice", "-c", "3", "rm"], Some("rm")),
+            (&["timeout", "-s", "KILL", "5", "rm"], Some("rm")),
+            (
+                &["timeout", "--kill-after=1", "5s", "nohup", "rm"],
+                Some("rm"),
+            ),
+            (&["xargs", "-n", "1", "rm"], Some("rm")),
+            (&["sh", "-c", "rm -rf /tmp/x"], Some("rm")),
+            (&["bash", "-ec", "LANG=C 'r''m' -rf /"], Some("rm")),
+            (&["sh", "-o", "errexit", "-c", "sudo rm"], Some("rm")),
+            (&["sh", "-c", "uptime; rm -rf /"], None),
+            (&["sh", "-c", "$(echo rm) -rf /"], None),
+            (&["sh", "-c", "/bin/r? -rf /"], None),
+            (&["sh", "-c", "eval rm"], None),
+            (&["sh"], None),
+            (&["bash", "-s"], None),
+            (&["sh", "/tmp/script.sh"], None),
+            (&["uptime"], Some("uptime")),
+        ];
+        for (words, expected) in cases {
+            assert_eq!(resolve(words).as_deref(), *expected, "{:?}", words);
+        }
+    }
+
+    #[test]
+    fn test_command_policy_resolves_wrappers() {
+        let deny = CommandPolicy {
+            deny: parse_command_list("rm"),
+            allow: None,
+        };
+        let allow = CommandPolicy {
+            deny: Vec::new(),
+            allow: Some(parse_command_list("uptime")),
+        };
+        let bypasses = [
+            serde_json::json!({"cmd": "env", "args": ["rm", "-rf", "/"]}),
+            serde_json::json!({"cmd": "sh", "args": ["-c", "rm -rf /"]}),
+            serde_json::json!({"cmd": "sudo", "args": ["-u", "root", "rm", "-rf", "/"]}),
+            // run_ssh_sudo_command's run_as doesn't change the command
+            serde_json::json!({"cmd": "rm", "args": ["-rf", "/"], "run_as": "postgres"}),
+            serde_json::json!({"cmd": "rm", "remote_shell": "/bin/bash"}),
+            // run_with_stdin piping a script into a shell
+            serde_json::json!({"cmd": "sh", "stdin": "rm -rf /"}),
+            serde_json::json!({"steps": [{"cmd": "uptime"}, {"cmd": "timeout", "args": ["5", "rm"]}]}),
+        ];
+        for params in &bypasses {
+            for policy in [&deny, &allow] {
+                let result = target_commands(params)
+                    .iter()
+                    .try_for_each(|command| policy.check_command(command));
+                let error = result.expect_err(&params.to_string());
+                let message = error.to_string();
+                assert_eq!(
+                    crate::tools::error_kind(&message),
+                    "rejected",
+                    "{}",
+                    message
+                );
+            }
+        }
+
+        let params = serde_json::json!({"cmd": "sudo", "args": ["-n", "uptime"]});
+        for policy in [&deny, &allow] {
+            assert!(policy.check_command(&target_commands(&params)[0]).is_ok());
+        }
+
+        // Without lists, nothing needs resolving
+        let params = serde_json::json!({"cmd": "sh", "remote_shell": "/bin/bash"});
+        assert!(
+            CommandPolicy::default()
+                .check_command(&target_commands(&params)[0])
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_enabled_tools() {
+        let enabled = parse_tool_list(" run_ssh_command, check_connection,,");
+        assert_eq!(enabled, ["run_ssh_command", "check_connection"]);
+
+        let tools = filter_tools(crate::tools::POSIXSSHTools::tools(), Some(&enabled));
+        let names: Vec<&str> = tools.iter().map(|tool| tool.name.as_str()).collect();
+        assert_eq!(names, ["run_ssh_command", "check_connection"]);
+        assert!(!names.contains(&"run_local_command"));
+        assert_eq!(
+            filter_tools(crate::tools::POSIXSSHTools::tools(), None).len(),
+            crate::tools::POSIXSSHTools::tools().len()
+        );
+
+        assert!(check_tool_enabled_in("run_ssh_command", Some(&enabled)).is_ok());
+        let error = check_tool_enabled_in("run_local_command", Some(&enabled)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Tool run_local_command is disabled: it is not in MCP_LINUX_SSH_ENABLED_TOOLS"
+        );
+        assert!(check_tool_enabled_in("run_local_command", None).is_ok());
+    }
+
+    #[test]
+    fn test_host_name() {
+        assert_eq!(host_name("web1"), "web1");
+        assert_eq!(host_name("alice@web1:2222"), "web1");
+        assert_eq!(host_name("[fd12::1]:22"), "fd12::1");
+        assert_eq!(host_name("fd12::1"), "fd12::1");
+    }
+
+    #[test]
+    fn test_check_hosts() {
+        let patterns = parse_allowed_hosts("*.example.com, 10.0.0.0/8,");
+        assert_eq!(patterns.len(), 2);
+
+        let allowed = serde_json::json!({
+            "remote_host": "root@web1.example.com",
+            "jump_host": "bastion.example.com,10.0.0.1:2222",
+        });
+        assert!(check_hosts_against(&patterns, &allowed).is_ok());
+
+        let denied = serde_json::json!({ "remote_host": "web1.other.com" });
+        assert!(check_hosts_against(&patterns, &denied).is_err());
+
+        let denied_jump = serde_json::json!({
+            "remote_host": "web1.example.com",
+            "jump_host": "bastion.other.com",
+        });
+        assert!(check_hosts_against(&patterns, &denied_jump).is_err());
+
+        let fleet = serde_json::json!({ "remote_hosts": ["web1.example.com", "10.1.2.3"] });
+        assert!(check_hosts_against(&patterns, &fleet).is_ok());
+        let denied_fleet =
+            serde_json::json!({ "remote_hosts": ["web1.example.com", "db.other.com"] });
+        assert!(check_hosts_against(&patterns, &denied_fleet).is_err());
+
+        let redirected = serde_json::json!({
+            "remote_host": "web1.example.com",
+            "options": ["ConnectTimeout=5", "proxycommand=nc evil.com 22"],
+        });
+        assert!(check_hosts_against(&patterns, &redirected).is_err());
+        let shared = serde_json::json!({
+            "remote_host": "web1.example.com",
+            "options": ["ControlPath=/tmp/cm-db.other.com"],
+        });
+        assert!(check_hosts_against(&patterns, &shared).is_err());
+
+        // Local commands have no host to check
+        let local = serde_json::json!({ "command": "ls" });
+        assert!(check_hosts_against(&patterns, &local).is_ok());
+    }
+}
@@ -0,0 +1,94 @@
+use rust_mcp_sdk::McpServer;
+use rust_mcp_sdk::schema::{CallToolRequestParams, ProgressNotificationParams};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Time between progress notifications, the first one included, so that
+/// commands finishing quickly send none.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The progress token a client sets in the request's `_meta.progressToken`
+/// to ask for progress notifications, if any.
+pub fn progress_token(params: &CallToolRequestParams) -> Option<serde_json::Value> {
+    serde_json::to_value(params)
+        .ok()?
+        .pointer("/_meta/progressToken")
+        .filter(|token| !token.is_null())
+        .cloned()
+}
+
+/// The progress notification sent `elapsed` into a call. The progress is the
+/// number of seconds elapsed, without a total since the command's duration
+/// isn't known.
+fn progress_params(
+    token: &serde_json::Value,
+    elapsed: Duration,
+) -> Option<ProgressNotificationParams> {
+    let seconds = elapsed.as_secs();
+    serde_json::from_value(serde_json::json!({
+        "progressToken": token,
+        "progress": seconds,
+        "message": format!("still running, {}s elapsed", seconds),
+    }))
+    .ok()
+}
+
+/// Run `future`, sending a progress notification for `token` through
+/// `runtime` every [`PROGRESS_INTERVAL`] until it completes. Failing to send
+/// a notification doesn't affect the call.
+pub async fn with_progress<F: Future>(
+    future: F,
+    runtime: Arc<dyn McpServer>,
+    token: serde_json::Value,
+) -> F::Output {
+    let started = Instant::now();
+    let mut interval = tokio::time::interval_at(
+        tokio::time::Instant::now() + PROGRESS_INTERVAL,
+        PROGRESS_INTERVAL,
+    );
+    tokio::pin!(future);
+    loop {
+        // Notify outside of select!, whose state would otherwise hold the
+        // future's output type across the await and make the caller non-Send
+        tokio::select! {
+            output = &mut future => return output,
+            _ = interval.tick() => {}
+        }
+        let Some(params) = progress_params(&token, started.elapsed()) else {
+            continue;
+        };
+        if let Err(e) = runtime.notify_progress(params).await {
+            tracing::debug!("Failed to send progress notification: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_params() {
+        let params = progress_params(&"call-1".into(), Duration::from_millis(20_500)).unwrap();
+        let json = serde_json::to_value(params).unwrap();
+        assert_eq!(json["progressToken"], "call-1");
+        assert_eq!(json["progress"], 20.0);
+        assert_eq!(json["message"], "still running, 20s elapsed");
+    }
+
+    #[test]
+    fn test_progress_token() {
+        let params: CallToolRequestParams = serde_json::from_value(serde_json::json!({
+            "name": "run_ssh_command",
+            "arguments": {"cmd": "uptime"},
+            "_meta": {"progressToken": 7}
+        }))
+        .unwrap();
+        assert_eq!(progress_token(&params), Some(7.into()));
+
+        let params: CallToolRequestParams =
+            serde_json::from_value(serde_json::json!({"name": "run_ssh_command"})).unwrap();
+        assert_eq!(progress_token(&params), None);
+    }
+}
@@ -0,0 +1,153 @@
+use rust_mcp_sdk::schema::{
+    ContentBlock, GetPromptResult, Prompt, PromptArgument, PromptMessage, Role, RpcError,
+    TextContent,
+};
+use std::collections::HashMap;
+
+/// Name of the guided incident triage prompt.
+pub const TRIAGE_HOST: &str = "triage_host";
+
+/// List the prompts exposed by the server
+pub fn list_prompts() -> Vec<Prompt> {
+    vec![Prompt {
+        arguments: vec![
+            PromptArgument {
+                description: Some("The host to investigate".to_string()),
+                name: "remote_host".to_string(),
+                required: Some(true),
+                title: Some("Remote Host".to_string()),
+            },
+            PromptArgument {
+                description: Some(
+                    "What is wrong, e.g. \"the site is slow\" or \"nginx keeps restarting\""
+                        .to_string(),
+                ),
+                name: "symptom".to_string(),
+                required: Some(true),
+                title: Some("Symptom".to_string()),
+            },
+        ],
+        description: Some(
+            "Investigate a misbehaving host step by step: load, disk, memory, failed services and recent logs"
+                .to_string(),
+        ),
+        icons: vec![],
+        meta: None,
+        name: TRIAGE_HOST.to_string(),
+        title: Some("Triage Host".to_string()),
+    }]
+}
+
+/// Get a prompt by name, filled in with `arguments`
+pub fn get_prompt(
+    name: &str,
+    arguments: Option<&HashMap<String, String>>,
+) -> Result<GetPromptResult, RpcError> {
+    match name {
+        TRIAGE_HOST => {
+            let remote_host = required_argument(arguments, "remote_host")?;
+            let symptom = required_argument(arguments, "symptom")?;
+            Ok(GetPromptResult {
+                description: Some(format!("Triage {}: {}", remote_host, symptom)),
+                messages: triage_host_messages(remote_host, symptom),
+                meta: None,
+            })
+        }
+        _ => Err(RpcError::invalid_params().with_message(format!("Unknown prompt: {}", name))),
+    }
+}
+
+fn required_argument<'a>(
+    arguments: Option<&'a HashMap<String, String>>,
+    name: &str,
+) -> Result<&'a str, RpcError> {
+    arguments
+        .and_then(|arguments| arguments.get(name))
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| {
+            RpcError::invalid_params().with_message(format!("Missing required argument: {}", name))
+        })
+}
+
+/// The investigation plan for `triage_host`, one message per step
+fn triage_host_messages(remote_host: &str, symptom: &str) -> Vec<PromptMessage> {
+    let steps = [
+        format!(
+            "{} is having a problem: {}. Investigate it using the run_ssh_command tool \
+            with remote_host set to {}. Work through the following steps in order, \
+            summarizing what you find after each one before moving on. Only read \
+            state; do not change anything on the host without asking first.",
+            remote_host, symptom, remote_host
+        ),
+        "1. Load: run `uptime` and `ps -eo pid,user,%cpu,%mem,etime,cmd --sort=-%cpu` \
+        (first 15 lines) to check load averages and the busiest processes."
+            .to_string(),
+        "2. Disk: run `df -h` and `df -i` to check for full filesystems or exhausted \
+        inodes."
+            .to_string(),
+        "3. Memory: run `free -m` and check `dmesg` or the journal for OOM killer \
+        activity."
+            .to_string(),
+        "4. Services: run `systemctl --failed` and inspect the status of any failed \
+        or restarting unit related to the symptom."
+            .to_string(),
+        "5. Logs: run `journalctl -p warning --since \"1 hour ago\" --no-pager` and \
+        read the logs of the affected service to find errors that line up with the \
+        symptom."
+            .to_string(),
+        format!(
+            "Finally, explain the most likely cause of \"{}\" with the evidence for it, \
+            and propose a fix. List any commands that would change the host and wait \
+            for confirmation before running them.",
+            symptom
+        ),
+    ];
+
+    steps
+        .into_iter()
+        .map(|text| PromptMessage {
+            content: ContentBlock::TextContent(TextContent::from(text)),
+            role: Role::User,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_get_triage_host_prompt() {
+        let arguments = arguments(&[("remote_host", "web1"), ("symptom", "the site is slow")]);
+        let result = get_prompt(TRIAGE_HOST, Some(&arguments)).unwrap();
+
+        assert!(result.messages.len() > 1);
+        match &result.messages[0].content {
+            ContentBlock::TextContent(content) => {
+                assert!(content.text.contains("web1"));
+                assert!(content.text.contains("the site is slow"));
+            }
+            other => panic!("Unexpected content: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_get_triage_host_prompt_requires_arguments() {
+        let arguments = arguments(&[("remote_host", "web1")]);
+        assert!(get_prompt(TRIAGE_HOST, Some(&arguments)).is_err());
+        assert!(get_prompt(TRIAGE_HOST, None).is_err());
+    }
+
+    #[test]
+    fn test_get_unknown_prompt() {
+        assert!(get_prompt("unknown", None).is_err());
+    }
+}
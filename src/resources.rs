@@ -0,0 +1,832 @@
+use anyhow::{Error, Result};
+use expand_tilde::expand_tilde;
+use rust_mcp_sdk::schema::{
+    ReadResourceContent, ReadResourceResult, Resource, RpcError, TextResourceContents,
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Comma-separated list of the public key file names in ~/.ssh.
+pub const PUBLIC_KEYS_URI: &str = "file:///public_keys";
+/// JSON array describing each public key in ~/.ssh.
+pub const PUBLIC_KEYS_JSON_URI: &str = "file:///public_keys.json";
+/// JSON array of the Host aliases defined in ~/.ssh/config.
+pub const SSH_HOSTS_URI: &str = "file:///ssh_hosts";
+/// JSON array of the host keys in ~/.ssh/known_hosts and known_hosts2.
+pub const KNOWN_HOSTS_URI: &str = "file:///known_hosts";
+/// JSON object of the server's effective configuration, secrets redacted.
+pub const SERVER_CONFIG_URI: &str = "config://server";
+
+/// The known_hosts files read, in ~/.ssh
+const KNOWN_HOSTS_FILES: [&str; 2] = ["known_hosts", "known_hosts2"];
+
+/// How deeply Include directives may nest, as in ssh
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// A public key found in ~/.ssh
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PublicKey {
+    pub filename: String,
+    pub algorithm: String,
+    pub comment: Option<String>,
+    pub fingerprint: Option<String>,
+}
+
+/// A Host alias defined in ~/.ssh/config, with the settings ssh would use for
+/// it. Settings the config doesn't set are `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SshHost {
+    pub alias: String,
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+}
+
+/// A host key in ~/.ssh/known_hosts. Hashed entries only record a hash of the
+/// host, so `host` is `None` and `hashed` is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KnownHost {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub hashed: bool,
+    pub key_type: String,
+    pub fingerprint: Option<String>,
+    /// `@cert-authority` or `@revoked`, if the entry has one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+}
+
+/// List the resources exposed by the server
+pub fn list_resources() -> Vec<Resource> {
+    vec![
+        Resource {
+            annotations: None,
+            description: Some(
+                "Comma-separated list of the public key files available in ~/.ssh".to_string(),
+            ),
+            icons: vec![],
+            meta: None,
+            mime_type: Some("text/plain".to_string()),
+            name: "public_keys".to_string(),
+            size: None,
+            title: Some("Public Keys".to_string()),
+            uri: PUBLIC_KEYS_URI.to_string(),
+        },
+        Resource {
+            annotations: None,
+            description: Some(
+                "The public keys available in ~/.ssh with their algorithm, comment and fingerprint"
+                    .to_string(),
+            ),
+            icons: vec![],
+            meta: None,
+            mime_type: Some("application/json".to_string()),
+            name: "public_keys.json".to_string(),
+            size: None,
+            title: Some("Public Keys (JSON)".to_string()),
+            uri: PUBLIC_KEYS_JSON_URI.to_string(),
+        },
+        Resource {
+            annotations: None,
+            description: Some(
+                "The Host aliases defined in ~/.ssh/config with their HostName, User and Port"
+                    .to_string(),
+            ),
+            icons: vec![],
+            meta: None,
+            mime_type: Some("application/json".to_string()),
+            name: "ssh_hosts".to_string(),
+            size: None,
+            title: Some("SSH Hosts".to_string()),
+            uri: SSH_HOSTS_URI.to_string(),
+        },
+        Resource {
+            annotations: None,
+            description: Some(
+                "The host keys in ~/.ssh/known_hosts with their key type and fingerprint"
+                    .to_string(),
+            ),
+            icons: vec![],
+            meta: None,
+            mime_type: Some("application/json".to_string()),
+            name: "known_hosts".to_string(),
+            size: None,
+            title: Some("Known Hosts".to_string()),
+            uri: KNOWN_HOSTS_URI.to_string(),
+        },
+        Resource {
+            annotations: None,
+            description: Some(
+                "The server's effective configuration: transport, timeouts, host and command \
+                lists, and whether and how the judge runs, with secrets redacted"
+                    .to_string(),
+            ),
+            icons: vec![],
+            meta: None,
+            mime_type: Some("application/json".to_string()),
+            name: "server_config".to_string(),
+            size: None,
+            title: Some("Server Configuration".to_string()),
+            uri: SERVER_CONFIG_URI.to_string(),
+        },
+    ]
+}
+
+/// Read a resource by URI. The URI is normalized first, so resources are
+/// looked up by their canonical URI and no URI can reach outside of them.
+/// `judge_enabled` tells whether the server runs the judge, for
+/// config://server.
+pub async fn read_resource(
+    uri: &str,
+    judge_enabled: bool,
+) -> std::result::Result<ReadResourceResult, RpcError> {
+    let normalized = normalize_uri(uri)?;
+    let (mime_type, text) = match normalized.as_str() {
+        PUBLIC_KEYS_URI => {
+            let keys = public_keys().await.map_err(internal_error)?;
+            let filenames: Vec<String> = keys.into_iter().map(|key| key.filename).collect();
+            ("text/plain", filenames.join(","))
+        }
+        PUBLIC_KEYS_JSON_URI => {
+            let keys = public_keys().await.map_err(internal_error)?;
+            let json = serde_json::to_string_pretty(&keys).map_err(|e| internal_error(e.into()))?;
+            ("application/json", json)
+        }
+        SSH_HOSTS_URI => {
+            let hosts = ssh_hosts().map_err(internal_error)?;
+            let json =
+                serde_json::to_string_pretty(&hosts).map_err(|e| internal_error(e.into()))?;
+            ("application/json", json)
+        }
+        KNOWN_HOSTS_URI => {
+            let hosts = known_hosts().await.map_err(internal_error)?;
+            let json =
+                serde_json::to_string_pretty(&hosts).map_err(|e| internal_error(e.into()))?;
+            ("application/json", json)
+        }
+        SERVER_CONFIG_URI => {
+            let config = crate::config::effective_config(judge_enabled);
+            let json =
+                serde_json::to_string_pretty(&config).map_err(|e| internal_error(e.into()))?;
+            ("application/json", json)
+        }
+        _ => {
+            return Err(
+                RpcError::invalid_params().with_message(format!("Unknown resource: {}", uri))
+            );
+        }
+    };
+
+    Ok(ReadResourceResult {
+        contents: vec![ReadResourceContent::TextResourceContents(
+            TextResourceContents {
+                meta: None,
+                mime_type: Some(mime_type.to_string()),
+                text,
+                uri: uri.to_string(),
+            },
+        )],
+        meta: None,
+    })
+}
+
+/// Normalize a file:// resource URI: percent-escapes are decoded, and empty
+/// and `.` path components are dropped, so that file:///./public_keys is
+/// file:///public_keys. URIs with `..` components are rejected outright
+/// rather than resolved, so a resource backed by a file or directory can never
+/// be used to reach a path above it. Other URIs are returned unchanged.
+fn normalize_uri(uri: &str) -> std::result::Result<String, RpcError> {
+    let Some(path) = uri
+        .get(.."file://".len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case("file://"))
+        .map(|_| &uri["file://".len()..])
+    else {
+        return Ok(uri.to_string());
+    };
+
+    let path = percent_decode(path);
+    let mut components = Vec::new();
+    for component in path.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                return Err(RpcError::invalid_params().with_message(format!(
+                    "Path traversal is not allowed in resource URIs: {}",
+                    uri
+                )));
+            }
+            component => components.push(component),
+        }
+    }
+    Ok(format!("file:///{}", components.join("/")))
+}
+
+/// Decode the %XX escapes in a URI path. Invalid escapes are kept as is.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = (bytes[i] == b'%')
+            .then(|| path.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn internal_error(e: Error) -> RpcError {
+    RpcError::internal_error().with_message(e.to_string())
+}
+
+/// Find the public keys in ~/.ssh, sorted by file name
+async fn public_keys() -> Result<Vec<PublicKey>> {
+    let ssh_dir = expand_tilde("~/.ssh")
+        .map_err(|e| Error::msg(format!("Failed to expand ~/.ssh: {}", e)))?
+        .into_owned();
+
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(&ssh_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "pub"))
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(Error::msg(format!("Failed to read ~/.ssh: {}", e))),
+    };
+    paths.sort();
+
+    let mut keys = Vec::new();
+    for path in paths {
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                tracing::warn!("Failed to read public key {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let fingerprint = fingerprint(&path).await;
+        if let Some(key) = parse_public_key(&filename, &contents, fingerprint) {
+            keys.push(key);
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Compute a public key's fingerprint with `ssh-keygen -lf`
+async fn fingerprint(path: &Path) -> Option<String> {
+    let output = Command::new("ssh-keygen")
+        .arg("-lf")
+        .arg(path)
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    // Output looks like: 256 SHA256:abc... comment (ED25519)
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .nth(1)
+        .map(str::to_string)
+}
+
+/// Parse a public key file in the form `algorithm base64-key [comment]`
+fn parse_public_key(
+    filename: &str,
+    contents: &str,
+    fingerprint: Option<String>,
+) -> Option<PublicKey> {
+    let line = contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+
+    let mut fields = line.splitn(3, char::is_whitespace);
+    let algorithm = fields.next()?.to_string();
+    fields.next()?;
+    let comment = fields
+        .next()
+        .map(str::trim)
+        .filter(|comment| !comment.is_empty())
+        .map(str::to_string);
+
+    Some(PublicKey {
+        filename: filename.to_string(),
+        algorithm,
+        comment,
+        fingerprint,
+    })
+}
+
+/// Read the host keys in ~/.ssh/known_hosts and known_hosts2, in file order.
+/// Missing files have no keys.
+async fn known_hosts() -> Result<Vec<KnownHost>> {
+    let ssh_dir = expand_tilde("~/.ssh")
+        .map_err(|e| Error::msg(format!("Failed to expand ~/.ssh: {}", e)))?
+        .into_owned();
+
+    let mut contents = String::new();
+    for file in KNOWN_HOSTS_FILES {
+        match std::fs::read_to_string(ssh_dir.join(file)) {
+            Ok(file_contents) => {
+                contents.push_str(&file_contents);
+                contents.push('\n');
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(Error::msg(format!("Failed to read ~/.ssh/{}: {}", file, e))),
+        }
+    }
+
+    let entries: Vec<(KnownHost, &str)> = contents.lines().filter_map(parse_known_host).collect();
+    let fingerprints = key_fingerprints(
+        entries
+            .iter()
+            .map(|(host, key)| (host.key_type.as_str(), *key)),
+    )
+    .await;
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, (mut host, _))| {
+            host.fingerprint = fingerprints.get(&index).cloned();
+            host
+        })
+        .collect())
+}
+
+/// Parse a known_hosts line in the form `[marker] hosts key-type base64-key
+/// [comment]`, returning the entry and its base64 key
+fn parse_known_host(line: &str) -> Option<(KnownHost, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut fields = line.split_whitespace().peekable();
+    let marker = fields
+        .next_if(|field| field.starts_with('@'))
+        .map(str::to_string);
+    let hosts = fields.next()?;
+    let key_type = fields.next()?.to_string();
+    let key = fields.next()?;
+
+    let hashed = hosts.starts_with("|1|");
+    Some((
+        KnownHost {
+            host: (!hashed).then(|| hosts.to_string()),
+            hashed,
+            key_type,
+            fingerprint: None,
+            marker,
+        },
+        key,
+    ))
+}
+
+/// Compute the fingerprints of public keys, given as key type and base64 key,
+/// with a single `ssh-keygen -lf -`. Each key is passed with its index as its
+/// comment, so the fingerprints can be matched to the keys even though
+/// ssh-keygen skips invalid ones. Returns the fingerprints by index.
+async fn key_fingerprints<'a>(
+    keys: impl Iterator<Item = (&'a str, &'a str)>,
+) -> std::collections::HashMap<usize, String> {
+    let input: String = keys
+        .enumerate()
+        .map(|(index, (key_type, key))| format!("{} {} {}\n", key_type, key, index))
+        .collect();
+    if input.is_empty() {
+        return Default::default();
+    }
+
+    let mut cmd = Command::new("ssh-keygen");
+    cmd.args(["-lf", "-"]);
+    let output = match crate::tools::output_with_stdin(&mut cmd, input.as_bytes()).await {
+        Ok(output) => output,
+        Err(e) => {
+            tracing::warn!("Failed to run ssh-keygen: {}", e);
+            return Default::default();
+        }
+    };
+
+    // Output looks like: 256 SHA256:abc... 0 (ED25519)
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            let fingerprint = fields.next()?.to_string();
+            let index = fields.next()?.parse().ok()?;
+            Some((index, fingerprint))
+        })
+        .collect()
+}
+
+/// Find the Host aliases defined in ~/.ssh/config. A missing config defines
+/// none.
+fn ssh_hosts() -> Result<Vec<SshHost>> {
+    Ok(ssh_hosts_from(&ssh_config_path()?))
+}
+
+/// The path of the user's ssh config file, ~/.ssh/config
+pub(crate) fn ssh_config_path() -> Result<PathBuf> {
+    expand_tilde("~/.ssh/config")
+        .map(|config| config.into_owned())
+        .map_err(|e| Error::msg(format!("Failed to expand ~/.ssh/config: {}", e)))
+}
+
+/// Whether `destination`, as `[user@]host[:port]`, names a Host alias defined
+/// in an ssh config file or the files it includes
+pub(crate) fn is_ssh_config_alias(config: &Path, destination: &str) -> bool {
+    let host = crate::policy::host_name(destination);
+    ssh_hosts_from(config)
+        .iter()
+        .any(|alias| alias.alias.eq_ignore_ascii_case(host))
+}
+
+/// A Host block of an ssh config file: its patterns and its settings, as
+/// lowercase keywords and values
+type HostBlock = (Vec<String>, Vec<(String, String)>);
+
+/// Find the Host aliases defined in an ssh config file and the files it
+/// includes, resolving the HostName, User and Port of each as ssh would: the
+/// first value set by a matching Host block wins. Host patterns with
+/// wildcards or negations only contribute settings; Match blocks are skipped.
+fn ssh_hosts_from(config: &Path) -> Vec<SshHost> {
+    let base_dir = config.parent().unwrap_or(Path::new("."));
+    let mut lines = Vec::new();
+    read_config_lines(config, base_dir, 0, &mut lines);
+
+    // Settings before the first Host apply to every host
+    let mut blocks: Vec<HostBlock> = vec![(vec!["*".to_string()], vec![])];
+    for (keyword, value) in lines {
+        match keyword.as_str() {
+            "host" => blocks.push((
+                value.split_whitespace().map(str::to_string).collect(),
+                vec![],
+            )),
+            // Match conditions aren't evaluated, so the block never applies
+            "match" => blocks.push((vec![], vec![])),
+            _ => {
+                if let Some((_, settings)) = blocks.last_mut() {
+                    settings.push((keyword, value));
+                }
+            }
+        }
+    }
+
+    let mut aliases: Vec<&str> = Vec::new();
+    for (patterns, _) in &blocks[1..] {
+        for pattern in patterns {
+            if !pattern.contains(['*', '?', '!']) && !aliases.contains(&pattern.as_str()) {
+                aliases.push(pattern);
+            }
+        }
+    }
+
+    aliases
+        .into_iter()
+        .map(|alias| {
+            let mut host = SshHost {
+                alias: alias.to_string(),
+                hostname: None,
+                user: None,
+                port: None,
+            };
+            let matching = blocks
+                .iter()
+                .filter(|(patterns, _)| host_patterns_match(patterns, alias));
+            for (_, settings) in matching {
+                for (keyword, value) in settings {
+                    match keyword.as_str() {
+                        "hostname" if host.hostname.is_none() => {
+                            host.hostname = Some(value.replace("%h", alias));
+                        }
+                        "user" if host.user.is_none() => host.user = Some(value.clone()),
+                        "port" if host.port.is_none() => host.port = value.parse().ok(),
+                        _ => {}
+                    }
+                }
+            }
+            host
+        })
+        .collect()
+}
+
+/// Read the keyword and value of each line of an ssh config file, with the
+/// lines of included files in place of their Include directives. Keywords are
+/// lowercased. Files that can't be read are skipped.
+fn read_config_lines(
+    path: &Path,
+    base_dir: &Path,
+    depth: usize,
+    lines: &mut Vec<(String, String)>,
+) {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!("Failed to read {}: {}", path.display(), e);
+            }
+            return;
+        }
+    };
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((keyword, value)) = line.split_once(|c: char| c.is_whitespace() || c == '=')
+        else {
+            continue;
+        };
+        let keyword = keyword.to_lowercase();
+        let value = value
+            .trim_start_matches(|c: char| c.is_whitespace() || c == '=')
+            .trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value);
+
+        if keyword != "include" {
+            lines.push((keyword, value.to_string()));
+            continue;
+        }
+        if depth >= MAX_INCLUDE_DEPTH {
+            tracing::warn!("Ignoring Include nested too deeply in {}", path.display());
+            continue;
+        }
+        for pattern in value.split_whitespace() {
+            let pattern = match expand_tilde(pattern) {
+                Ok(pattern) => pattern,
+                Err(_) => continue,
+            };
+            let mut paths = crate::tools::glob_paths(base_dir, &pattern);
+            paths.sort();
+            for included in paths {
+                read_config_lines(&included, base_dir, depth + 1, lines);
+            }
+        }
+    }
+}
+
+/// Whether a Host line's patterns match a host: at least one pattern must
+/// match and no negated (`!`) pattern may.
+fn host_patterns_match(patterns: &[String], host: &str) -> bool {
+    let host = host.to_lowercase();
+    let matches = |pattern: &str| crate::policy::glob_match(&pattern.to_lowercase(), &host);
+    let mut matched = false;
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(negated) if matches(negated) => return false,
+            Some(_) => {}
+            None => matched |= matches(pattern),
+        }
+    }
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::TestPath;
+
+    #[test]
+    fn test_parse_public_key() {
+        let key = parse_public_key(
+            "id_ed25519.pub",
+            "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIG user@example.com\n",
+            Some("SHA256:abc".to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(key.filename, "id_ed25519.pub");
+        assert_eq!(key.algorithm, "ssh-ed25519");
+        assert_eq!(key.comment.as_deref(), Some("user@example.com"));
+        assert_eq!(key.fingerprint.as_deref(), Some("SHA256:abc"));
+    }
+
+    #[test]
+    fn test_parse_public_key_without_comment() {
+        let key = parse_public_key("id_rsa.pub", "ssh-rsa AAAAB3NzaC1yc2E", None).unwrap();
+
+        assert_eq!(key.algorithm, "ssh-rsa");
+        assert!(key.comment.is_none());
+    }
+
+    #[test]
+    fn test_parse_public_key_invalid() {
+        assert!(parse_public_key("empty.pub", "", None).is_none());
+        assert!(parse_public_key("garbage.pub", "ssh-rsa", None).is_none());
+    }
+
+    #[test]
+    fn test_ssh_hosts_from() {
+        let dir = TestPath::dir("ssh_hosts");
+        std::fs::create_dir_all(dir.join("config.d")).unwrap();
+        std::fs::write(
+            dir.join("config"),
+            "Include config.d/*\n\
+             \n\
+             Host db db-replica\n\
+             \x20   HostName %h.internal.example.com\n\
+             \x20   User postgres\n\
+             \n\
+             Host web\n\
+             \x20   HostName=10.0.0.5\n\
+             \x20   Port 2222\n\
+             \n\
+             Match host web\n\
+             \x20   User ignored\n\
+             \n\
+             Host * !db\n\
+             \x20   User admin\n\
+             \x20   Port 22\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("config.d/bastion"),
+            "Host bastion\n    HostName bastion.example.com\n",
+        )
+        .unwrap();
+
+        let hosts = ssh_hosts_from(&dir.join("config"));
+
+        let host = |alias: &str, hostname: &str, user: &str, port: Option<u16>| SshHost {
+            alias: alias.to_string(),
+            hostname: Some(hostname.to_string()),
+            user: Some(user.to_string()),
+            port,
+        };
+        assert_eq!(
+            hosts,
+            vec![
+                host("bastion", "bastion.example.com", "admin", Some(22)),
+                host("db", "db.internal.example.com", "postgres", None),
+                host(
+                    "db-replica",
+                    "db-replica.internal.example.com",
+                    "postgres",
+                    Some(22)
+                ),
+                host("web", "10.0.0.5", "admin", Some(2222)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_ssh_config_alias() {
+        let config = TestPath::new("ssh_config_alias");
+        std::fs::write(
+            &config,
+            "Host prod-db\n    User postgres\n\nHost *.internal !bastion\n    User admin\n",
+        )
+        .unwrap();
+
+        assert!(is_ssh_config_alias(&config, "prod-db"));
+        assert!(is_ssh_config_alias(&config, "alice@PROD-DB:2222"));
+        assert!(!is_ssh_config_alias(&config, "example.com"));
+        assert!(!is_ssh_config_alias(&config, "db.internal"));
+        assert!(!is_ssh_config_alias(&config, "bastion"));
+
+        // Nor does a missing config define any
+        drop(config);
+        let config = TestPath::new("ssh_config_alias");
+        assert!(!is_ssh_config_alias(&config, "prod-db"));
+    }
+
+    #[test]
+    fn test_parse_known_host() {
+        let (host, key) =
+            parse_known_host("github.com,140.82.112.3 ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMq")
+                .unwrap();
+        assert_eq!(
+            host,
+            KnownHost {
+                host: Some("github.com,140.82.112.3".to_string()),
+                hashed: false,
+                key_type: "ssh-ed25519".to_string(),
+                fingerprint: None,
+                marker: None,
+            }
+        );
+        assert_eq!(key, "AAAAC3NzaC1lZDI1NTE5AAAAIOMq");
+
+        let (host, _) = parse_known_host(
+            "|1|F1E1KeoE/eEWhi10WpGv4OdiO6Y=|3988QV0VE8wmZL7suNrYQLITLCg= ssh-rsa AAAAB3",
+        )
+        .unwrap();
+        assert!(host.hashed);
+        assert!(host.host.is_none());
+        assert_eq!(
+            serde_json::to_value(&host).unwrap(),
+            serde_json::json!({"hashed": true, "key_type": "ssh-rsa", "fingerprint": null})
+        );
+
+        let (host, _) =
+            parse_known_host("@revoked *.example.com ssh-ed25519 AAAAC3 old key").unwrap();
+        assert_eq!(host.marker.as_deref(), Some("@revoked"));
+        assert_eq!(host.host.as_deref(), Some("*.example.com"));
+
+        assert!(parse_known_host("# comment").is_none());
+        assert!(parse_known_host("example.com ssh-rsa").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_key_fingerprints() {
+        // The ed25519 key from RFC 8709's test vectors, with an invalid key
+        // before it that ssh-keygen skips
+        let key = "AAAAC3NzaC1lZDI1NTE5AAAAINdamAGCsQq31Uv+08lkBzoO4XLz2qYjJa8CGmj3B1Ea";
+        let fingerprints =
+            key_fingerprints([("ssh-ed25519", "invalid"), ("ssh-ed25519", key)].into_iter()).await;
+        if fingerprints.is_empty() {
+            // ssh-keygen isn't installed
+            return;
+        }
+        assert!(!fingerprints.contains_key(&0));
+        assert!(fingerprints[&1].starts_with("SHA256:"));
+    }
+
+    #[test]
+    fn test_ssh_hosts_from_missing_config() {
+        assert!(ssh_hosts_from(Path::new("/nonexistent/.ssh/config")).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_read_unknown_resource() {
+        assert!(read_resource("file:///etc/passwd", false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_server_config() {
+        let result = read_resource(SERVER_CONFIG_URI, true).await.unwrap();
+        let ReadResourceContent::TextResourceContents(content) = &result.contents[0] else {
+            panic!("expected text contents");
+        };
+        assert_eq!(content.mime_type.as_deref(), Some("application/json"));
+        let config: serde_json::Value = serde_json::from_str(&content.text).unwrap();
+        assert_eq!(config["judge"]["enabled"], true);
+    }
+
+    #[test]
+    fn test_normalize_uri() {
+        assert_eq!(
+            normalize_uri("file:///public_keys").unwrap(),
+            PUBLIC_KEYS_URI
+        );
+        assert_eq!(
+            normalize_uri("FILE:///./public_keys/").unwrap(),
+            PUBLIC_KEYS_URI
+        );
+        assert_eq!(
+            normalize_uri("file:////public_keys%2Ejson").unwrap(),
+            PUBLIC_KEYS_JSON_URI
+        );
+        assert_eq!(
+            normalize_uri("https://example.com/../x").unwrap(),
+            "https://example.com/../x"
+        );
+    }
+
+    #[test]
+    fn test_normalize_uri_rejects_traversal() {
+        for uri in [
+            "file:///public_keys/../etc/passwd",
+            "file:///../etc/passwd",
+            "file:///public_keys/..",
+            "file:///public_keys/%2e%2e/etc/passwd",
+            "file:///public_keys/%2E./etc/passwd",
+            "file:///public_keys\\..\\etc",
+        ] {
+            let error = normalize_uri(uri).unwrap_err();
+            assert!(error.message.contains("Path traversal"), "{}", uri);
+        }
+        // Dots within a component aren't traversal
+        assert!(normalize_uri("file:///public_keys..json").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_read_resource_rejects_traversal() {
+        let error = read_resource("file:///public_keys/../etc/passwd", false)
+            .await
+            .unwrap_err();
+        assert!(error.message.contains("Path traversal"));
+    }
+}
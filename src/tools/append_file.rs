@@ -0,0 +1,201 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+/// Script appending stdin to the file $1 with `tee -a`. Exits with status 3
+/// if the file doesn't exist and $2 isn't 1. When $3 is 1 and the file
+/// doesn't end with a newline, one is added first so the content starts on a
+/// line of its own. Prints the size of the file after the append.
+const APPEND_SCRIPT: &str = r#"set -e
+if [ ! -e "$1" ] && [ "$2" != 1 ]; then
+    echo "$1: No such file" >&2
+    exit 3
+fi
+if [ "$3" = 1 ] && [ -s "$1" ] && [ -n "$(tail -c 1 -- "$1")" ]; then
+    printf '\n' >> "$1"
+fi
+tee -a -- "$1" > /dev/null
+wc -c < "$1""#;
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "append_file",
+        description = "Append content to a file on a remote POSIX compatible system (Linux, BSD, macOS). \
+        The content is streamed to tee -a over SSH, so it is appended literally without any shell quoting. \
+        Use this instead of echo >> to add lines to logs or configuration files.",
+        title = "Append File"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct AppendFile {
+        /// The content to append to the file.
+        pub content: String,
+        /// The path of the file on the remote machine. The directory must exist.
+        pub remote_path: String,
+        /// Create the file if it doesn't exist. Defaults to true; when false, appending to a missing file is an error.
+        pub create_if_missing: Option<bool>,
+        /// Make sure the content is appended as whole lines: a newline is added before it if the file doesn't end with one, and after it if the content doesn't. Defaults to false, appending the content exactly as given.
+        pub ensure_newline: Option<bool>,
+        /// The host to append to the file on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+impl AppendFile {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let create_if_missing = if self.create_if_missing.unwrap_or(true) {
+            "1"
+        } else {
+            "0"
+        };
+        let ensure_newline = self.ensure_newline.unwrap_or(false);
+        let content = self.content_to_append();
+
+        let connection = self.connection();
+        let mut cmd = super::ssh::ssh_command(
+            &connection,
+            "sh",
+            &[
+                "-c",
+                APPEND_SCRIPT,
+                "sh",
+                self.remote_path.as_str(),
+                create_if_missing,
+                if ensure_newline { "1" } else { "0" },
+            ],
+        )?;
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(
+                &cmd,
+                &self.remote_host,
+                Some(&content),
+            ));
+        }
+
+        let output =
+            super::ssh::run_ssh_with_stdin(&mut cmd, content.as_bytes(), timeout_seconds).await?;
+        if !output.status.success() {
+            return Err(CallToolError::from_message(format!(
+                "Failed to append to {}: {}",
+                self.remote_path,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        // wc -c prints the size of the file after the append
+        let size = String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse::<u64>()
+            .ok();
+
+        let mut structured_content = serde_json::Map::new();
+        structured_content.insert(
+            "path".to_string(),
+            serde_json::Value::from(self.remote_path.as_str()),
+        );
+        structured_content.insert("bytes".to_string(), serde_json::Value::from(content.len()));
+        structured_content.insert("size".to_string(), serde_json::Value::from(size));
+
+        let mut text = format!("Appended {} bytes to {}", content.len(), self.remote_path);
+        if let Some(size) = size {
+            text.push_str(&format!(", now {} bytes", size));
+        }
+        Ok(CallToolResult::text_content(vec![TextContent::from(text)])
+            .with_structured_content(structured_content))
+    }
+
+    /// The content sent to the file, with a trailing newline added when
+    /// `ensure_newline` is set and the content doesn't end with one
+    fn content_to_append(&self) -> String {
+        let mut content = self.content.clone();
+        if self.ensure_newline.unwrap_or(false) && !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content
+    }
+
+    /// Describe the append, e.g. "append 12 bytes of content to
+    /// /etc/hosts on prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        let mut description = format!(
+            "append {} bytes of content to {} {}",
+            self.content.len(),
+            super::shell_quote(&self.remote_path),
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        );
+        if !self.create_if_missing.unwrap_or(true) {
+            description.push_str(", only if it exists");
+        }
+        description
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        self.common_connection(&self.remote_host)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::tools::{TestPath, run_script};
+
+    #[test]
+    fn test_content_to_append() {
+        let tool = |content: &str, ensure_newline| AppendFile {
+            content: content.to_string(),
+            ensure_newline: Some(ensure_newline),
+            ..Default::default()
+        };
+        assert_eq!(tool("a=1", false).content_to_append(), "a=1");
+        assert_eq!(tool("a=1", true).content_to_append(), "a=1\n");
+        assert_eq!(tool("a=1\n", true).content_to_append(), "a=1\n");
+        assert_eq!(tool("", true).content_to_append(), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_append_script() {
+        let dir = TestPath::dir("append_file");
+        let path = dir.join("-hosts");
+
+        // The path is passed relative to the directory, so it looks like an
+        // option to tail and tee
+        let append = |content: &str, create: &str, newline: &str| {
+            let args = ["-hosts", create, newline];
+            run_script(APPEND_SCRIPT, &args, content.as_bytes(), Some(&dir))
+        };
+
+        // A missing file is only created when asked to
+        let output = append("10.0.0.1 db", "0", "0");
+        assert_eq!(output.status.code(), Some(3));
+        assert!(!path.exists());
+
+        let output = append("10.0.0.1 db", "1", "0");
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "11");
+
+        // The file doesn't end with a newline, so one is added first
+        let output = append("10.0.0.2 web\n", "0", "1");
+        assert!(output.status.success());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "10.0.0.1 db\n10.0.0.2 web\n"
+        );
+
+        let output = append("10.0.0.3 cache\n", "0", "1");
+        assert!(output.status.success());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "10.0.0.1 db\n10.0.0.2 web\n10.0.0.3 cache\n"
+        );
+    }
+}
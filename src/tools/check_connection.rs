@@ -0,0 +1,258 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+use std::time::Instant;
+use tokio::time::{Duration, timeout};
+
+/// ssh messages that mean the host was reached but authentication failed.
+const AUTH_FAILURES: [&str; 3] = [
+    "Permission denied",
+    "Too many authentication failures",
+    "Host key verification failed",
+];
+
+super::ssh_tool! {
+    reuse_connection = "Check through a shared master connection to the host (SSH ControlMaster multiplexing), if one is open. Defaults to false so that the connection and authentication are checked from scratch.";
+    connect_timeout_seconds = "Timeout in seconds for establishing the TCP connection, passed to ssh as ConnectTimeout. Defaults to timeout_seconds.";
+    #[mcp_tool(
+        name = "check_connection",
+        description = "Check whether a remote host is reachable over SSH and whether authentication works, without running a real command. Returns reachable, auth_ok, latency_ms and the server's version banner. Use this before running commands on a host that may be down or misconfigured.",
+        title = "Check Connection"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct CheckConnection {
+        /// The host to check. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for connecting and authenticating. Defaults to 10 seconds, and is limited to the server's maximum timeout if one is set.
+        pub timeout_seconds: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+/// The outcome of a connection check.
+#[derive(Debug, Clone, PartialEq, Eq, ::serde::Serialize)]
+struct ConnectionStatus {
+    reachable: bool,
+    auth_ok: bool,
+    latency_ms: u64,
+    banner: Option<String>,
+    error: Option<String>,
+}
+
+impl CheckConnection {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = match self.timeout_seconds.unwrap_or(10) {
+            0 => 10,
+            timeout_seconds => timeout_seconds,
+        };
+        let timeout_seconds = super::clamp_timeout(timeout_seconds, super::max_timeout());
+        let connection = self.connection(timeout_seconds);
+        let mut cmd = super::ssh::ssh_command(&connection, "true", &[])?;
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(&cmd, &self.remote_host, None));
+        }
+
+        let start = Instant::now();
+        // ConnectTimeout only covers the TCP connection, so the whole check is
+        // also bounded, with a margin for ssh to report its own timeout
+        let result = timeout(Duration::from_secs(timeout_seconds + 5), cmd.output()).await;
+        let latency_ms = start.elapsed().as_millis() as u64;
+
+        let status = match result {
+            Ok(Ok(output)) => connection_status(
+                output.status.code(),
+                &String::from_utf8_lossy(&output.stderr),
+                latency_ms,
+            ),
+            Ok(Err(e)) => {
+                return Err(CallToolError::from_message(format!(
+                    "Failed to execute ssh command: {}",
+                    e
+                )));
+            }
+            Err(_) => ConnectionStatus {
+                reachable: false,
+                auth_ok: false,
+                latency_ms,
+                banner: None,
+                error: Some(format!(
+                    "Connection check timed out after {} seconds",
+                    timeout_seconds
+                )),
+            },
+        };
+
+        let text = match (status.reachable, status.auth_ok) {
+            (true, true) => format!("{} is reachable and authentication works", self.remote_host),
+            (true, false) => format!(
+                "{} is reachable but authentication failed",
+                self.remote_host
+            ),
+            _ => format!("{} is not reachable", self.remote_host),
+        };
+        let structured_content = match serde_json::to_value(&status) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(text)])
+            .with_structured_content(structured_content))
+    }
+
+    /// Describe the check, e.g. "check the SSH connection to prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        match self
+            .jump_host
+            .as_deref()
+            .filter(|jump_host| !jump_host.is_empty())
+        {
+            Some(jump_host) => format!(
+                "check the SSH connection to {} via {}",
+                self.remote_host, jump_host
+            ),
+            None => format!("check the SSH connection to {}", self.remote_host),
+        }
+    }
+
+    fn connection(&self, timeout_seconds: u64) -> SshConnectionParams {
+        // Fail instead of prompting, and log the server's version banner.
+        // BatchMode would disable the password authentication sshpass uses.
+        let connect_timeout_seconds = self.connect_timeout_seconds.unwrap_or(timeout_seconds);
+        let mut options = vec![
+            format!("ConnectTimeout={}", connect_timeout_seconds),
+            "LogLevel=DEBUG1".to_string(),
+        ];
+        if self.password.is_none() {
+            options.push("BatchMode=yes".to_string());
+        }
+        options.extend(self.options.iter().flatten().cloned());
+
+        SshConnectionParams {
+            // ConnectTimeout is set in the options
+            connect_timeout_seconds: None,
+            // A shared master connection would skip the handshake being checked
+            reuse_connection: Some(self.reuse_connection.unwrap_or(false)),
+            options: Some(options),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// Interpret the exit status and stderr of `ssh ... true`. ssh exits with 255
+/// when it fails to connect or authenticate; anything else comes from the
+/// remote `true` and means the check passed.
+fn connection_status(status_code: Option<i32>, stderr: &str, latency_ms: u64) -> ConnectionStatus {
+    let banner = parse_banner(stderr);
+    match status_code {
+        Some(255) | None => {
+            let auth_failed = AUTH_FAILURES.iter().any(|failure| stderr.contains(failure));
+            ConnectionStatus {
+                reachable: auth_failed || banner.is_some(),
+                auth_ok: false,
+                latency_ms,
+                banner,
+                error: last_error(stderr),
+            }
+        }
+        Some(_) => ConnectionStatus {
+            reachable: true,
+            auth_ok: true,
+            latency_ms,
+            banner,
+            error: None,
+        },
+    }
+}
+
+/// Find the server's software version in ssh's debug output, e.g.
+/// `debug1: Remote protocol version 2.0, remote software version OpenSSH_9.6`.
+fn parse_banner(stderr: &str) -> Option<String> {
+    stderr.lines().find_map(|line| {
+        line.split_once("remote software version ")
+            .map(|(_, version)| version.trim().to_string())
+            .filter(|version| !version.is_empty())
+    })
+}
+
+/// The last line of ssh's output that isn't debug logging, which is normally
+/// the reason it failed.
+fn last_error(stderr: &str) -> Option<String> {
+    stderr
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty() && !line.starts_with("debug"))
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BANNER: &str = "debug1: Connecting to example.com port 22.\n\
+        debug1: Remote protocol version 2.0, remote software version OpenSSH_9.6p1 Ubuntu-3ubuntu13\n";
+
+    #[test]
+    fn test_parse_banner() {
+        assert_eq!(
+            parse_banner(BANNER).as_deref(),
+            Some("OpenSSH_9.6p1 Ubuntu-3ubuntu13")
+        );
+        assert_eq!(parse_banner("ssh: connect to host example.com"), None);
+    }
+
+    #[test]
+    fn test_connection_status_ok() {
+        let status = connection_status(Some(0), BANNER, 42);
+        assert!(status.reachable);
+        assert!(status.auth_ok);
+        assert_eq!(status.latency_ms, 42);
+        assert!(status.banner.is_some());
+    }
+
+    #[test]
+    fn test_connection_status_auth_failure() {
+        let stderr = format!(
+            "{}user@example.com: Permission denied (publickey).\n",
+            BANNER
+        );
+        let status = connection_status(Some(255), &stderr, 42);
+        assert!(status.reachable);
+        assert!(!status.auth_ok);
+        assert_eq!(
+            status.error.as_deref(),
+            Some("user@example.com: Permission denied (publickey).")
+        );
+    }
+
+    #[test]
+    fn test_connection_status_unreachable() {
+        let stderr = "debug1: Connecting to example.com port 22.\n\
+            ssh: connect to host example.com port 22: Connection refused\n";
+        let status = connection_status(Some(255), stderr, 42);
+        assert!(!status.reachable);
+        assert!(!status.auth_ok);
+        assert!(status.banner.is_none());
+    }
+
+    #[test]
+    fn test_connection_connect_timeout() {
+        let check = CheckConnection {
+            remote_host: "example.com".to_string(),
+            ..Default::default()
+        };
+        let options = check.connection(10).options.unwrap();
+        assert_eq!(options[0], "ConnectTimeout=10");
+
+        let check = CheckConnection {
+            connect_timeout_seconds: Some(3),
+            ..check
+        };
+        let options = check.connection(10).options.unwrap();
+        assert_eq!(options[0], "ConnectTimeout=3");
+    }
+}
@@ -1,81 +1,141 @@
+use super::{CommonConnection, SshConnectionParams};
 use expand_tilde::expand_tilde;
 use rust_mcp_sdk::{
     macros::{JsonSchema, mcp_tool},
-    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+    schema::{CallToolResult, schema_utils::CallToolError},
 };
 use tokio::{
     process::Command,
     time::{Duration, timeout},
 };
 
-#[mcp_tool(
-    name = "copy_file",
-    description = "Copy a file from the local machine to a remote POSIX compatible system (Linux, BSD, macOS) using rsync over SSH. Preserves file attributes and creates a backup if the destination file already exists.",
-    title = "Copy File"
-)]
-#[derive(Debug, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
-pub struct CopyFile {
-    /// The source file path on the local machine.
-    pub source: String,
-    /// The destination file path on the remote machine.
-    pub destination: String,
-    /// The host to copy the file to. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
-    pub remote_host: String,
-    /// Timeout in seconds for the command execution. Defaults to 30 seconds. Set to 0 to disable timeout.
-    pub timeout_seconds: Option<u64>,
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "copy_file",
+        description = "Copy a file or directory from the local machine to a remote POSIX compatible system (Linux, BSD, macOS) using rsync over SSH. Preserves file attributes and creates a backup if a destination file already exists. A directory is copied recursively, with its contents placed in the destination directory.",
+        title = "Copy File"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct CopyFile {
+        /// The source file or directory path on the local machine.
+        pub source: String,
+        /// The destination file path on the remote machine.
+        pub destination: String,
+        /// The host to copy the file to. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// The program used to copy the file: rsync (default), sftp or scp. Use sftp or scp for systems without rsync, and scp where only scp is allowed. sftp does not create backups of existing files; scp backs up an existing destination file to destination~ before copying, as rsync does.
+        pub transport: Option<String>,
+        /// Whether source is a directory. Defaults to checking the local path. A directory is copied recursively so that its contents end up in the destination directory, whether or not either path ends with a slash. rsync only.
+        pub is_directory: Option<bool>,
+        /// Compress data during the transfer (rsync -z). Useful for large text files over slow links. Defaults to false. rsync only.
+        pub compress: Option<bool>,
+        /// Limit the transfer rate to this many kilobytes per second (rsync --bwlimit), so that large copies don't saturate the link. 0 means unlimited, which is the default. rsync only.
+        pub bwlimit_kbps: Option<u64>,
+        /// Delete files in the destination directory that don't exist in the source directory (rsync --delete), mirroring the source. Only has an effect when copying a directory. Defaults to false. rsync only.
+        pub delete: Option<bool>,
+        /// Patterns of files to skip, each passed to rsync as --exclude, e.g. *.log or .git/. rsync only.
+        pub excludes: Option<Vec<String>>,
+        /// Additional options to pass to rsync after the defaults (-avb --stats --protect-args), e.g. --checksum or --chmod=F644. Only options that affect what is copied are allowed: flags such as --checksum, --delete-excluded, --partial, --update or -P, and --bwlimit, --chmod, --chown, --compress-level, --exclude, --include, --max-size, --min-size, --modify-window, --suffix and --timeout given as --option=VALUE. The source and destination are set by the source and destination parameters. rsync only.
+        pub extra_args: Option<Vec<String>>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+/// Script backing up the file $1 to $1~ if it exists, as rsync -b does, for
+/// transports that can't back up files themselves.
+const BACKUP_SCRIPT: &str = r#"[ ! -f "$1" ] || cp -p -- "$1" "$1~""#;
+
+/// The program used to copy the file to the remote host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    Rsync,
+    Sftp,
+    Scp,
+}
+
+impl Transport {
+    fn parse(transport: Option<&str>) -> Result<Self, CallToolError> {
+        match transport.unwrap_or("rsync") {
+            "rsync" => Ok(Transport::Rsync),
+            "sftp" => Ok(Transport::Sftp),
+            "scp" => Ok(Transport::Scp),
+            other => Err(CallToolError::from_message(format!(
+                "Invalid transport: {} (expected rsync, sftp or scp)",
+                other
+            ))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Transport::Rsync => "rsync",
+            Transport::Sftp => "sftp",
+            Transport::Scp => "scp",
+        }
+    }
 }
 
 impl CopyFile {
-    #[tracing::instrument]
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
     pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
         let source = expand_tilde(&self.source).map_err(|e| {
             CallToolError::from_message(format!("Failed to expand source path: {}", e))
         })?;
+        let source = source.to_string_lossy().into_owned();
 
-        let timeout_seconds = self.timeout_seconds.unwrap_or(30);
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let transport = Transport::parse(self.transport.as_deref())?;
 
-        // Get multiplexing options for SSH
-        let multiplexing_opts = super::get_multiplexing_options().map_err(|e| {
-            CallToolError::from_message(format!("Failed to get multiplexing options: {}", e))
-        })?;
-
-        // Build SSH command with multiplexing options
-        let mut ssh_args = vec!["ssh".to_string()];
-        for opt in &multiplexing_opts {
-            ssh_args.push("-o".to_string());
-            ssh_args.push(opt.clone());
+        if self.dry_run.unwrap_or(false) {
+            return self.dry_run(transport, &source);
         }
-        // Always append StrictHostKeyChecking=yes to ensure SSH fails instead of prompting interactively
-        ssh_args.push("-o".to_string());
-        ssh_args.push("StrictHostKeyChecking=yes".to_string());
-        let ssh_command = ssh_args.join(" ");
 
-        let remote_target = format!("{}:{}", self.remote_host, self.destination);
-
-        // Build the rsync command
-        // -a: archive mode (preserves permissions, timestamps, etc.)
-        // -v: verbose
-        // -b: create backups of existing files
-        // -e: specify ssh command with multiplexing options
-        let command_future = Command::new("rsync")
-            .arg("-avb")
-            .arg("-e")
-            .arg(&ssh_command)
-            .arg(source.to_string_lossy().into_owned())
-            .arg(&remote_target)
-            .output();
+        let ssh_program = self
+            .connection()
+            .ssh_program()
+            .map_err(|e| CallToolError::from_message(e.to_string()))?;
+        let programs = match transport {
+            Transport::Rsync => vec!["rsync", ssh_program.as_str()],
+            Transport::Sftp => vec!["sftp"],
+            Transport::Scp => vec!["scp", ssh_program.as_str()],
+        };
+        super::require_installed(&programs)?;
+        let command_future = async {
+            match transport {
+                Transport::Rsync => {
+                    self.rsync_command(&source)?
+                        .kill_on_drop(true)
+                        .output()
+                        .await
+                }
+                Transport::Sftp => {
+                    let batch = sftp_batch(&source, &self.destination);
+                    super::output_with_stdin(&mut self.sftp_command(&source)?, batch.as_bytes())
+                        .await
+                }
+                Transport::Scp => {
+                    let mut cmd = self.scp_command(&source)?;
+                    self.back_up_destination().await?;
+                    cmd.kill_on_drop(true).output().await
+                }
+            }
+        };
 
-        let result = if timeout_seconds == 0 {
+        let (result, timing) = if timeout_seconds == 0 {
             // No timeout - run indefinitely
-            command_future.await
+            super::Timing::measure(command_future).await
         } else {
             // Apply timeout
             let timeout_duration = Duration::from_secs(timeout_seconds);
-            match timeout(timeout_duration, command_future).await {
-                Ok(result) => result,
-                Err(_) => {
+            match super::Timing::measure(timeout(timeout_duration, command_future)).await {
+                (Ok(result), timing) => (result, timing),
+                (Err(_), _) => {
                     return Err(CallToolError::from_message(format!(
-                        "rsync command timed out after {} seconds",
+                        "{} command timed out after {} seconds",
+                        transport.name(),
                         timeout_seconds
                     )));
                 }
@@ -86,30 +146,455 @@ impl CopyFile {
             Ok(output) => {
                 // The command executed successfully. This doesn't mean it
                 // succeeded, so output is returned as a successful tool call.
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let status_code = output.status.code();
-
-                Ok(
-                    CallToolResult::text_content(vec![TextContent::from(stdout.clone())])
-                        .with_structured_content(super::map_from_output(
-                            stdout,
-                            stderr,
-                            status_code,
-                        )),
-                )
+                let mut result = super::result_from_output(&output, None, Some(timing));
+                if transport == Transport::Rsync
+                    && let Some(stats) = parse_rsync_stats(&String::from_utf8_lossy(&output.stdout))
+                {
+                    result
+                        .structured_content
+                        .get_or_insert_default()
+                        .extend(stats.into_map());
+                }
+                Ok(result)
             }
             Err(e) => Err(CallToolError::from_message(format!(
-                "Failed to execute rsync command: {}",
+                "Failed to execute {} command: {}",
+                transport.name(),
                 e
             ))),
         }
     }
+
+    /// Describe the command that would copy `source` without running it.
+    fn dry_run(&self, transport: Transport, source: &str) -> Result<CallToolResult, CallToolError> {
+        let (cmd, stdin) = match transport {
+            Transport::Rsync => (self.rsync_command(source), None),
+            Transport::Sftp => (
+                self.sftp_command(source),
+                Some(sftp_batch(source, &self.destination)),
+            ),
+            Transport::Scp => (self.scp_command(source), None),
+        };
+        let cmd = cmd.map_err(|e| CallToolError::from_message(e.to_string()))?;
+        Ok(super::dry_run_result(
+            &cmd,
+            &self.remote_host,
+            stdin.as_deref(),
+        ))
+    }
+
+    /// Build the rsync command copying `source` to the remote destination.
+    fn rsync_command(&self, source: &str) -> std::io::Result<Command> {
+        // Build SSH command with host key checking, multiplexing and jump
+        // host options
+        let connection = self.connection();
+        let ssh_command = connection
+            .rsync_ssh_command()
+            .map_err(|e| std::io::Error::other(format!("Failed to build SSH options: {}", e)))?;
+
+        let (source, destination) = if self.is_directory(source) {
+            // rsync copies a directory's contents when the source ends with a
+            // slash, but the directory itself when it doesn't
+            (
+                with_trailing_slash(source),
+                with_trailing_slash(&self.destination),
+            )
+        } else {
+            (source.to_string(), self.destination.clone())
+        };
+        let remote_target = format!(
+            "{}:{}",
+            super::path_destination(&self.remote_host).map_err(std::io::Error::other)?,
+            destination
+        );
+
+        // Build the rsync command
+        // -a: archive mode (preserves permissions, timestamps, etc.)
+        // -v: verbose
+        // -b: create backups of existing files
+        // --stats: print transfer statistics, parsed into the result
+        // --protect-args: pass the destination to the remote rsync without
+        //   the remote shell splitting it on spaces or expanding it
+        // -e: specify ssh command with multiplexing options
+        let mut cmd = Command::new("rsync");
+        cmd.arg("-avb").arg("--stats").arg("--protect-args");
+        if self.compress.unwrap_or(false) {
+            cmd.arg("-z");
+        }
+        if let Some(bwlimit_kbps) = self.bwlimit_kbps.filter(|&limit| limit > 0) {
+            cmd.arg(format!("--bwlimit={}", bwlimit_kbps));
+        }
+        if self.delete.unwrap_or(false) {
+            cmd.arg("--delete");
+        }
+        for exclude in self.excludes.iter().flatten() {
+            cmd.arg(format!("--exclude={}", exclude));
+        }
+        let extra_args = self.extra_args.as_deref().unwrap_or_default();
+        validate_extra_args(extra_args, &source, &destination).map_err(std::io::Error::other)?;
+        cmd.args(extra_args)
+            .arg("-e")
+            .arg(&ssh_command)
+            .arg("--")
+            .arg(&source)
+            .arg(&remote_target);
+        connection.apply_password(&mut cmd);
+        Ok(cmd)
+    }
+
+    /// Whether `source` is a directory, from is_directory or the local path.
+    fn is_directory(&self, source: &str) -> bool {
+        self.is_directory
+            .unwrap_or_else(|| std::path::Path::new(source).is_dir())
+    }
+
+    /// Build the sftp command uploading `source`, which reads its batch script
+    /// from stdin.
+    fn sftp_command(&self, source: &str) -> std::io::Result<Command> {
+        if self.is_directory(source) {
+            return Err(std::io::Error::other(
+                "Copying a directory is not supported with the sftp transport",
+            ));
+        }
+        if self.password.is_some() {
+            // sftp runs non-interactively in batch mode, which disables
+            // password authentication
+            return Err(std::io::Error::other(
+                "Password authentication is not supported with the sftp transport",
+            ));
+        }
+
+        if self.compress.is_some()
+            || self.bwlimit_kbps.is_some()
+            || self.delete.is_some()
+            || self.excludes.is_some()
+            || self.extra_args.is_some()
+        {
+            return Err(std::io::Error::other(
+                "compress, bwlimit_kbps, delete, excludes and extra_args are only supported with the rsync transport",
+            ));
+        }
+
+        let sftp_args = self
+            .connection()
+            .sftp_args()
+            .map_err(|e| std::io::Error::other(format!("Failed to build SSH options: {}", e)))?;
+
+        let destination =
+            super::path_destination(&self.remote_host).map_err(std::io::Error::other)?;
+
+        // -b -: read batch commands from stdin, aborting on the first failure
+        let mut cmd = Command::new("sftp");
+        cmd.args(&sftp_args)
+            .arg("-b")
+            .arg("-")
+            .arg("--")
+            .arg(destination);
+        Ok(cmd)
+    }
+
+    /// Build the scp command copying `source` to the remote destination.
+    fn scp_command(&self, source: &str) -> std::io::Result<Command> {
+        if self.is_directory(source) {
+            return Err(std::io::Error::other(
+                "Copying a directory is not supported with the scp transport",
+            ));
+        }
+        if self.compress.is_some()
+            || self.bwlimit_kbps.is_some()
+            || self.delete.is_some()
+            || self.excludes.is_some()
+            || self.extra_args.is_some()
+        {
+            return Err(std::io::Error::other(
+                "compress, bwlimit_kbps, delete, excludes and extra_args are only supported with the rsync transport",
+            ));
+        }
+
+        // scp takes the same options as sftp, including -P for the port
+        let connection = self.connection();
+        let scp_args = connection
+            .sftp_args()
+            .map_err(|e| std::io::Error::other(format!("Failed to build SSH options: {}", e)))?;
+        let remote_target = format!(
+            "{}:{}",
+            super::path_destination(&self.remote_host).map_err(std::io::Error::other)?,
+            self.destination
+        );
+
+        // -p: preserve modification times and modes, as rsync -a does
+        let mut cmd = connection.command("scp").map_err(std::io::Error::other)?;
+        cmd.args(&scp_args)
+            .arg("-p")
+            .arg("--")
+            .arg(source)
+            .arg(&remote_target);
+        Ok(cmd)
+    }
+
+    /// Back up the destination file to destination~ if it exists, since scp
+    /// overwrites it without a backup.
+    async fn back_up_destination(&self) -> std::io::Result<()> {
+        let connection = self.connection();
+        let mut cmd = super::ssh::ssh_command(
+            &connection,
+            "sh",
+            &["-c", BACKUP_SCRIPT, "sh", self.destination.as_str()],
+        )
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+        // The copy's timeout covers the backup
+        let output = super::ssh::run_ssh(&connection, &mut cmd, 0, false)
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        if !output.status.success() {
+            return Err(std::io::Error::other(format!(
+                "Failed to back up {}: {}",
+                self.destination,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Describe the copy, e.g. "copy the local ./app.conf to /etc/app.conf
+    /// on prod-db with rsync, deleting destination files not in the source"
+    pub fn describe_effective_command(&self) -> String {
+        let mut description = format!(
+            "copy the local {} to {} {} with {}",
+            super::shell_quote(&self.source),
+            super::shell_quote(&self.destination),
+            super::on_host(&self.remote_host, self.jump_host.as_deref()),
+            self.transport.as_deref().unwrap_or("rsync")
+        );
+        if self.delete.unwrap_or(false) {
+            description.push_str(", deleting destination files not in the source");
+        }
+        if let Some(excludes) = self
+            .excludes
+            .as_ref()
+            .filter(|excludes| !excludes.is_empty())
+        {
+            description.push_str(&format!(", excluding {}", excludes.join(", ")));
+        }
+        if let Some(extra_args) = self.extra_args.as_ref().filter(|args| !args.is_empty()) {
+            description.push_str(&format!(
+                ", with the extra arguments {}",
+                super::command_line(extra_args)
+            ));
+        }
+        description.push_str(&super::using_ssh_binary(self.ssh_binary.as_deref()));
+        description
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        self.common_connection(&self.remote_host)
+    }
+}
+
+fn with_trailing_slash(path: &str) -> String {
+    if path.ends_with('/') {
+        path.to_string()
+    } else {
+        format!("{}/", path)
+    }
+}
+
+/// rsync options without a value that extra_args may pass. Anything else is
+/// rejected: --rsync-path runs a command on the remote host, -e/--rsh replaces
+/// the ssh command built from the connection parameters, and --files-from,
+/// --exclude-from, --password-file and the like read local files. rsync also
+/// accepts unambiguous abbreviations of long options, such as --rsync-p for
+/// --rsync-path, so options are matched exactly.
+const ALLOWED_FLAGS: &[&str] = &[
+    "--append",
+    "--append-verify",
+    "--checksum",
+    "--copy-links",
+    "--delete",
+    "--delete-after",
+    "--delete-before",
+    "--delete-delay",
+    "--delete-during",
+    "--delete-excluded",
+    "--dry-run",
+    "--existing",
+    "--hard-links",
+    "--human-readable",
+    "--ignore-existing",
+    "--ignore-times",
+    "--inplace",
+    "--itemize-changes",
+    "--no-group",
+    "--no-owner",
+    "--no-perms",
+    "--no-times",
+    "--numeric-ids",
+    "--omit-dir-times",
+    "--one-file-system",
+    "--partial",
+    "--progress",
+    "--prune-empty-dirs",
+    "--safe-links",
+    "--size-only",
+    "--sparse",
+    "--update",
+    "--whole-file",
+];
+
+/// rsync options taking a value that extra_args may pass, in the form
+/// --option=VALUE.
+const ALLOWED_VALUE_OPTIONS: &[&str] = &[
+    "--bwlimit",
+    "--chmod",
+    "--chown",
+    "--compress-level",
+    "--exclude",
+    "--include",
+    "--max-size",
+    "--min-size",
+    "--modify-window",
+    "--suffix",
+    "--timeout",
+];
+
+/// Short rsync options without a value that extra_args may pass, alone or
+/// combined such as -cP.
+const ALLOWED_SHORT_FLAGS: &str = "cHhIiLlmnPpqStuvWxz";
+
+/// Whether `arg` is one of the rsync options extra_args may pass.
+fn is_allowed_option(arg: &str) -> bool {
+    if let Some(flags) = arg.strip_prefix('-').filter(|rest| !rest.starts_with('-')) {
+        return !flags.is_empty() && flags.chars().all(|flag| ALLOWED_SHORT_FLAGS.contains(flag));
+    }
+    match arg.split_once('=') {
+        Some((option, _)) => ALLOWED_VALUE_OPTIONS.contains(&option),
+        None => ALLOWED_FLAGS.contains(&arg),
+    }
+}
+
+/// Check that extra rsync arguments are allowed options. Paths would be taken
+/// as additional sources, and other options could run commands or read files
+/// outside of the copy.
+fn validate_extra_args(
+    extra_args: &[String],
+    source: &str,
+    destination: &str,
+) -> Result<(), String> {
+    for arg in extra_args {
+        if !arg.starts_with('-') {
+            return Err(format!(
+                "Invalid extra argument {:?}: extra_args must be options starting with -",
+                arg
+            ));
+        }
+        if [source, destination]
+            .iter()
+            .any(|path| !path.is_empty() && arg.contains(path))
+        {
+            return Err(format!(
+                "Invalid extra argument {:?}: set the source and destination with the source and destination parameters",
+                arg
+            ));
+        }
+        if !is_allowed_option(arg) {
+            return Err(format!(
+                "Invalid extra argument {:?}: not one of the rsync options extra_args may pass",
+                arg
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Transfer statistics printed by rsync --stats
+#[derive(Debug, Clone, PartialEq)]
+struct RsyncStats {
+    files_transferred: u64,
+    total_bytes: u64,
+    speedup: f64,
+    bytes_sent: u64,
+    bytes_received: u64,
+}
+
+impl RsyncStats {
+    fn into_map(self) -> serde_json::Map<String, serde_json::Value> {
+        let mut map = serde_json::Map::new();
+        map.insert(
+            "files_transferred".to_string(),
+            self.files_transferred.into(),
+        );
+        map.insert("total_bytes".to_string(), self.total_bytes.into());
+        map.insert("speedup".to_string(), self.speedup.into());
+        map.insert("bytes_sent".to_string(), self.bytes_sent.into());
+        map.insert("bytes_received".to_string(), self.bytes_received.into());
+        map
+    }
+}
+
+/// Parse the statistics from rsync --stats output, or None if any is missing,
+/// e.g. because the transfer failed before rsync printed them.
+fn parse_rsync_stats(stdout: &str) -> Option<RsyncStats> {
+    let mut files_transferred = None;
+    let mut total_bytes = None;
+    let mut speedup = None;
+    let mut bytes_sent = None;
+    let mut bytes_received = None;
+
+    for line in stdout.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            // The summary line: "total size is 1,234  speedup is 0.90"
+            if let Some((_, value)) = line.split_once("speedup is ") {
+                speedup = value.split_whitespace().next().and_then(|v| v.parse().ok());
+            }
+            continue;
+        };
+        let slot = match key.trim() {
+            // rsync 3.1 and later count regular files separately
+            "Number of regular files transferred" | "Number of files transferred" => {
+                &mut files_transferred
+            }
+            "Total file size" => &mut total_bytes,
+            "Total bytes sent" => &mut bytes_sent,
+            "Total bytes received" => &mut bytes_received,
+            _ => continue,
+        };
+        *slot = parse_count(value);
+    }
+
+    Some(RsyncStats {
+        files_transferred: files_transferred?,
+        total_bytes: total_bytes?,
+        speedup: speedup?,
+        bytes_sent: bytes_sent?,
+        bytes_received: bytes_received?,
+    })
+}
+
+/// Parse a count such as "1,234 bytes", ignoring thousands separators.
+fn parse_count(value: &str) -> Option<u64> {
+    value
+        .split_whitespace()
+        .next()?
+        .replace([',', '.'], "")
+        .parse()
+        .ok()
+}
+
+/// Build an sftp batch script that uploads `source` to `destination`.
+fn sftp_batch(source: &str, destination: &str) -> String {
+    format!("put {} {}\n", sftp_quote(source), sftp_quote(destination))
+}
+
+/// Quote a path for an sftp batch script.
+fn sftp_quote(path: &str) -> String {
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(unix)]
+    use crate::tools::{TestPath, run_script};
 
     #[test]
     fn test_copy_file_struct_creation() {
@@ -118,6 +603,7 @@ mod tests {
             destination: "/home/user/test.txt".to_string(),
             remote_host: "localhost".to_string(),
             timeout_seconds: Some(60),
+            ..Default::default()
         };
 
         assert_eq!(copy.source, "/tmp/test.txt");
@@ -132,8 +618,449 @@ mod tests {
             destination: "/remote/path/file.txt".to_string(),
             remote_host: "example.com".to_string(),
             timeout_seconds: None,
+            ..Default::default()
         };
 
         assert!(copy.timeout_seconds.is_none());
     }
+
+    #[test]
+    fn test_copy_file_ssh_command_with_jump_host() {
+        let copy = CopyFile {
+            source: "file.txt".to_string(),
+            destination: "/remote/path/file.txt".to_string(),
+            remote_host: "example.com".to_string(),
+            timeout_seconds: None,
+            jump_host: Some("admin@bastion:2222".to_string()),
+            ..Default::default()
+        };
+
+        let ssh_command = copy.connection().rsync_ssh_command().unwrap();
+        assert!(ssh_command.starts_with("ssh "));
+        assert!(ssh_command.contains("-J admin@bastion:2222"));
+    }
+
+    #[test]
+    fn test_transport_parse() {
+        assert_eq!(Transport::parse(None).unwrap(), Transport::Rsync);
+        assert_eq!(Transport::parse(Some("rsync")).unwrap(), Transport::Rsync);
+        assert_eq!(Transport::parse(Some("sftp")).unwrap(), Transport::Sftp);
+        assert_eq!(Transport::parse(Some("scp")).unwrap(), Transport::Scp);
+        assert!(Transport::parse(Some("ftp")).is_err());
+    }
+
+    fn rsync_args(copy: &CopyFile) -> Vec<String> {
+        copy.rsync_command(&copy.source)
+            .unwrap()
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_rsync_command_default_args() {
+        let copy = CopyFile {
+            source: "/tmp/dir/".to_string(),
+            destination: "/srv/dir/".to_string(),
+            remote_host: "example.com".to_string(),
+            ..Default::default()
+        };
+
+        let args = rsync_args(&copy);
+        assert_eq!(args[..4], ["-avb", "--stats", "--protect-args", "-e"]);
+        assert!(args.ends_with(&["/tmp/dir/".to_string(), "example.com:/srv/dir/".to_string()]));
+    }
+
+    #[test]
+    fn test_rsync_command_destination_with_space() {
+        let copy = CopyFile {
+            source: "/tmp/report.pdf".to_string(),
+            destination: "/srv/shared docs/report $(date).pdf".to_string(),
+            remote_host: "example.com".to_string(),
+            is_directory: Some(false),
+            ..Default::default()
+        };
+
+        // The remote rsync gets the path as is rather than through the
+        // remote shell, so it isn't split or expanded there and needs no
+        // quoting
+        let args = rsync_args(&copy);
+        assert!(args.contains(&"--protect-args".to_string()));
+        assert_eq!(
+            args.last().unwrap(),
+            "example.com:/srv/shared docs/report $(date).pdf"
+        );
+    }
+
+    #[test]
+    fn test_rsync_command_options() {
+        let copy = CopyFile {
+            source: "/tmp/dir/".to_string(),
+            destination: "/srv/dir/".to_string(),
+            remote_host: "example.com".to_string(),
+            compress: Some(true),
+            delete: Some(true),
+            excludes: Some(vec!["*.log".to_string(), ".git/".to_string()]),
+            extra_args: Some(vec!["--checksum".to_string()]),
+            ..Default::default()
+        };
+
+        let args = rsync_args(&copy);
+        let e = args.iter().position(|arg| arg == "-e").unwrap();
+        assert_eq!(
+            args[..e],
+            [
+                "-avb",
+                "--stats",
+                "--protect-args",
+                "-z",
+                "--delete",
+                "--exclude=*.log",
+                "--exclude=.git/",
+                "--checksum",
+            ]
+        );
+        assert!(args.ends_with(&["/tmp/dir/".to_string(), "example.com:/srv/dir/".to_string()]));
+    }
+
+    #[test]
+    fn test_rsync_command_bwlimit() {
+        let copy = CopyFile {
+            source: "/tmp/backup.tar".to_string(),
+            destination: "/srv/backup.tar".to_string(),
+            remote_host: "example.com".to_string(),
+            is_directory: Some(false),
+            bwlimit_kbps: Some(5000),
+            ..Default::default()
+        };
+        assert!(rsync_args(&copy).contains(&"--bwlimit=5000".to_string()));
+
+        // 0 means unlimited
+        let copy = CopyFile {
+            bwlimit_kbps: Some(0),
+            ..copy
+        };
+        assert!(
+            !rsync_args(&copy)
+                .iter()
+                .any(|arg| arg.starts_with("--bwlimit"))
+        );
+    }
+
+    #[test]
+    fn test_rsync_command_cipher() {
+        let copy = CopyFile {
+            source: "/tmp/backup.tar".to_string(),
+            destination: "/srv/backup.tar".to_string(),
+            remote_host: "example.com".to_string(),
+            is_directory: Some(false),
+            cipher: Some("aes128-gcm@openssh.com".to_string()),
+            ..Default::default()
+        };
+        let args = rsync_args(&copy);
+        let position = args.iter().position(|arg| arg == "-e").unwrap();
+        assert!(args[position + 1].contains(" -c aes128-gcm@openssh.com"));
+
+        let copy = CopyFile {
+            cipher: Some("aes128-gcm -oProxyCommand=nc".to_string()),
+            ..copy
+        };
+        assert!(copy.rsync_command(&copy.source).is_err());
+    }
+
+    #[test]
+    fn test_rsync_command_ipv6_host() {
+        let copy = CopyFile {
+            source: "app.conf".to_string(),
+            destination: "/etc/app.conf".to_string(),
+            remote_host: "admin@fe80::1".to_string(),
+            is_directory: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            rsync_args(&copy).last().unwrap(),
+            "admin@[fe80::1]:/etc/app.conf"
+        );
+    }
+
+    #[test]
+    fn test_rsync_command_directory() {
+        for (source, destination) in [("/tmp/dir", "/srv/dir"), ("/tmp/dir/", "/srv/dir/")] {
+            let copy = CopyFile {
+                source: source.to_string(),
+                destination: destination.to_string(),
+                remote_host: "example.com".to_string(),
+                is_directory: Some(true),
+                ..Default::default()
+            };
+
+            let args = rsync_args(&copy);
+            assert!(
+                args.ends_with(&["/tmp/dir/".to_string(), "example.com:/srv/dir/".to_string()]),
+                "{} -> {}: {:?}",
+                source,
+                destination,
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_directory_detects_local_path() {
+        let copy = CopyFile::default();
+        let dir = std::env::temp_dir();
+        assert!(copy.is_directory(&dir.to_string_lossy()));
+        assert!(!copy.is_directory("/nonexistent/file.txt"));
+
+        let copy = CopyFile {
+            is_directory: Some(false),
+            ..Default::default()
+        };
+        assert!(!copy.is_directory(&dir.to_string_lossy()));
+    }
+
+    #[test]
+    fn test_validate_extra_args() {
+        let valid = [
+            "--checksum".to_string(),
+            "--chmod=F644".to_string(),
+            "--exclude=*.tmp".to_string(),
+            "-c".to_string(),
+            "-cP".to_string(),
+        ];
+        assert!(validate_extra_args(&valid, "/tmp/a", "/srv/a").is_ok());
+
+        for invalid in [
+            "/etc/passwd",
+            "--files-from=/tmp/a",
+            "--rsh=sh",
+            "--rsh",
+            "-e",
+            "-ve",
+            "-",
+            "--checksum=yes",
+            "--exclude",
+        ] {
+            assert!(
+                validate_extra_args(&[invalid.to_string()], "/tmp/a", "/srv/a").is_err(),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_extra_args_rejects_remote_commands() {
+        // --rsync-path runs any command on the remote host, including through
+        // an abbreviation rsync accepts
+        for invalid in [
+            "--rsync-path=sh -c 'rm -rf /'",
+            "--rsync-path",
+            "--rsync-p=sh",
+        ] {
+            assert!(
+                validate_extra_args(&[invalid.to_string()], "/tmp/a", "/srv/a").is_err(),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_extra_args_rejects_local_files() {
+        for invalid in [
+            "--include-from=/etc/shadow",
+            "--exclude-from=/etc/shadow",
+            "--files-from=/etc/shadow",
+            "--password-file=/etc/shadow",
+            "--filter=merge /etc/shadow",
+        ] {
+            assert!(
+                validate_extra_args(&[invalid.to_string()], "/tmp/a", "/srv/a").is_err(),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_sftp_command_rejects_rsync_options() {
+        let copy = CopyFile {
+            source: "file.txt".to_string(),
+            destination: "/remote/path/file.txt".to_string(),
+            remote_host: "example.com".to_string(),
+            transport: Some("sftp".to_string()),
+            compress: Some(true),
+            ..Default::default()
+        };
+        assert!(copy.sftp_command(&copy.source).is_err());
+    }
+
+    #[test]
+    fn test_parse_rsync_stats() {
+        let stdout = "sending incremental file list
+file.txt
+
+Number of files: 1 (reg: 1)
+Number of created files: 1 (reg: 1)
+Number of deleted files: 0
+Number of regular files transferred: 1
+Total file size: 1,048,576 bytes
+Total transferred file size: 1,048,576 bytes
+Literal data: 1,048,576 bytes
+Matched data: 0 bytes
+File list size: 0
+File list generation time: 0.001 seconds
+File list transfer time: 0.000 seconds
+Total bytes sent: 1,048,917
+Total bytes received: 35
+
+sent 1,048,917 bytes  received 35 bytes  2,097,904.00 bytes/sec
+total size is 1,048,576  speedup is 1.00
+";
+        assert_eq!(
+            parse_rsync_stats(stdout).unwrap(),
+            RsyncStats {
+                files_transferred: 1,
+                total_bytes: 1048576,
+                speedup: 1.0,
+                bytes_sent: 1048917,
+                bytes_received: 35,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_rsync_stats_incomplete() {
+        assert!(parse_rsync_stats("").is_none());
+        assert!(parse_rsync_stats("sending incremental file list\nfile.txt\n").is_none());
+    }
+
+    #[test]
+    fn test_sftp_batch() {
+        assert_eq!(
+            sftp_batch("/tmp/my file.txt", "/etc/app/\"quoted\".conf"),
+            "put \"/tmp/my file.txt\" \"/etc/app/\\\"quoted\\\".conf\"\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_copy_file_dry_run() {
+        let copy = CopyFile {
+            source: "/tmp/file.txt".to_string(),
+            destination: "/remote/path/file.txt".to_string(),
+            remote_host: "example.com".to_string(),
+            transport: Some("sftp".to_string()),
+            dry_run: Some(true),
+            ..Default::default()
+        };
+
+        let result = copy.call_tool().await.unwrap();
+        let content = result.structured_content.unwrap();
+        assert_eq!(content["dry_run"], true);
+        assert_eq!(content["command"], "sftp");
+        assert_eq!(
+            content["stdin"],
+            "put \"/tmp/file.txt\" \"/remote/path/file.txt\"\n"
+        );
+    }
+
+    #[test]
+    fn test_scp_command() {
+        let copy = CopyFile {
+            source: "/tmp/app.conf".to_string(),
+            destination: "/etc/app.conf".to_string(),
+            remote_host: "admin@fe80::1".to_string(),
+            port: Some(2222),
+            jump_host: Some("bastion".to_string()),
+            transport: Some("scp".to_string()),
+            is_directory: Some(false),
+            ..Default::default()
+        };
+
+        let cmd = copy.scp_command(&copy.source).unwrap();
+        let args: Vec<String> = cmd
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(cmd.as_std().get_program(), "scp");
+        assert!(args.windows(2).any(|pair| pair == ["-P", "2222"]));
+        assert!(args.windows(2).any(|pair| pair == ["-J", "bastion"]));
+        assert!(args.ends_with(&[
+            "-p".to_string(),
+            "--".to_string(),
+            "/tmp/app.conf".to_string(),
+            "admin@[fe80::1]:/etc/app.conf".to_string()
+        ]));
+    }
+
+    #[test]
+    fn test_scp_command_rejects_directories_and_rsync_options() {
+        let copy = CopyFile {
+            source: "/tmp/dir".to_string(),
+            destination: "/srv/dir".to_string(),
+            remote_host: "example.com".to_string(),
+            transport: Some("scp".to_string()),
+            is_directory: Some(true),
+            ..Default::default()
+        };
+        assert!(copy.scp_command(&copy.source).is_err());
+
+        let copy = CopyFile {
+            is_directory: Some(false),
+            delete: Some(true),
+            ..copy
+        };
+        assert!(copy.scp_command(&copy.source).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_backup_script() {
+        let dir = TestPath::dir("copy_file_backup");
+        let (file, backup) = (dir.join("app.conf"), dir.join("app.conf~"));
+        let back_up = |path: &str| run_script(BACKUP_SCRIPT, &[path], b"", Some(&dir)).status;
+
+        // Nothing to back up yet
+        assert!(back_up(file.to_str().unwrap()).success());
+        assert!(!backup.exists());
+
+        std::fs::write(&file, "old").unwrap();
+        assert!(back_up(file.to_str().unwrap()).success());
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), "old");
+
+        // A relative path looking like an option is still backed up
+        std::fs::write(dir.join("-r"), "old").unwrap();
+        assert!(back_up("-r").success());
+        assert_eq!(std::fs::read_to_string(dir.join("-r~")).unwrap(), "old");
+    }
+
+    #[test]
+    fn test_sftp_command() {
+        let copy = CopyFile {
+            source: "file.txt".to_string(),
+            destination: "/remote/path/file.txt".to_string(),
+            remote_host: "example.com".to_string(),
+            port: Some(2222),
+            transport: Some("sftp".to_string()),
+            ..Default::default()
+        };
+
+        let cmd = copy.sftp_command(&copy.source).unwrap();
+        let args: Vec<String> = cmd
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(cmd.as_std().get_program(), "sftp");
+        assert!(args.windows(2).any(|pair| pair == ["-P", "2222"]));
+        assert!(args.ends_with(&[
+            "-b".to_string(),
+            "-".to_string(),
+            "--".to_string(),
+            "example.com".to_string()
+        ]));
+    }
 }
@@ -0,0 +1,198 @@
+use super::{CommonConnection, SshConnectionParams};
+use expand_tilde::expand_tilde;
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, schema_utils::CallToolError},
+};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "fetch_file",
+        description = "Copy a file from a remote POSIX compatible system (Linux, BSD, macOS) to the local machine using rsync over SSH. Refuses to overwrite an existing local file unless overwrite is set.",
+        title = "Fetch File"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct FetchFile {
+        /// The source file path on the remote machine.
+        pub source: String,
+        /// The destination file path on the local machine.
+        pub destination: String,
+        /// The host to copy the file from. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Overwrite the local destination file if it already exists. Defaults to false.
+        pub overwrite: Option<bool>,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+impl FetchFile {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let destination = expand_tilde(&self.destination).map_err(|e| {
+            CallToolError::from_message(format!("Failed to expand destination path: {}", e))
+        })?;
+
+        if !self.overwrite.unwrap_or(false) {
+            let target = local_target(&destination, &self.source);
+            if target.exists() {
+                return Err(CallToolError::from_message(format!(
+                    "Local file {} already exists; set overwrite to replace it",
+                    target.display()
+                )));
+            }
+        }
+
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+
+        // Build SSH command with host key checking, multiplexing and jump
+        // host options
+        let connection = self.connection();
+        let ssh_command = connection
+            .rsync_ssh_command()
+            .map_err(super::ssh_options_error)?;
+
+        let remote_source = format!(
+            "{}:{}",
+            super::path_destination(&self.remote_host).map_err(super::ssh_options_error)?,
+            self.source
+        );
+
+        // Build the rsync command
+        // -a: archive mode (preserves permissions, timestamps, etc.)
+        // -v: verbose
+        // -z: compress file data during the transfer
+        // --protect-args: pass the source to the remote rsync without the
+        //   remote shell splitting it on spaces or expanding it
+        // -e: specify ssh command with multiplexing options
+        let mut cmd = Command::new("rsync");
+        cmd.arg("-avz")
+            .arg("--protect-args")
+            .arg("-e")
+            .arg(&ssh_command)
+            .arg("--")
+            .arg(&remote_source)
+            .arg(destination.to_string_lossy().into_owned());
+        connection.apply_password(&mut cmd);
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(&cmd, &self.remote_host, None));
+        }
+
+        let ssh_program = connection
+            .ssh_program()
+            .map_err(|e| CallToolError::from_message(e.to_string()))?;
+        super::require_installed(&["rsync", ssh_program.as_str()])?;
+        match super::output_with_timeout(&mut cmd, timeout_seconds, false).await {
+            Ok(output) if output.timed_out => Err(CallToolError::from_message(format!(
+                "rsync command timed out after {} seconds",
+                timeout_seconds
+            ))),
+            Ok(output) => {
+                // The command executed successfully. This doesn't mean it
+                // succeeded, so output is returned as a successful tool call.
+                Ok(super::result_from_output(&output.output, None, None))
+            }
+            Err(e) => Err(CallToolError::from_message(format!(
+                "Failed to execute rsync command: {}",
+                e
+            ))),
+        }
+    }
+
+    /// Describe the download, e.g. "download /var/log/syslog from prod-db
+    /// to the local file ./syslog"
+    pub fn describe_effective_command(&self) -> String {
+        let mut description = format!(
+            "download {} from {} to the local file {}",
+            super::shell_quote(&self.source),
+            self.remote_host,
+            super::shell_quote(&self.destination)
+        );
+        if self.overwrite.unwrap_or(false) {
+            description.push_str(", overwriting it if it exists");
+        }
+        description
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        self.common_connection(&self.remote_host)
+    }
+}
+
+/// Resolve the local path rsync will write to. When the destination is an
+/// existing directory, rsync places the file inside it using the source's
+/// file name.
+fn local_target(destination: &Path, source: &str) -> PathBuf {
+    if destination.is_dir() {
+        match Path::new(source).file_name() {
+            Some(file_name) => destination.join(file_name),
+            None => destination.to_path_buf(),
+        }
+    } else {
+        destination.to_path_buf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_target_for_file_destination() {
+        let target = local_target(Path::new("/nonexistent/local.conf"), "/etc/remote.conf");
+        assert_eq!(target, PathBuf::from("/nonexistent/local.conf"));
+    }
+
+    #[test]
+    fn test_local_target_for_directory_destination() {
+        let dir = std::env::temp_dir();
+        let target = local_target(&dir, "/etc/nginx/nginx.conf");
+        assert_eq!(target, dir.join("nginx.conf"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_file_refuses_overwrite() {
+        let dir = std::env::temp_dir();
+        let existing = dir.join("mcp_linux_ssh_fetch_file_test.txt");
+        std::fs::write(&existing, "existing").unwrap();
+
+        let fetch = FetchFile {
+            source: "/etc/hostname".to_string(),
+            destination: existing.to_string_lossy().into_owned(),
+            remote_host: "localhost".to_string(),
+            ..Default::default()
+        };
+
+        let result = fetch.call_tool().await;
+        std::fs::remove_file(&existing).unwrap();
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("overwrite"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_file_source_with_space() {
+        let fetch = FetchFile {
+            source: "/srv/shared docs/report $(date).pdf".to_string(),
+            destination: "/nonexistent/report.pdf".to_string(),
+            remote_host: "example.com".to_string(),
+            dry_run: Some(true),
+            ..Default::default()
+        };
+
+        // With --protect-args the source reaches the remote rsync without
+        // the remote shell, so it isn't split or expanded there
+        let result = fetch.call_tool().await.unwrap();
+        let args = result.structured_content.unwrap()["args"].clone();
+        let args = args.as_array().unwrap();
+        assert_eq!(args[..2], ["-avz", "--protect-args"]);
+        assert_eq!(
+            args[args.len() - 2],
+            "example.com:/srv/shared docs/report $(date).pdf"
+        );
+    }
+}
@@ -0,0 +1,425 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+/// Signals that may be sent, by name, with their number when it is the same
+/// on every POSIX system. The others are only accepted by name since their
+/// numbers differ, e.g. SIGUSR1 is 10 on Linux and 30 on macOS and BSD.
+const SIGNALS: &[(&str, Option<u32>)] = &[
+    ("HUP", Some(1)),
+    ("INT", Some(2)),
+    ("QUIT", Some(3)),
+    ("ABRT", Some(6)),
+    ("KILL", Some(9)),
+    ("USR1", None),
+    ("USR2", None),
+    ("ALRM", Some(14)),
+    ("TERM", Some(15)),
+    ("STOP", None),
+    ("CONT", None),
+];
+
+/// Exit statuses of the pattern script
+const PATTERN_NO_MATCH: i32 = 3;
+const PATTERN_MATCHES_INIT: i32 = 4;
+
+/// Script sending the signal $1 to the processes whose command line matches
+/// the pattern read from stdin, and printing their PIDs first. The pattern is
+/// read from stdin rather than passed as an argument, but pgrep -f can still
+/// match this shell, sudo, the login shell or the sshd session through the
+/// script itself or the remote command line, so the shell and its ancestors
+/// are left out.
+const PATTERN_SCRIPT: &str = r#"IFS= read -r pattern
+pids=$(pgrep -f -- "$pattern") || exit 3
+own=" $$ "
+pid=$PPID
+while [ -n "$pid" ] && [ "$pid" -gt 1 ]; do
+    own="$own$pid "
+    pid=$(ps -o ppid= -p "$pid" | tr -d ' ')
+done
+targets=
+for pid in $pids; do
+    case "$own" in *" $pid "*) continue ;; esac
+    [ "$pid" -eq 1 ] && exit 4
+    targets="$targets $pid"
+done
+[ -n "$targets" ] || exit 3
+echo $targets
+kill -s "$1" $targets"#;
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "kill_process",
+        description = "Send a signal to processes on a remote POSIX compatible system (Linux, BSD, macOS): to a single process by pid, as with kill, \
+        or to every process whose full command line matches pattern, as with pkill -f. \
+        The signal defaults to TERM; prefer it to KILL, which gives the process no chance to clean up. \
+        Signalling PID 1 is refused. Set use_sudo to signal processes of other users. Returns the PIDs that were signalled.",
+        title = "Kill Process"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct KillProcess {
+        /// The PID of the process to signal. Set either pid or pattern.
+        pub pid: Option<u32>,
+        /// Extended regular expression matched against the full command line of the processes to signal, as with pgrep -f. Set either pid or pattern.
+        pub pattern: Option<String>,
+        /// The signal to send, by name (TERM, KILL, HUP, INT, QUIT, ABRT, ALRM, USR1, USR2, STOP or CONT, with or without the SIG prefix) or by number for those whose number is the same on every system (1, 2, 3, 6, 9, 14 or 15). Defaults to TERM.
+        pub signal: Option<String>,
+        /// Send the signal with sudo, to signal processes owned by other users. Defaults to false.
+        pub use_sudo: Option<bool>,
+        /// The host to signal processes on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Number of times to retry when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+/// The processes to signal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target<'a> {
+    Pid(u32),
+    Pattern(&'a str),
+}
+
+impl KillProcess {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let signal = parse_signal(self.signal.as_deref())?;
+        let target = self.target()?;
+        let connection = self.connection();
+
+        let (program, args) = self.command(signal, target);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let mut cmd = super::ssh::ssh_command(&connection, program, &args)?;
+        let stdin = match target {
+            Target::Pattern(pattern) => Some(format!("{}\n", pattern)),
+            Target::Pid(_) => None,
+        };
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(
+                &cmd,
+                &self.remote_host,
+                stdin.as_deref(),
+            ));
+        }
+
+        let output = match &stdin {
+            Some(stdin) => {
+                super::ssh::run_ssh_with_stdin(&mut cmd, stdin.as_bytes(), timeout_seconds).await?
+            }
+            None => super::ssh::run_ssh(&connection, &mut cmd, timeout_seconds, false).await?,
+        };
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let pids: Vec<u32> = match target {
+            Target::Pid(pid) => vec![pid],
+            Target::Pattern(_) => parse_pids(&stdout),
+        };
+        match output.status.code() {
+            Some(0) => {}
+            Some(PATTERN_NO_MATCH) if matches!(target, Target::Pattern(_)) => {
+                return Err(CallToolError::from_message(format!(
+                    "No process matches {:?} on {}",
+                    self.pattern.as_deref().unwrap_or_default(),
+                    self.remote_host
+                )));
+            }
+            Some(PATTERN_MATCHES_INIT) if matches!(target, Target::Pattern(_)) => {
+                return Err(CallToolError::from_message(format!(
+                    "Refusing to signal PID 1, which {:?} matches",
+                    self.pattern.as_deref().unwrap_or_default()
+                )));
+            }
+            _ => {
+                return Err(CallToolError::from_message(format!(
+                    "Failed to send SIG{} to {}: {}",
+                    signal,
+                    join_pids(&pids),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+
+        let mut structured_content = serde_json::Map::new();
+        structured_content.insert("signal".to_string(), signal.into());
+        structured_content.insert("pids".to_string(), pids.clone().into());
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(format!(
+            "Sent SIG{} to {}",
+            signal,
+            join_pids(&pids)
+        ))])
+        .with_structured_content(structured_content))
+    }
+
+    /// The processes to signal: exactly one of pid and pattern must be set,
+    /// and PID 1 is never signalled. PIDs above i32::MAX are rejected, as
+    /// some kill implementations would wrap them to -1, every process.
+    fn target(&self) -> Result<Target<'_>, CallToolError> {
+        match (self.pid, self.pattern.as_deref()) {
+            (Some(0 | 1), None) => {
+                Err(CallToolError::from_message("Refusing to signal PID 0 or 1"))
+            }
+            (Some(pid), None) if i32::try_from(pid).is_err() => {
+                Err(CallToolError::from_message(format!("Invalid PID: {}", pid)))
+            }
+            (Some(pid), None) => Ok(Target::Pid(pid)),
+            (None, Some(pattern)) if pattern.is_empty() || pattern.contains('\n') => Err(
+                CallToolError::from_message("pattern must be a non-empty single line"),
+            ),
+            (None, Some(pattern)) => Ok(Target::Pattern(pattern)),
+            _ => Err(CallToolError::from_message(
+                "Set exactly one of pid and pattern",
+            )),
+        }
+    }
+
+    /// The remote program and arguments: `kill -s <signal> <pid>`, or the
+    /// pattern script, under sudo if use_sudo is set.
+    fn command(&self, signal: &str, target: Target) -> (&'static str, Vec<String>) {
+        let mut args = match target {
+            Target::Pid(pid) => vec![
+                "kill".to_string(),
+                "-s".to_string(),
+                signal.to_string(),
+                pid.to_string(),
+            ],
+            Target::Pattern(_) => vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                PATTERN_SCRIPT.to_string(),
+                "sh".to_string(),
+                signal.to_string(),
+            ],
+        };
+        if self.use_sudo.unwrap_or(false) {
+            return ("sudo", args);
+        }
+        let program = if matches!(target, Target::Pid(_)) {
+            "kill"
+        } else {
+            "sh"
+        };
+        args.remove(0);
+        (program, args)
+    }
+
+    /// Describe the signal to send, e.g. "sudo pkill -TERM -f 'nginx: worker'
+    /// on prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        let signal_flag = match parse_signal(self.signal.as_deref()) {
+            Ok(signal) => format!("-{}", signal),
+            Err(_) => format!("-{}", self.signal.as_deref().unwrap_or_default()),
+        };
+        let pid = self.pid.map(|pid| pid.to_string()).unwrap_or_default();
+        let mut command = Vec::new();
+        if self.use_sudo.unwrap_or(false) {
+            command.push("sudo");
+        }
+        match self.pattern.as_deref() {
+            Some(pattern) => command.extend(["pkill", signal_flag.as_str(), "-f", pattern]),
+            None => command.extend(["kill", signal_flag.as_str(), pid.as_str()]),
+        }
+        format!(
+            "{} {}",
+            super::command_line(&command),
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// Resolve a signal name or number to its name without the SIG prefix,
+/// defaulting to TERM.
+fn parse_signal(signal: Option<&str>) -> Result<&'static str, CallToolError> {
+    let Some(signal) = signal.map(str::trim) else {
+        return Ok("TERM");
+    };
+    let upper = signal.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    SIGNALS
+        .iter()
+        .find(|(known, number)| {
+            *known == name || number.is_some_and(|number| name == number.to_string())
+        })
+        .map(|(known, _)| *known)
+        .ok_or_else(|| {
+            CallToolError::from_message(format!(
+                "Invalid signal: {} (expected one of {}, or 1, 2, 3, 6, 9, 14 or 15)",
+                signal,
+                SIGNALS
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })
+}
+
+/// Parse the PIDs printed by the pattern script
+fn parse_pids(output: &str) -> Vec<u32> {
+    output
+        .split_whitespace()
+        .filter_map(|pid| pid.parse().ok())
+        .collect()
+}
+
+fn join_pids(pids: &[u32]) -> String {
+    pids.iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signal() {
+        assert_eq!(parse_signal(None).unwrap(), "TERM");
+        assert_eq!(parse_signal(Some("kill")).unwrap(), "KILL");
+        assert_eq!(parse_signal(Some("SIGHUP")).unwrap(), "HUP");
+        assert_eq!(parse_signal(Some("9")).unwrap(), "KILL");
+        assert_eq!(parse_signal(Some("usr1")).unwrap(), "USR1");
+        // USR1's number differs between systems
+        assert!(parse_signal(Some("10")).is_err());
+        assert!(parse_signal(Some("SEGV")).is_err());
+        assert!(parse_signal(Some("TERM; reboot")).is_err());
+    }
+
+    #[test]
+    fn test_target() {
+        let tool = |pid: Option<u32>, pattern: Option<&str>| KillProcess {
+            pid,
+            pattern: pattern.map(str::to_string),
+            ..Default::default()
+        };
+        assert_eq!(tool(Some(1234), None).target().unwrap(), Target::Pid(1234));
+        assert_eq!(
+            tool(None, Some("nginx: worker")).target().unwrap(),
+            Target::Pattern("nginx: worker")
+        );
+        assert!(tool(Some(1), None).target().is_err());
+        assert!(tool(Some(0), None).target().is_err());
+        assert!(tool(Some(i32::MAX as u32), None).target().is_ok());
+        assert!(tool(Some(i32::MAX as u32 + 1), None).target().is_err());
+        assert!(tool(Some(u32::MAX), None).target().is_err());
+        assert!(tool(None, None).target().is_err());
+        assert!(tool(Some(1234), Some("nginx")).target().is_err());
+        assert!(tool(None, Some("")).target().is_err());
+        assert!(tool(None, Some("a\nb")).target().is_err());
+    }
+
+    #[test]
+    fn test_command() {
+        let tool = KillProcess::default();
+        assert_eq!(
+            tool.command("KILL", Target::Pid(1234)),
+            (
+                "kill",
+                vec!["-s".to_string(), "KILL".to_string(), "1234".to_string()]
+            )
+        );
+
+        let tool = KillProcess {
+            use_sudo: Some(true),
+            ..Default::default()
+        };
+        let (program, args) = tool.command("TERM", Target::Pattern("nginx"));
+        assert_eq!(program, "sudo");
+        assert_eq!(args[..2], ["sh", "-c"]);
+        assert_eq!(args[4], "TERM");
+        // The pattern is sent on stdin, never as an argument
+        assert!(!args.iter().any(|arg| arg == "nginx"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pattern_script() {
+        use tokio::io::AsyncWriteExt;
+
+        // The shell stays in the foreground of sleep, with the marker as $0
+        let marker = format!("mcp_linux_ssh_kill_test_{}", std::process::id());
+        let mut sleeper = std::process::Command::new("sh")
+            .args(["-c", "sleep 30; :", marker.as_str()])
+            .spawn()
+            .unwrap();
+
+        // The script runs under a shell whose command line matches too, as
+        // the login shell of the ssh session would
+        let parent = &format!("{}_parent", marker);
+        let run = |pattern: String| async move {
+            let mut child = tokio::process::Command::new("sh")
+                .args(["-c", "sh -c \"$1\" sh TERM; exit $?", parent.as_str()])
+                .arg(PATTERN_SCRIPT)
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::piped())
+                .spawn()
+                .unwrap();
+            let mut stdin = child.stdin.take().unwrap();
+            stdin.write_all(pattern.as_bytes()).await.unwrap();
+            drop(stdin);
+            child.wait_with_output().await.unwrap()
+        };
+
+        let output = run(format!("{}\n", marker)).await;
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            parse_pids(&String::from_utf8_lossy(&output.stdout)),
+            [sleeper.id()]
+        );
+        assert!(!sleeper.wait().unwrap().success());
+
+        let output = run(format!("{}_missing\n", marker)).await;
+        assert_eq!(output.status.code(), Some(PATTERN_NO_MATCH));
+
+        // Only the script's own ancestors match
+        let output = run(format!("{}_parent\n", marker)).await;
+        assert_eq!(output.status.code(), Some(PATTERN_NO_MATCH));
+
+        // Nor does the script signal itself through its own text
+        let output = run("pgrep -f\n".to_string()).await;
+        assert_eq!(output.status.code(), Some(PATTERN_NO_MATCH));
+    }
+
+    #[test]
+    fn test_describe_effective_command() {
+        let tool = KillProcess {
+            pattern: Some("nginx: worker".to_string()),
+            use_sudo: Some(true),
+            remote_host: "prod-db".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            tool.describe_effective_command(),
+            "sudo pkill -TERM -f 'nginx: worker' on prod-db"
+        );
+
+        let tool = KillProcess {
+            pid: Some(1234),
+            signal: Some("9".to_string()),
+            remote_host: "prod-db".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            tool.describe_effective_command(),
+            "kill -KILL 1234 on prod-db"
+        );
+    }
+}
@@ -0,0 +1,245 @@
+use super::{CommonConnection, SshConnectionParams, shell_quote};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+use serde::Serialize;
+
+/// find -printf format for one directory entry: type, size, mode, mtime,
+/// owner, group and name, separated by tabs. The name comes last so that
+/// names containing tabs can be split off intact, and entries are terminated
+/// by NUL since names may contain newlines.
+const ENTRY_FORMAT: &str = r"%y\t%s\t%m\t%T@\t%u\t%g\t%f\0";
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "list_directory",
+        description = "List the contents of a directory on a remote Linux system and return them as structured data: name, type, size, mode, mtime, owner and group for each entry. Requires GNU find on the remote host.",
+        title = "List Directory"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct ListDirectory {
+        /// The path of the directory on the remote machine.
+        pub path: String,
+        /// The host to list the directory on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Number of times to retry when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+/// An entry in a remote directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct DirectoryEntry {
+    name: String,
+    #[serde(rename = "type")]
+    entry_type: String,
+    size: u64,
+    mode: String,
+    mtime: i64,
+    owner: String,
+    group: String,
+}
+
+impl ListDirectory {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let connection = self.connection();
+
+        let script = list_script(&self.path);
+        let mut cmd = super::ssh::ssh_command(&connection, "sh", &["-c", script.as_str()])?;
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(&cmd, &self.remote_host, None));
+        }
+
+        let output = super::ssh::run_ssh(&connection, &mut cmd, timeout_seconds, false).await?;
+        match output.status.code() {
+            Some(0) => {}
+            Some(2) => {
+                return Err(CallToolError::from_message(format!(
+                    "Directory not found: {}",
+                    self.path
+                )));
+            }
+            _ => {
+                return Err(CallToolError::from_message(format!(
+                    "Failed to list directory {}: {}",
+                    self.path,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+
+        let entries = parse_entries(&output.stdout)?;
+        let text = if entries.is_empty() {
+            format!("Directory {} is empty", self.path)
+        } else {
+            entries
+                .iter()
+                .map(|entry| entry.name.as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let mut structured_content = serde_json::Map::new();
+        structured_content.insert(
+            "path".to_string(),
+            serde_json::Value::from(self.path.as_str()),
+        );
+        structured_content.insert(
+            "entries".to_string(),
+            serde_json::to_value(&entries)
+                .map_err(|e| CallToolError::from_message(e.to_string()))?,
+        );
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(text)])
+            .with_structured_content(structured_content))
+    }
+
+    /// Describe the listing, e.g. "list the directory /var/log on prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        format!(
+            "list the directory {} {}",
+            super::shell_quote(&self.path),
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// Parse the NUL-terminated entries printed by find, sorted by name.
+fn parse_entries(output: &[u8]) -> Result<Vec<DirectoryEntry>, CallToolError> {
+    let mut entries = output
+        .split(|&byte| byte == 0)
+        .filter(|record| !record.is_empty())
+        .map(|record| {
+            let record = String::from_utf8_lossy(record);
+            parse_entry(&record).ok_or_else(|| {
+                CallToolError::from_message(format!("Unexpected find output: {:?}", record))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+fn parse_entry(record: &str) -> Option<DirectoryEntry> {
+    let mut fields = record.splitn(7, '\t');
+    let entry_type = match fields.next()? {
+        "f" => "file",
+        "d" => "directory",
+        "l" => "symlink",
+        "b" => "block_device",
+        "c" => "char_device",
+        "p" => "fifo",
+        "s" => "socket",
+        _ => "unknown",
+    };
+    let size = fields.next()?.parse().ok()?;
+    let mode = fields.next()?.to_string();
+    // %T@ prints seconds with a fractional part
+    let mtime = fields.next()?.split('.').next()?.parse().ok()?;
+    let owner = fields.next()?.to_string();
+    let group = fields.next()?.to_string();
+    let name = fields.next()?.to_string();
+
+    Some(DirectoryEntry {
+        name,
+        entry_type: entry_type.to_string(),
+        size,
+        mode,
+        mtime,
+        owner,
+        group,
+    })
+}
+
+/// The script listing the directory `path`. The path and format are quoted
+/// for sh, and a path starting with - is prefixed with ./ so find doesn't take
+/// it for an expression. The test makes a missing directory distinguishable
+/// from other failures.
+fn list_script(path: &str) -> String {
+    let path = if path.starts_with('-') {
+        shell_quote(&format!("./{}", path))
+    } else {
+        shell_quote(path)
+    };
+    format!(
+        "test -d {path} || exit 2; find {path} -mindepth 1 -maxdepth 1 -printf {}",
+        shell_quote(ENTRY_FORMAT)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_entries() {
+        let output = b"f\t12\t644\t1700000000.5\troot\twheel\tb.txt\0\
+            d\t4096\t755\t1700000001.0000000000\talice\tstaff\ta dir\0";
+        let entries = parse_entries(output).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0],
+            DirectoryEntry {
+                name: "a dir".to_string(),
+                entry_type: "directory".to_string(),
+                size: 4096,
+                mode: "755".to_string(),
+                mtime: 1700000001,
+                owner: "alice".to_string(),
+                group: "staff".to_string(),
+            }
+        );
+        assert_eq!(entries[1].name, "b.txt");
+        assert_eq!(entries[1].entry_type, "file");
+    }
+
+    #[test]
+    fn test_parse_entries_empty() {
+        assert!(parse_entries(b"").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_entries_invalid() {
+        assert!(parse_entries(b"garbage\0").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_list_script() {
+        use crate::tools::{TestPath, run_script};
+
+        let dir = TestPath::dir("list_directory");
+        std::fs::create_dir(dir.join("-dir")).unwrap();
+        std::fs::write(dir.join("-dir").join("app.conf"), "a = 1\n").unwrap();
+
+        // A relative path starting with - isn't taken for a find expression
+        let output = run_script(&list_script("-dir"), &[], b"", Some(&dir));
+        assert!(output.status.success());
+        let entries = parse_entries(&output.stdout).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "app.conf");
+        assert_eq!(entries[0].size, 6);
+
+        let output = run_script(&list_script("-missing"), &[], b"", Some(&dir));
+        assert_eq!(output.status.code(), Some(2));
+    }
+}
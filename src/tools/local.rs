@@ -1,65 +1,89 @@
 use rust_mcp_sdk::{
     macros::{JsonSchema, mcp_tool},
-    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
-};
-use tokio::{
-    process::Command,
-    time::{Duration, timeout},
+    schema::{CallToolResult, schema_utils::CallToolError},
 };
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
 
 #[mcp_tool(
     name = "run_local_command",
     description = "Run a command on the local system and return the output. Use this sparingly; only when needed to troubleshoot why connecting to the remote system is failing.",
     title = "Run a local command"
 )]
-#[derive(Debug, ::serde::Deserialize, ::serde::Serialize, JsonSchema)]
+#[derive(Debug, Default, ::serde::Deserialize, ::serde::Serialize, JsonSchema)]
 pub struct RunLocalCommand {
     /// The command to run. This must be a single command. Arguments must be passed in the args parameter.
     cmd: String,
     /// The arguments to pass to the command.
     args: Vec<String>,
-    /// Timeout in seconds for the command execution. Defaults to 30 seconds. Set to 0 to disable timeout.
+    /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
     timeout_seconds: Option<u64>,
+    /// Maximum number of bytes of stdout and of stderr to return. Longer output is truncated and marked as truncated. Defaults to 1048576 (1 MiB). Set to 0 to disable truncation.
+    max_output_bytes: Option<u64>,
+    /// Expand glob patterns such as *.log in the arguments against the local filesystem, as a shell would, where * matches any run of characters and ? any single character. Arguments that match no files are passed unchanged. Defaults to false, in which case arguments are passed literally.
+    expand_globs: Option<bool>,
+    /// Directory to run the command in, which must exist. A leading ~ is expanded to the home directory. Defaults to the server's working directory, which depends on how the server was launched.
+    working_dir: Option<String>,
+    /// Content to write to the command's standard input, which is then closed, e.g. a private key for ssh-keygen -y -f /dev/stdin. Without it, the command's standard input is empty.
+    stdin: Option<String>,
 }
 
 impl RunLocalCommand {
-    #[tracing::instrument]
+    /// Describe the command that will run, e.g. "ping -c 1 db on the local
+    /// machine"
+    pub fn describe_effective_command(&self) -> String {
+        let dir = self.working_dir().ok().flatten();
+        let command = std::iter::once(self.cmd.clone())
+            .chain(self.command_args(dir.as_deref()))
+            .collect::<Vec<String>>();
+        let mut description = format!("{} on the local machine", super::command_line(&command));
+        if let Some(dir) = &dir {
+            description.push_str(&format!(" in {}", dir.display()));
+        }
+        if let Some(stdin) = &self.stdin {
+            description.push_str(&format!(", with {} bytes of standard input", stdin.len()));
+        }
+        description
+    }
+
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
     pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
-        let command_future = Command::new(&self.cmd).args(&self.args).output();
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let dir = self.working_dir()?;
+        let mut cmd = Command::new(&self.cmd);
+        cmd.args(self.command_args(dir.as_deref()));
+        if let Some(dir) = &dir {
+            cmd.current_dir(dir);
+        }
 
-        let result = if self.timeout_seconds == Some(0) {
-            // No timeout - run indefinitely
-            command_future.await
-        } else {
-            // Apply timeout
-            let timeout_duration = Duration::from_secs(self.timeout_seconds.unwrap_or(30));
-            match timeout(timeout_duration, command_future).await {
-                Ok(result) => result,
-                Err(_) => {
-                    return Err(CallToolError::from_message(format!(
-                        "Local command timed out after {:?} seconds",
-                        self.timeout_seconds
-                    )));
-                }
+        let (result, timing) = match &self.stdin {
+            Some(stdin) => {
+                super::Timing::measure(super::output_with_input_timeout(
+                    &mut cmd,
+                    stdin.as_bytes(),
+                    timeout_seconds,
+                ))
+                .await
+            }
+            None => {
+                super::Timing::measure(super::output_with_timeout(&mut cmd, timeout_seconds, false))
+                    .await
             }
         };
-
         match result {
             Ok(output) => {
                 // The command executed successfully. This doesn't mean it
                 // succeeded, so output is returned as a successful tool call.
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let status_code = output.status.code();
-
-                Ok(
-                    CallToolResult::text_content(vec![TextContent::from(stdout.clone())])
-                        .with_structured_content(super::map_from_output(
-                            stdout,
-                            stderr,
-                            status_code,
-                        )),
-                )
+                // A command that timed out is returned with its output so far.
+                let mut result =
+                    super::result_from_output(&output.output, self.max_output_bytes, Some(timing));
+                if output.timed_out {
+                    result
+                        .structured_content
+                        .get_or_insert_default()
+                        .insert("timed_out".to_string(), serde_json::Value::Bool(true));
+                }
+                Ok(result)
             }
             Err(err) => Err(CallToolError::from_message(format!(
                 "Failed to run local command: {}",
@@ -69,9 +93,80 @@ impl RunLocalCommand {
     }
 }
 
+impl RunLocalCommand {
+    /// The arguments passed to the command, with globs expanded if requested.
+    /// Relative patterns are matched in `dir`, if given.
+    fn command_args(&self, dir: Option<&Path>) -> Vec<String> {
+        if self.expand_globs.unwrap_or(false) {
+            self.args
+                .iter()
+                .flat_map(|arg| expand_glob(arg, dir))
+                .collect()
+        } else {
+            self.args.clone()
+        }
+    }
+
+    /// The directory to run the command in, with ~ expanded, if set. A
+    /// directory that doesn't exist is an error, rather than the command
+    /// failing to start with a message that doesn't say why.
+    fn working_dir(&self) -> Result<Option<PathBuf>, CallToolError> {
+        let Some(working_dir) = self.working_dir.as_deref() else {
+            return Ok(None);
+        };
+        let dir = PathBuf::from(
+            super::expand_path(working_dir)
+                .map_err(|e| CallToolError::from_message(e.to_string()))?,
+        );
+        match std::fs::metadata(&dir) {
+            Ok(metadata) if metadata.is_dir() => Ok(Some(dir)),
+            Ok(_) => Err(CallToolError::from_message(format!(
+                "Working directory is not a directory: {}",
+                dir.display()
+            ))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(CallToolError::from_message(
+                format!("Working directory not found: {}", dir.display()),
+            )),
+            Err(e) => Err(CallToolError::from_message(format!(
+                "Failed to access working directory {}: {}",
+                dir.display(),
+                e
+            ))),
+        }
+    }
+}
+
+/// Expand a glob pattern into the sorted paths it matches. Relative patterns
+/// are matched in `dir`, if given, and expand to paths relative to it, as in
+/// a shell started there. Arguments that aren't patterns or match nothing are
+/// returned unchanged, as in a shell.
+fn expand_glob(arg: &str, dir: Option<&Path>) -> Vec<String> {
+    if !arg.contains(['*', '?']) {
+        return vec![arg.to_string()];
+    }
+    let dir = dir.filter(|_| Path::new(arg).is_relative());
+    let mut matches: Vec<String> = super::glob_paths(dir.unwrap_or(Path::new("")), Path::new(arg))
+        .into_iter()
+        .map(|path| match dir {
+            Some(dir) => path
+                .strip_prefix(dir)
+                .map(Path::to_path_buf)
+                .unwrap_or(path),
+            None => path,
+        })
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    if matches.is_empty() {
+        return vec![arg.to_string()];
+    }
+    matches.sort();
+    matches
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tools::TestPath;
 
     #[tokio::test]
     async fn test_run_local_command_success() {
@@ -79,21 +174,213 @@ mod tests {
             cmd: "echo".to_string(),
             args: vec!["hello".to_string()],
             timeout_seconds: None,
+            ..Default::default()
         };
 
         let result = cmd.call_tool().await;
         assert!(result.is_ok());
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_local_command_reports_timing() {
+        let cmd = RunLocalCommand {
+            cmd: "sleep".to_string(),
+            args: vec!["0.1".to_string()],
+            ..Default::default()
+        };
+
+        let result = cmd.call_tool().await.unwrap();
+        let structured_content = result.structured_content.unwrap();
+        assert!(structured_content["started_at"].as_u64().unwrap() > 0);
+        assert!(structured_content["duration_ms"].as_u64().unwrap() > 0);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_local_command_killed_by_signal() {
+        let cmd = RunLocalCommand {
+            cmd: "sh".to_string(),
+            args: vec!["-c".to_string(), "kill -KILL $$".to_string()],
+            timeout_seconds: None,
+            ..Default::default()
+        };
+
+        let result = cmd.call_tool().await.unwrap();
+        let structured_content = result.structured_content.unwrap();
+        assert!(structured_content["status_code"].is_null());
+        assert_eq!(structured_content["signal"], 9);
+        assert_eq!(structured_content["core_dumped"], false);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_local_command_timeout_returns_partial_output() {
+        let cmd = RunLocalCommand {
+            cmd: "sh".to_string(),
+            args: vec!["-c".to_string(), "echo partial; sleep 10".to_string()],
+            timeout_seconds: Some(1),
+            ..Default::default()
+        };
+
+        let result = cmd.call_tool().await.unwrap();
+        let structured_content = result.structured_content.unwrap();
+        assert_eq!(structured_content["timed_out"], true);
+        assert_eq!(structured_content["stdout"], "partial\n");
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let dir = TestPath::dir("expand_glob");
+        for name in ["b.log", "a.log", "notes.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let dir_str = dir.to_string_lossy();
+
+        assert_eq!(
+            expand_glob(&format!("{}/*.log", dir_str), None),
+            [format!("{}/a.log", dir_str), format!("{}/b.log", dir_str)]
+        );
+        // Patterns matching nothing and plain arguments are left alone
+        let unmatched = format!("{}/*.gz", dir_str);
+        assert_eq!(
+            expand_glob(&unmatched, None),
+            std::slice::from_ref(&unmatched)
+        );
+        assert_eq!(expand_glob("-la", None), ["-la"]);
+
+        // Relative patterns are matched in the working directory
+        assert_eq!(expand_glob("*.log", Some(&dir)), ["a.log", "b.log"]);
+    }
+
+    #[test]
+    fn test_command_args_without_expand_globs() {
+        let cmd = RunLocalCommand {
+            cmd: "ls".to_string(),
+            args: vec!["/*".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(cmd.command_args(None), ["/*"]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_local_command_working_dir() {
+        let dir = std::env::temp_dir().canonicalize().unwrap();
+        let cmd = RunLocalCommand {
+            cmd: "pwd".to_string(),
+            working_dir: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let result = cmd.call_tool().await.unwrap();
+        let structured_content = result.structured_content.unwrap();
+        assert_eq!(structured_content["stdout"], format!("{}\n", dir.display()));
+
+        let cmd = RunLocalCommand {
+            cmd: "pwd".to_string(),
+            working_dir: Some("/nonexistent/mcp_linux_ssh".to_string()),
+            ..Default::default()
+        };
+        let err = cmd.call_tool().await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Working directory not found: /nonexistent/mcp_linux_ssh")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_local_command_stdin() {
+        let cmd = RunLocalCommand {
+            cmd: "cat".to_string(),
+            stdin: Some("line one\nline two\n".to_string()),
+            ..Default::default()
+        };
+        let result = cmd.call_tool().await.unwrap();
+        let structured_content = result.structured_content.unwrap();
+        assert_eq!(structured_content["stdout"], "line one\nline two\n");
+        assert_eq!(structured_content["status_code"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_local_command_large_stdin() {
+        // More than the pipes hold, so cat blocks writing stdout unless it is
+        // read while stdin is written
+        let input = "0123456789abcdef".repeat(64 * 1024);
+        let cmd = RunLocalCommand {
+            cmd: "cat".to_string(),
+            stdin: Some(input.clone()),
+            timeout_seconds: Some(10),
+            max_output_bytes: Some(0),
+            ..Default::default()
+        };
+        let result = cmd.call_tool().await.unwrap();
+        let structured_content = result.structured_content.unwrap();
+        assert_eq!(
+            structured_content["stdout"].as_str().unwrap().len(),
+            input.len()
+        );
+        assert!(!structured_content.contains_key("timed_out"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_local_command_stdin_timeout() {
+        // The output printed before the timeout is returned
+        let cmd = RunLocalCommand {
+            cmd: "sh".to_string(),
+            args: vec!["-c".to_string(), "head -n 1; sleep 10".to_string()],
+            stdin: Some("first\nsecond\n".to_string()),
+            timeout_seconds: Some(1),
+            ..Default::default()
+        };
+        let result = cmd.call_tool().await.unwrap();
+        let structured_content = result.structured_content.unwrap();
+        assert_eq!(structured_content["timed_out"], true);
+        assert_eq!(structured_content["stdout"], "first\n");
+    }
+
     #[tokio::test]
     async fn test_run_local_command_nonexistent() {
         let cmd = RunLocalCommand {
             cmd: "nonexistent_command_12345".to_string(),
             args: vec![],
             timeout_seconds: None,
+            ..Default::default()
         };
 
         let result = cmd.call_tool().await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_run_local_command_binary_output() {
+        let cmd = RunLocalCommand {
+            cmd: "printf".to_string(),
+            args: vec!["\\377\\376".to_string()],
+            timeout_seconds: None,
+            ..Default::default()
+        };
+
+        let result = cmd.call_tool().await.unwrap();
+        let structured_content = result.structured_content.unwrap();
+        assert_eq!(structured_content["encoding"], "base64");
+        assert_eq!(structured_content["stdout"], "");
+        assert_eq!(structured_content["stdout_base64"], "//4=");
+    }
+
+    #[tokio::test]
+    async fn test_run_local_command_truncates_output() {
+        let cmd = RunLocalCommand {
+            cmd: "echo".to_string(),
+            args: vec!["hello world".to_string()],
+            timeout_seconds: None,
+            max_output_bytes: Some(5),
+            ..Default::default()
+        };
+
+        let result = cmd.call_tool().await.unwrap();
+        let structured_content = result.structured_content.unwrap();
+        assert_eq!(structured_content["stdout"], "hello...[truncated 7 bytes]");
+        assert_eq!(structured_content["truncated"], true);
+    }
 }
@@ -1,17 +1,68 @@
+mod append_file;
+mod check_connection;
 mod copy_file;
+mod fetch_file;
+mod kill_process;
+mod list_directory;
 mod local;
+mod move_file;
+mod package;
+mod parse_output;
 mod patch_file;
+mod read_remote_file;
+mod remote_identity;
+mod render_and_write;
+mod run_sequence;
+mod run_with_stdin;
 mod ssh;
+mod system_snapshot;
+mod systemctl;
+mod tail_file;
+mod verify_checksum;
+mod write_file;
 
+use crate::config::ConnectionDefaults;
 use anyhow::Error;
+use base64::{Engine, prelude::BASE64_STANDARD};
 use expand_tilde::expand_tilde;
-use std::ops::Deref;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    process::{Child, Command},
+    time::{timeout, timeout_at},
+};
 
+use append_file::AppendFile;
+use check_connection::CheckConnection;
 use copy_file::CopyFile;
+use fetch_file::FetchFile;
+use kill_process::KillProcess;
+use list_directory::ListDirectory;
 use local::RunLocalCommand;
+use move_file::MoveFile;
+use package::Package;
 use patch_file::PatchFile;
-use rust_mcp_sdk::tool_box;
+use read_remote_file::ReadRemoteFile;
+use remote_identity::RemoteIdentity;
+use render_and_write::RenderAndWrite;
+use run_sequence::RunSequence;
+use run_with_stdin::RunWithStdin;
+use rust_mcp_sdk::{
+    schema::{CallToolResult, RpcError, TextContent, schema_utils::CallToolError},
+    tool_box,
+};
 use ssh::{RunSSHCommand, RunSSHSudoCommand};
+use system_snapshot::SystemSnapshot;
+use systemctl::Systemctl;
+use tail_file::TailFile;
+use verify_checksum::VerifyChecksum;
+use write_file::WriteFile;
 
 tool_box!(
     POSIXSSHTools,
@@ -19,45 +70,1554 @@ tool_box!(
         RunLocalCommand,
         RunSSHCommand,
         RunSSHSudoCommand,
+        RunWithStdin,
+        RunSequence,
+        CheckConnection,
         CopyFile,
-        PatchFile
+        FetchFile,
+        KillProcess,
+        ListDirectory,
+        Package,
+        PatchFile,
+        ReadRemoteFile,
+        RemoteIdentity,
+        SystemSnapshot,
+        Systemctl,
+        TailFile,
+        VerifyChecksum,
+        WriteFile,
+        AppendFile,
+        RenderAndWrite,
+        MoveFile
     ]
 );
 
-/// Get SSH multiplexing options for a given host.
-/// Returns a vector of SSH option strings that can be passed via `-o` flags.
-///
-/// The options enable SSH connection multiplexing:
-/// - ControlMaster auto: Automatically use existing master connection or create one
-/// - ControlPath: Socket path template for the control socket
-/// - ControlPersist: Keep master connection alive for 10 minutes after last use
+impl POSIXSSHTools {
+    /// Describe in plain words what a tool call will do, e.g. "sudo apt remove
+    /// --purge nginx on prod-db", so the judge evaluates what will really run
+    pub fn describe_effective_command(&self) -> String {
+        match self {
+            POSIXSSHTools::RunLocalCommand(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::RunSSHCommand(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::RunSSHSudoCommand(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::RunWithStdin(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::RunSequence(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::CheckConnection(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::CopyFile(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::FetchFile(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::KillProcess(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::ListDirectory(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::Package(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::PatchFile(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::ReadRemoteFile(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::Systemctl(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::SystemSnapshot(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::RemoteIdentity(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::TailFile(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::VerifyChecksum(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::WriteFile(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::AppendFile(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::RenderAndWrite(tool) => tool.describe_effective_command(),
+            POSIXSSHTools::MoveFile(tool) => tool.describe_effective_command(),
+        }
+    }
+
+    /// Describe each command a tool call will run, the way
+    /// describe_effective_command does, so that the judge's allow and deny
+    /// patterns are matched against each step of a sequence on its own
+    pub fn describe_effective_commands(&self) -> Vec<String> {
+        match self {
+            POSIXSSHTools::RunSequence(tool) => tool.describe_steps(),
+            tool => vec![tool.describe_effective_command()],
+        }
+    }
+}
+
+/// Join the words of a command into a command line, quoting words as a shell
+/// would need them.
+fn command_line<S: AsRef<str>>(words: &[S]) -> String {
+    words
+        .iter()
+        .map(|word| shell_quote(word.as_ref()))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Split `[user@]host` into the user, if any, and the host, without the
+/// brackets around an IPv6 address such as `[fe80::1]`.
+fn split_destination(remote_host: &str) -> (Option<&str>, &str) {
+    let (user, host) = match remote_host.rsplit_once('@') {
+        Some((user, host)) => (Some(user), host),
+        None => (None, remote_host),
+    };
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    (user, host)
+}
+
+/// Whether `host` is an IPv6 address, with or without a zone such as %eth0.
+fn is_ipv6_literal(host: &str) -> bool {
+    let address = host.split_once('%').map_or(host, |(address, _)| address);
+    address.parse::<std::net::Ipv6Addr>().is_ok()
+}
+
+/// Format `[user@]host` for the ssh command line. ssh takes IPv6 addresses
+/// without brackets, so brackets given around one are removed.
+pub(crate) fn ssh_destination(remote_host: &str) -> Result<String, Error> {
+    validate_remote_host(remote_host)?;
+    Ok(match split_destination(remote_host) {
+        (Some(user), host) if is_ipv6_literal(host) => format!("{}@{}", user, host),
+        (None, host) if is_ipv6_literal(host) => host.to_string(),
+        _ => remote_host.to_string(),
+    })
+}
+
+/// Format `[user@]host` for a `host:path` argument of rsync or sftp, wrapping
+/// an IPv6 address in brackets so its colons aren't taken for the one
+/// separating the path.
+pub(crate) fn path_destination(remote_host: &str) -> Result<String, Error> {
+    validate_remote_host(remote_host)?;
+    Ok(match split_destination(remote_host) {
+        (Some(user), host) if is_ipv6_literal(host) => format!("{}@[{}]", user, host),
+        (None, host) if is_ipv6_literal(host) => format!("[{}]", host),
+        _ => remote_host.to_string(),
+    })
+}
+
+/// Reject a remote host that ssh, rsync or sftp would parse as an option,
+/// such as -oProxyCommand=...
+fn validate_remote_host(remote_host: &str) -> Result<(), Error> {
+    if remote_host.starts_with('-') {
+        return Err(Error::msg(format!(
+            "Invalid remote host: {:?}",
+            remote_host
+        )));
+    }
+    Ok(())
+}
+
+/// Describe the host a tool call runs on, e.g. "on prod-db via bastion".
+fn on_host(remote_host: &str, jump_host: Option<&str>) -> String {
+    match jump_host.filter(|jump_host| !jump_host.is_empty()) {
+        Some(jump_host) => format!("on {} via {}", remote_host, jump_host),
+        None => format!("on {}", remote_host),
+    }
+}
+
+/// Describe the ssh program a tool call selected, e.g. ", using
+/// /opt/openssh/bin/ssh", or nothing when it runs the configured one.
+fn using_ssh_binary(ssh_binary: Option<&str>) -> String {
+    match ssh_binary
+        .map(str::trim)
+        .filter(|program| !program.is_empty())
+    {
+        Some(program) => format!(", using {}", shell_quote(program)),
+        None => String::new(),
+    }
+}
+
+/// How long a master connection stays open after its last session ends
+const CONTROL_PERSIST: &str = "60s";
+
+/// Directory holding the control sockets of multiplexed connections. It is
+/// created on first use, readable only by the server's user, and removed by
+/// [`remove_control_dir`] when the server shuts down.
+static CONTROL_DIR: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+
+fn control_dir() -> Result<&'static std::path::Path, Error> {
+    CONTROL_DIR
+        .get_or_init(|| {
+            create_control_dir()
+                .map_err(|e| format!("Failed to create the control socket directory: {}", e))
+        })
+        .as_deref()
+        .map_err(Error::msg)
+}
+
+fn create_control_dir() -> std::io::Result<PathBuf> {
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    let mut attempts = 0;
+    loop {
+        // A fresh RandomState is randomly seeded, so the name can't be
+        // guessed and taken over in the shared temporary directory. It is
+        // kept short as control socket paths are limited to about 100 bytes.
+        let suffix = std::hash::BuildHasher::hash_one(
+            &std::collections::hash_map::RandomState::new(),
+            std::process::id(),
+        ) as u32;
+        let dir = std::env::temp_dir().join(format!("mcp_linux_ssh-{:08x}", suffix));
+        match builder.create(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempts < 10 => {
+                attempts += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Close the master connections opened by this process and remove their
+/// control socket directory. Called when the server shuts down.
+pub fn remove_control_dir() {
+    let Some(Ok(dir)) = CONTROL_DIR.get() else {
+        return;
+    };
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            // With the control path given, the host is only a placeholder
+            let _ = std::process::Command::new(default_ssh_program())
+                .arg("-o")
+                .arg(format!("ControlPath={}", entry.path().display()))
+                .args(["-O", "exit", "mcp_linux_ssh"])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+    }
+    if let Err(e) = std::fs::remove_dir_all(dir) {
+        tracing::warn!("Failed to remove {}: {}", dir.display(), e);
+    }
+}
+
+/// Get SSH multiplexing options, passed via `-o` flags:
+/// - ControlMaster auto: Use an existing master connection or create one
+/// - ControlPath: The control socket, under this process's [`control_dir`]
+/// - ControlPersist: Keep the master connection alive for 60 seconds after
+///   its last use
 pub(crate) fn get_multiplexing_options() -> Result<Vec<String>, Error> {
-    // Expand ~ to home directory for ControlPath
-    let control_path_template = "~/.ssh/control-%h-%p-%r";
-    let expanded_path = expand_tilde(control_path_template)
-        .map_err(|e| Error::msg(format!("Failed to expand ControlPath: {}", e)))?;
-    let control_path = expanded_path.deref().as_os_str().to_str().ok_or_else(|| {
+    let control_path = control_dir()?.join("cm-%r@%h:%p");
+    Ok(vec![
+        "ControlMaster=auto".to_string(),
+        format!("ControlPath={}", control_path.display()),
+        format!("ControlPersist={}", CONTROL_PERSIST),
+    ])
+}
+
+/// A secret whose value is never included in Debug output.
+#[derive(Clone, Default)]
+pub(crate) struct Secret(pub String);
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+/// Names of tool parameters whose values are never logged or sent to the judge.
+const SENSITIVE_FIELDS: &[&str] = &["password"];
+
+/// Replace the values of sensitive fields in serialized tool parameters.
+pub(crate) fn redact(params: &mut serde_json::Value) {
+    if let Some(map) = params.as_object_mut() {
+        for field in SENSITIVE_FIELDS {
+            if let Some(value) = map.get_mut(*field)
+                && !value.is_null()
+            {
+                *value = serde_json::Value::from("[REDACTED]");
+            }
+        }
+    }
+}
+
+/// Environment variable setting the timeout, in seconds, of tool calls that
+/// don't pass one.
+const DEFAULT_TIMEOUT_VAR: &str = "MCP_LINUX_SSH_DEFAULT_TIMEOUT";
+
+/// Environment variable setting the longest timeout, in seconds, a tool call
+/// may use. When set, calls can't disable their timeout.
+const MAX_TIMEOUT_VAR: &str = "MCP_LINUX_SSH_MAX_TIMEOUT";
+
+/// Timeout of tool calls that don't pass one, unless configured otherwise.
+const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
+
+/// The timeout in seconds of a tool call that asked for `requested`: the
+/// configured default if it didn't ask, clamped to the configured maximum.
+pub(crate) fn timeout_seconds(requested: Option<u64>) -> u64 {
+    let default = timeout_var(DEFAULT_TIMEOUT_VAR).unwrap_or(DEFAULT_TIMEOUT_SECONDS);
+    clamp_timeout(requested.unwrap_or(default), max_timeout())
+}
+
+/// The longest timeout a tool call may use, if one is configured.
+pub(crate) fn max_timeout() -> Option<u64> {
+    timeout_var(MAX_TIMEOUT_VAR).filter(|max| *max > 0)
+}
+
+/// Limit a timeout to `max`. A timeout of 0, which disables the timeout, is
+/// only kept when there is no maximum.
+pub(crate) fn clamp_timeout(timeout_seconds: u64, max: Option<u64>) -> u64 {
+    match max {
+        Some(max) if timeout_seconds == 0 || timeout_seconds > max => max,
+        _ => timeout_seconds,
+    }
+}
+
+fn timeout_var(var: &str) -> Option<u64> {
+    let value = std::env::var(var).ok()?;
+    match value.trim().parse() {
+        Ok(seconds) => Some(seconds),
+        Err(_) => {
+            tracing::warn!("Ignoring invalid {}: {:?}", var, value);
+            None
+        }
+    }
+}
+
+/// Environment variable controlling whether patch, stdin and file contents
+/// are logged. Set it to false to log only their size.
+const LOG_PATCH_BODIES_VAR: &str = "MCP_LINUX_SSH_LOG_PATCH_BODIES";
+
+/// Parameters holding content to be streamed to a remote command, which is
+/// summarized in the logs.
+const BODY_FIELDS: &[&str] = &["patch", "stdin", "content", "template"];
+
+/// Contents longer than this are truncated in the logs.
+const MAX_LOGGED_BODY_BYTES: usize = 1024;
+
+/// Serialize tool parameters for logging, with sensitive fields redacted and
+/// patch, stdin and file contents truncated or omitted.
+pub(crate) fn redacted<T: Serialize>(params: &T) -> String {
+    let mut value = serde_json::to_value(params).unwrap_or_default();
+    redact(&mut value);
+    for field in BODY_FIELDS {
+        summarize_body(&mut value, field, log_patch_bodies());
+    }
+    value.to_string()
+}
+
+fn log_patch_bodies() -> bool {
+    std::env::var(LOG_PATCH_BODIES_VAR)
+        .map(|value| !matches!(value.trim().to_lowercase().as_str(), "false" | "0" | "no"))
+        .unwrap_or(true)
+}
+
+/// Shorten a content field of serialized tool parameters, such as `patch`, for
+/// logging. When `log_bodies` is false the content is replaced by its size.
+fn summarize_body(params: &mut serde_json::Value, field: &str, log_bodies: bool) {
+    let Some(serde_json::Value::String(body)) = params.get_mut(field) else {
+        return;
+    };
+
+    if !log_bodies {
+        *body = format!(
+            "[omitted {} bytes, {} lines]",
+            body.len(),
+            body.lines().count()
+        );
+    } else if body.len() > MAX_LOGGED_BODY_BYTES {
+        let mut end = MAX_LOGGED_BODY_BYTES;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        let omitted = body.len() - end;
+        body.truncate(end);
+        body.push_str(&format!("...[truncated {} bytes]", omitted));
+    }
+}
+
+/// Deserialize a parameter given either as a single string or as a list of
+/// strings.
+pub(crate) fn one_or_many<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(
+        Option::<OneOrMany>::deserialize(deserializer)?.map(|value| match value {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }),
+    )
+}
+
+/// Find an executable on PATH, or at `name` if it is a path.
+pub(crate) fn find_executable(name: &str) -> Option<PathBuf> {
+    // A path is used as is rather than looked up on PATH
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return path.is_file().then_some(path);
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Check that the programs a tool runs locally are on PATH, so that a missing
+/// one is reported as such instead of as "No such file or directory" when it
+/// fails to start. Programs that were found are remembered; missing ones are
+/// looked for again on each call, so installing one doesn't need a restart.
+pub(crate) fn require_installed(programs: &[&str]) -> Result<(), CallToolError> {
+    static INSTALLED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+    let mut installed = INSTALLED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    for &program in programs {
+        if installed.contains(program) {
+            continue;
+        }
+        if find_executable(program).is_none() {
+            let name = std::path::Path::new(program)
+                .file_name()
+                .map_or(program.into(), |name| name.to_string_lossy());
+            let package = match name.as_ref() {
+                "ssh" | "sftp" | "scp" => "an OpenSSH client",
+                program => program,
+            };
+            return Err(CallToolError::from_message(format!(
+                "{} not found; install {}",
+                program, package
+            )));
+        }
+        installed.insert(program.to_string());
+    }
+    Ok(())
+}
+
+/// Environment variable holding the path of the ssh program to run, for
+/// hardened or FIPS builds that aren't the first ssh on PATH.
+const SSH_BINARY_VAR: &str = "MCP_LINUX_SSH_SSH_BINARY";
+
+/// The ssh program run unless another is configured
+const DEFAULT_SSH_BINARY: &str = "ssh";
+
+/// The ssh program the tools run unless a call selects another:
+/// MCP_LINUX_SSH_SSH_BINARY, else ssh from PATH
+pub(crate) fn default_ssh_program() -> String {
+    std::env::var(SSH_BINARY_VAR)
+        .map(|program| program.trim().to_string())
+        .ok()
+        .filter(|program| !program.is_empty())
+        .unwrap_or_else(|| DEFAULT_SSH_BINARY.to_string())
+}
+
+/// The ciphers OpenSSH supports, as listed by `ssh -Q cipher`. A cipher must
+/// be one of these, which also keeps anything else out of the rsync -e
+/// command line.
+const CIPHERS: &[&str] = &[
+    "3des-cbc",
+    "aes128-cbc",
+    "aes192-cbc",
+    "aes256-cbc",
+    "aes128-ctr",
+    "aes192-ctr",
+    "aes256-ctr",
+    "aes128-gcm@openssh.com",
+    "aes256-gcm@openssh.com",
+    "chacha20-poly1305@openssh.com",
+];
+
+/// Declare the parameters of a tool that reaches a remote host over SSH, with
+/// the connection parameters every such tool takes appended to its own, and
+/// implement [`CommonConnection`] to turn them into [`SshConnectionParams`].
+/// A tool whose `reuse_connection` or `connect_timeout_seconds` behaves
+/// differently describes it first, e.g. `reuse_connection = "...";`.
+macro_rules! ssh_tool {
+    (
+        reuse_connection = $reuse_connection:tt;
+        connect_timeout_seconds = $connect_timeout_seconds:tt;
+        $(#[$($attr:tt)*])*
+        pub struct $name:ident {
+            $($fields:tt)*
+        }
+    ) => {
+        $(#[$($attr)*])*
+        pub struct $name {
+            $($fields)*
+            /// Additional options to pass to the ssh command. Each option should be a key-value pair separated by an equal sign (=). The options are passed to the ssh command using the -o flag.
+            pub options: Option<Vec<String>>,
+            /// Jump host (bastion) to connect through, in the form [user@]host[:port]. The jump host is passed to the ssh command using the -J flag.
+            pub jump_host: Option<String>,
+            /// Port to connect to on the remote host. Defaults to the port configured for the host in ~/.ssh/config, or 22.
+            pub port: Option<u16>,
+            #[doc = $connect_timeout_seconds]
+            pub connect_timeout_seconds: Option<u64>,
+            /// Path to the private key to authenticate with, or a list of paths to try in order. Each is passed to ssh as IdentityFile, must exist and should only be readable by its owner. Defaults to the keys configured in ~/.ssh/config or ssh's defaults.
+            #[serde(default, deserialize_with = "crate::tools::one_or_many")]
+            pub private_key: Option<Vec<String>>,
+            /// Path to an SSH certificate to authenticate with, signed for one of the private keys, passed to ssh as CertificateFile. The private key (or ssh-agent) still provides the signing key. Must exist. Defaults to the certificates configured in ~/.ssh/config or found next to the keys.
+            pub certificate: Option<String>,
+            /// Authenticate with the keys held by ssh-agent, ignoring private_key and setting IdentitiesOnly=no. Defaults to false.
+            pub use_agent: Option<bool>,
+            #[doc = $reuse_connection]
+            pub reuse_connection: Option<bool>,
+            /// Let ~/.ssh/config decide every setting not passed explicitly, such as ProxyJump and host key checking, and the user and identity file when remote_host is a Host alias defined there. Defaults to false, in which case host key checking defaults to yes.
+            pub use_ssh_config: Option<bool>,
+            /// Password for password or keyboard-interactive authentication. Requires sshpass to be installed locally. The password is passed to sshpass through the SSHPASS environment variable and is never logged. Prefer key-based authentication where possible.
+            pub password: Option<String>,
+            /// Host key checking policy: yes, no, or accept-new. Defaults to yes so that ssh fails instead of prompting for unknown host keys.
+            pub strict_host_key_checking: Option<String>,
+            /// Path to the known_hosts file to verify host keys against. Defaults to the file ssh normally uses (~/.ssh/known_hosts).
+            pub known_hosts_file: Option<String>,
+            /// Path of the ssh program to run, e.g. /opt/openssh/bin/ssh for a hardened build that isn't first on PATH. Defaults to MCP_LINUX_SSH_SSH_BINARY if the server sets it, or to ssh.
+            pub ssh_binary: Option<String>,
+            /// SSH cipher to use, or a comma-separated list in order of preference, passed to ssh as -c, e.g. aes128-gcm@openssh.com or chacha20-poly1305@openssh.com. On fast links the cipher can limit the transfer rate. Must be a cipher OpenSSH supports. Defaults to ssh's own choice.
+            pub cipher: Option<String>,
+        }
+
+        impl $crate::tools::CommonConnection for $name {
+            fn common_connection(&self, remote_host: &str) -> $crate::tools::SshConnectionParams {
+                $crate::tools::SshConnectionParams {
+                    remote_host: remote_host.to_string(),
+                    jump_host: self.jump_host.clone(),
+                    port: self.port,
+                    connect_timeout_seconds: self.connect_timeout_seconds,
+                    private_keys: self.private_key.clone().unwrap_or_default(),
+                    certificate: self.certificate.clone(),
+                    use_agent: self.use_agent.unwrap_or(false),
+                    reuse_connection: self.reuse_connection,
+                    use_ssh_config: self.use_ssh_config.unwrap_or(false),
+                    password: self.password.clone().map($crate::tools::Secret),
+                    strict_host_key_checking: self.strict_host_key_checking.clone(),
+                    known_hosts_file: self.known_hosts_file.clone(),
+                    ssh_binary: self.ssh_binary.clone(),
+                    cipher: self.cipher.clone(),
+                    options: self.options.clone(),
+                    ..Default::default()
+                }
+            }
+        }
+    };
+    (
+        reuse_connection = $reuse_connection:tt;
+        $($tool:tt)*
+    ) => {
+        $crate::tools::ssh_tool! {
+            reuse_connection = $reuse_connection;
+            connect_timeout_seconds = "Timeout in seconds for establishing the SSH connection, passed to ssh as ConnectTimeout. Independent of timeout_seconds, so that unreachable hosts fail fast while commands can still run for long. Defaults to ssh's own connection timeout.";
+            $($tool)*
+        }
+    };
+    ($($tool:tt)*) => {
+        $crate::tools::ssh_tool! {
+            reuse_connection = "Reuse a shared master connection to the host (SSH ControlMaster multiplexing), so that consecutive calls skip the TCP and SSH handshakes. Defaults to false.";
+            $($tool)*
+        }
+    };
+}
+pub(crate) use ssh_tool;
+
+/// The connection parameters of a tool declared with [`ssh_tool`].
+pub(crate) trait CommonConnection {
+    /// The connection to `remote_host` with the tool's connection parameters.
+    /// Retries, compression, a terminal and a temporary directory are left
+    /// for the tools taking them to set.
+    fn common_connection(&self, remote_host: &str) -> SshConnectionParams;
+}
+
+/// Connection settings shared by the tools that reach a remote host over SSH.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SshConnectionParams {
+    /// The host to connect to.
+    pub remote_host: String,
+    /// Jump host to reach `remote_host` through, in the form `[user@]host[:port]`.
+    pub jump_host: Option<String>,
+    /// Port to connect to on the remote host.
+    pub port: Option<u16>,
+    /// Timeout for establishing the connection, passed as ConnectTimeout.
+    pub connect_timeout_seconds: Option<u64>,
+    /// Host key checking policy (`yes`, `no` or `accept-new`). Defaults to `yes`.
+    pub strict_host_key_checking: Option<String>,
+    /// known_hosts file to verify host keys against. Defaults to ssh's own.
+    pub known_hosts_file: Option<String>,
+    /// Private keys to authenticate with, in addition to the keys ssh would
+    /// otherwise try.
+    pub private_keys: Vec<String>,
+    /// SSH certificate for one of the private keys, passed as CertificateFile.
+    pub certificate: Option<String>,
+    /// Authenticate with ssh-agent instead of `private_keys`.
+    pub use_agent: bool,
+    /// Password for password or keyboard-interactive authentication, supplied
+    /// to ssh by sshpass through the SSHPASS environment variable.
+    pub password: Option<Secret>,
+    /// Additional options passed to ssh using the `-o` flag.
+    pub options: Option<Vec<String>>,
+    /// Leave settings the caller didn't pass to ~/.ssh/config instead of
+    /// applying our defaults for host key checking, and for the user and
+    /// private key when `remote_host` is a Host alias defined there.
+    pub use_ssh_config: bool,
+    /// Whether to multiplex connections to the host over a shared master
+    /// connection. Defaults to false.
+    pub reuse_connection: Option<bool>,
+    /// Number of times to retry a command that failed to connect.
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each further retry.
+    pub retry_backoff_ms: u64,
+    /// Compress the connection (`ssh -C`).
+    pub compression: bool,
+    /// Force a pseudo-terminal for the remote command (`ssh -tt`). Only used
+    /// when running commands with ssh, not for copying files.
+    pub allocate_tty: bool,
+    /// The ssh program to run. Defaults to MCP_LINUX_SSH_SSH_BINARY, or ssh.
+    pub ssh_binary: Option<String>,
+    /// Ciphers to offer, in order of preference, as a comma-separated list
+    /// passed using the `-c` flag. Defaults to ssh's own preference.
+    pub cipher: Option<String>,
+    /// Directory the remote command creates its temporary files in, exported
+    /// as TMPDIR. Defaults to the remote host's own, normally /tmp. Only
+    /// run_with_stdin and patch_file take it: write_file creates its temporary
+    /// file next to the destination, so that it can be renamed into place
+    /// atomically, and the other tools' scripts create none.
+    pub remote_tmp_dir: Option<String>,
+}
+
+impl SshConnectionParams {
+    /// The words to run the remote command with, so that it sees the
+    /// connection's environment: `env TMPDIR=<dir>` with `remote_tmp_dir`,
+    /// or nothing.
+    pub(crate) fn remote_env(&self) -> Result<Vec<String>, Error> {
+        let Some(dir) = &self.remote_tmp_dir else {
+            return Ok(Vec::new());
+        };
+        validate_remote_tmp_dir(dir)?;
+        Ok(vec!["env".to_string(), format!("TMPDIR={}", dir)])
+    }
+
+    /// Build the ssh arguments that precede the destination host.
+    ///
+    /// StrictHostKeyChecking is always included (`yes` unless overridden) to
+    /// ensure SSH fails instead of prompting interactively, followed by the
+    /// multiplexing options and finally any user-provided options. With
+    /// `use_ssh_config`, only the settings the caller passed are included.
+    pub(crate) fn ssh_args(&self) -> Result<Vec<String>, Error> {
+        self.args_with_port_flag("-p")
+    }
+
+    /// Build the sftp arguments that precede the destination host. These are
+    /// the same as the ssh arguments except that sftp takes the port using
+    /// `-P`, preceded by `-S` when another ssh program is configured. scp
+    /// takes the same arguments.
+    pub(crate) fn sftp_args(&self) -> Result<Vec<String>, Error> {
+        let mut args = Vec::new();
+        let ssh_program = self.ssh_program()?;
+        if ssh_program != DEFAULT_SSH_BINARY {
+            args.push("-S".to_string());
+            args.push(ssh_program);
+        }
+        args.extend(self.args_with_port_flag("-P")?);
+        Ok(args)
+    }
+
+    /// The ssh program to run: `ssh_binary`, else MCP_LINUX_SSH_SSH_BINARY,
+    /// else ssh from PATH. The program runs locally, so a call may only
+    /// select the configured one or one listed in
+    /// MCP_LINUX_SSH_ALLOWED_SSH_BINARIES.
+    pub(crate) fn ssh_program(&self) -> Result<String, Error> {
+        self.ssh_program_in(crate::policy::allowed_ssh_binaries())
+    }
+
+    fn ssh_program_in(&self, allowed: &[String]) -> Result<String, Error> {
+        let configured = default_ssh_program();
+        match self
+            .ssh_binary
+            .as_deref()
+            .map(str::trim)
+            .filter(|program| !program.is_empty())
+        {
+            None => Ok(configured),
+            Some(program) if program == configured || allowed.iter().any(|a| a == program) => {
+                Ok(program.to_string())
+            }
+            Some(program) => Err(Error::msg(format!(
+                "ssh_binary {} is not in {}",
+                program,
+                crate::policy::ALLOWED_SSH_BINARIES_VAR
+            ))),
+        }
+    }
+
+    fn args_with_port_flag(&self, port_flag: &str) -> Result<Vec<String>, Error> {
+        let ssh_config = crate::resources::ssh_config_path().ok();
+        self.args_with_defaults(
+            port_flag,
+            crate::config::connection_defaults(),
+            ssh_config.as_deref(),
+        )
+    }
+
+    /// Build the arguments, falling back to `defaults` for the private key and
+    /// user when the call doesn't set them. The defaults don't apply with
+    /// `use_ssh_config` when `remote_host` is a Host alias in `ssh_config`,
+    /// which then decides both.
+    fn args_with_defaults(
+        &self,
+        port_flag: &str,
+        defaults: &ConnectionDefaults,
+        ssh_config: Option<&Path>,
+    ) -> Result<Vec<String>, Error> {
+        let mut args = Vec::new();
+        let config_host = self.use_ssh_config
+            && ssh_config.is_some_and(|config| {
+                crate::resources::is_ssh_config_alias(config, &self.remote_host)
+            });
+
+        // With use_ssh_config, only explicitly requested host key checking is
+        // passed so that the policy from ~/.ssh/config applies
+        let strict_host_key_checking = match self.strict_host_key_checking.as_deref() {
+            Some(value) => Some(value),
+            None if self.use_ssh_config => None,
+            None => Some("yes"),
+        };
+        if let Some(strict_host_key_checking) = strict_host_key_checking {
+            if !["yes", "no", "accept-new"].contains(&strict_host_key_checking) {
+                return Err(Error::msg(format!(
+                    "Invalid StrictHostKeyChecking value: {:?} (expected yes, no or accept-new)",
+                    strict_host_key_checking
+                )));
+            }
+            args.push("-o".to_string());
+            args.push(format!(
+                "StrictHostKeyChecking={}",
+                strict_host_key_checking
+            ));
+        }
+
+        if let Some(known_hosts_file) = &self.known_hosts_file {
+            args.push("-o".to_string());
+            args.push(format!(
+                "UserKnownHostsFile={}",
+                quote_option_value(&expand_path(known_hosts_file)?)?
+            ));
+        }
+
+        if self.use_agent {
+            args.push("-o".to_string());
+            args.push("IdentitiesOnly=no".to_string());
+        } else {
+            let private_keys =
+                if self.private_keys.is_empty() && !config_host && !defaults.key.is_empty() {
+                    std::slice::from_ref(&defaults.key)
+                } else {
+                    self.private_keys.as_slice()
+                };
+            for private_key in private_keys {
+                let private_key = expand_path(private_key)?;
+                validate_private_key(&private_key)?;
+                args.push("-o".to_string());
+                args.push(format!(
+                    "IdentityFile={}",
+                    quote_option_value(&private_key)?
+                ));
+            }
+        }
+
+        if let Some(certificate) = &self.certificate {
+            let certificate = expand_path(certificate)?;
+            validate_certificate(&certificate)?;
+            args.push("-o".to_string());
+            args.push(format!(
+                "CertificateFile={}",
+                quote_option_value(&certificate)?
+            ));
+        }
+
+        // A user in remote_host or options wins over the default
+        if !defaults.user.is_empty() && !config_host && !self.sets_user() {
+            args.push("-o".to_string());
+            args.push(format!("User={}", quote_option_value(&defaults.user)?));
+        }
+
+        if self.reuse_connection.unwrap_or(false) {
+            for opt in get_multiplexing_options()? {
+                args.push("-o".to_string());
+                args.push(opt);
+            }
+        }
+
+        if self.compression {
+            args.push("-C".to_string());
+        }
+
+        if let Some(cipher) = &self.cipher {
+            validate_cipher(cipher)?;
+            args.push("-c".to_string());
+            args.push(cipher.clone());
+        }
+
+        if let Some(connect_timeout_seconds) = self.connect_timeout_seconds {
+            if connect_timeout_seconds == 0 {
+                return Err(Error::msg("Invalid connect timeout: 0"));
+            }
+            args.push("-o".to_string());
+            args.push(format!("ConnectTimeout={}", connect_timeout_seconds));
+        }
+
+        if let Some(port) = self.port {
+            if port == 0 {
+                return Err(Error::msg("Invalid port: 0"));
+            }
+            args.push(port_flag.to_string());
+            args.push(port.to_string());
+        }
+
+        if let Some(jump_host) = &self.jump_host {
+            validate_jump_host(jump_host)?;
+            args.push("-J".to_string());
+            args.push(jump_host.clone());
+        }
+
+        if let Some(opts) = &self.options {
+            for opt in opts {
+                args.push("-o".to_string());
+                args.push(normalize_option(opt)?);
+            }
+        }
+
+        Ok(args)
+    }
+
+    /// Whether the call names the user to log in as, either as `user@host` or
+    /// with a User option.
+    fn sets_user(&self) -> bool {
+        self.remote_host.contains('@')
+            || self.options.iter().flatten().any(|opt| {
+                opt.split_once('=')
+                    .is_some_and(|(key, _)| key.trim().eq_ignore_ascii_case("user"))
+            })
+    }
+
+    /// Build the ssh command line passed to rsync using the `-e` flag.
+    ///
+    /// When a password is set, ssh is run through `sshpass -e`; the command
+    /// running rsync must be given the password using [`Self::apply_password`].
+    pub(crate) fn rsync_ssh_command(&self) -> Result<String, Error> {
+        // rsync splits the command into words as a shell would
+        Ok(self
+            .program_prefix(&shell_quote(&self.ssh_program()?))?
+            .into_iter()
+            .chain(self.ssh_args()?)
+            .collect::<Vec<String>>()
+            .join(" "))
+    }
+
+    /// Create a command running `program` (ssh, sftp, ...). When a password is
+    /// set, the program is run through `sshpass -e` with the password in the
+    /// SSHPASS environment variable, so it never appears on the command line.
+    pub(crate) fn command(&self, program: &str) -> Result<Command, Error> {
+        let mut prefix = self.program_prefix(program)?.into_iter();
+        let mut cmd = Command::new(prefix.next().unwrap_or_default());
+        cmd.args(prefix);
+        self.apply_password(&mut cmd);
+        Ok(cmd)
+    }
+
+    /// Set the SSHPASS environment variable on a command when a password is set.
+    pub(crate) fn apply_password(&self, cmd: &mut Command) {
+        if let Some(Secret(password)) = &self.password {
+            cmd.env("SSHPASS", password);
+        }
+    }
+
+    /// The program and the arguments preceding its own, prefixed with
+    /// `sshpass -e` when a password is set.
+    fn program_prefix(&self, program: &str) -> Result<Vec<String>, Error> {
+        if self.password.is_none() {
+            return Ok(vec![program.to_string()]);
+        }
+        if find_executable("sshpass").is_none() {
+            return Err(Error::msg(
+                "sshpass not found; install sshpass to use password authentication",
+            ));
+        }
+        Ok(vec![
+            "sshpass".to_string(),
+            "-e".to_string(),
+            program.to_string(),
+        ])
+    }
+}
+
+/// Expand a leading `~` in a local path.
+fn expand_path(path: &str) -> Result<String, Error> {
+    let expanded = expand_tilde(path)
+        .map_err(|e| Error::msg(format!("Failed to expand path {}: {}", path, e)))?;
+    Ok(expanded.to_string_lossy().into_owned())
+}
+
+/// The existing paths matching `pattern`, whose components may contain `*`
+/// and `?` wildcards as in [`crate::policy::glob_match`]. A relative pattern
+/// is matched in `dir`, which is taken literally, and the paths returned start
+/// with it. Unreadable directories match nothing.
+pub(crate) fn glob_paths(dir: &Path, pattern: &Path) -> Vec<PathBuf> {
+    let mut paths = vec![dir.to_path_buf()];
+    for component in pattern.components() {
+        let name = component.as_os_str().to_string_lossy();
+        if !name.contains(['*', '?']) {
+            paths = paths.into_iter().map(|path| path.join(component)).collect();
+            continue;
+        }
+        paths = paths
+            .into_iter()
+            .flat_map(|path| {
+                let listed = if path.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    &path
+                };
+                let entries = std::fs::read_dir(listed).into_iter().flatten().flatten();
+                entries
+                    .filter(|entry| {
+                        crate::policy::glob_match(&name, &entry.file_name().to_string_lossy())
+                    })
+                    .map(|entry| path.join(entry.file_name()))
+                    .collect::<Vec<PathBuf>>()
+            })
+            .collect();
+    }
+    paths.retain(|path| path.exists());
+    paths
+}
+
+/// Quote an `-o` option value containing whitespace so ssh (and rsync, when
+/// splitting its `-e` command) treat it as a single value.
+fn quote_option_value(value: &str) -> Result<String, Error> {
+    if value.contains('"') {
+        return Err(Error::msg(format!("Invalid option value: {:?}", value)));
+    }
+    if value.chars().any(char::is_whitespace) {
+        Ok(format!("\"{}\"", value))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// ssh options that run commands on this machine, which a tool call could
+/// otherwise use to get around the enabled tools and the host allowlist.
+const LOCAL_COMMAND_OPTIONS: &[&str] = &[
+    "LocalCommand",
+    "PermitLocalCommand",
+    "KnownHostsCommand",
+    "ProxyCommand",
+    "ProxyUseFdpass",
+    "Match",
+];
+
+/// Validate a user-provided ssh option given as `Key=Value`, returning it
+/// without the whitespace around the key and value, and with the value quoted
+/// if it contains whitespace. Keys must be alphanumeric, as ssh's are, and
+/// values non-empty without control characters, so that an entry can't split
+/// into several arguments or options, e.g. in the rsync `-e` command. Options
+/// that run local commands are rejected.
+fn normalize_option(option: &str) -> Result<String, Error> {
+    let invalid = || {
         Error::msg(format!(
-            "Failed to convert ControlPath to string: {}",
-            control_path_template
+            "Invalid SSH option {:?} (expected Key=Value, e.g. ServerAliveInterval=30)",
+            option
         ))
+    };
+    let (key, value) = option.split_once('=').ok_or_else(invalid)?;
+    let (key, value) = (key.trim(), value.trim());
+    let valid_key = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && key.chars().all(|c| c.is_ascii_alphanumeric());
+    if !valid_key || value.is_empty() || value.chars().any(char::is_control) {
+        return Err(invalid());
+    }
+    if LOCAL_COMMAND_OPTIONS
+        .iter()
+        .any(|denied| key.eq_ignore_ascii_case(denied))
+    {
+        return Err(Error::msg(format!(
+            "Invalid SSH option {:?} ({} runs commands on this machine)",
+            option, key
+        )));
+    }
+    Ok(format!("{}={}", key, quote_option_value(value)?))
+}
+
+/// Environment variable that turns private keys readable by other users from
+/// a warning into an error.
+#[cfg(unix)]
+const STRICT_KEY_PERMISSIONS_VAR: &str = "MCP_LINUX_SSH_STRICT_KEY_PERMISSIONS";
+
+/// Check that a private key exists and, on Unix, isn't accessible by other
+/// users, so a bad key fails immediately with a clear message instead of
+/// after an ssh handshake.
+fn validate_private_key(path: &str) -> Result<(), Error> {
+    let metadata = std::fs::metadata(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => Error::msg(format!("Private key not found: {}", path)),
+        _ => Error::msg(format!("Failed to read private key {}: {}", path, e)),
     })?;
+    if !metadata.is_file() {
+        return Err(Error::msg(format!("Private key is not a file: {}", path)));
+    }
 
-    Ok(vec![
-        "ControlMaster=auto".to_string(),
-        format!("ControlPath={}", control_path),
-        "ControlPersist=10m".to_string(),
-    ])
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = metadata.permissions().mode() & 0o777;
+        if !is_private_mode(mode) {
+            let message = format!(
+                "Private key {} has permissions {:o}, which allow access by other users; \
+                ssh will refuse to use it. Run chmod 600 {}",
+                path, mode, path
+            );
+            if strict_key_permissions() {
+                return Err(Error::msg(message));
+            }
+            tracing::warn!("{}", message);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether a file mode grants no access to the group or other users.
+#[cfg(unix)]
+fn is_private_mode(mode: u32) -> bool {
+    mode & 0o077 == 0
+}
+
+#[cfg(unix)]
+fn strict_key_permissions() -> bool {
+    std::env::var(STRICT_KEY_PERMISSIONS_VAR)
+        .map(|value| matches!(value.trim().to_lowercase().as_str(), "true" | "1" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Quote a string for a POSIX shell, leaving strings made only of characters
+/// the shell treats literally as they are.
+pub(crate) fn shell_quote(value: &str) -> String {
+    let is_literal = |c: char| c.is_ascii_alphanumeric() || "_-+=@%:,./".contains(c);
+    if !value.is_empty() && value.chars().all(is_literal) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Validate a cipher list, a comma-separated list of names from [`CIPHERS`].
+fn validate_cipher(cipher: &str) -> Result<(), Error> {
+    if cipher.split(',').all(|name| CIPHERS.contains(&name)) {
+        return Ok(());
+    }
+    Err(Error::msg(format!(
+        "Invalid cipher: {:?} (expected one or more of {}, separated by commas)",
+        cipher,
+        CIPHERS.join(", ")
+    )))
+}
+
+/// Validate an SSH certificate path, so that a missing certificate fails
+/// immediately instead of ssh silently authenticating without it.
+fn validate_certificate(path: &str) -> Result<(), Error> {
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => Ok(()),
+        Ok(_) => Err(Error::msg(format!("Certificate is not a file: {}", path))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(Error::msg(format!("Certificate not found: {}", path)))
+        }
+        Err(e) => Err(Error::msg(format!(
+            "Failed to read certificate {}: {}",
+            path, e
+        ))),
+    }
+}
+
+/// Validate a remote temporary directory, which must be an absolute path.
+fn validate_remote_tmp_dir(dir: &str) -> Result<(), Error> {
+    if !dir.starts_with('/') || dir.contains(['\n', '\0']) {
+        return Err(Error::msg(format!(
+            "Invalid remote_tmp_dir: {:?} (expected an absolute path)",
+            dir
+        )));
+    }
+    Ok(())
+}
+
+/// Validate a jump host given in the form `[user@]host[:port]`.
+fn validate_jump_host(jump_host: &str) -> Result<(), Error> {
+    if jump_host.is_empty()
+        || jump_host.starts_with('-')
+        || jump_host.chars().any(char::is_whitespace)
+    {
+        return Err(Error::msg(format!("Invalid jump host: {:?}", jump_host)));
+    }
+    Ok(())
+}
+
+/// Spawn a command, write `input` to its stdin, close it and wait for the
+/// command to exit, capturing its output. The input is written while the
+/// output is read, so that a command echoing it back can't block on a full
+/// stdout pipe. The command is killed if the returned future is dropped, e.g.
+/// when it times out, rather than left to finish on its own.
+pub(crate) async fn output_with_stdin(cmd: &mut Command, input: &[u8]) -> std::io::Result<Output> {
+    let mut child = cmd
+        .kill_on_drop(true)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdin = child.stdin.take();
+    let write = async {
+        match stdin {
+            Some(stdin) => write_input(stdin, input.to_vec()).await,
+            None => Ok(()),
+        }
+    };
+    let (written, output) = tokio::join!(write, child.wait_with_output());
+    written?;
+    output
+}
+
+/// How long a timed-out command has to exit after SIGTERM before it is killed.
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// How long to keep reading the output of a command after it exits, when it
+/// timed out or has no timeout. Processes it started may hold its stdout and
+/// stderr open indefinitely.
+const OUTPUT_DRAIN_PERIOD: Duration = Duration::from_millis(500);
+
+/// When a command started and how long it ran, returned with its output as
+/// `started_at` (milliseconds since the epoch) and `duration_ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Timing {
+    pub started_at: SystemTime,
+    pub duration: Duration,
+}
+
+impl Timing {
+    /// Run `future`, returning its output and how long it took
+    pub(crate) async fn measure<F: Future>(future: F) -> (F::Output, Timing) {
+        let started_at = SystemTime::now();
+        let start = Instant::now();
+        let output = future.await;
+        let timing = Timing {
+            started_at,
+            duration: start.elapsed(),
+        };
+        (output, timing)
+    }
+
+    fn started_at_ms(&self) -> u64 {
+        self.started_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since_epoch| {
+                since_epoch.as_millis().try_into().unwrap_or(u64::MAX)
+            })
+    }
+
+    fn duration_ms(&self) -> u64 {
+        self.duration.as_millis().try_into().unwrap_or(u64::MAX)
+    }
+}
+
+/// The output of a command run with [`output_with_timeout`].
+pub(crate) struct TimedOutput {
+    pub output: Output,
+    /// Whether the command was stopped because it ran past its timeout, in
+    /// which case `output` holds what it printed until then.
+    pub timed_out: bool,
+    /// Whether the timeout that stopped it was the idle timeout.
+    pub idle: bool,
+}
+
+/// Spawn a command and capture its output, like [`Command::output`], but stop
+/// it if it runs longer than `timeout_seconds` (0 to disable the timeout):
+/// it gets SIGTERM, then SIGKILL if it hasn't exited after a grace period,
+/// and the output captured so far is returned with `timed_out` set.
+///
+/// With `log_lines`, each line of output is also logged at DEBUG as it is
+/// produced.
+pub(crate) async fn output_with_timeout(
+    cmd: &mut Command,
+    timeout_seconds: u64,
+    log_lines: bool,
+) -> std::io::Result<TimedOutput> {
+    output_with_idle_timeout(cmd, timeout_seconds, None, log_lines).await
+}
+
+/// Run a command like [`output_with_timeout`], also stopping it once it has
+/// printed nothing on stdout or stderr for `idle_timeout_seconds` (None or 0
+/// to disable), however long it has run in total. `idle` is then set along
+/// with `timed_out`.
+pub(crate) async fn output_with_idle_timeout(
+    cmd: &mut Command,
+    timeout_seconds: u64,
+    idle_timeout_seconds: Option<u64>,
+    log_lines: bool,
+) -> std::io::Result<TimedOutput> {
+    output_with_timeouts(cmd, None, timeout_seconds, idle_timeout_seconds, log_lines).await
+}
+
+/// Run a command like [`output_with_timeout`] with `input` written to its
+/// stdin, which is then closed. The input is written while the output is
+/// read, so that a command echoing it back, such as cat or tee, can't block on
+/// a full stdout pipe before it has read all of it.
+pub(crate) async fn output_with_input_timeout(
+    cmd: &mut Command,
+    input: &[u8],
+    timeout_seconds: u64,
+) -> std::io::Result<TimedOutput> {
+    output_with_timeouts(cmd, Some(input.to_vec()), timeout_seconds, None, false).await
+}
+
+async fn output_with_timeouts(
+    cmd: &mut Command,
+    input: Option<Vec<u8>>,
+    timeout_seconds: u64,
+    idle_timeout_seconds: Option<u64>,
+    log_lines: bool,
+) -> std::io::Result<TimedOutput> {
+    let stdin = if input.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    };
+    let deadline =
+        (timeout_seconds > 0).then(|| Instant::now() + Duration::from_secs(timeout_seconds));
+    let mut child = cmd
+        .kill_on_drop(true)
+        .stdin(stdin)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let writer = input
+        .zip(child.stdin.take())
+        .map(|(input, stdin)| tokio::spawn(write_input(stdin, input)));
+
+    let stdout = Arc::new(Mutex::new(Vec::new()));
+    let stderr = Arc::new(Mutex::new(Vec::new()));
+    let last_output = Arc::new(Mutex::new(Instant::now()));
+    let mut readers = Vec::new();
+    if let Some(pipe) = child.stdout.take() {
+        readers.push(tokio::spawn(capture_output(
+            pipe,
+            stdout.clone(),
+            last_output.clone(),
+            log_lines.then_some("stdout"),
+        )));
+    }
+    if let Some(pipe) = child.stderr.take() {
+        readers.push(tokio::spawn(capture_output(
+            pipe,
+            stderr.clone(),
+            last_output.clone(),
+            log_lines.then_some("stderr"),
+        )));
+    }
+
+    let total_timeout = async {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+            None => std::future::pending().await,
+        }
+    };
+    let (exited, idle) = tokio::select! {
+        status = child.wait() => (Some(status?), false),
+        () = total_timeout => (None, false),
+        () = idle_timeout(&last_output, idle_timeout_seconds) => (None, true),
+    };
+    let (status, timed_out) = match exited {
+        Some(status) => (status, false),
+        None => (terminate(&mut child).await?, true),
+    };
+
+    if let Some(writer) = writer {
+        if timed_out {
+            writer.abort();
+        } else {
+            writer.await.map_err(std::io::Error::other)??;
+        }
+    }
+
+    // Processes the command started in the background may still hold its
+    // stdout and stderr, so the output is only read until the deadline
+    let drain_deadline = match deadline {
+        Some(deadline) if !timed_out => deadline,
+        _ => Instant::now() + OUTPUT_DRAIN_PERIOD,
+    };
+    for mut reader in readers {
+        match timeout_at(drain_deadline.into(), &mut reader).await {
+            Ok(result) if !timed_out => result.map_err(std::io::Error::other)??,
+            Ok(_) => {}
+            Err(_) => reader.abort(),
+        }
+    }
+
+    let take = |captured: &Mutex<Vec<u8>>| {
+        std::mem::take(&mut *captured.lock().unwrap_or_else(PoisonError::into_inner))
+    };
+    Ok(TimedOutput {
+        output: Output {
+            status,
+            stdout: take(&stdout),
+            stderr: take(&stderr),
+        },
+        timed_out,
+        idle,
+    })
+}
+
+/// Write `input` to a command's stdin and close it
+async fn write_input(mut stdin: tokio::process::ChildStdin, input: Vec<u8>) -> std::io::Result<()> {
+    match stdin.write_all(&input).await {
+        // The command may exit without reading all of its input
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+/// Wait until no output has been captured for `idle_timeout_seconds` since
+/// `last_output`, or forever if it is None or 0
+async fn idle_timeout(last_output: &Mutex<Instant>, idle_timeout_seconds: Option<u64>) {
+    let Some(idle_timeout) = idle_timeout_seconds
+        .filter(|&seconds| seconds > 0)
+        .map(Duration::from_secs)
+    else {
+        return std::future::pending().await;
+    };
+    loop {
+        let deadline = *last_output.lock().unwrap_or_else(PoisonError::into_inner) + idle_timeout;
+        if Instant::now() >= deadline {
+            return;
+        }
+        // Output may have arrived meanwhile, moving the deadline
+        tokio::time::sleep_until(deadline.into()).await;
+    }
+}
+
+/// Stop a command that timed out: SIGTERM lets it clean up (ssh closes the
+/// connection), and SIGKILL follows if it hasn't exited after a grace period.
+async fn terminate(child: &mut Child) -> std::io::Result<ExitStatus> {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        let _ = Command::new("kill")
+            .args(["-TERM", &pid.to_string()])
+            .status()
+            .await;
+        if let Ok(status) = timeout(KILL_GRACE_PERIOD, child.wait()).await {
+            return status;
+        }
+    }
+    child.kill().await?;
+    child.wait().await
+}
+
+/// Read a pipe until EOF, appending to `captured` as output arrives so that
+/// it is kept if the read is aborted, and recording when it last did in
+/// `last_output`. With `stream` set, each line is logged.
+async fn capture_output<R: AsyncRead + Unpin>(
+    mut pipe: R,
+    captured: Arc<Mutex<Vec<u8>>>,
+    last_output: Arc<Mutex<Instant>>,
+    stream: Option<&'static str>,
+) -> std::io::Result<()> {
+    let mut chunk = [0; 8192];
+    let mut line = Vec::new();
+
+    loop {
+        let n = pipe.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        captured
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(&chunk[..n]);
+        *last_output.lock().unwrap_or_else(PoisonError::into_inner) = Instant::now();
+
+        if let Some(stream) = stream {
+            line.extend_from_slice(&chunk[..n]);
+            while let Some(end) = line.iter().position(|&byte| byte == b'\n') {
+                let rest = line.split_off(end + 1);
+                log_output_line(stream, &line);
+                line = rest;
+            }
+        }
+    }
+
+    if let Some(stream) = stream
+        && !line.is_empty()
+    {
+        log_output_line(stream, &line);
+    }
+    Ok(())
+}
+
+fn log_output_line(stream: &'static str, line: &[u8]) {
+    tracing::debug!(
+        stream,
+        line = %String::from_utf8_lossy(line).trim_end(),
+        "Command output"
+    );
+}
+
+/// Format a command as a shell command line, for error messages and logs. The
+/// environment isn't included, so passwords passed to sshpass through
+/// SSHPASS are never shown.
+pub(crate) fn display_command(cmd: &Command) -> String {
+    let cmd = cmd.as_std();
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|word| shell_quote(&word.to_string_lossy()))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Build the result of a dry run, describing the command that would have been
+/// run: its program, arguments, the remote host and, if the command reads from
+/// stdin, what would be written to it.
+fn dry_run_result(cmd: &Command, remote_host: &str, stdin: Option<&str>) -> CallToolResult {
+    let cmd = cmd.as_std();
+    let command = cmd.get_program().to_string_lossy().into_owned();
+    let args: Vec<String> = cmd
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+
+    let mut structured_content = serde_json::Map::new();
+    structured_content.insert("dry_run".to_string(), serde_json::Value::Bool(true));
+    structured_content.insert(
+        "command".to_string(),
+        serde_json::Value::from(command.as_str()),
+    );
+    structured_content.insert("args".to_string(), serde_json::Value::from(args.clone()));
+    structured_content.insert(
+        "remote_host".to_string(),
+        serde_json::Value::from(remote_host),
+    );
+    if let Some(stdin) = stdin {
+        structured_content.insert("stdin".to_string(), serde_json::Value::from(stdin));
+    }
+
+    let text = std::iter::once(command)
+        .chain(args)
+        .collect::<Vec<_>>()
+        .join(" ");
+    CallToolResult::text_content(vec![TextContent::from(text)])
+        .with_structured_content(structured_content)
+}
+
+/// Default cap on the size of each of stdout and stderr returned to the model.
+const DEFAULT_MAX_OUTPUT_BYTES: usize = 1024 * 1024;
+
+/// Build a tool result from a command's output. The text content is stdout,
+/// base64-encoded if the output isn't valid UTF-8.
+///
+/// stdout and stderr are each truncated to `max_output_bytes` (1 MiB by
+/// default, 0 to disable), in which case `truncated` is set in the structured
+/// content. `stdout_len` and `stderr_len` are the lengths in bytes of the
+/// output before truncation. Output is otherwise returned as is, whitespace
+/// included. `signal` and `core_dumped` describe a process killed by a signal,
+/// whose `status_code` is null.
+fn result_from_output(
+    output: &Output,
+    max_output_bytes: Option<u64>,
+    timing: Option<Timing>,
+) -> CallToolResult {
+    let max_output_bytes = max_output_bytes.map_or(DEFAULT_MAX_OUTPUT_BYTES, |max| {
+        usize::try_from(max).unwrap_or(usize::MAX)
+    });
+    let (stdout, stdout_truncated) = truncate_output(&output.stdout, max_output_bytes);
+    let (stderr, stderr_truncated) = truncate_output(&output.stderr, max_output_bytes);
+
+    let mut structured_content = map_from_output(&stdout, &stderr, output.status.code(), timing);
+    let (signal, core_dumped) = exit_signal(&output.status);
+    structured_content.insert("signal".to_string(), serde_json::Value::from(signal));
+    structured_content.insert(
+        "core_dumped".to_string(),
+        serde_json::Value::Bool(core_dumped),
+    );
+    structured_content.insert("stdout_len".to_string(), output.stdout.len().into());
+    structured_content.insert("stderr_len".to_string(), output.stderr.len().into());
+    if stdout_truncated || stderr_truncated {
+        structured_content.insert("truncated".to_string(), serde_json::Value::Bool(true));
+    }
+
+    let text = ["stdout_base64", "stdout"]
+        .iter()
+        .find_map(|key| structured_content.get(*key).and_then(|v| v.as_str()))
+        .unwrap_or_default()
+        .to_string();
+
+    CallToolResult::text_content(vec![TextContent::from(text)])
+        .with_structured_content(structured_content)
+}
+
+/// Get the signal that terminated a process, if any, and whether it dumped
+/// core. Always `(None, false)` on platforms without signals.
+pub(crate) fn exit_signal(status: &ExitStatus) -> (Option<i32>, bool) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        (status.signal(), status.core_dumped())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        (None, false)
+    }
+}
+
+/// Truncate output to at most `max_bytes` bytes (0 disables truncation),
+/// returning the output and whether it was truncated.
+///
+/// Text is cut on a character boundary and a `...[truncated N bytes]` marker
+/// is appended. Output that isn't valid UTF-8 is cut exactly, without a marker.
+fn truncate_output(output: &[u8], max_bytes: usize) -> (Cow<'_, [u8]>, bool) {
+    if max_bytes == 0 || output.len() <= max_bytes {
+        return (Cow::Borrowed(output), false);
+    }
+
+    match std::str::from_utf8(output) {
+        Ok(text) => {
+            let mut end = max_bytes;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            let marker = format!("...[truncated {} bytes]", output.len() - end);
+            let mut truncated = output[..end].to_vec();
+            truncated.extend_from_slice(marker.as_bytes());
+            (Cow::Owned(truncated), true)
+        }
+        Err(_) => (Cow::Borrowed(&output[..max_bytes]), true),
+    }
 }
 
+/// Build the structured content for a command's output.
+///
+/// Output that isn't valid UTF-8 would be mangled by a lossy conversion, so in
+/// that case both streams are returned base64-encoded in `stdout_base64` and
+/// `stderr_base64`, `stdout` and `stderr` are empty, and `encoding` is set to
+/// `base64`. When the command was timed, `started_at` and `duration_ms` say
+/// when it started and how long it ran.
 fn map_from_output(
-    stdout: String,
-    stderr: String,
+    stdout: &[u8],
+    stderr: &[u8],
     status_code: Option<i32>,
+    timing: Option<Timing>,
 ) -> serde_json::Map<String, serde_json::Value> {
     let mut structured_content = serde_json::Map::new();
-    structured_content.insert("stdout".to_string(), serde_json::Value::String(stdout));
-    structured_content.insert("stderr".to_string(), serde_json::Value::String(stderr));
+    match (std::str::from_utf8(stdout), std::str::from_utf8(stderr)) {
+        (Ok(stdout), Ok(stderr)) => {
+            structured_content.insert("stdout".to_string(), serde_json::Value::from(stdout));
+            structured_content.insert("stderr".to_string(), serde_json::Value::from(stderr));
+        }
+        _ => {
+            structured_content.insert("stdout".to_string(), serde_json::Value::from(""));
+            structured_content.insert("stderr".to_string(), serde_json::Value::from(""));
+            structured_content.insert(
+                "stdout_base64".to_string(),
+                serde_json::Value::String(BASE64_STANDARD.encode(stdout)),
+            );
+            structured_content.insert(
+                "stderr_base64".to_string(),
+                serde_json::Value::String(BASE64_STANDARD.encode(stderr)),
+            );
+            structured_content.insert("encoding".to_string(), serde_json::Value::from("base64"));
+        }
+    }
     structured_content.insert(
         "status_code".to_string(),
         match status_code {
@@ -65,19 +1625,1133 @@ fn map_from_output(
             None => serde_json::Value::Null,
         },
     );
+    if let Some(timing) = timing {
+        structured_content.insert("started_at".to_string(), timing.started_at_ms().into());
+        structured_content.insert("duration_ms".to_string(), timing.duration_ms().into());
+    }
+    // The shell couldn't find the command
+    if status_code == Some(127) {
+        structured_content.insert("error_kind".to_string(), "command_not_found".into());
+    }
     structured_content
 }
 
+/// Kinds of tool call failures, reported as `error_kind`, with the messages
+/// from the tools or from ssh that identify them. The first kind with a
+/// matching message wins, so the cause a message reports (such as ssh's
+/// "Connection refused") takes precedence over how the tool wrapped it.
+const ERROR_KINDS: &[(&str, &[&str])] = &[
+    (
+        "rejected",
+        &[
+            "rejected by judge",
+            " is denied by MCP_LINUX_SSH_",
+            " is not in MCP_LINUX_SSH_",
+            " is not allowed with MCP_LINUX_SSH_",
+            "You may not run commands with sudo",
+        ],
+    ),
+    (
+        "invalid_request",
+        &[
+            "Invalid SSH option",
+            "Invalid remote host",
+            "Private key not found",
+            "Private key is not a file",
+            "which allow access by other users",
+            "Certificate not found",
+            "Certificate is not a file",
+        ],
+    ),
+    (
+        "timeout",
+        &[
+            "timed out after",
+            "Connection timed out",
+            "Operation timed out",
+        ],
+    ),
+    ("connection_refused", &["Connection refused"]),
+    (
+        "host_not_found",
+        &[
+            "Could not resolve hostname",
+            "Name or service not known",
+            "nodename nor servname provided",
+        ],
+    ),
+    (
+        "host_unreachable",
+        &["No route to host", "Network is unreachable"],
+    ),
+    (
+        "host_key_verification_failed",
+        &["Host key verification failed"],
+    ),
+    (
+        "auth_failed",
+        &[
+            "Permission denied (",
+            "Too many authentication failures",
+            "Authentication failed",
+        ],
+    ),
+    ("program_not_found", &["not found; install"]),
+    (
+        "spawn_failed",
+        &["Failed to execute", "Failed to run local command"],
+    ),
+    // ssh exited with 255 for another reason
+    ("connection_failed", &["SSH command failed unexpectedly"]),
+];
+
+/// Classify a tool call failure by its message, as one of [`ERROR_KINDS`] or
+/// `other`
+pub(crate) fn error_kind(message: &str) -> &'static str {
+    ERROR_KINDS
+        .iter()
+        .find(|(_, messages)| messages.iter().any(|m| message.contains(m)))
+        .map_or("other", |(kind, _)| kind)
+}
+
+/// Turn a failure to build the ssh arguments into a tool error, an invalid
+/// request when the call's parameters are at fault, e.g. its private key is
+/// missing
+pub(crate) fn ssh_options_error(error: Error) -> CallToolError {
+    let message = format!("Failed to build SSH options: {}", error);
+    if error_kind(&message) == "invalid_request" {
+        CallToolError::new(RpcError::invalid_request().with_message(message))
+    } else {
+        CallToolError::from_message(message)
+    }
+}
+
+/// Turn a failed tool call into an error result whose structured content
+/// holds the `error` message and its `error_kind`, so clients can tell
+/// failures apart without parsing the message.
+pub(crate) fn error_result(error: &CallToolError) -> CallToolResult {
+    let message = error.to_string();
+    let mut structured_content = serde_json::Map::new();
+    structured_content.insert("error_kind".to_string(), error_kind(&message).into());
+    structured_content.insert("error".to_string(), message.as_str().into());
+
+    let mut result = CallToolResult::text_content(vec![TextContent::from(message)])
+        .with_structured_content(structured_content);
+    result.is_error = Some(true);
+    result
+}
+
+/// A path under the temporary directory for a test, unique to the test and
+/// this process. Whatever is at the path is removed when the guard is dropped,
+/// so a failing test doesn't leave it behind.
+#[cfg(test)]
+pub(crate) struct TestPath(PathBuf);
+
+#[cfg(test)]
+impl TestPath {
+    /// The path for the test `name`, e.g. `mcp_linux_ssh_append_file_test_<pid>`,
+    /// with nothing there yet
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "mcp_linux_ssh_{}_test_{}",
+            name,
+            std::process::id()
+        ));
+        let test_path = TestPath(path);
+        test_path.remove();
+        test_path
+    }
+
+    /// The path for the test `name`, created as an empty directory
+    pub(crate) fn dir(name: &str) -> Self {
+        let test_path = Self::new(name);
+        std::fs::create_dir_all(&test_path.0).unwrap();
+        test_path
+    }
+
+    fn remove(&self) {
+        match std::fs::symlink_metadata(&self.0) {
+            Ok(metadata) if metadata.is_dir() => {
+                let _ = std::fs::remove_dir_all(&self.0);
+            }
+            Ok(_) => {
+                let _ = std::fs::remove_file(&self.0);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TestPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TestPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestPath {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// Run a tool's script locally as it runs on the remote host, with `sh -c`,
+/// `args` as its positional parameters and `input` on stdin. With `dir`, the
+/// script runs in that directory.
+#[cfg(all(test, unix))]
+pub(crate) fn run_script(script: &str, args: &[&str], input: &[u8], dir: Option<&Path>) -> Output {
+    use std::io::Write;
+
+    let mut cmd = std::process::Command::new("sh");
+    cmd.args(["-c", script, "sh"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(dir) = dir {
+        cmd.current_dir(dir);
+    }
+    let mut child = cmd.spawn().unwrap();
+    // Written from another thread so that a script printing more than a pipe
+    // holds before reading all of stdin can't block the test
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_vec();
+    let writer = std::thread::spawn(move || {
+        let _ = stdin.write_all(&input);
+    });
+    let output = child.wait_with_output().unwrap();
+    writer.join().unwrap();
+    output
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_error_kind() {
+        let cases = [
+            (
+                "SSH command failed unexpectedly: ssh: connect to host 10.0.0.5 port 22: Connection refused",
+                "connection_refused",
+            ),
+            (
+                "SSH command failed unexpectedly: ssh: connect to host 10.0.0.5 port 22: Connection timed out",
+                "timeout",
+            ),
+            ("SSH command timed out after 30 seconds", "timeout"),
+            (
+                "SSH command failed unexpectedly: ssh: Could not resolve hostname nope: Name or service not known",
+                "host_not_found",
+            ),
+            (
+                "SSH command failed unexpectedly: ssh: connect to host 10.0.0.5 port 22: No route to host",
+                "host_unreachable",
+            ),
+            (
+                "SSH command failed unexpectedly: admin@web1: Permission denied (publickey,password).",
+                "auth_failed",
+            ),
+            (
+                "SSH command failed unexpectedly: Host key verification failed.",
+                "host_key_verification_failed",
+            ),
+            ("rsync not found; install rsync", "program_not_found"),
+            (
+                "Failed to execute remote SSH command: Resource temporarily unavailable (os error 11)",
+                "spawn_failed",
+            ),
+            (
+                "SSH command failed unexpectedly: kex_exchange_identification: read: Connection reset by peer",
+                "connection_failed",
+            ),
+            ("Tool call rejected by judge: deletes every log", "rejected"),
+            ("Host db1 is not in MCP_LINUX_SSH_ALLOWED_HOSTS", "rejected"),
+            ("File not found: /etc/app.conf", "other"),
+        ];
+        for (message, kind) in cases {
+            assert_eq!(error_kind(message), kind, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_error_result() {
+        let result = error_result(&CallToolError::from_message(
+            "SSH command timed out after 5 seconds",
+        ));
+        assert_eq!(result.is_error, Some(true));
+        let structured_content = result.structured_content.unwrap();
+        assert_eq!(structured_content["error_kind"], "timeout");
+        assert_eq!(
+            structured_content["error"],
+            "SSH command timed out after 5 seconds"
+        );
+    }
+
+    #[test]
+    fn test_command_not_found_error_kind() {
+        let structured_content = map_from_output(b"", b"sh: 1: nope: not found\n", Some(127), None);
+        assert_eq!(structured_content["error_kind"], "command_not_found");
+        assert!(!map_from_output(b"", b"", Some(1), None).contains_key("error_kind"));
+    }
+
     #[test]
     fn test_get_multiplexing_options() {
         let options = get_multiplexing_options().unwrap();
-        assert!(options.len() >= 3);
-        assert!(options.iter().any(|opt| opt.starts_with("ControlMaster=")));
-        assert!(options.iter().any(|opt| opt.starts_with("ControlPath=")));
-        assert!(options.iter().any(|opt| opt.starts_with("ControlPersist=")));
+        let control_dir = control_dir().unwrap();
+        assert_eq!(
+            options,
+            vec![
+                "ControlMaster=auto".to_string(),
+                format!("ControlPath={}/cm-%r@%h:%p", control_dir.display()),
+                "ControlPersist=60s".to_string(),
+            ]
+        );
+        // One directory per process, private to its user
+        assert_eq!(control_dir, self::control_dir().unwrap());
+        assert!(control_dir.starts_with(std::env::temp_dir()));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(control_dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_require_installed() {
+        assert!(require_installed(&["sh"]).is_ok());
+
+        let err = require_installed(&["sh", "mcp_linux_ssh_missing"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "mcp_linux_ssh_missing not found; install mcp_linux_ssh_missing"
+        );
+    }
+
+    #[test]
+    fn test_map_from_output_utf8() {
+        let map = map_from_output(b"hello\n", b"warning\n", Some(0), None);
+        assert_eq!(map["stdout"], "hello\n");
+        assert_eq!(map["stderr"], "warning\n");
+        assert_eq!(map["status_code"], 0);
+        assert!(!map.contains_key("encoding"));
+        assert!(!map.contains_key("stdout_base64"));
+    }
+
+    #[test]
+    fn test_map_from_output_binary() {
+        let map = map_from_output(&[0x1f, 0x8b, 0xff], b"", Some(0), None);
+        assert_eq!(map["stdout"], "");
+        assert_eq!(map["stderr"], "");
+        assert_eq!(map["stdout_base64"], "H4v/");
+        assert_eq!(map["stderr_base64"], "");
+        assert_eq!(map["encoding"], "base64");
+    }
+
+    #[test]
+    fn test_map_from_output_timing() {
+        let timing = Timing {
+            started_at: SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+            duration: Duration::from_millis(1500),
+        };
+        let map = map_from_output(b"", b"", Some(0), Some(timing));
+        assert_eq!(map["started_at"], 1_700_000_000_123u64);
+        assert_eq!(map["duration_ms"], 1500);
+    }
+
+    #[test]
+    fn test_result_from_output_keeps_whitespace_and_lengths() {
+        let output = std::process::Command::new("printf")
+            .arg("  a  b\\t")
+            .output()
+            .unwrap();
+        let result = result_from_output(&output, None, None);
+        let content = result.structured_content.unwrap();
+        assert_eq!(content["stdout"], "  a  b\t");
+        assert_eq!(content["stdout_len"], 7);
+        assert_eq!(content["stderr_len"], 0);
+
+        // The lengths are those of the output before truncation
+        let result = result_from_output(&output, Some(3), None);
+        let content = result.structured_content.unwrap();
+        assert_eq!(content["truncated"], true);
+        assert_eq!(content["stdout_len"], 7);
+    }
+
+    #[test]
+    fn test_truncate_output_under_limit() {
+        let (output, truncated) = truncate_output(b"hello", 10);
+        assert_eq!(&*output, b"hello");
+        assert!(!truncated);
+
+        let (output, truncated) = truncate_output(b"hello", 0);
+        assert_eq!(&*output, b"hello");
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn test_truncate_output_over_limit() {
+        let (output, truncated) = truncate_output(b"hello world", 5);
+        assert_eq!(&*output, b"hello...[truncated 6 bytes]");
+        assert!(truncated);
+    }
+
+    #[test]
+    fn test_truncate_output_keeps_characters_whole() {
+        // "é" is two bytes; cutting after 2 bytes would split it
+        let (output, truncated) = truncate_output("aéb".as_bytes(), 2);
+        assert_eq!(&*output, "a...[truncated 3 bytes]".as_bytes());
+        assert!(truncated);
+    }
+
+    #[test]
+    fn test_truncate_output_binary() {
+        let (output, truncated) = truncate_output(&[0xff, 0xfe, 0xfd], 2);
+        assert_eq!(&*output, &[0xff, 0xfe]);
+        assert!(truncated);
+    }
+
+    #[test]
+    fn test_secret_debug_is_redacted() {
+        let params = SshConnectionParams {
+            password: Some(Secret("hunter2".to_string())),
+            ..Default::default()
+        };
+        assert!(!format!("{:?}", params).contains("hunter2"));
+    }
+
+    #[test]
+    fn test_redacted() {
+        let params = serde_json::json!({"remote_host": "db", "password": "hunter2"});
+        let text = redacted(&params);
+        assert!(!text.contains("hunter2"));
+        assert!(text.contains("[REDACTED]"));
+        assert!(text.contains("db"));
+
+        let params = serde_json::json!({"remote_host": "db", "password": null});
+        assert!(!redacted(&params).contains("[REDACTED]"));
+    }
+
+    #[test]
+    fn test_summarize_body() {
+        let mut params = serde_json::json!({"patch": "--- a\n+++ b\n"});
+        summarize_body(&mut params, "patch", true);
+        assert_eq!(params["patch"], "--- a\n+++ b\n");
+
+        summarize_body(&mut params, "patch", false);
+        assert_eq!(params["patch"], "[omitted 12 bytes, 2 lines]");
+
+        let mut params = serde_json::json!({"patch": "x".repeat(MAX_LOGGED_BODY_BYTES + 10)});
+        summarize_body(&mut params, "patch", true);
+        let patch = params["patch"].as_str().unwrap();
+        assert!(patch.ends_with("...[truncated 10 bytes]"));
+        assert!(patch.starts_with(&"x".repeat(MAX_LOGGED_BODY_BYTES)));
+
+        let params = serde_json::json!({"stdin": "x".repeat(MAX_LOGGED_BODY_BYTES + 10)});
+        assert!(redacted(&params).contains("...[truncated 10 bytes]"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_with_timeout_returns_partial_output() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo partial; echo oops >&2; sleep 10"]);
+
+        let started = std::time::Instant::now();
+        let output = output_with_timeout(&mut cmd, 1, false).await.unwrap();
+        assert!(output.timed_out);
+        assert_eq!(output.output.stdout, b"partial\n");
+        assert_eq!(output.output.stderr, b"oops\n");
+        assert!(output.output.status.code().is_none());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_with_idle_timeout() {
+        // Steady output keeps the command running past the idle timeout
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "for i in 1 2 3 4; do echo $i; sleep 0.5; done"]);
+        let output = output_with_idle_timeout(&mut cmd, 0, Some(1), false)
+            .await
+            .unwrap();
+        assert!(!output.timed_out);
+        assert_eq!(output.output.stdout, b"1\n2\n3\n4\n");
+
+        // Silence stops it, well before the total timeout
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo started; sleep 10; echo done"]);
+        let started = std::time::Instant::now();
+        let output = output_with_idle_timeout(&mut cmd, 30, Some(1), false)
+            .await
+            .unwrap();
+        assert!(output.timed_out);
+        assert!(output.idle);
+        assert_eq!(output.output.stdout, b"started\n");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_output_with_timeout_background_process() {
+        // The background sleep keeps stdout open after the command exits
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo started; sleep 10 &"]);
+
+        let started = std::time::Instant::now();
+        let output = output_with_timeout(&mut cmd, 1, false).await.unwrap();
+        assert!(!output.timed_out);
+        assert_eq!(output.output.stdout, b"started\n");
+        assert_eq!(output.output.status.code(), Some(0));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo started; sleep 10 &"]);
+        let output = output_with_timeout(&mut cmd, 0, false).await.unwrap();
+        assert_eq!(output.output.stdout, b"started\n");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_output_with_timeout_completes() {
+        let mut cmd = Command::new("echo");
+        cmd.arg("hello");
+
+        let output = output_with_timeout(&mut cmd, 5, true).await.unwrap();
+        assert!(!output.timed_out);
+        assert_eq!(output.output.stdout, b"hello\n");
+        assert_eq!(output.output.status.code(), Some(0));
+    }
+
+    #[test]
+    fn test_command_without_password() {
+        let params = SshConnectionParams::default();
+        let cmd = params.command("ssh").unwrap();
+        assert_eq!(cmd.as_std().get_program(), "ssh");
+        assert_eq!(cmd.as_std().get_args().count(), 0);
+    }
+
+    #[test]
+    fn test_command_with_password() {
+        let params = SshConnectionParams {
+            password: Some(Secret("hunter2".to_string())),
+            ..Default::default()
+        };
+        match params.command("ssh") {
+            Ok(cmd) => {
+                let cmd = cmd.as_std();
+                assert_eq!(cmd.get_program(), "sshpass");
+                let args: Vec<_> = cmd.get_args().collect();
+                assert_eq!(args, ["-e", "ssh"]);
+                assert!(cmd.get_envs().any(|(key, value)| key == "SSHPASS"
+                    && value == Some(std::ffi::OsStr::new("hunter2"))));
+            }
+            Err(e) => assert!(e.to_string().contains("install sshpass")),
+        }
+    }
+
+    #[test]
+    fn test_ipv6_destinations() {
+        assert_eq!(ssh_destination("fe80::1").unwrap(), "fe80::1");
+        assert_eq!(ssh_destination("admin@[fe80::1]").unwrap(), "admin@fe80::1");
+        assert_eq!(ssh_destination("db.example.com").unwrap(), "db.example.com");
+        assert_eq!(path_destination("fe80::1").unwrap(), "[fe80::1]");
+        assert_eq!(
+            path_destination("admin@fe80::1%eth0").unwrap(),
+            "admin@[fe80::1%eth0]"
+        );
+        assert_eq!(path_destination("[2001:db8::1]").unwrap(), "[2001:db8::1]");
+        assert_eq!(
+            path_destination("admin@192.0.2.1").unwrap(),
+            "admin@192.0.2.1"
+        );
+        assert_eq!(path_destination("db").unwrap(), "db");
+    }
+
+    #[test]
+    fn test_destinations_reject_options() {
+        for remote_host in ["-oProxyCommand=touch /tmp/x", "-V"] {
+            assert!(ssh_destination(remote_host).is_err());
+            assert!(path_destination(remote_host).is_err());
+        }
+    }
+
+    #[test]
+    fn test_clamp_timeout() {
+        assert_eq!(clamp_timeout(30, None), 30);
+        assert_eq!(clamp_timeout(0, None), 0);
+        assert_eq!(clamp_timeout(30, Some(60)), 30);
+        assert_eq!(clamp_timeout(600, Some(60)), 60);
+        // Disabling the timeout isn't allowed when there is a maximum
+        assert_eq!(clamp_timeout(0, Some(60)), 60);
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/var/log/nginx"), "/var/log/nginx");
+        assert_eq!(shell_quote("LANG=C.UTF-8"), "LANG=C.UTF-8");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("hello world"), "'hello world'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+        assert_eq!(shell_quote("$(id)"), "'$(id)'");
+        assert_eq!(shell_quote("a;b|c&d"), "'a;b|c&d'");
+        assert_eq!(shell_quote("/tmp/it's here"), r"'/tmp/it'\''s here'");
+    }
+
+    #[test]
+    fn test_ssh_args_with_jump_host() {
+        let params = SshConnectionParams {
+            remote_host: "target".to_string(),
+            jump_host: Some("admin@bastion.example.com:2222".to_string()),
+            ..Default::default()
+        };
+
+        let args = params.ssh_args().unwrap();
+        let position = args.iter().position(|arg| arg == "-J").unwrap();
+        assert_eq!(args[position + 1], "admin@bastion.example.com:2222");
+    }
+
+    #[test]
+    fn test_ssh_args_compression() {
+        let mut params = SshConnectionParams {
+            remote_host: "target".to_string(),
+            ..Default::default()
+        };
+        assert!(!params.ssh_args().unwrap().contains(&"-C".to_string()));
+
+        params.compression = true;
+        assert!(params.ssh_args().unwrap().contains(&"-C".to_string()));
+    }
+
+    #[test]
+    fn test_ssh_args_cipher() {
+        let mut params = SshConnectionParams {
+            remote_host: "target".to_string(),
+            ..Default::default()
+        };
+        assert!(!params.ssh_args().unwrap().contains(&"-c".to_string()));
+
+        params.cipher = Some("aes128-gcm@openssh.com".to_string());
+        let args = params.ssh_args().unwrap();
+        let position = args.iter().position(|arg| arg == "-c").unwrap();
+        assert_eq!(args[position + 1], "aes128-gcm@openssh.com");
+
+        // The same flag is passed to sftp and scp, and to ssh by rsync
+        assert!(params.sftp_args().unwrap().contains(&"-c".to_string()));
+        params.cipher = Some("chacha20-poly1305@openssh.com,aes256-ctr".to_string());
+        assert!(
+            params
+                .rsync_ssh_command()
+                .unwrap()
+                .ends_with(" -c chacha20-poly1305@openssh.com,aes256-ctr")
+        );
+    }
+
+    #[test]
+    fn test_ssh_args_invalid_cipher() {
+        for cipher in [
+            "",
+            "chacha20",
+            "aes128-ctr,",
+            "aes128-ctr -oProxyCommand=sh",
+            "aes128-ctr;id",
+        ] {
+            let params = SshConnectionParams {
+                remote_host: "target".to_string(),
+                cipher: Some(cipher.to_string()),
+                ..Default::default()
+            };
+            let error = params.ssh_args().unwrap_err();
+            assert!(
+                error.to_string().starts_with("Invalid cipher"),
+                "{}",
+                cipher
+            );
+        }
+    }
+
+    #[test]
+    fn test_ssh_args_connect_timeout() {
+        let mut params = SshConnectionParams {
+            remote_host: "target".to_string(),
+            ..Default::default()
+        };
+        let args = params.ssh_args().unwrap();
+        assert!(!args.iter().any(|arg| arg.starts_with("ConnectTimeout=")));
+
+        params.connect_timeout_seconds = Some(5);
+        let args = params.ssh_args().unwrap();
+        let position = args
+            .iter()
+            .position(|arg| arg == "ConnectTimeout=5")
+            .unwrap();
+        assert_eq!(args[position - 1], "-o");
+
+        params.connect_timeout_seconds = Some(0);
+        assert!(params.ssh_args().is_err());
+    }
+
+    #[test]
+    fn test_ssh_args_without_jump_host() {
+        let params = SshConnectionParams {
+            remote_host: "target".to_string(),
+            ..Default::default()
+        };
+
+        let args = params.ssh_args().unwrap();
+        assert!(!args.iter().any(|arg| arg == "-J"));
+    }
+
+    #[test]
+    fn test_ssh_args_rejects_invalid_jump_host() {
+        for jump_host in ["", "-oProxyCommand=evil", "bastion host"] {
+            let params = SshConnectionParams {
+                remote_host: "target".to_string(),
+                jump_host: Some(jump_host.to_string()),
+                ..Default::default()
+            };
+            assert!(params.ssh_args().is_err());
+        }
+    }
+
+    #[test]
+    fn test_ssh_args_host_key_checking() {
+        let params = SshConnectionParams::default();
+        let args = params.ssh_args().unwrap();
+        assert!(args.contains(&"StrictHostKeyChecking=yes".to_string()));
+        assert!(
+            !args
+                .iter()
+                .any(|arg| arg.starts_with("UserKnownHostsFile="))
+        );
+
+        let params = SshConnectionParams {
+            strict_host_key_checking: Some("accept-new".to_string()),
+            known_hosts_file: Some("/tmp/known hosts".to_string()),
+            ..Default::default()
+        };
+        let args = params.ssh_args().unwrap();
+        assert!(args.contains(&"StrictHostKeyChecking=accept-new".to_string()));
+        assert!(args.contains(&"UserKnownHostsFile=\"/tmp/known hosts\"".to_string()));
+    }
+
+    #[test]
+    fn test_ssh_args_rejects_invalid_host_key_checking() {
+        let params = SshConnectionParams {
+            strict_host_key_checking: Some("ask".to_string()),
+            ..Default::default()
+        };
+        assert!(params.ssh_args().is_err());
+    }
+
+    #[test]
+    fn test_ssh_args_reuse_connection() {
+        let multiplexed = |params: &SshConnectionParams| {
+            params
+                .ssh_args()
+                .unwrap()
+                .iter()
+                .any(|arg| arg == "ControlMaster=auto")
+        };
+
+        // Multiplexing is opt-in
+        let mut params = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            ..Default::default()
+        };
+        assert!(!multiplexed(&params));
+
+        params.reuse_connection = Some(true);
+        assert!(multiplexed(&params));
+        assert!(
+            params
+                .ssh_args()
+                .unwrap()
+                .contains(&"ControlPersist=60s".to_string())
+        );
+
+        params.use_ssh_config = true;
+        assert!(multiplexed(&params));
+
+        params.reuse_connection = Some(false);
+        assert!(!multiplexed(&params));
+    }
+
+    #[test]
+    fn test_ssh_args_use_ssh_config() {
+        let params = SshConnectionParams {
+            remote_host: "prod-db".to_string(),
+            use_ssh_config: true,
+            ..Default::default()
+        };
+        let args = params.ssh_args().unwrap();
+        assert!(args.is_empty());
+
+        let params = SshConnectionParams {
+            remote_host: "prod-db".to_string(),
+            strict_host_key_checking: Some("accept-new".to_string()),
+            port: Some(2222),
+            use_ssh_config: true,
+            ..Default::default()
+        };
+        let args = params.ssh_args().unwrap();
+        assert_eq!(
+            args,
+            ["-o", "StrictHostKeyChecking=accept-new", "-p", "2222"]
+        );
+    }
+
+    #[test]
+    fn test_ssh_args_private_key() {
+        let key = TestPath::new("private_key");
+        std::fs::write(&key, "key").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&key, std::fs::Permissions::from_mode(0o600)).unwrap();
+        }
+
+        let params = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            private_keys: vec![key.to_string_lossy().into_owned()],
+            ..Default::default()
+        };
+        let expected = format!("IdentityFile={}", key.display());
+        assert!(params.ssh_args().unwrap().contains(&expected));
+    }
+
+    #[test]
+    fn test_ssh_args_rejects_missing_private_key() {
+        let params = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            private_keys: vec!["/nonexistent/id_ed25519".to_string()],
+            ..Default::default()
+        };
+        let err = params.ssh_args().unwrap_err();
+        assert!(err.to_string().contains("Private key not found"));
+
+        let err = ssh_options_error(err);
+        assert!(err.to_string().contains("Private key not found"));
+        assert_eq!(error_kind(&err.to_string()), "invalid_request");
+        assert_eq!(
+            error_result(&err).structured_content.unwrap()["error_kind"],
+            "invalid_request"
+        );
+        assert_eq!(
+            error_kind(
+                "Private key /tmp/key has permissions 644, which allow access by other users; \
+                ssh will refuse to use it. Run chmod 600 /tmp/key"
+            ),
+            "invalid_request"
+        );
+    }
+
+    #[test]
+    fn test_glob_paths() {
+        let dir = TestPath::dir("glob_paths");
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        for name in ["conf.d/a.conf", "conf.d/b.conf", "conf.d/notes.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let mut paths = glob_paths(&dir, Path::new("conf.?/*.conf"));
+        paths.sort();
+        assert_eq!(
+            paths,
+            [dir.join("conf.d/a.conf"), dir.join("conf.d/b.conf")]
+        );
+        // Absolute patterns ignore the directory
+        assert_eq!(
+            glob_paths(Path::new("/nonexistent"), &dir.join("conf.d/notes.*")),
+            [dir.join("conf.d/notes.txt")]
+        );
+        // Paths without wildcards must exist
+        assert_eq!(glob_paths(&dir, Path::new("conf.d/a.conf")).len(), 1);
+        assert!(glob_paths(&dir, Path::new("conf.d/c.conf")).is_empty());
+    }
+
+    #[test]
+    fn test_ssh_args_certificate() {
+        let dir = TestPath::dir("certificate");
+        let certificate = dir.join("id_ed25519-cert.pub");
+        std::fs::write(&certificate, "ssh-ed25519-cert-v01@openssh.com AAAA").unwrap();
+
+        let params = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            certificate: Some(certificate.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        let expected = format!("CertificateFile={}", certificate.display());
+        assert!(params.ssh_args().unwrap().contains(&expected));
+        // rsync runs ssh with the same arguments, and sftp and scp take them
+        assert!(params.sftp_args().unwrap().contains(&expected));
+
+        let params = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            certificate: Some("/nonexistent/id_ed25519-cert.pub".to_string()),
+            ..Default::default()
+        };
+        let err = params.ssh_args().unwrap_err();
+        assert!(err.to_string().contains("Certificate not found"));
+    }
+
+    #[test]
+    fn test_normalize_option() {
+        assert_eq!(
+            normalize_option("ServerAliveInterval=30").unwrap(),
+            "ServerAliveInterval=30"
+        );
+        assert_eq!(normalize_option(" User = bob ").unwrap(), "User=bob");
+        assert_eq!(
+            normalize_option("SetEnv=LANG=C TZ=UTC").unwrap(),
+            "SetEnv=\"LANG=C TZ=UTC\""
+        );
+        assert_eq!(
+            normalize_option("SendEnv=LANG=C").unwrap(),
+            "SendEnv=LANG=C"
+        );
+
+        for invalid in [
+            "ServerAliveInterval",
+            "ServerAliveInterval 30",
+            "=30",
+            "Server-Alive=30",
+            "1Key=value",
+            "User=",
+            "User=bob\nProxyCommand=sh",
+            "User=bob -oProxyCommand=\"sh\"",
+            "ProxyCommand=ssh -W %h:%p bastion",
+            "localcommand=touch /tmp/x",
+            "PermitLocalCommand=yes",
+            "KnownHostsCommand=/bin/sh -c id",
+            "ProxyUseFdpass=yes",
+            "Match=exec true",
+        ] {
+            let err = normalize_option(invalid).unwrap_err();
+            assert!(
+                err.to_string().contains("Invalid"),
+                "{:?} should be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_ssh_args_rejects_invalid_options() {
+        let params = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            options: Some(vec![
+                "BatchMode=yes".to_string(),
+                "-oProxyCommand=sh".to_string(),
+            ]),
+            ..Default::default()
+        };
+        let err = params.ssh_args().unwrap_err();
+        assert!(err.to_string().contains("Invalid SSH option"));
+        assert_eq!(error_kind(&err.to_string()), "invalid_request");
+    }
+
+    #[test]
+    fn test_ssh_args_use_agent() {
+        let params = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            private_keys: vec!["/nonexistent/id_ed25519".to_string()],
+            use_agent: true,
+            ..Default::default()
+        };
+        let args = params.ssh_args().unwrap();
+        assert!(args.contains(&"IdentitiesOnly=no".to_string()));
+        assert!(!args.iter().any(|arg| arg.starts_with("IdentityFile=")));
+    }
+
+    #[test]
+    fn test_ssh_args_defaults_precedence() {
+        let mut defaults = ConnectionDefaults::default();
+        defaults.key = "/nonexistent/default_key".to_string();
+        defaults.user = "deploy".to_string();
+
+        // Defaults apply when the call sets neither key nor user
+        let params = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            ..Default::default()
+        };
+        let err = params
+            .args_with_defaults("-p", &defaults, None)
+            .unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/default_key"));
+
+        // A per-call key and user win over the defaults
+        let params = SshConnectionParams {
+            remote_host: "alice@example.com".to_string(),
+            private_keys: vec!["/nonexistent/call_key".to_string()],
+            ..Default::default()
+        };
+        let err = params
+            .args_with_defaults("-p", &defaults, None)
+            .unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/call_key"));
+
+        let params = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            use_agent: true,
+            options: Some(vec!["User=bob".to_string()]),
+            ..Default::default()
+        };
+        let args = params.args_with_defaults("-p", &defaults, None).unwrap();
+        assert!(!args.contains(&"User=deploy".to_string()));
+        assert!(!args.iter().any(|arg| arg.starts_with("IdentityFile=")));
+
+        let params = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            use_agent: true,
+            ..Default::default()
+        };
+        let args = params.args_with_defaults("-p", &defaults, None).unwrap();
+        assert!(args.contains(&"User=deploy".to_string()));
+
+        // With use_ssh_config, ~/.ssh/config decides both for the hosts it
+        // defines, and the defaults still apply to the others
+        let config = TestPath::new("defaults_ssh_config");
+        std::fs::write(&config, "Host prod-db\n    User postgres\n").unwrap();
+        let mut params = SshConnectionParams {
+            remote_host: "prod-db".to_string(),
+            use_ssh_config: true,
+            ..Default::default()
+        };
+        let args = params
+            .args_with_defaults("-p", &defaults, Some(&config))
+            .unwrap();
+        assert!(!args.contains(&"User=deploy".to_string()));
+        assert!(!args.iter().any(|arg| arg.starts_with("IdentityFile=")));
+
+        params.remote_host = "example.com".to_string();
+        let err = params
+            .args_with_defaults("-p", &defaults, Some(&config))
+            .unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/default_key"));
+        params.use_agent = true;
+        let args = params
+            .args_with_defaults("-p", &defaults, Some(&config))
+            .unwrap();
+        assert!(args.contains(&"User=deploy".to_string()));
+    }
+
+    #[test]
+    fn test_one_or_many() {
+        #[derive(serde::Deserialize)]
+        struct Params {
+            #[serde(default, deserialize_with = "one_or_many")]
+            keys: Option<Vec<String>>,
+        }
+
+        let parse = |json| serde_json::from_str::<Params>(json).unwrap().keys;
+        assert_eq!(parse(r#"{"keys": "a"}"#), Some(vec!["a".to_string()]));
+        assert_eq!(
+            parse(r#"{"keys": ["a", "b"]}"#),
+            Some(vec!["a".to_string(), "b".to_string()])
+        );
+        assert_eq!(parse(r#"{"keys": null}"#), None);
+        assert_eq!(parse("{}"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_is_private_mode() {
+        assert!(is_private_mode(0o600));
+        assert!(is_private_mode(0o400));
+        assert!(!is_private_mode(0o640));
+        assert!(!is_private_mode(0o644));
+    }
+
+    #[test]
+    fn test_ssh_args_port() {
+        let params = SshConnectionParams {
+            remote_host: "target".to_string(),
+            port: Some(2222),
+            ..Default::default()
+        };
+        let args = params.ssh_args().unwrap();
+        let position = args.iter().position(|arg| arg == "-p").unwrap();
+        assert_eq!(args[position + 1], "2222");
+
+        let params = SshConnectionParams {
+            remote_host: "target".to_string(),
+            ..Default::default()
+        };
+        let args = params.ssh_args().unwrap();
+        assert!(!args.iter().any(|arg| arg == "-p"));
+    }
+
+    #[test]
+    fn test_sftp_args_port() {
+        let params = SshConnectionParams {
+            remote_host: "target".to_string(),
+            port: Some(2222),
+            ..Default::default()
+        };
+        let args = params.sftp_args().unwrap();
+        let position = args.iter().position(|arg| arg == "-P").unwrap();
+        assert_eq!(args[position + 1], "2222");
+        assert!(!args.iter().any(|arg| arg == "-p"));
+    }
+
+    #[test]
+    fn test_ssh_binary() {
+        let params = SshConnectionParams {
+            remote_host: "target".to_string(),
+            ssh_binary: Some("/opt/openssh/bin/ssh".to_string()),
+            ..Default::default()
+        };
+        let allowed = ["/opt/openssh/bin/ssh".to_string()];
+        assert_eq!(
+            params.ssh_program_in(&allowed).unwrap(),
+            "/opt/openssh/bin/ssh"
+        );
+
+        // A program that isn't configured or allowed is never run
+        let error = params.ssh_program_in(&[]).unwrap_err().to_string();
+        assert_eq!(error_kind(&error), "rejected");
+        assert!(params.rsync_ssh_command().is_err());
+        assert!(params.sftp_args().is_err());
+
+        // Selecting the configured program needs no allowlist
+        let params = SshConnectionParams {
+            ssh_binary: Some(default_ssh_program()),
+            ..params
+        };
+        assert!(params.ssh_program_in(&[]).is_ok());
+
+        let params = SshConnectionParams {
+            ssh_binary: Some(" ".to_string()),
+            ..params
+        };
+        assert!(!params.sftp_args().unwrap().contains(&"-S".to_string()));
+        assert_eq!(using_ssh_binary(params.ssh_binary.as_deref()), "");
+        assert_eq!(
+            using_ssh_binary(Some("/opt/my ssh")),
+            ", using '/opt/my ssh'"
+        );
+    }
+
+    #[test]
+    fn test_ssh_args_rejects_port_zero() {
+        let params = SshConnectionParams {
+            remote_host: "target".to_string(),
+            port: Some(0),
+            ..Default::default()
+        };
+        assert!(params.ssh_args().is_err());
     }
 }
@@ -0,0 +1,224 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+/// Script moving $1 to $2 with `mv`. Exits with status 3 if $2 exists and $3
+/// isn't 1, with status 4 if $1 doesn't exist, and with status 5 if $2 is a
+/// directory, which mv would move $1 into rather than replace. Prints 1 if an
+/// existing file was replaced, 0 otherwise.
+const MOVE_SCRIPT: &str = r#"set -e
+if [ ! -e "$1" ] && [ ! -L "$1" ]; then
+    echo "$1: No such file or directory" >&2
+    exit 4
+fi
+if [ -d "$2" ] && [ ! -L "$2" ]; then
+    echo "$2: Is a directory; give the full path of the destination" >&2
+    exit 5
+fi
+overwritten=0
+if [ -e "$2" ] || [ -L "$2" ]; then
+    if [ "$3" != 1 ]; then
+        echo "$2: File exists" >&2
+        exit 3
+    fi
+    overwritten=1
+fi
+mv -f -- "$1" "$2"
+echo "$overwritten""#;
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "move_file",
+        description = "Move or rename a file or directory on a remote POSIX compatible system (Linux, BSD, macOS) with mv over SSH. \
+        The paths are passed as arguments, so they need no shell quoting. \
+        An existing destination is only replaced when overwrite is set, and the result reports whether it was. \
+        Use this instead of building mv commands for log rotation or staging configuration files.",
+        title = "Move File"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct MoveFile {
+        /// The path of the file or directory to move on the remote machine.
+        pub source: String,
+        /// The full path to move it to on the remote machine, including the new name. Its directory must exist.
+        pub destination: String,
+        /// Replace the destination if it already exists. Defaults to false, in which case an existing destination is an error.
+        pub overwrite: Option<bool>,
+        /// The host to move the file on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+impl MoveFile {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+
+        let connection = self.connection();
+        let mut cmd = super::ssh::ssh_command(&connection, "sh", &self.script_args())?;
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(&cmd, &self.remote_host, None));
+        }
+
+        let output = super::ssh::run_ssh(&connection, &mut cmd, timeout_seconds, false).await?;
+        if !output.status.success() {
+            return Err(CallToolError::from_message(format!(
+                "Failed to move {} to {}: {}",
+                self.source,
+                self.destination,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        // The script prints 1 if it replaced an existing destination
+        let overwritten = String::from_utf8_lossy(&output.stdout).trim() == "1";
+
+        let mut structured_content = serde_json::Map::new();
+        structured_content.insert(
+            "source".to_string(),
+            serde_json::Value::from(self.source.as_str()),
+        );
+        structured_content.insert(
+            "destination".to_string(),
+            serde_json::Value::from(self.destination.as_str()),
+        );
+        structured_content.insert(
+            "overwritten".to_string(),
+            serde_json::Value::from(overwritten),
+        );
+
+        let mut text = format!("Moved {} to {}", self.source, self.destination);
+        if overwritten {
+            text.push_str(", replacing the existing file");
+        }
+        Ok(CallToolResult::text_content(vec![TextContent::from(text)])
+            .with_structured_content(structured_content))
+    }
+
+    /// The arguments of the remote `sh` running [`MOVE_SCRIPT`]
+    fn script_args(&self) -> [&str; 6] {
+        [
+            "-c",
+            MOVE_SCRIPT,
+            "sh",
+            self.source.as_str(),
+            self.destination.as_str(),
+            if self.overwrite.unwrap_or(false) {
+                "1"
+            } else {
+                "0"
+            },
+        ]
+    }
+
+    /// Describe the move, e.g. "move /var/log/app.log to /var/log/app.log.1
+    /// on prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        let mut description = format!(
+            "move {} to {} {}",
+            super::shell_quote(&self.source),
+            super::shell_quote(&self.destination),
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        );
+        if self.overwrite.unwrap_or(false) {
+            description.push_str(", replacing the destination if it exists");
+        }
+        description
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        self.common_connection(&self.remote_host)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::tools::{TestPath, run_script};
+
+    #[tokio::test]
+    async fn test_move_file_args() {
+        let tool = MoveFile {
+            source: "/var/log/app log".to_string(),
+            destination: "/var/log/app log.1".to_string(),
+            remote_host: "db".to_string(),
+            dry_run: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            tool.script_args()[3..],
+            ["/var/log/app log", "/var/log/app log.1", "0"]
+        );
+
+        let result = tool.call_tool().await.unwrap();
+        let content = result.structured_content.unwrap();
+        let args = content["args"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|arg| arg.as_str().unwrap().to_string())
+            .collect::<Vec<String>>();
+        // The paths are quoted for the remote shell
+        assert!(args.ends_with(&[
+            "sh".to_string(),
+            "'/var/log/app log'".to_string(),
+            "'/var/log/app log.1'".to_string(),
+            "0".to_string(),
+        ]));
+
+        let tool = MoveFile {
+            overwrite: Some(true),
+            ..tool
+        };
+        assert_eq!(tool.script_args()[5], "1");
+        assert_eq!(
+            tool.describe_effective_command(),
+            "move '/var/log/app log' to '/var/log/app log.1' on db, replacing the destination if it exists"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_move_script() {
+        let dir = TestPath::dir("move_file");
+        let (source, destination) = (dir.join("app.conf.new"), dir.join("app.conf"));
+        let move_file = |overwrite: &str| {
+            let paths = [source.to_str().unwrap(), destination.to_str().unwrap()];
+            run_script(MOVE_SCRIPT, &[paths[0], paths[1], overwrite], b"", None)
+        };
+
+        // A missing source
+        assert_eq!(move_file("0").status.code(), Some(4));
+
+        std::fs::write(&source, "new").unwrap();
+        let output = move_file("0");
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "0");
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "new");
+        assert!(!source.exists());
+
+        // An existing destination is only replaced when asked to
+        std::fs::write(&source, "newer").unwrap();
+        assert_eq!(move_file("0").status.code(), Some(3));
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "new");
+
+        let output = move_file("1");
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "1");
+        assert_eq!(std::fs::read_to_string(&destination).unwrap(), "newer");
+
+        // A directory destination isn't moved into
+        std::fs::remove_file(&destination).unwrap();
+        std::fs::create_dir(&destination).unwrap();
+        std::fs::write(&source, "newest").unwrap();
+        assert_eq!(move_file("1").status.code(), Some(5));
+        assert!(source.exists());
+    }
+}
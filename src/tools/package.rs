@@ -0,0 +1,292 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, schema_utils::CallToolError},
+};
+
+/// Shell script printing the first package manager found on the host.
+const DETECT_SCRIPT: &str = "for manager in apt-get dnf yum pacman zypper apk; do \
+    if command -v $manager >/dev/null 2>&1; then echo $manager; exit 0; fi; \
+    done; exit 1";
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "package",
+        description = "Install, remove or query a package on a remote Linux system, whatever its package manager. The package manager (apt, dnf, yum, pacman, zypper or apk) is detected automatically. Installing and removing run with sudo.",
+        title = "Package"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct Package {
+        /// The action to perform: install, remove or query.
+        pub action: String,
+        /// The name of the package, as known to the package manager. Paths and URLs of package files are rejected.
+        pub name: String,
+        /// The host to manage the package on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Number of times to retry when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+/// A package action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Install,
+    Remove,
+    Query,
+}
+
+impl Action {
+    fn parse(action: &str) -> Result<Self, CallToolError> {
+        match action {
+            "install" => Ok(Action::Install),
+            "remove" => Ok(Action::Remove),
+            "query" => Ok(Action::Query),
+            other => Err(CallToolError::from_message(format!(
+                "Invalid action: {} (expected install, remove or query)",
+                other
+            ))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Install => "install",
+            Action::Remove => "remove",
+            Action::Query => "query",
+        }
+    }
+}
+
+/// A package manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PackageManager {
+    Apt,
+    Dnf,
+    Yum,
+    Pacman,
+    Zypper,
+    Apk,
+}
+
+impl PackageManager {
+    /// Parse the output of the detection script.
+    fn parse(output: &str) -> Option<Self> {
+        match output.trim() {
+            "apt-get" => Some(PackageManager::Apt),
+            "dnf" => Some(PackageManager::Dnf),
+            "yum" => Some(PackageManager::Yum),
+            "pacman" => Some(PackageManager::Pacman),
+            "zypper" => Some(PackageManager::Zypper),
+            "apk" => Some(PackageManager::Apk),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            PackageManager::Apt => "apt",
+            PackageManager::Dnf => "dnf",
+            PackageManager::Yum => "yum",
+            PackageManager::Pacman => "pacman",
+            PackageManager::Zypper => "zypper",
+            PackageManager::Apk => "apk",
+        }
+    }
+
+    /// The command performing `action` on `package`, non-interactively.
+    /// Install and remove are run with sudo.
+    fn command<'a>(&self, action: Action, package: &'a str) -> Vec<&'a str> {
+        let command: &[&str] = match (self, action) {
+            (PackageManager::Apt, Action::Install) => &[
+                "env",
+                "DEBIAN_FRONTEND=noninteractive",
+                "apt-get",
+                "install",
+                "-y",
+            ],
+            (PackageManager::Apt, Action::Remove) => &[
+                "env",
+                "DEBIAN_FRONTEND=noninteractive",
+                "apt-get",
+                "remove",
+                "-y",
+            ],
+            (PackageManager::Apt, Action::Query) => &["dpkg", "-s"],
+            (PackageManager::Dnf, Action::Install) => &["dnf", "install", "-y"],
+            (PackageManager::Dnf, Action::Remove) => &["dnf", "remove", "-y"],
+            (PackageManager::Yum, Action::Install) => &["yum", "install", "-y"],
+            (PackageManager::Yum, Action::Remove) => &["yum", "remove", "-y"],
+            (PackageManager::Dnf | PackageManager::Yum | PackageManager::Zypper, Action::Query) => {
+                &["rpm", "-q"]
+            }
+            (PackageManager::Pacman, Action::Install) => &["pacman", "-S", "--noconfirm"],
+            (PackageManager::Pacman, Action::Remove) => &["pacman", "-R", "--noconfirm"],
+            (PackageManager::Pacman, Action::Query) => &["pacman", "-Q"],
+            (PackageManager::Zypper, Action::Install) => {
+                &["zypper", "--non-interactive", "install"]
+            }
+            (PackageManager::Zypper, Action::Remove) => &["zypper", "--non-interactive", "remove"],
+            (PackageManager::Apk, Action::Install) => &["apk", "add"],
+            (PackageManager::Apk, Action::Remove) => &["apk", "del"],
+            (PackageManager::Apk, Action::Query) => &["apk", "info", "-e"],
+        };
+
+        let sudo: &[&str] = if action == Action::Query {
+            &[]
+        } else {
+            &["sudo"]
+        };
+        sudo.iter()
+            .chain(command)
+            .copied()
+            .chain(std::iter::once(package))
+            .collect()
+    }
+}
+
+impl Package {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let action = Action::parse(&self.action)?;
+        validate_package_name(&self.name)?;
+        let connection = self.connection();
+
+        let mut detect = super::ssh::ssh_command(&connection, "sh", &["-c", DETECT_SCRIPT])?;
+        if self.dry_run.unwrap_or(false) {
+            // The command depends on the detected package manager, so only
+            // the detection can be shown
+            return Ok(super::dry_run_result(&detect, &self.remote_host, None));
+        }
+
+        let output = super::ssh::run_ssh(&connection, &mut detect, timeout_seconds, false).await?;
+        let manager = PackageManager::parse(&String::from_utf8_lossy(&output.stdout))
+            .ok_or_else(|| {
+                CallToolError::from_message(format!(
+                    "No supported package manager (apt, dnf, yum, pacman, zypper or apk) found on {}",
+                    self.remote_host
+                ))
+            })?;
+
+        let command = manager.command(action, &self.name);
+        let mut cmd = super::ssh::ssh_command(&connection, command[0], &command[1..])?;
+        let output = super::ssh::run_ssh(&connection, &mut cmd, timeout_seconds, false).await?;
+
+        let mut result = super::result_from_output(&output, None, None);
+        let structured_content = result.structured_content.get_or_insert_default();
+        structured_content.insert("manager".to_string(), manager.name().into());
+        structured_content.insert("action".to_string(), action.name().into());
+        structured_content.insert("name".to_string(), self.name.as_str().into());
+        structured_content.insert("success".to_string(), output.status.success().into());
+        Ok(result)
+    }
+
+    /// Describe the operation, e.g. "install the package nginx with sudo
+    /// using the host's package manager on prod-db". The exact command
+    /// depends on the package manager, which is only detected when the tool
+    /// runs.
+    pub fn describe_effective_command(&self) -> String {
+        let sudo = match Action::parse(&self.action) {
+            Ok(Action::Query) => "",
+            _ => " with sudo",
+        };
+        format!(
+            "{} the package {}{} using the host's package manager {}",
+            self.action,
+            super::shell_quote(&self.name),
+            sudo,
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// Reject package names that a package manager would parse as options, or as
+/// a local package file or URL to install, such as ./evil.deb.
+fn validate_package_name(name: &str) -> Result<(), CallToolError> {
+    if name.is_empty()
+        || name.starts_with(['-', '.'])
+        || name.contains('/')
+        || name.chars().any(char::is_whitespace)
+    {
+        return Err(CallToolError::from_message(format!(
+            "Invalid package name: {:?}",
+            name
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_parse() {
+        assert_eq!(Action::parse("install").unwrap(), Action::Install);
+        assert_eq!(Action::parse("query").unwrap(), Action::Query);
+        assert!(Action::parse("upgrade").is_err());
+    }
+
+    #[test]
+    fn test_package_manager_parse() {
+        assert_eq!(
+            PackageManager::parse("apt-get\n"),
+            Some(PackageManager::Apt)
+        );
+        assert_eq!(PackageManager::parse("apk"), Some(PackageManager::Apk));
+        assert_eq!(PackageManager::parse(""), None);
+    }
+
+    #[test]
+    fn test_package_manager_command() {
+        assert_eq!(
+            PackageManager::Apt.command(Action::Install, "htop"),
+            [
+                "sudo",
+                "env",
+                "DEBIAN_FRONTEND=noninteractive",
+                "apt-get",
+                "install",
+                "-y",
+                "htop"
+            ]
+        );
+        assert_eq!(
+            PackageManager::Dnf.command(Action::Query, "htop"),
+            ["rpm", "-q", "htop"]
+        );
+        assert_eq!(
+            PackageManager::Pacman.command(Action::Remove, "htop"),
+            ["sudo", "pacman", "-R", "--noconfirm", "htop"]
+        );
+    }
+
+    #[test]
+    fn test_validate_package_name() {
+        assert!(validate_package_name("nginx").is_ok());
+        assert!(validate_package_name("python3-pip").is_ok());
+        assert!(validate_package_name("").is_err());
+        assert!(validate_package_name("--force").is_err());
+        assert!(validate_package_name("a b").is_err());
+        assert!(validate_package_name("libc6:i386").is_ok());
+        assert!(validate_package_name("./evil.deb").is_err());
+        assert!(validate_package_name("../evil.deb").is_err());
+        assert!(validate_package_name("/tmp/evil.rpm").is_err());
+        assert!(validate_package_name("https://example.com/x.rpm").is_err());
+    }
+}
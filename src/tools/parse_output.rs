@@ -0,0 +1,299 @@
+//! Parsing of the tables printed by common commands into JSON, for the
+//! parse_as parameter of run_ssh_command.
+//!
+//! The tables are read from their header line rather than assuming a fixed
+//! layout, so that they parse whatever options the command was run with:
+//! each row becomes an object keyed by the column names, in snake_case, e.g.
+//! `Use%` becomes `use_percent` and `Recv-Q` becomes `recv_q`.
+
+/// The commands whose output can be parsed
+const PARSERS: &[&str] = &["df", "ps", "ss"];
+
+/// Parse the `stdout` of the command named by `parse_as` into an array of
+/// row objects. Returns why it couldn't otherwise, such as an unknown
+/// command or output without the expected header.
+pub(crate) fn parse(parse_as: &str, stdout: &str) -> Result<serde_json::Value, String> {
+    let rows = match parse_as {
+        "df" => parse_df(stdout),
+        "ps" => parse_ps(stdout),
+        "ss" => parse_ss(stdout),
+        _ => {
+            return Err(format!(
+                "Unknown parse_as value {:?} (expected {}); returning the raw output",
+                parse_as,
+                PARSERS.join(", ")
+            ));
+        }
+    };
+    rows.map(serde_json::Value::Array).ok_or_else(|| {
+        format!(
+            "Output doesn't look like {} output; returning the raw output",
+            parse_as
+        )
+    })
+}
+
+/// Parse df output. Long filesystem names that df wraps onto a line of their
+/// own are joined with the line after them.
+fn parse_df(stdout: &str) -> Option<Vec<serde_json::Value>> {
+    let mut lines = stdout.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next()?;
+    if !header.starts_with("Filesystem") {
+        return None;
+    }
+    let columns = header_columns(header, &[("Mounted", "on")])
+        .into_iter()
+        .map(|column| match column.as_str() {
+            "avail" => "available".to_string(),
+            "capacity" => "use_percent".to_string(),
+            column if column == "size" || column.ends_with("_blocks") => "size".to_string(),
+            _ => column,
+        })
+        .collect::<Vec<String>>();
+
+    let mut rows = Vec::new();
+    let mut wrapped: Option<&str> = None;
+    for line in lines {
+        if line.split_whitespace().count() == 1 && wrapped.is_none() {
+            wrapped = Some(line.trim());
+            continue;
+        }
+        let line = match wrapped.take() {
+            Some(filesystem) => format!("{} {}", filesystem, line.trim()),
+            None => line.to_string(),
+        };
+        rows.push(row(&columns, &line));
+    }
+    Some(rows)
+}
+
+/// Parse ps output. The last column, the command, keeps its spaces.
+fn parse_ps(stdout: &str) -> Option<Vec<serde_json::Value>> {
+    let mut lines = stdout.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next()?;
+    let columns = header_columns(header, &[]);
+    if !columns.iter().any(|column| column == "pid") {
+        return None;
+    }
+    Some(lines.map(|line| row(&columns, line)).collect())
+}
+
+/// Parse ss output, splitting each `Address:Port` column into an address and
+/// a port
+fn parse_ss(stdout: &str) -> Option<Vec<serde_json::Value>> {
+    let mut lines = stdout.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next()?;
+    let columns = header_columns(
+        header,
+        &[("Local", "Address:Port"), ("Peer", "Address:Port")],
+    );
+    if !columns.iter().any(|column| column == "local_address_port") {
+        return None;
+    }
+
+    let rows = lines
+        .map(|line| {
+            let mut row = row(&columns, line);
+            if let serde_json::Value::Object(fields) = &mut row {
+                for side in ["local", "peer"] {
+                    let Some(serde_json::Value::String(address)) =
+                        fields.remove(&format!("{}_address_port", side))
+                    else {
+                        continue;
+                    };
+                    let (address, port) = address.rsplit_once(':').unwrap_or((&address, ""));
+                    fields.insert(format!("{}_address", side), address.into());
+                    fields.insert(format!("{}_port", side), value(port));
+                }
+            }
+            row
+        })
+        .collect();
+    Some(rows)
+}
+
+/// The column names of a header line, in snake_case. `phrases` are pairs of
+/// words that make up a single column name, such as `Mounted on`.
+fn header_columns(header: &str, phrases: &[(&str, &str)]) -> Vec<String> {
+    let mut columns = Vec::new();
+    let mut words = header.split_whitespace().peekable();
+    while let Some(word) = words.next() {
+        let phrase = phrases
+            .iter()
+            .find(|(first, second)| *first == word && words.peek() == Some(second));
+        match phrase {
+            Some((first, second)) => {
+                words.next();
+                columns.push(column_key(&format!("{} {}", first, second)));
+            }
+            None => columns.push(column_key(word)),
+        }
+    }
+    columns
+}
+
+/// The snake_case key of a column name, with a `%` before or after the name
+/// becoming a `_percent` suffix, e.g. `%CPU` becomes `cpu_percent`
+fn column_key(name: &str) -> String {
+    let (name, percent) = match name.strip_prefix('%').or_else(|| name.strip_suffix('%')) {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+    let mut key = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            key.push(c.to_ascii_lowercase());
+        } else if !key.is_empty() && !key.ends_with('_') {
+            key.push('_');
+        }
+    }
+    let mut key = key.trim_end_matches('_').to_string();
+    if percent {
+        key.push_str("_percent");
+    }
+    key
+}
+
+/// A row of a table as an object keyed by `columns`. The last column takes
+/// the rest of the line, so that commands and process lists keep their
+/// spaces; rows with fewer fields than columns leave the last ones out.
+fn row(columns: &[String], line: &str) -> serde_json::Value {
+    let mut fields = serde_json::Map::new();
+    let mut rest = line.trim();
+    for (i, column) in columns.iter().enumerate() {
+        if rest.is_empty() {
+            break;
+        }
+        let field = if i + 1 == columns.len() {
+            std::mem::take(&mut rest)
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let (field, remainder) = rest.split_at(end);
+            rest = remainder.trim_start();
+            field
+        };
+        let field = if column.ends_with("_percent") {
+            field.strip_suffix('%').unwrap_or(field)
+        } else {
+            field
+        };
+        let field = if i + 1 == columns.len() && i > 0 {
+            // The last column is free text, such as a command or mount point
+            serde_json::Value::from(field)
+        } else {
+            value(field)
+        };
+        fields.insert(column.clone(), field);
+    }
+    serde_json::Value::Object(fields)
+}
+
+/// A field as a JSON number when it is one, null for `-`, or a string
+fn value(field: &str) -> serde_json::Value {
+    if field == "-" {
+        return serde_json::Value::Null;
+    }
+    if let Ok(n) = field.parse::<u64>() {
+        return n.into();
+    }
+    if field.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && let Ok(n) = field.parse::<f64>()
+    {
+        return n.into();
+    }
+    field.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_df() {
+        let stdout = "\
+Filesystem     1K-blocks     Used Available Use% Mounted on
+/dev/sda1       41152736 12345678  26693488  32% /
+tmpfs            4030544        0   4030544   0% /dev/shm
+/dev/mapper/very-long-volume-group-name
+                10255636  1048576   8666444  11% /mnt/My Data
+";
+        assert_eq!(
+            parse("df", stdout).unwrap(),
+            json!([
+                {"filesystem": "/dev/sda1", "size": 41152736, "used": 12345678, "available": 26693488, "use_percent": 32, "mounted_on": "/"},
+                {"filesystem": "tmpfs", "size": 4030544, "used": 0, "available": 4030544, "use_percent": 0, "mounted_on": "/dev/shm"},
+                {"filesystem": "/dev/mapper/very-long-volume-group-name", "size": 10255636, "used": 1048576, "available": 8666444, "use_percent": 11, "mounted_on": "/mnt/My Data"},
+            ])
+        );
+
+        let stdout = "\
+Filesystem     Type   Size  Used Avail Use% Mounted on
+/dev/sda1      ext4    40G   12G   25G  32% /
+";
+        assert_eq!(
+            parse("df", stdout).unwrap(),
+            json!([
+                {"filesystem": "/dev/sda1", "type": "ext4", "size": "40G", "used": "12G", "available": "25G", "use_percent": 32, "mounted_on": "/"},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_ps() {
+        let stdout = "\
+USER         PID %CPU %MEM    VSZ   RSS TTY      STAT START   TIME COMMAND
+root           1  0.0  0.1 167748 11508 ?        Ss   Jan01   0:12 /sbin/init splash
+postgres    1234 12.5  3.2 215000 65000 ?        Ss   10:00   1:02 postgres: writer process
+";
+        assert_eq!(
+            parse("ps", stdout).unwrap(),
+            json!([
+                {"user": "root", "pid": 1, "cpu_percent": 0.0, "mem_percent": 0.1, "vsz": 167748, "rss": 11508, "tty": "?", "stat": "Ss", "start": "Jan01", "time": "0:12", "command": "/sbin/init splash"},
+                {"user": "postgres", "pid": 1234, "cpu_percent": 12.5, "mem_percent": 3.2, "vsz": 215000, "rss": 65000, "tty": "?", "stat": "Ss", "start": "10:00", "time": "1:02", "command": "postgres: writer process"},
+            ])
+        );
+
+        // The command is kept as text even when it looks like a number
+        let stdout = "  PID CMD\n   42 7\n";
+        assert_eq!(
+            parse("ps", stdout).unwrap(),
+            json!([{"pid": 42, "cmd": "7"}])
+        );
+    }
+
+    #[test]
+    fn test_parse_ss() {
+        let stdout = "\
+State  Recv-Q Send-Q Local Address:Port  Peer Address:Port Process
+LISTEN 0      128          0.0.0.0:22         0.0.0.0:*     users:((\"sshd\",pid=812,fd=3))
+LISTEN 0      511             [::]:80            [::]:*
+";
+        assert_eq!(
+            parse("ss", stdout).unwrap(),
+            json!([
+                {"state": "LISTEN", "recv_q": 0, "send_q": 128, "local_address": "0.0.0.0", "local_port": 22, "peer_address": "0.0.0.0", "peer_port": "*", "process": "users:((\"sshd\",pid=812,fd=3))"},
+                {"state": "LISTEN", "recv_q": 0, "send_q": 511, "local_address": "[::]", "local_port": 80, "peer_address": "[::]", "peer_port": "*"},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_commands_and_output() {
+        let err = parse("lsblk", "NAME MAJ:MIN\n").unwrap_err();
+        assert!(err.contains("Unknown parse_as value \"lsblk\" (expected df, ps, ss)"));
+
+        let err = parse("df", "df: /mnt: Permission denied\n").unwrap_err();
+        assert!(err.contains("doesn't look like df output"));
+        assert!(parse("ps", "").is_err());
+    }
+
+    #[test]
+    fn test_column_key() {
+        assert_eq!(column_key("%CPU"), "cpu_percent");
+        assert_eq!(column_key("Use%"), "use_percent");
+        assert_eq!(column_key("Recv-Q"), "recv_q");
+        assert_eq!(column_key("1K-blocks"), "1k_blocks");
+        assert_eq!(column_key("Mounted on"), "mounted_on");
+    }
+}
@@ -1,136 +1,366 @@
+use super::{CommonConnection, SshConnectionParams};
 use rust_mcp_sdk::{
     macros::{JsonSchema, mcp_tool},
-    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
-};
-use tokio::{
-    io::AsyncWriteExt,
-    process::Command,
-    time::{Duration, timeout},
+    schema::{CallToolResult, schema_utils::CallToolError},
 };
 
-#[mcp_tool(
-    name = "patch_file",
-    description = "Apply a patch or diff to a file on the remote machine using the patch command. \
-    The patch content is streamed via stdin over SSH. By default, patch will attempt to \
-    automatically detect the correct strip level (-p). Use unified diff format for best results.",
-    title = "Patch File"
-)]
-#[derive(Debug, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
-pub struct PatchFile {
-    /// The patch/diff content to apply.
-    pub patch: String,
-    /// The path to the file on the remote machine to patch.
-    pub remote_file: String,
-    /// The host to run the command on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
-    pub remote_host: String,
-    /// Timeout in seconds for the command execution. Defaults to 30 seconds. Set to 0 to disable timeout.
-    pub timeout_seconds: Option<u64>,
-    /// Additional options to pass to the ssh command. Each option should be a key-value pair separated by an equal sign (=). The options are passed to the ssh command using the -o flag.
-    pub options: Option<Vec<String>>,
+/// Exit statuses of the pre-flight check of the remote file
+const PREFLIGHT_MISSING: i32 = 3;
+const PREFLIGHT_NOT_WRITABLE: i32 = 4;
+const PREFLIGHT_NO_PATCH: i32 = 5;
+
+/// Pre-flight scripts run with the remote file as $1
+const PREFLIGHT_EXISTS: &str =
+    "command -v patch >/dev/null 2>&1 || exit 5; test -f \"$1\" || exit 3";
+const PREFLIGHT_WRITABLE: &str = "command -v patch >/dev/null 2>&1 || exit 5; \
+    test -f \"$1\" || exit 3; test -w \"$1\" || exit 4";
+
+/// Bytes read back from each reject or .orig file left by a failed patch
+const REJECT_MAX_BYTES: usize = 64 * 1024;
+
+/// Script printing at most $2 bytes of the file $1, failing if it doesn't
+/// exist
+const READ_REJECT: &str = "test -f \"$1\" || exit 3; head -c \"$2\" -- \"$1\"";
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "patch_file",
+        description = "Apply a patch or diff to a file on the remote machine using the patch command. \
+        The patch content is streamed via stdin over SSH. By default, patch will attempt to \
+        automatically detect the correct strip level (-p). Use unified diff format for best results. \
+        Set check to test whether the patch applies without changing the file.",
+        title = "Patch File"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct PatchFile {
+        /// The patch/diff content to apply.
+        pub patch: String,
+        /// The path to the file on the remote machine to patch.
+        pub remote_file: String,
+        /// The host to run the command on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Absolute path of the directory the remote command creates its temporary files in, exported to it as TMPDIR. Useful when /tmp is full or mounted noexec. Defaults to the remote host's own, normally /tmp.
+        pub remote_tmp_dir: Option<String>,
+        /// Number of leading path components to strip from file names in the patch (patch -p<n>). Defaults to letting patch detect it.
+        pub strip_level: Option<u32>,
+        /// Check whether the patch applies cleanly without modifying the file (patch --dry-run). The result's applies field says whether it would. Defaults to false.
+        pub check: Option<bool>,
+        /// Keep a copy of the original file with a .orig suffix (patch -b). Defaults to false.
+        pub backup: Option<bool>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
 }
 
 impl PatchFile {
-    #[tracing::instrument]
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
     pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
-        let timeout_seconds = self.timeout_seconds.unwrap_or(30);
-        let options_vec: Option<Vec<&str>> = self
-            .options
-            .as_ref()
-            .map(|v| v.iter().map(String::as_str).collect());
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
 
-        // Get multiplexing options
-        let multiplexing_opts = super::get_multiplexing_options().map_err(|e| {
-            CallToolError::from_message(format!("Failed to get multiplexing options: {}", e))
-        })?;
+        // Build SSH options (host key checking, multiplexing, jump host and
+        // user-provided options)
+        let connection = self.connection();
+        let ssh_args = connection.ssh_args().map_err(super::ssh_options_error)?;
 
         // Build SSH command that will run patch on the remote side
         // The patch command reads from stdin and applies to the specified file
-        let mut cmd = Command::new("ssh");
-        cmd.arg(&self.remote_host);
+        let ssh_program = connection
+            .ssh_program()
+            .map_err(|e| CallToolError::from_message(e.to_string()))?;
+        let mut cmd = connection
+            .command(&ssh_program)
+            .map_err(|e| CallToolError::from_message(e.to_string()))?;
+        let destination =
+            super::ssh_destination(&self.remote_host).map_err(super::ssh_options_error)?;
+        cmd.args(&ssh_args).arg("--").arg(destination);
 
-        // Always append StrictHostKeyChecking=yes to ensure SSH fails instead of prompting interactively
-        cmd.args(["-o", "StrictHostKeyChecking=yes"]);
+        cmd.args(self.patch_args());
 
-        // Add multiplexing options first to ensure they take precedence
-        for opt in &multiplexing_opts {
-            cmd.args(["-o", opt]);
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(&cmd, &self.remote_host, None));
         }
 
-        // Add user-provided options last
-        if let Some(opts) = options_vec {
-            for opt in opts {
-                cmd.args(["-o", opt]);
-            }
+        self.check_remote_file(&connection, timeout_seconds).await?;
+
+        // Stream the patch to patch's stdin
+        let (output, timing) = super::Timing::measure(super::ssh::run_ssh_with_stdin(
+            &mut cmd,
+            self.patch.as_bytes(),
+            timeout_seconds,
+        ))
+        .await;
+        let output = output?;
+
+        if !self.check.unwrap_or(false) && !output.status.success() {
+            // patch explains which hunks failed on stdout
+            let message = format!(
+                "Patch failed to apply to {}: {}",
+                self.remote_file,
+                [&output.stdout, &output.stderr]
+                    .map(|stream| String::from_utf8_lossy(stream).trim().to_string())
+                    .into_iter()
+                    .filter(|stream| !stream.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+            // The error is dropped here, since it isn't Send and the rejects
+            // are read across an await
+            let mut result = super::error_result(&CallToolError::from_message(message));
+            let structured_content = result.structured_content.get_or_insert_default();
+            insert_diff_summary(structured_content, &self.patch, &output);
+            structured_content.insert(
+                "rejects".to_string(),
+                self.read_rejects(&connection, timeout_seconds).await.into(),
+            );
+            return Ok(result);
         }
 
-        cmd.arg("patch")
-            .arg(&self.remote_file)
-            .stdin(std::process::Stdio::piped())
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped());
-
-        let command_future = async {
-            let mut child = cmd.spawn().map_err(|e| {
-                CallToolError::from_message(format!("Failed to spawn SSH command: {}", e))
-            })?;
-
-            // Write the patch content to stdin
-            if let Some(mut stdin) = child.stdin.take() {
-                stdin.write_all(self.patch.as_bytes()).await.map_err(|e| {
-                    CallToolError::from_message(format!("Failed to write patch to stdin: {}", e))
-                })?;
-                // Close stdin to signal EOF
-                drop(stdin);
-            }
+        let mut result = super::result_from_output(&output, None, Some(timing));
+        let structured_content = result.structured_content.get_or_insert_default();
+        if self.check.unwrap_or(false) {
+            structured_content.insert("applies".to_string(), output.status.success().into());
+        }
+        insert_diff_summary(structured_content, &self.patch, &output);
+        Ok(result)
+    }
 
-            // Wait for the command to complete
-            child.wait_with_output().await.map_err(|e| {
-                CallToolError::from_message(format!("Failed to wait for SSH command: {}", e))
-            })
+    /// Check that the remote file exists and, unless only checking whether the
+    /// patch applies, is writable, so that these fail with a clear message
+    /// instead of patch's own output after the whole patch has been sent.
+    async fn check_remote_file(
+        &self,
+        connection: &SshConnectionParams,
+        timeout_seconds: u64,
+    ) -> Result<(), CallToolError> {
+        let script = if self.check.unwrap_or(false) {
+            PREFLIGHT_EXISTS
+        } else {
+            PREFLIGHT_WRITABLE
         };
+        let mut cmd = super::ssh::ssh_command(
+            connection,
+            "sh",
+            &["-c", script, "sh", self.remote_file.as_str()],
+        )?;
+        let output = super::ssh::run_ssh(connection, &mut cmd, timeout_seconds, false).await?;
+        match output.status.code() {
+            Some(0) => Ok(()),
+            Some(PREFLIGHT_MISSING) => Err(CallToolError::from_message(format!(
+                "File not found: {}",
+                self.remote_file
+            ))),
+            Some(PREFLIGHT_NOT_WRITABLE) => Err(CallToolError::from_message(format!(
+                "File is not writable: {}",
+                self.remote_file
+            ))),
+            Some(PREFLIGHT_NO_PATCH) => Err(CallToolError::from_message(format!(
+                "patch not found on {}; install patch there",
+                self.remote_host
+            ))),
+            _ => Err(CallToolError::from_message(format!(
+                "Failed to check {}: {}",
+                self.remote_file,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))),
+        }
+    }
 
-        let result = if timeout_seconds == 0 {
-            // No timeout - run indefinitely
-            command_future.await
-        } else {
-            // Apply timeout
-            let timeout_duration = Duration::from_secs(timeout_seconds);
-            match timeout(timeout_duration, command_future).await {
-                Ok(result) => result,
-                Err(_) => {
-                    return Err(CallToolError::from_message(format!(
-                        "Patch command timed out after {} seconds",
-                        timeout_seconds
-                    )));
+    /// Read back the reject file patch leaves next to the remote file for the
+    /// hunks that failed, and the .orig copy of the file as it was before, so
+    /// the failed hunks can be seen without another call. Files that don't
+    /// exist or can't be read are left out.
+    async fn read_rejects(
+        &self,
+        connection: &SshConnectionParams,
+        timeout_seconds: u64,
+    ) -> serde_json::Map<String, serde_json::Value> {
+        let mut rejects = serde_json::Map::new();
+        let max_bytes = REJECT_MAX_BYTES.to_string();
+        for suffix in [".rej", ".orig"] {
+            let path = format!("{}{}", self.remote_file, suffix);
+            let Ok(mut cmd) = super::ssh::ssh_command(
+                connection,
+                "sh",
+                &["-c", READ_REJECT, "sh", path.as_str(), max_bytes.as_str()],
+            ) else {
+                continue;
+            };
+            match super::ssh::run_ssh(connection, &mut cmd, timeout_seconds, false).await {
+                Ok(output) if output.status.success() => {
+                    rejects.insert(
+                        path,
+                        String::from_utf8_lossy(&output.stdout).into_owned().into(),
+                    );
                 }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Failed to read {}: {}", path, e),
             }
-        };
+        }
+        rejects
+    }
 
-        match result {
-            Ok(output) => {
-                // The command executed successfully. This doesn't mean it
-                // succeeded, so output is returned as a successful tool call.
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let status_code = output.status.code();
-
-                Ok(
-                    CallToolResult::text_content(vec![TextContent::from(stdout.clone())])
-                        .with_structured_content(super::map_from_output(
-                            stdout,
-                            stderr,
-                            status_code,
-                        )),
-                )
+    /// Build the remote patch command line.
+    fn patch_args(&self) -> Vec<String> {
+        let mut args = vec!["patch".to_string()];
+        if let Some(strip_level) = self.strip_level {
+            args.push(format!("-p{}", strip_level));
+        }
+        if self.backup.unwrap_or(false) {
+            args.push("-b".to_string());
+        }
+        if self.check.unwrap_or(false) {
+            args.push("--dry-run".to_string());
+        }
+        // Quote the file name since the remote shell parses the command line,
+        // after -- so that a name starting with - isn't taken for an option
+        args.push("--".to_string());
+        args.push(super::shell_quote(&self.remote_file));
+        args
+    }
+
+    /// Describe the patch, e.g. "patch -b -- /etc/app.conf on prod-db, adding
+    /// 2 lines and removing 1"
+    pub fn describe_effective_command(&self) -> String {
+        let summary = DiffSummary::parse(&self.patch);
+        format!(
+            "{} {}, adding {} lines and removing {}{}",
+            // The file name is already quoted for the remote shell
+            self.patch_args().join(" "),
+            super::on_host(&self.remote_host, self.jump_host.as_deref()),
+            summary.lines_added,
+            summary.lines_removed,
+            super::using_ssh_binary(self.ssh_binary.as_deref())
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            remote_tmp_dir: self.remote_tmp_dir.clone(),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// What a unified diff changes: the files it names, its hunks and the lines
+/// they add and remove.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct DiffSummary {
+    files_touched: Vec<String>,
+    hunks: usize,
+    lines_added: usize,
+    lines_removed: usize,
+}
+
+impl DiffSummary {
+    /// Parse a unified diff. Hunk bodies are consumed according to the line
+    /// counts of their @@ header, so that a removed line starting with "--" or
+    /// an added line starting with "++" isn't taken for a file header.
+    fn parse(patch: &str) -> Self {
+        let mut summary = DiffSummary::default();
+        let mut old_file: Option<&str> = None;
+        // Lines left to read in the current hunk, on the old and new side
+        let (mut old_left, mut new_left) = (0usize, 0usize);
+        for line in patch.lines() {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if old_left > 0 || new_left > 0 {
+                match line.chars().next() {
+                    Some('+') => {
+                        summary.lines_added += 1;
+                        new_left = new_left.saturating_sub(1);
+                    }
+                    Some('-') => {
+                        summary.lines_removed += 1;
+                        old_left = old_left.saturating_sub(1);
+                    }
+                    // "\ No newline at end of file"
+                    Some('\\') => {}
+                    _ => {
+                        old_left = old_left.saturating_sub(1);
+                        new_left = new_left.saturating_sub(1);
+                    }
+                }
+            } else if let Some(name) = line.strip_prefix("--- ") {
+                old_file = Some(header_file_name(name));
+            } else if let Some(name) = line.strip_prefix("+++ ") {
+                // A deleted file is named by its old side
+                let name = match header_file_name(name) {
+                    "/dev/null" => old_file.unwrap_or("/dev/null"),
+                    name => name,
+                };
+                if !summary.files_touched.iter().any(|file| file == name) {
+                    summary.files_touched.push(name.to_string());
+                }
+            } else if let Some((old_lines, new_lines)) = hunk_line_counts(line) {
+                summary.hunks += 1;
+                old_left = old_lines;
+                new_left = new_lines;
             }
-            Err(e) => Err(e),
         }
+        summary
     }
 }
 
+/// The file name of a ---/+++ header, without the timestamp diff -u appends
+/// after a tab
+fn header_file_name(header: &str) -> &str {
+    header.split('\t').next().unwrap_or(header).trim_end()
+}
+
+/// The old and new line counts of a hunk header such as
+/// "@@ -1,2 +1,3 @@", where an omitted count means 1
+fn hunk_line_counts(line: &str) -> Option<(usize, usize)> {
+    let mut ranges = line.strip_prefix("@@ ")?.split_whitespace();
+    let count = |range: Option<&str>, sign: char| -> Option<usize> {
+        let range = range?.strip_prefix(sign)?;
+        match range.split_once(',') {
+            Some((_, count)) => count.parse().ok(),
+            None => range.parse::<usize>().ok().map(|_| 1),
+        }
+    };
+    Some((count(ranges.next(), '-')?, count(ranges.next(), '+')?))
+}
+
+/// Insert the diff summary of the patch into a result, with the hunks that
+/// patch reports as failed in its output.
+fn insert_diff_summary(
+    structured_content: &mut serde_json::Map<String, serde_json::Value>,
+    patch: &str,
+    output: &std::process::Output,
+) {
+    let summary = DiffSummary::parse(patch);
+    let hunks_failed = failed_hunks(&format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    ))
+    .min(summary.hunks);
+    structured_content.insert("files_touched".to_string(), summary.files_touched.into());
+    structured_content.insert(
+        "hunks_applied".to_string(),
+        (summary.hunks - hunks_failed).into(),
+    );
+    structured_content.insert("hunks_failed".to_string(), hunks_failed.into());
+    structured_content.insert("lines_added".to_string(), summary.lines_added.into());
+    structured_content.insert("lines_removed".to_string(), summary.lines_removed.into());
+}
+
+/// Count the hunks patch reports as failed, from its "Hunk #2 FAILED at 10."
+/// lines. Hunks that apply cleanly aren't reported at all, and those applied
+/// with an offset or fuzz are reported as succeeded.
+fn failed_hunks(output: &str) -> usize {
+    output
+        .lines()
+        .filter(|line| line.trim_start().starts_with("Hunk #") && line.contains(" FAILED"))
+        .count()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(unix)]
+    use crate::tools::{TestPath, run_script};
 
     #[test]
     fn test_patch_file_struct_creation() {
@@ -140,6 +370,7 @@ mod tests {
             remote_host: "localhost".to_string(),
             timeout_seconds: Some(60),
             options: Some(vec!["StrictHostKeyChecking=no".to_string()]),
+            ..Default::default()
         };
 
         assert_eq!(patch_cmd.remote_file, "/home/user/file.txt");
@@ -148,6 +379,143 @@ mod tests {
         assert!(patch_cmd.patch.contains("new"));
     }
 
+    #[test]
+    fn test_patch_args() {
+        let patch_cmd = PatchFile {
+            remote_file: "/etc/my app.conf".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            patch_cmd.patch_args(),
+            ["patch", "--", "'/etc/my app.conf'"]
+        );
+
+        let patch_cmd = PatchFile {
+            remote_file: "/etc/app.conf".to_string(),
+            strip_level: Some(1),
+            check: Some(true),
+            backup: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            patch_cmd.patch_args(),
+            ["patch", "-p1", "-b", "--dry-run", "--", "/etc/app.conf"]
+        );
+    }
+
+    #[test]
+    fn test_describe_effective_command() {
+        let patch_cmd = PatchFile {
+            patch: "--- a/app.conf\n+++ b/app.conf\n@@ -1,2 +1,3 @@\n-old\n+new\n+more\n same"
+                .to_string(),
+            remote_file: "/etc/app.conf".to_string(),
+            remote_host: "prod-db".to_string(),
+            backup: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            patch_cmd.describe_effective_command(),
+            "patch -b -- /etc/app.conf on prod-db, adding 2 lines and removing 1"
+        );
+    }
+
+    #[test]
+    fn test_diff_summary() {
+        let patch = "diff -u a/app.conf b/app.conf\n\
+            --- a/app.conf\t2024-01-01 00:00:00\n\
+            +++ b/app.conf\t2024-01-02 00:00:00\n\
+            @@ -1,3 +1,3 @@\n\
+            -old\n\
+            +new\n\
+            \x20same\n\
+            --- not a header\n\
+            +++ not a header either\n\
+            @@ -10 +10,2 @@\n\
+            \x20last\n\
+            +added\n\
+            \\ No newline at end of file\n\
+            --- a/removed.conf\n\
+            +++ /dev/null\n\
+            @@ -1 +0,0 @@\n\
+            -gone\n";
+        assert_eq!(
+            DiffSummary::parse(patch),
+            DiffSummary {
+                files_touched: vec!["b/app.conf".to_string(), "a/removed.conf".to_string()],
+                hunks: 3,
+                lines_added: 3,
+                lines_removed: 3,
+            }
+        );
+        assert_eq!(DiffSummary::parse("not a diff"), DiffSummary::default());
+    }
+
+    #[test]
+    fn test_failed_hunks() {
+        let output = "patching file app.conf\n\
+            Hunk #1 succeeded at 12 (offset 2 lines).\n\
+            Hunk #2 FAILED at 30.\n\
+            Hunk #3 FAILED at 41.\n\
+            2 out of 3 hunks FAILED -- saving rejects to file app.conf.rej\n";
+        assert_eq!(failed_hunks(output), 2);
+        assert_eq!(failed_hunks("patching file app.conf\n"), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preflight_scripts() {
+        let status =
+            |script: &str, path: &str| run_script(script, &[path], b"", None).status.code();
+        let file = TestPath::new("preflight");
+        std::fs::write(&file, "content").unwrap();
+        let file = file.to_str().unwrap();
+
+        assert_eq!(status(PREFLIGHT_WRITABLE, file), Some(0));
+        assert_eq!(status(PREFLIGHT_EXISTS, file), Some(0));
+        assert_eq!(
+            status(PREFLIGHT_WRITABLE, "/nonexistent/file"),
+            Some(PREFLIGHT_MISSING)
+        );
+        assert_eq!(
+            status(PREFLIGHT_EXISTS, "/nonexistent/file"),
+            Some(PREFLIGHT_MISSING)
+        );
+
+        // Without patch on the remote host, the patch can't be applied
+        let status = std::process::Command::new("/bin/sh")
+            .args(["-c", PREFLIGHT_EXISTS, "sh", file])
+            .env("PATH", "/nonexistent")
+            .status()
+            .unwrap();
+        assert_eq!(status.code(), Some(PREFLIGHT_NO_PATCH));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_reject_script() {
+        let file = TestPath::new("reject");
+        std::fs::write(&file, "@@ -1 +1 @@\n-old\n+new\n").unwrap();
+        let read =
+            |path: &str, max_bytes: &str| run_script(READ_REJECT, &[path, max_bytes], b"", None);
+
+        let output = read(file.to_str().unwrap(), "1024");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"@@ -1 +1 @@\n-old\n+new\n");
+
+        let output = read(file.to_str().unwrap(), "5");
+        assert_eq!(output.stdout, b"@@ -1");
+
+        let output = read("/nonexistent/file.rej", "1024");
+        assert_eq!(output.status.code(), Some(3));
+
+        // A relative path starting with - isn't taken for an option
+        let dir = TestPath::dir("reject_dir");
+        std::fs::write(dir.join("-app.conf.rej"), "-old\n").unwrap();
+        let output = run_script(READ_REJECT, &["-app.conf.rej", "1024"], b"", Some(&dir));
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"-old\n");
+    }
+
     #[test]
     fn test_patch_file_defaults() {
         let patch_cmd = PatchFile {
@@ -156,6 +524,7 @@ mod tests {
             remote_host: "example.com".to_string(),
             timeout_seconds: None,
             options: None,
+            ..Default::default()
         };
 
         assert!(patch_cmd.timeout_seconds.is_none());
@@ -0,0 +1,303 @@
+use super::{CommonConnection, SshConnectionParams};
+use base64::{Engine, prelude::BASE64_STANDARD};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+/// Number of bytes read when the call doesn't set max_bytes.
+const DEFAULT_MAX_BYTES: u64 = 65536;
+
+/// Cap on max_bytes, the same as on the output of commands
+const MAX_BYTES: u64 = super::DEFAULT_MAX_OUTPUT_BYTES as u64;
+
+/// Exit statuses of the read script
+const READ_MISSING: i32 = 3;
+const READ_NOT_READABLE: i32 = 4;
+
+/// Script printing the size and modification time (seconds since the epoch)
+/// of the file $1 on one line, followed by at most $3 bytes of it starting at
+/// byte offset $2. stat takes -c on GNU systems and -f on BSD and macOS.
+const READ_SCRIPT: &str = r#"[ -f "$1" ] || exit 3
+[ -r "$1" ] || exit 4
+size=$(stat -c %s -- "$1" 2>/dev/null || stat -f %z -- "$1")
+mtime=$(stat -c %Y -- "$1" 2>/dev/null || stat -f %m -- "$1")
+echo "$size $mtime"
+tail -c +"$(($2 + 1))" -- "$1" | head -c "$3""#;
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "read_remote_file",
+        description = "Read a file on a remote POSIX compatible system (Linux, BSD, macOS), returning at most max_bytes of it starting at offset. \
+        Text files are returned as text and binary files base64-encoded. The result also has the file's size and mtime, so large files can be read in parts. \
+        Use this instead of cat to inspect configuration files and logs.",
+        title = "Read Remote File"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct ReadRemoteFile {
+        /// The path of the file on the remote machine.
+        pub remote_path: String,
+        /// Maximum number of bytes to read, at most 1048576 (1 MiB). Defaults to 65536 (64 KiB).
+        pub max_bytes: Option<u64>,
+        /// Byte offset to start reading at. Defaults to 0, the start of the file.
+        pub offset: Option<u64>,
+        /// The host to read the file on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Number of times to retry when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+/// The part of a file returned by the read script, with the file's size and
+/// modification time.
+#[derive(Debug, PartialEq, Eq)]
+struct FileContent<'a> {
+    size: u64,
+    mtime: i64,
+    content: &'a [u8],
+}
+
+impl ReadRemoteFile {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let max_bytes = self.max_bytes();
+        if max_bytes == 0 {
+            return Err(CallToolError::from_message(
+                "max_bytes must be greater than 0",
+            ));
+        }
+        let offset = self.offset.unwrap_or(0);
+
+        let connection = self.connection();
+        let (offset_arg, max_bytes_arg) = (offset.to_string(), max_bytes.to_string());
+        let mut cmd = super::ssh::ssh_command(
+            &connection,
+            "sh",
+            &[
+                "-c",
+                READ_SCRIPT,
+                "sh",
+                self.remote_path.as_str(),
+                offset_arg.as_str(),
+                max_bytes_arg.as_str(),
+            ],
+        )?;
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(&cmd, &self.remote_host, None));
+        }
+
+        let output = super::ssh::run_ssh(&connection, &mut cmd, timeout_seconds, false).await?;
+        match output.status.code() {
+            Some(0) => {}
+            Some(READ_MISSING) => {
+                return Err(CallToolError::from_message(format!(
+                    "File not found: {}",
+                    self.remote_path
+                )));
+            }
+            Some(READ_NOT_READABLE) => {
+                return Err(CallToolError::from_message(format!(
+                    "File is not readable: {}",
+                    self.remote_path
+                )));
+            }
+            _ => {
+                return Err(CallToolError::from_message(format!(
+                    "Failed to read {}: {}",
+                    self.remote_path,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+
+        let file = parse_output(&output.stdout).ok_or_else(|| {
+            CallToolError::from_message(format!(
+                "Failed to read {}: unexpected output",
+                self.remote_path
+            ))
+        })?;
+        let truncated = offset.saturating_add(file.content.len() as u64) < file.size;
+        let (encoding, content, bytes) = match utf8_prefix(file.content, truncated) {
+            Some(text) => ("utf-8", text.to_string(), text.len() as u64),
+            None => (
+                "base64",
+                BASE64_STANDARD.encode(file.content),
+                file.content.len() as u64,
+            ),
+        };
+
+        let mut structured_content = serde_json::Map::new();
+        structured_content.insert("path".to_string(), self.remote_path.as_str().into());
+        structured_content.insert("size".to_string(), file.size.into());
+        structured_content.insert("mtime".to_string(), file.mtime.into());
+        structured_content.insert("offset".to_string(), offset.into());
+        structured_content.insert("bytes".to_string(), bytes.into());
+        structured_content.insert(
+            "truncated".to_string(),
+            (offset.saturating_add(bytes) < file.size).into(),
+        );
+        structured_content.insert("encoding".to_string(), encoding.into());
+        structured_content.insert("content".to_string(), content.as_str().into());
+
+        Ok(
+            CallToolResult::text_content(vec![TextContent::from(content)])
+                .with_structured_content(structured_content),
+        )
+    }
+
+    /// Describe the read, e.g. "read up to 65536 bytes of /etc/app.conf from
+    /// offset 0 on prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        format!(
+            "read up to {} bytes of {} from offset {} {}",
+            self.max_bytes(),
+            super::shell_quote(&self.remote_path),
+            self.offset.unwrap_or(0),
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        )
+    }
+
+    /// The number of bytes to read: max_bytes, capped at MAX_BYTES
+    fn max_bytes(&self) -> u64 {
+        self.max_bytes.unwrap_or(DEFAULT_MAX_BYTES).min(MAX_BYTES)
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// The text of a part of a file, or None if it isn't valid UTF-8. A part that
+/// stops before the end of the file may cut a character, which is left for the
+/// next read.
+fn utf8_prefix(content: &[u8], truncated: bool) -> Option<&str> {
+    match std::str::from_utf8(content) {
+        Ok(text) => Some(text),
+        Err(e) if truncated && e.error_len().is_none() && e.valid_up_to() > 0 => {
+            std::str::from_utf8(&content[..e.valid_up_to()]).ok()
+        }
+        Err(_) => None,
+    }
+}
+
+/// Split the read script's output into the size and mtime line and the
+/// content that follows it.
+fn parse_output(stdout: &[u8]) -> Option<FileContent<'_>> {
+    let newline = stdout.iter().position(|&b| b == b'\n')?;
+    let header = std::str::from_utf8(&stdout[..newline]).ok()?;
+    let (size, mtime) = header.trim().split_once(' ')?;
+    Some(FileContent {
+        size: size.parse().ok()?,
+        mtime: mtime.parse().ok()?,
+        content: &stdout[newline + 1..],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::tools::{TestPath, run_script};
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!(
+            parse_output(b"12 1700000000\nkey = value\n"),
+            Some(FileContent {
+                size: 12,
+                mtime: 1700000000,
+                content: b"key = value\n",
+            })
+        );
+        assert_eq!(
+            parse_output(b"0 1700000000\n"),
+            Some(FileContent {
+                size: 0,
+                mtime: 1700000000,
+                content: b"",
+            })
+        );
+        assert_eq!(parse_output(b"no header"), None);
+    }
+
+    #[test]
+    fn test_utf8_prefix() {
+        assert_eq!(utf8_prefix("héllo".as_bytes(), false), Some("héllo"));
+        // A read ending inside é leaves it for the next one
+        assert_eq!(utf8_prefix(&"héllo".as_bytes()[..2], true), Some("h"));
+        assert_eq!(utf8_prefix(&"héllo".as_bytes()[..2], false), None);
+        assert_eq!(utf8_prefix(&"é".as_bytes()[..1], true), None);
+        assert_eq!(utf8_prefix(b"\xff\xfeabc", true), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_script() {
+        let dir = TestPath::dir("read_remote_file");
+        let file = dir.join("app.log");
+        std::fs::write(&file, "0123456789").unwrap();
+        let read = |path: &str, offset: &str, max_bytes: &str| {
+            run_script(READ_SCRIPT, &[path, offset, max_bytes], b"", Some(&dir))
+        };
+
+        let output = read(file.to_str().unwrap(), "2", "5");
+        assert!(output.status.success());
+        let content = parse_output(&output.stdout).unwrap();
+        assert_eq!(content.size, 10);
+        assert!(content.mtime > 0);
+        assert_eq!(content.content, b"23456");
+
+        let output = read(file.to_str().unwrap(), "0", "100");
+        assert_eq!(parse_output(&output.stdout).unwrap().content, b"0123456789");
+
+        let output = read("/nonexistent/file", "0", "100");
+        assert_eq!(output.status.code(), Some(READ_MISSING));
+
+        // A relative path looking like an option is still read as a path
+        std::fs::write(dir.join("-n"), "0123456789").unwrap();
+        let output = read("-n", "3", "4");
+        let content = parse_output(&output.stdout).unwrap();
+        assert_eq!((content.size, content.content), (10, &b"3456"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_read_remote_file_rejects_zero_max_bytes() {
+        let tool = ReadRemoteFile {
+            remote_path: "/etc/hostname".to_string(),
+            max_bytes: Some(0),
+            remote_host: "example.com".to_string(),
+            ..Default::default()
+        };
+        assert!(tool.call_tool().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_remote_file_caps_max_bytes() {
+        let tool = ReadRemoteFile {
+            remote_path: "/var/log/syslog".to_string(),
+            max_bytes: Some(1 << 30),
+            remote_host: "example.com".to_string(),
+            dry_run: Some(true),
+            ..Default::default()
+        };
+        assert!(
+            tool.describe_effective_command()
+                .starts_with("read up to 1048576 bytes of /var/log/syslog")
+        );
+        let result = tool.call_tool().await.unwrap();
+        let args = &result.structured_content.unwrap()["args"];
+        assert_eq!(args.as_array().unwrap().last().unwrap(), "1048576");
+    }
+}
@@ -0,0 +1,214 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+/// Script printing what the remote account is and may do, as key=value lines.
+/// `sudo -n` fails instead of prompting when sudo would need a password, so
+/// can_sudo is only true for passwordless sudo.
+const IDENTITY_SCRIPT: &str = r#"printf 'user=%s\n' "$(id -un)"
+printf 'uid=%s\n' "$(id -u)"
+printf 'groups=%s\n' "$(id -Gn)"
+if sudo -n true >/dev/null 2>&1; then echo can_sudo=true; else echo can_sudo=false; fi
+printf 'hostname=%s\n' "$(hostname 2>/dev/null || uname -n)"
+printf 'uname=%s\n' "$(uname -a)"
+if [ -r /etc/os-release ]; then
+    printf 'distribution=%s\n' "$(. /etc/os-release && echo "$PRETTY_NAME")"
+fi"#;
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "remote_identity",
+        description = "Find out who you are and what you may do on a remote POSIX compatible system (Linux, BSD, macOS) in one SSH round trip. \
+        Returns the user, uid, groups, whether passwordless sudo works (can_sudo, tested with sudo -n true so it never prompts), \
+        the hostname, OS, kernel release and, on Linux, the distribution. \
+        Use this before choosing between run_ssh_command and run_ssh_sudo_command.",
+        title = "Remote Identity"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct RemoteIdentity {
+        /// The host to probe. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Number of times to retry when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+/// The account and system found by the identity script.
+#[derive(Debug, Default, Clone, PartialEq, Eq, ::serde::Serialize)]
+struct Identity {
+    user: String,
+    uid: Option<u32>,
+    groups: Vec<String>,
+    can_sudo: bool,
+    hostname: String,
+    os: String,
+    kernel: String,
+    distribution: Option<String>,
+}
+
+impl RemoteIdentity {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let connection = self.connection();
+        let mut cmd = super::ssh::ssh_command(&connection, "sh", &["-c", IDENTITY_SCRIPT])?;
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(&cmd, &self.remote_host, None));
+        }
+
+        let output = super::ssh::run_ssh(&connection, &mut cmd, timeout_seconds, false).await?;
+        if !output.status.success() {
+            return Err(CallToolError::from_message(format!(
+                "Failed to identify the user on {}: {}",
+                self.remote_host,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let identity = parse_identity(&String::from_utf8_lossy(&output.stdout));
+        let text = format!(
+            "{} (uid {}) on {}, {} {}: {}",
+            identity.user,
+            identity
+                .uid
+                .map_or_else(|| "unknown".to_string(), |uid| uid.to_string()),
+            identity.hostname,
+            identity.os,
+            identity.kernel,
+            if identity.can_sudo {
+                "passwordless sudo works"
+            } else {
+                "no passwordless sudo"
+            }
+        );
+        let structured_content = match serde_json::to_value(&identity) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+
+        Ok(CallToolResult::text_content(vec![TextContent::from(text)])
+            .with_structured_content(structured_content))
+    }
+
+    /// Describe the probe, e.g. "identify the user and system on prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        format!(
+            "identify the user and system {}",
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// Parse the key=value lines printed by the identity script. The OS and
+/// kernel release are the first and third fields of `uname -a`.
+fn parse_identity(output: &str) -> Identity {
+    let mut identity = Identity::default();
+    for (key, value) in output.lines().filter_map(|line| line.split_once('=')) {
+        let value = value.trim();
+        match key {
+            "user" => identity.user = value.to_string(),
+            "uid" => identity.uid = value.parse().ok(),
+            "groups" => identity.groups = value.split_whitespace().map(str::to_string).collect(),
+            "can_sudo" => identity.can_sudo = value == "true",
+            "hostname" => identity.hostname = value.to_string(),
+            "uname" => {
+                let fields: Vec<&str> = value.split_whitespace().collect();
+                identity.os = fields.first().copied().unwrap_or_default().to_string();
+                identity.kernel = fields.get(2).copied().unwrap_or_default().to_string();
+            }
+            "distribution" if !value.is_empty() => identity.distribution = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    identity
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::tools::run_script;
+
+    #[test]
+    fn test_parse_identity() {
+        let output = "user=deploy\n\
+            uid=1001\n\
+            groups=deploy adm docker\n\
+            can_sudo=true\n\
+            hostname=web1\n\
+            uname=Linux web1 6.8.0-45-generic #45-Ubuntu SMP PREEMPT_DYNAMIC x86_64 x86_64 x86_64 GNU/Linux\n\
+            distribution=Ubuntu 24.04.1 LTS\n";
+        assert_eq!(
+            parse_identity(output),
+            Identity {
+                user: "deploy".to_string(),
+                uid: Some(1001),
+                groups: vec![
+                    "deploy".to_string(),
+                    "adm".to_string(),
+                    "docker".to_string()
+                ],
+                can_sudo: true,
+                hostname: "web1".to_string(),
+                os: "Linux".to_string(),
+                kernel: "6.8.0-45-generic".to_string(),
+                distribution: Some("Ubuntu 24.04.1 LTS".to_string()),
+            }
+        );
+
+        let identity = parse_identity(
+            "user=admin\nuid=501\ngroups=staff\ncan_sudo=false\nhostname=mac\nuname=Darwin mac 23.6.0 Darwin Kernel\n",
+        );
+        assert!(!identity.can_sudo);
+        assert_eq!(identity.os, "Darwin");
+        assert_eq!(identity.kernel, "23.6.0");
+        assert_eq!(identity.distribution, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_identity_script() {
+        let output = run_script(IDENTITY_SCRIPT, &[], b"", None);
+        assert!(output.status.success());
+
+        let identity = parse_identity(&String::from_utf8_lossy(&output.stdout));
+        let user = std::process::Command::new("id")
+            .arg("-un")
+            .output()
+            .unwrap();
+        assert_eq!(identity.user, String::from_utf8_lossy(&user.stdout).trim());
+        assert!(identity.uid.is_some());
+        assert!(!identity.groups.is_empty());
+        assert!(!identity.os.is_empty());
+    }
+
+    #[test]
+    fn test_describe_effective_command() {
+        let tool = RemoteIdentity {
+            remote_host: "prod-db".to_string(),
+            jump_host: Some("bastion".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            tool.describe_effective_command(),
+            "identify the user and system on prod-db via bastion"
+        );
+    }
+}
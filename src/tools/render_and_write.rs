@@ -0,0 +1,240 @@
+use super::{CommonConnection, SshConnectionParams, write_file};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, schema_utils::CallToolError},
+};
+use std::collections::HashMap;
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "render_and_write",
+        description = "Render a template by substituting {{variable}} placeholders with the given values, \
+        then write the result to a file on a remote POSIX compatible system (Linux, BSD, macOS) atomically, as write_file does. \
+        Templates with placeholders that have no value are rejected unless allow_missing is set. \
+        Use this instead of building configuration files with heredocs.",
+        title = "Render and Write"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct RenderAndWrite {
+        /// The template to render. Placeholders are variable names between double braces, such as {{port}} or {{ server_name }}. Variable names are made of letters, digits and underscores, not starting with a digit; anything else between double braces is left as is.
+        pub template: String,
+        /// The values of the template's variables, by name.
+        pub vars: Option<HashMap<String, String>>,
+        /// Leave placeholders without a value in vars as they are instead of rejecting the template. Defaults to false.
+        pub allow_missing: Option<bool>,
+        /// The path of the file on the remote machine. The directory must exist.
+        pub remote_path: String,
+        /// File mode in octal, e.g. 644 or 0600. Defaults to the mode of the existing file, or 644 for a new file.
+        pub mode: Option<String>,
+        /// The host to write the file on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Return the command that would be run, with the rendered content, instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+impl RenderAndWrite {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let rendered = self.render()?;
+        let mut result = write_file::write(
+            &self.connection(),
+            &self.remote_path,
+            self.mode.as_deref(),
+            &rendered.content,
+            self.timeout_seconds,
+            self.dry_run.unwrap_or(false),
+        )
+        .await?;
+
+        let structured_content = result.structured_content.get_or_insert_default();
+        structured_content.insert(
+            "substituted".to_string(),
+            serde_json::Value::from(rendered.substituted),
+        );
+        if !rendered.missing.is_empty() {
+            structured_content.insert(
+                "missing".to_string(),
+                serde_json::Value::from(rendered.missing),
+            );
+        }
+        Ok(result)
+    }
+
+    /// Render the template, rejecting it if it has placeholders without a
+    /// value unless `allow_missing` is set
+    fn render(&self) -> Result<Rendered, CallToolError> {
+        let rendered = render(&self.template, &self.vars.clone().unwrap_or_default());
+        if !rendered.missing.is_empty() && !self.allow_missing.unwrap_or(false) {
+            return Err(CallToolError::from_message(format!(
+                "Template has variables without a value: {} (pass them in vars, or set allow_missing to leave them as is)",
+                rendered.missing.join(", ")
+            )));
+        }
+        Ok(rendered)
+    }
+
+    /// Describe the write, e.g. "render a template and replace
+    /// /etc/app.conf on prod-db with 120 bytes of content, mode 600"
+    pub fn describe_effective_command(&self) -> String {
+        let content = self
+            .render()
+            .map(|rendered| rendered.content)
+            .unwrap_or_else(|_| self.template.clone());
+        format!(
+            "render a template and {}",
+            write_file::describe_write(
+                &self.connection(),
+                &self.remote_path,
+                self.mode.as_deref(),
+                &content
+            )
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        self.common_connection(&self.remote_host)
+    }
+}
+
+/// A rendered template
+#[derive(Debug, PartialEq, Eq)]
+struct Rendered {
+    content: String,
+    /// Number of placeholders replaced by a value
+    substituted: usize,
+    /// Names of the variables without a value, in order of first use
+    missing: Vec<String>,
+}
+
+/// Substitute the `{{name}}` placeholders of `template` with their values in
+/// `vars`. Whitespace around the name is allowed. Placeholders without a
+/// value, and double braces around anything other than a variable name, are
+/// left as they are.
+fn render(template: &str, vars: &HashMap<String, String>) -> Rendered {
+    let mut content = String::with_capacity(template.len());
+    let mut substituted = 0;
+    let mut missing: Vec<String> = Vec::new();
+
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}").map(|end| start + 2 + end) else {
+            break;
+        };
+        let name = rest[start + 2..end].trim();
+        if !is_variable_name(name) {
+            // Not a placeholder: keep the first brace and look for one
+            // starting at the second, as in {{{name}}}
+            content.push_str(&rest[..start + 1]);
+            rest = &rest[start + 1..];
+            continue;
+        }
+
+        content.push_str(&rest[..start]);
+        match vars.get(name) {
+            Some(value) => {
+                content.push_str(value);
+                substituted += 1;
+            }
+            None => {
+                content.push_str(&rest[start..end + 2]);
+                if !missing.iter().any(|missing| missing == name) {
+                    missing.push(name.to_string());
+                }
+            }
+        }
+        rest = &rest[end + 2..];
+    }
+    content.push_str(rest);
+
+    Rendered {
+        content,
+        substituted,
+        missing,
+    }
+}
+
+/// Whether `name` is a variable name: letters, digits and underscores, not
+/// starting with a digit
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render() {
+        let rendered = render(
+            "server {\n    listen {{port}};\n    server_name {{ server_name }};\n    root /srv/{{server_name}};\n}\n",
+            &vars(&[("port", "8080"), ("server_name", "app.example.com")]),
+        );
+        assert_eq!(
+            rendered,
+            Rendered {
+                content: "server {\n    listen 8080;\n    server_name app.example.com;\n    root /srv/app.example.com;\n}\n".to_string(),
+                substituted: 3,
+                missing: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_render_missing_variables() {
+        let rendered = render(
+            "{{user}}:{{password}}@{{host}}/{{user}}",
+            &vars(&[("host", "db")]),
+        );
+        assert_eq!(rendered.content, "{{user}}:{{password}}@db/{{user}}");
+        assert_eq!(rendered.substituted, 1);
+        assert_eq!(rendered.missing, vec!["user", "password"]);
+    }
+
+    #[test]
+    fn test_render_leaves_other_braces() {
+        // Values aren't rendered again, and double braces around anything
+        // other than a variable name aren't placeholders
+        let rendered = render(
+            "{{ .Values.x }} {{{name}}} {{name}} {{",
+            &vars(&[("name", "{{other}}")]),
+        );
+        assert_eq!(rendered.content, "{{ .Values.x }} {{{other}}} {{other}} {{");
+        assert_eq!(rendered.substituted, 2);
+        assert!(rendered.missing.is_empty());
+    }
+
+    #[test]
+    fn test_is_variable_name() {
+        assert!(is_variable_name("port"));
+        assert!(is_variable_name("_server_name2"));
+        assert!(!is_variable_name("2fast"));
+        assert!(!is_variable_name(".Values.x"));
+        assert!(!is_variable_name(""));
+    }
+
+    #[tokio::test]
+    async fn test_render_and_write_rejects_missing_variables() {
+        let tool = RenderAndWrite {
+            template: "listen {{port}};".to_string(),
+            remote_path: "/etc/nginx/conf.d/app.conf".to_string(),
+            remote_host: "example.com".to_string(),
+            ..Default::default()
+        };
+        let err = tool.call_tool().await.unwrap_err();
+        assert!(err.to_string().contains("variables without a value: port"));
+    }
+}
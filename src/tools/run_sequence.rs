@@ -0,0 +1,361 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+super::ssh_tool! {
+    reuse_connection = "Reuse a shared master connection to the host (SSH ControlMaster multiplexing), so that the steps after the first skip the TCP and SSH handshakes. Defaults to true; set to false to connect afresh for each step.";
+    #[mcp_tool(
+        name = "run_sequence",
+        description = "Run an ordered list of commands on a remote POSIX compatible system (Linux, BSD, macOS), one after the other, and return a result per step. \
+        With abort_on_failure (the default), the sequence stops at the first step that fails and the remaining steps are marked as skipped, \
+        so that \"do A, then B only if A succeeded\" doesn't need && in a shell command. Consecutive steps share one multiplexed SSH connection. \
+        This tool does not permit commands to be run with sudo.",
+        title = "Run Sequence"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct RunSequence {
+        /// The host to run the commands on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// The commands to run, in order. Each step is a single command with its arguments, passed literally, without shell expansion.
+        pub steps: Vec<SequenceStep>,
+        /// Stop at the first step that exits with a non-zero status, times out or fails to run, and mark the remaining steps as skipped. Defaults to true.
+        pub abort_on_failure: Option<bool>,
+        /// Timeout in seconds for each step. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Number of times to retry a step when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Compress the SSH connection (ssh -C), which can speed up commands with a lot of text output over slow or high-latency links. Defaults to false.
+        pub compression: Option<bool>,
+        /// Maximum number of bytes of stdout and of stderr to return for each step. Longer output is truncated and marked as truncated. Defaults to 1048576 (1 MiB). Set to 0 to disable truncation.
+        pub max_output_bytes: Option<u64>,
+        /// Send MCP progress notifications ("still running, 20s elapsed") every 10 seconds while the sequence runs, so that a long sequence can be told apart from a hung one. Only sent when the request carries a progress token. Defaults to false.
+        pub report_progress: Option<bool>,
+        /// Return the commands that would be run instead of running them. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+/// A command run by [`RunSequence`]
+#[derive(Debug, Default, Clone, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+pub struct SequenceStep {
+    /// The command to run. This must be a single command. Arguments must be passed in the args parameter.
+    pub cmd: String,
+    /// The arguments to pass to the command. Arguments are passed literally, without shell expansion.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl SequenceStep {
+    /// The step's command and arguments as a command line, e.g. "rm -r
+    /// '/var/cache/my app'"
+    fn command_line(&self) -> String {
+        let command = std::iter::once(&self.cmd)
+            .chain(&self.args)
+            .collect::<Vec<&String>>();
+        super::command_line(&command)
+    }
+}
+
+impl RunSequence {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+
+        if self.steps.is_empty() {
+            return Err(CallToolError::from_message("steps must not be empty"));
+        }
+        // Check every step up front, so that a rejected step doesn't leave the
+        // sequence half run
+        if self
+            .steps
+            .iter()
+            .any(|step| super::ssh::uses_privilege_escalation(&step.cmd, &step.args))
+        {
+            // sudo and doas are not permitted for this tool.
+            return Err(CallToolError::from_message(
+                "You may not run commands with sudo or doas using this tool",
+            ));
+        }
+
+        let connection = self.connection();
+        let abort_on_failure = self.abort_on_failure.unwrap_or(true);
+        let dry_run = self.dry_run.unwrap_or(false);
+        let mut results = Vec::with_capacity(self.steps.len());
+        let mut aborted = false;
+        for step in &self.steps {
+            if aborted {
+                results.push(None);
+                continue;
+            }
+            let args: Vec<&str> = step.args.iter().map(|arg| arg.as_str()).collect();
+            // Errors are kept as messages, since CallToolError isn't Send and
+            // the results are held across the next steps
+            let cmd =
+                super::ssh::ssh_command(&connection, &step.cmd, &args).map_err(|e| e.to_string());
+            let result = match cmd {
+                Ok(cmd) => super::ssh::exec_ssh(
+                    &connection,
+                    cmd,
+                    timeout_seconds,
+                    None,
+                    false,
+                    self.max_output_bytes,
+                    dry_run,
+                )
+                .await
+                .map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            };
+            aborted = abort_on_failure && !dry_run && step_failed(&result);
+            results.push(Some(result));
+        }
+
+        Ok(sequence_result(&self.steps, results))
+    }
+
+    /// Describe the commands that will run, e.g. "systemctl stop app && rm -r
+    /// /var/cache/app on prod-db". Steps are joined with && when the sequence
+    /// stops at the first failure, and with ; otherwise.
+    pub fn describe_effective_command(&self) -> String {
+        let separator = if self.abort_on_failure.unwrap_or(true) {
+            " && "
+        } else {
+            "; "
+        };
+        let commands = self
+            .steps
+            .iter()
+            .map(SequenceStep::command_line)
+            .collect::<Vec<String>>()
+            .join(separator);
+        format!(
+            "{} {}",
+            commands,
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        )
+    }
+
+    /// Describe each step on its own, e.g. "systemctl stop app on prod-db"
+    pub fn describe_steps(&self) -> Vec<String> {
+        let host = super::on_host(&self.remote_host, self.jump_host.as_deref());
+        self.steps
+            .iter()
+            .map(|step| format!("{} {}", step.command_line(), host))
+            .collect()
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            // Steps share one connection unless the caller opts out
+            reuse_connection: self.reuse_connection.or(Some(true)),
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            compression: self.compression.unwrap_or(false),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// Whether a step failed to run, timed out or exited with a non-zero status.
+/// A step killed by a signal has a null status_code and counts as failed.
+fn step_failed(result: &Result<CallToolResult, String>) -> bool {
+    let Ok(result) = result else {
+        return true;
+    };
+    let Some(content) = &result.structured_content else {
+        return true;
+    };
+    content.get("timed_out").and_then(|v| v.as_bool()) == Some(true)
+        || content.get("status_code").and_then(|v| v.as_i64()) != Some(0)
+}
+
+/// Combine the results of the steps into one, holding for each step its
+/// command line, status code and output, the error that stopped it, or that it was
+/// skipped. `succeeded` is set when every step ran and exited with status 0.
+fn sequence_result(
+    steps: &[SequenceStep],
+    results: Vec<Option<Result<CallToolResult, String>>>,
+) -> CallToolResult {
+    let mut succeeded = true;
+    let steps = steps
+        .iter()
+        .zip(results)
+        .map(|(step, result)| {
+            let mut entry = serde_json::Map::new();
+            entry.insert("step".to_string(), step.command_line().into());
+            match result {
+                None => {
+                    succeeded = false;
+                    entry.insert("skipped".to_string(), serde_json::Value::Bool(true));
+                }
+                Some(result) => {
+                    if step_failed(&result) {
+                        succeeded = false;
+                    }
+                    match result {
+                        Ok(result) => entry.extend(result.structured_content.unwrap_or_default()),
+                        Err(error) => {
+                            entry.insert("status_code".to_string(), serde_json::Value::Null);
+                            entry.insert("stdout".to_string(), "".into());
+                            entry.insert("stderr".to_string(), "".into());
+                            entry
+                                .insert("error_kind".to_string(), super::error_kind(&error).into());
+                            entry.insert("error".to_string(), error.into());
+                        }
+                    }
+                }
+            }
+            serde_json::Value::Object(entry)
+        })
+        .collect::<Vec<serde_json::Value>>();
+
+    let text = serde_json::to_string_pretty(&steps).unwrap_or_default();
+    let mut structured_content = serde_json::Map::new();
+    structured_content.insert("succeeded".to_string(), succeeded.into());
+    structured_content.insert("steps".to_string(), steps.into());
+    CallToolResult::text_content(vec![TextContent::from(text)])
+        .with_structured_content(structured_content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(cmd: &str, args: &[&str]) -> SequenceStep {
+        SequenceStep {
+            cmd: cmd.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    fn exited(status_code: i64) -> Option<Result<CallToolResult, String>> {
+        let mut content = serde_json::Map::new();
+        content.insert("status_code".to_string(), status_code.into());
+        Some(Ok(
+            CallToolResult::text_content(vec![]).with_structured_content(content)
+        ))
+    }
+
+    #[test]
+    fn test_sequence_result() {
+        let steps = [
+            step("systemctl", &["stop", "app"]),
+            step("rm", &["-r", "/var/cache/app"]),
+            step("systemctl", &["start", "app"]),
+        ];
+        let result = sequence_result(&steps, vec![exited(0), exited(1), None]);
+        let content = result.structured_content.unwrap();
+        assert_eq!(content["succeeded"], false);
+        let results = content["steps"].as_array().unwrap();
+        assert_eq!(results[0]["step"], "systemctl stop app");
+        assert_eq!(results[0]["status_code"], 0);
+        assert_eq!(results[1]["step"], "rm -r /var/cache/app");
+        assert_eq!(results[1]["status_code"], 1);
+        assert_eq!(results[2]["skipped"], true);
+        assert!(results[2].get("status_code").is_none());
+
+        let result = sequence_result(&steps[..1], vec![exited(0)]);
+        assert_eq!(result.structured_content.unwrap()["succeeded"], true);
+    }
+
+    #[test]
+    fn test_sequence_result_error() {
+        let steps = [step("uptime", &[])];
+        let error = "SSH command timed out after 30 seconds".to_string();
+        let result = sequence_result(&steps, vec![Some(Err(error))]);
+        let content = result.structured_content.unwrap();
+        assert_eq!(content["succeeded"], false);
+        assert_eq!(content["steps"][0]["status_code"], serde_json::Value::Null);
+        assert_eq!(content["steps"][0]["error_kind"], "timeout");
+    }
+
+    #[tokio::test]
+    async fn test_run_sequence_dry_run() {
+        let tool = RunSequence {
+            remote_host: "example.com".to_string(),
+            steps: vec![step("false", &[]), step("ls", &["/my dir"])],
+            dry_run: Some(true),
+            ..Default::default()
+        };
+
+        // Nothing runs, so no step fails and none is skipped
+        let result = tool.call_tool().await.unwrap();
+        let content = result.structured_content.unwrap();
+        let results = content["steps"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1]["dry_run"], true);
+        let args = results[1]["args"].as_array().unwrap();
+        assert_eq!(args[args.len() - 1], "'/my dir'");
+    }
+
+    #[tokio::test]
+    async fn test_run_sequence_rejects_sudo() {
+        let tool = RunSequence {
+            remote_host: "example.com".to_string(),
+            steps: vec![step("uptime", &[]), step("sudo", &["reboot"])],
+            ..Default::default()
+        };
+        assert!(tool.call_tool().await.is_err());
+
+        let tool = RunSequence {
+            remote_host: "example.com".to_string(),
+            ..Default::default()
+        };
+        assert!(tool.call_tool().await.is_err());
+    }
+
+    #[test]
+    fn test_connection_reuses_connection_by_default() {
+        let mut tool = RunSequence {
+            remote_host: "prod-db".to_string(),
+            ..Default::default()
+        };
+        let args = tool.connection().ssh_args().unwrap();
+        assert!(
+            args.contains(&"ControlMaster=auto".to_string()),
+            "{:?}",
+            args
+        );
+
+        tool.reuse_connection = Some(false);
+        let args = tool.connection().ssh_args().unwrap();
+        assert!(
+            !args.contains(&"ControlMaster=auto".to_string()),
+            "{:?}",
+            args
+        );
+    }
+
+    #[test]
+    fn test_describe_effective_command() {
+        let mut tool = RunSequence {
+            remote_host: "prod-db".to_string(),
+            steps: vec![
+                step("systemctl", &["stop", "app"]),
+                step("rm", &["-r", "/var/cache/my app"]),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            tool.describe_effective_command(),
+            "systemctl stop app && rm -r '/var/cache/my app' on prod-db"
+        );
+
+        tool.abort_on_failure = Some(false);
+        assert_eq!(
+            tool.describe_effective_command(),
+            "systemctl stop app; rm -r '/var/cache/my app' on prod-db"
+        );
+        assert_eq!(
+            tool.describe_steps(),
+            [
+                "systemctl stop app on prod-db",
+                "rm -r '/var/cache/my app' on prod-db"
+            ]
+        );
+    }
+}
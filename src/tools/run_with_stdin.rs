@@ -0,0 +1,142 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, schema_utils::CallToolError},
+};
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "run_with_stdin",
+        description = "Run a command on a remote POSIX compatible system (Linux, BSD, macOS) with the given content written to its standard input, and return the output. \
+        Use this to pipe content to a command, e.g. tee /etc/app.conf to write a file, sh -s to run a script, or base64 -d to decode binary data. \
+        This tool does not permit commands to be run with sudo.",
+        title = "Run With Stdin"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct RunWithStdin {
+        /// The host to run the command on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// The command to run. This must be a single command. Arguments must be passed in the args parameter.
+        pub cmd: String,
+        /// The arguments to pass to the command. Arguments are passed literally, without shell expansion.
+        pub args: Vec<String>,
+        /// The content to write to the command's standard input. Standard input is closed after it is written.
+        pub stdin: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Absolute path of the directory the remote command creates its temporary files in, exported to it as TMPDIR. Useful when /tmp is full or mounted noexec. Defaults to the remote host's own, normally /tmp.
+        pub remote_tmp_dir: Option<String>,
+        /// Compress the SSH connection (ssh -C), which can speed up commands with a lot of text output over slow or high-latency links. Defaults to false.
+        pub compression: Option<bool>,
+        /// Maximum number of bytes of stdout and of stderr to return. Longer output is truncated and marked as truncated. Defaults to 1048576 (1 MiB). Set to 0 to disable truncation.
+        pub max_output_bytes: Option<u64>,
+        /// Send MCP progress notifications ("still running, 20s elapsed") every 10 seconds while the command runs, so that a long command can be told apart from a hung one. Only sent when the request carries a progress token. Defaults to false.
+        pub report_progress: Option<bool>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+impl RunWithStdin {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+
+        if super::ssh::uses_privilege_escalation(&self.cmd, &self.args) {
+            // sudo and doas are not permitted for this tool.
+            return Err(CallToolError::from_message(
+                "You may not run commands with sudo or doas using this tool",
+            ));
+        }
+
+        let connection = self.connection();
+        let args: Vec<&str> = self.args.iter().map(|arg| arg.as_str()).collect();
+        let mut cmd = super::ssh::ssh_command(&connection, &self.cmd, &args)?;
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(
+                &cmd,
+                &self.remote_host,
+                Some(&self.stdin),
+            ));
+        }
+
+        let (output, timing) =
+            super::Timing::measure(super::ssh::run_ssh_with_stdin_until_timeout(
+                &mut cmd,
+                self.stdin.as_bytes(),
+                timeout_seconds,
+            ))
+            .await;
+        let output = output?;
+        // A command that timed out is returned with its output so far
+        let mut result =
+            super::result_from_output(&output.output, self.max_output_bytes, Some(timing));
+        if output.timed_out {
+            result
+                .structured_content
+                .get_or_insert_default()
+                .insert("timed_out".to_string(), serde_json::Value::Bool(true));
+        }
+        Ok(result)
+    }
+
+    /// Describe the command that will run, e.g. "tee /etc/app.conf on
+    /// prod-db, with 12 bytes of standard input"
+    pub fn describe_effective_command(&self) -> String {
+        let command = std::iter::once(&self.cmd)
+            .chain(&self.args)
+            .collect::<Vec<&String>>();
+        format!(
+            "{} {}, with {} bytes of standard input",
+            super::command_line(&command),
+            super::on_host(&self.remote_host, self.jump_host.as_deref()),
+            self.stdin.len()
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            compression: self.compression.unwrap_or(false),
+            remote_tmp_dir: self.remote_tmp_dir.clone(),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_with_stdin_dry_run() {
+        let tool = RunWithStdin {
+            remote_host: "example.com".to_string(),
+            cmd: "tee".to_string(),
+            args: vec!["/etc/my app.conf".to_string()],
+            stdin: "key = value\n".to_string(),
+            dry_run: Some(true),
+            ..Default::default()
+        };
+
+        let result = tool.call_tool().await.unwrap();
+        let content = result.structured_content.unwrap();
+        assert_eq!(content["dry_run"], true);
+        assert_eq!(content["stdin"], "key = value\n");
+        let args = content["args"].as_array().unwrap();
+        assert_eq!(args[args.len() - 2], "tee");
+        assert_eq!(args[args.len() - 1], "'/etc/my app.conf'");
+    }
+
+    #[tokio::test]
+    async fn test_run_with_stdin_rejects_sudo() {
+        let tool = RunWithStdin {
+            remote_host: "example.com".to_string(),
+            cmd: "sudo".to_string(),
+            args: vec!["tee".to_string(), "/etc/app.conf".to_string()],
+            stdin: "key = value\n".to_string(),
+            ..Default::default()
+        };
+        assert!(tool.call_tool().await.is_err());
+    }
+}
@@ -1,210 +1,834 @@
+use super::{CommonConnection, SshConnectionParams, TimedOutput};
 use rust_mcp_sdk::{
     macros::{JsonSchema, mcp_tool},
     schema::{CallToolResult, TextContent, schema_utils::CallToolError},
 };
-use tokio::{
-    process::Command,
-    time::{Duration, timeout},
-};
+use std::process::Output;
+use std::sync::Arc;
+use tokio::{process::Command, sync::Semaphore, time::Duration};
+use tracing::Instrument;
+
+/// Number of hosts in remote_hosts a command runs on at once when the call
+/// doesn't set max_concurrency.
+const DEFAULT_MAX_CONCURRENCY: u64 = 10;
 
-#[mcp_tool(
-    name = "run_ssh_command",
-    description = "Run a command on a remote POSIX compatible system (Linux, BSD, macOS) system and return the output. This tool does not permit commands to be run with sudo.",
-    title = "Run SSH Command"
-)]
-#[derive(Debug, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
-pub struct RunSSHCommand {
-    /// The host to run the command on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
-    pub remote_host: String,
-    /// The command to run. This must be a single command. Arguments must be passed in the args parameter.
-    pub cmd: String,
-    /// The arguments to pass to the command.
-    pub args: Vec<String>,
-    /// Timeout in seconds for the command execution. Defaults to 30 seconds. Set to 0 to disable timeout.
-    pub timeout_seconds: Option<u64>,
-    /// Additional options to pass to the ssh command. Each option should be a key-value pair separated by an equal sign (=). The options are passed to the ssh command using the -o flag.
-    pub options: Option<Vec<String>>,
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "run_ssh_command",
+        description = "Run a command on a remote POSIX compatible system (Linux, BSD, macOS) system and return the output. This tool does not permit commands to be run with sudo. \
+        To run the same command on several hosts at once, pass remote_hosts instead of remote_host.",
+        title = "Run SSH Command"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct RunSSHCommand {
+        /// The host to run the command on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address. Required unless remote_hosts is set.
+        pub remote_host: Option<String>,
+        /// Hosts to run the command on concurrently, instead of remote_host. Returns a result per host, in the order given; a failure on one host does not stop the others.
+        pub remote_hosts: Option<Vec<String>>,
+        /// Maximum number of hosts in remote_hosts to run the command on at once. Defaults to 10.
+        pub max_concurrency: Option<u64>,
+        /// The command to run. This must be a single command. Arguments must be passed in the args parameter.
+        pub cmd: String,
+        /// The arguments to pass to the command. Arguments are passed literally, without shell expansion; to use pipes, redirection or variables, run sh with -c and a script.
+        pub args: Vec<String>,
+        /// Shell to run the command with on the remote host, e.g. /bin/bash. The command is run as <shell> -c '<command>' so that it behaves the same whatever the account's login shell. Defaults to running the command directly with the login shell.
+        pub remote_shell: Option<String>,
+        /// Send the command's stderr to stdout (2>&1), so that stdout holds both streams interleaved in the order they were written and stderr is empty. Useful when the order of errors relative to output matters. Defaults to false, returning stdout and stderr separately.
+        pub merge_stderr: Option<bool>,
+        /// Environment variables to set for the command on the remote host, as KEY=VALUE pairs, e.g. DEBIAN_FRONTEND=noninteractive or LANG=C.UTF-8. The command is run with env KEY=VALUE ... cmd args.
+        pub env: Option<Vec<String>>,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Stop the command once it has printed nothing on stdout or stderr for this many seconds, however long it has run in total, returning its output so far with timed_out and idle_timed_out set. Suits long commands that always print progress, such as apt upgrade, with a long or disabled timeout_seconds. Defaults to no idle timeout.
+        pub idle_timeout_seconds: Option<u64>,
+        /// Number of times to retry when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Compress the SSH connection (ssh -C), which can speed up commands with a lot of text output over slow or high-latency links. Defaults to false.
+        pub compression: Option<bool>,
+        /// Allocate a pseudo-terminal for the command (ssh -tt), for commands that fail or behave differently without one, such as installers that detect non-interactive sessions. The terminal merges stderr into stdout, turns line endings into \r\n and lets the command print control characters such as colors and cursor movement into the output. Ignored with use_mosh, which always runs the command in a terminal. Defaults to false.
+        pub allocate_tty: Option<bool>,
+        /// Run the command through mosh instead of plain ssh, so that it survives connectivity blips and IP address changes on unreliable links. mosh must be installed on both machines and UDP ports 60000-61000 reachable on the remote host. ssh only starts the session. Password authentication is not supported. Defaults to false.
+        pub use_mosh: Option<bool>,
+        /// Stream output as the command runs instead of buffering it until exit. Output is logged incrementally and the full output is still returned when the command completes. Useful for long-running commands. Defaults to false.
+        pub stream: Option<bool>,
+        /// Maximum number of bytes of stdout and of stderr to return. Longer output is truncated and marked as truncated. Defaults to 1048576 (1 MiB). Set to 0 to disable truncation.
+        pub max_output_bytes: Option<u64>,
+        /// Parse the command's stdout into JSON, returned as parsed alongside the raw output, to save re-reading tables. One of df, ps or ss; the table is read from its header, so any options of the command are fine. Output that can't be parsed, or another value, returns the raw output with a parse_warning.
+        pub parse_as: Option<String>,
+        /// Send MCP progress notifications ("still running, 20s elapsed") every 10 seconds while the command runs, so that a long command can be told apart from a hung one. Only sent when the request carries a progress token. Defaults to false.
+        pub report_progress: Option<bool>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
 }
 
 impl RunSSHCommand {
-    #[tracing::instrument]
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
     pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
-        let timeout_seconds = self.timeout_seconds.unwrap_or(30);
-        let options_vec: Option<Vec<&str>> = self
-            .options
-            .as_ref()
-            .map(|v| v.iter().map(String::as_str).collect());
-
-        if self.cmd.contains("sudo") || self.args.iter().any(|arg| arg.contains("sudo")) {
-            // sudo is not permitted for this tool.
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+
+        if uses_privilege_escalation(&self.cmd, &self.args) {
+            // sudo and doas are not permitted for this tool.
             return Err(CallToolError::from_message(
-                "You may not run commands with sudo using this tool",
+                "You may not run commands with sudo or doas using this tool",
             ));
         }
 
-        exec_ssh(
-            &self.remote_host,
-            &self.cmd,
-            &self
-                .args
-                .iter()
-                .map(|arg| arg.as_str())
-                .collect::<Vec<&str>>(),
-            timeout_seconds,
-            options_vec.as_deref(),
+        let command = self.remote_command()?;
+
+        let remote_hosts = match (self.remote_host.as_deref(), self.remote_hosts.as_deref()) {
+            (Some(remote_host), None) => {
+                let connection = self.connection(remote_host);
+                let cmd = self.command(&connection, &command)?;
+                return exec_ssh(
+                    &connection,
+                    cmd,
+                    timeout_seconds,
+                    self.idle_timeout_seconds,
+                    self.stream.unwrap_or(false),
+                    self.max_output_bytes,
+                    self.dry_run.unwrap_or(false),
+                )
+                .await
+                .map(|result| with_parsed_output(result, self.parse_as.as_deref()));
+            }
+            (Some(_), Some(_)) => {
+                return Err(CallToolError::from_message(
+                    "remote_host and remote_hosts cannot be used together",
+                ));
+            }
+            (None, None) => {
+                return Err(CallToolError::from_message(
+                    "Either remote_host or remote_hosts is required",
+                ));
+            }
+            (None, Some(remote_hosts)) => remote_hosts,
+        };
+        if remote_hosts.is_empty() {
+            return Err(CallToolError::from_message(
+                "remote_hosts must not be empty",
+            ));
+        }
+        let max_concurrency = self.max_concurrency.unwrap_or(DEFAULT_MAX_CONCURRENCY);
+        if max_concurrency == 0 {
+            return Err(CallToolError::from_message(
+                "max_concurrency must be greater than 0",
+            ));
+        }
+
+        // More permits than hosts would never be used
+        let max_concurrency = usize::try_from(max_concurrency)
+            .unwrap_or(usize::MAX)
+            .min(remote_hosts.len());
+
+        let results = fan_out(remote_hosts, max_concurrency, |remote_host| {
+            let connection = self.connection(&remote_host);
+            // Errors are passed on as messages, since CallToolError isn't Send
+            let cmd = self
+                .command(&connection, &command)
+                .map_err(|e| e.to_string());
+            let (idle_timeout_seconds, stream, max_output_bytes, dry_run, parse_as) = (
+                self.idle_timeout_seconds,
+                self.stream.unwrap_or(false),
+                self.max_output_bytes,
+                self.dry_run.unwrap_or(false),
+                self.parse_as.clone(),
+            );
+            async move {
+                exec_ssh(
+                    &connection,
+                    cmd?,
+                    timeout_seconds,
+                    idle_timeout_seconds,
+                    stream,
+                    max_output_bytes,
+                    dry_run,
+                )
+                .await
+                .map(|result| with_parsed_output(result, parse_as.as_deref()))
+                .map_err(|e| e.to_string())
+            }
+        })
+        .await;
+        Ok(fan_out_result(results))
+    }
+
+    /// Build the ssh or, with use_mosh, mosh command running `command` on the
+    /// remote host
+    fn command(
+        &self,
+        connection: &SshConnectionParams,
+        command: &[String],
+    ) -> Result<Command, CallToolError> {
+        let args: Vec<&str> = command[1..].iter().map(|arg| arg.as_str()).collect();
+        if self.use_mosh.unwrap_or(false) {
+            mosh_command(connection, &command[0], &args)
+        } else {
+            ssh_command(connection, &command[0], &args)
+        }
+    }
+
+    /// The command run on the remote host, as words
+    fn remote_command(&self) -> Result<Vec<String>, CallToolError> {
+        let command = env_prefix(self.env.as_deref())?
+            .into_iter()
+            .chain(std::iter::once(self.cmd.clone()))
+            .chain(self.args.iter().cloned())
+            .collect::<Vec<String>>();
+        let command = with_remote_shell(self.remote_shell.as_deref(), command)?;
+        Ok(with_merged_stderr(
+            self.merge_stderr.unwrap_or(false),
+            command,
+        ))
+    }
+
+    /// Describe the command that will run, e.g. "df -h / on prod-db" or
+    /// "df -h / on web1, web2"
+    pub fn describe_effective_command(&self) -> String {
+        let command = self.remote_command().unwrap_or_else(|_| {
+            std::iter::once(self.cmd.clone())
+                .chain(self.args.iter().cloned())
+                .collect()
+        });
+        let remote_hosts = match (&self.remote_host, &self.remote_hosts) {
+            (Some(remote_host), _) => remote_host.clone(),
+            (None, Some(remote_hosts)) => remote_hosts.join(", "),
+            (None, None) => String::new(),
+        };
+        format!(
+            "{} {}{}",
+            super::command_line(&command),
+            super::on_host(&remote_hosts, self.jump_host.as_deref()),
+            super::using_ssh_binary(self.ssh_binary.as_deref())
         )
-        .await
+    }
+
+    fn connection(&self, remote_host: &str) -> SshConnectionParams {
+        SshConnectionParams {
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            compression: self.compression.unwrap_or(false),
+            allocate_tty: self.allocate_tty.unwrap_or(false),
+            ..self.common_connection(remote_host)
+        }
     }
 }
 
-#[mcp_tool(
-    name = "run_ssh_sudo_command",
-    description = "Run a command on a remote POSIX compatible system (Linux, \
-    BSD, macOS) system and return the output. This tool explicitly runs \
-    commands with sudo.",
-    title = "Run SSH Sudo Command"
-)]
-#[derive(Debug, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
-pub struct RunSSHSudoCommand {
-    /// The host to run the command on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
-    pub remote_host: String,
-    /// The command to run. This must be a single command. Arguments must be passed in the args parameter.
-    pub cmd: String,
-    /// The arguments to pass to the command.
-    pub args: Vec<String>,
-    /// Timeout in seconds for the command execution. Defaults to 30 seconds. Set to 0 to disable timeout.
-    pub timeout_seconds: Option<u64>,
-    /// Additional options to pass to the ssh command. Each option should be a key-value pair separated by an equal sign (=). The options are passed to the ssh command using the -o flag.
-    pub options: Option<Vec<String>>,
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "run_ssh_sudo_command",
+        description = "Run a command on a remote POSIX compatible system (Linux, \
+        BSD, macOS) system and return the output. This tool explicitly runs \
+        commands with sudo.",
+        title = "Run SSH Sudo Command"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct RunSSHSudoCommand {
+        /// The host to run the command on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// The command to run. This must be a single command. Arguments must be passed in the args parameter.
+        pub cmd: String,
+        /// The arguments to pass to the command. Arguments are passed literally, without shell expansion; to use pipes, redirection or variables, run sh with -c and a script.
+        pub args: Vec<String>,
+        /// User to run the command as, such as postgres, with sudo -u <user>. Letters, digits, underscores, dots and dashes, starting with a letter or underscore. Defaults to root.
+        pub run_as: Option<String>,
+        /// Shell to run the command with on the remote host, e.g. /bin/bash. The command is run as <shell> -c '<command>' so that it behaves the same whatever the account's login shell. Defaults to running the command directly with the login shell.
+        pub remote_shell: Option<String>,
+        /// Send the command's stderr to stdout (2>&1), so that stdout holds both streams interleaved in the order they were written and stderr is empty. Useful when the order of errors relative to output matters. Defaults to false, returning stdout and stderr separately.
+        pub merge_stderr: Option<bool>,
+        /// Environment variables to set for the command on the remote host, as KEY=VALUE pairs, e.g. DEBIAN_FRONTEND=noninteractive or LANG=C.UTF-8. The command is run with env KEY=VALUE ... cmd args.
+        pub env: Option<Vec<String>>,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Stop the command once it has printed nothing on stdout or stderr for this many seconds, however long it has run in total, returning its output so far with timed_out and idle_timed_out set. Suits long commands that always print progress, such as apt upgrade, with a long or disabled timeout_seconds. Defaults to no idle timeout.
+        pub idle_timeout_seconds: Option<u64>,
+        /// Number of times to retry when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Compress the SSH connection (ssh -C), which can speed up commands with a lot of text output over slow or high-latency links. Defaults to false.
+        pub compression: Option<bool>,
+        /// Allocate a pseudo-terminal for the command (ssh -tt), for commands that fail or behave differently without one, such as installers that detect non-interactive sessions. The terminal merges stderr into stdout, turns line endings into \r\n and lets the command print control characters such as colors and cursor movement into the output. Defaults to false.
+        pub allocate_tty: Option<bool>,
+        /// Stream output as the command runs instead of buffering it until exit. Output is logged incrementally and the full output is still returned when the command completes. Useful for long-running commands. Defaults to false.
+        pub stream: Option<bool>,
+        /// Maximum number of bytes of stdout and of stderr to return. Longer output is truncated and marked as truncated. Defaults to 1048576 (1 MiB). Set to 0 to disable truncation.
+        pub max_output_bytes: Option<u64>,
+        /// Send MCP progress notifications ("still running, 20s elapsed") every 10 seconds while the command runs, so that a long command can be told apart from a hung one. Only sent when the request carries a progress token. Defaults to false.
+        pub report_progress: Option<bool>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
 }
 
 impl RunSSHSudoCommand {
-    #[tracing::instrument]
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
     pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
         tracing::trace!("Calling run_ssh_sudo_command");
-        let timeout_seconds = self.timeout_seconds.unwrap_or(30);
-        let options_vec: Option<Vec<&str>> = self
-            .options
-            .as_ref()
-            .map(|v| v.iter().map(String::as_str).collect());
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+
+        let command = self.remote_command()?;
 
+        let connection = self.connection();
+        let args: Vec<&str> = command[1..].iter().map(|arg| arg.as_str()).collect();
+        let cmd = ssh_command(&connection, &command[0], &args)?;
         exec_ssh(
-            &self.remote_host,
-            "sudo",
-            std::iter::once(self.cmd.as_str())
-                .chain(self.args.iter().map(|arg| arg.as_str()))
-                .collect::<Vec<&str>>()
-                .as_slice(),
+            &connection,
+            cmd,
             timeout_seconds,
-            options_vec.as_deref(),
+            self.idle_timeout_seconds,
+            self.stream.unwrap_or(false),
+            self.max_output_bytes,
+            self.dry_run.unwrap_or(false),
         )
         .await
     }
+
+    /// The command run on the remote host, as words
+    fn remote_command(&self) -> Result<Vec<String>, CallToolError> {
+        // sudo resets the environment, so env runs under sudo
+        let command = std::iter::once("sudo".to_string())
+            .chain(run_as_flag(self.run_as.as_deref())?)
+            .chain(env_prefix(self.env.as_deref())?)
+            .chain(std::iter::once(self.cmd.clone()))
+            .chain(self.args.iter().cloned())
+            .collect::<Vec<String>>();
+        let command = with_remote_shell(self.remote_shell.as_deref(), command)?;
+        Ok(with_merged_stderr(
+            self.merge_stderr.unwrap_or(false),
+            command,
+        ))
+    }
+
+    /// Describe the command that will run, e.g. "sudo apt remove --purge
+    /// nginx on prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        let command = self.remote_command().unwrap_or_else(|_| {
+            ["sudo".to_string(), self.cmd.clone()]
+                .into_iter()
+                .chain(self.args.iter().cloned())
+                .collect()
+        });
+        format!(
+            "{} {}{}",
+            super::command_line(&command),
+            super::on_host(&self.remote_host, self.jump_host.as_deref()),
+            super::using_ssh_binary(self.ssh_binary.as_deref())
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            compression: self.compression.unwrap_or(false),
+            allocate_tty: self.allocate_tty.unwrap_or(false),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
 }
 
-/// Run a command on a remote POSIX compatible system (Linux, BSD, macOS) system
-/// via SSH.
-#[tracing::instrument]
-async fn exec_ssh(
-    host: &str,
-    command: &str,
-    args: &[&str],
-    timeout_seconds: u64,
-    options: Option<&[&str]>,
-) -> Result<CallToolResult, CallToolError> {
-    tracing::trace!("Executing SSH command");
-    // Get multiplexing options
-    let multiplexing_opts = super::get_multiplexing_options().map_err(|e| {
-        CallToolError::from_message(format!("Failed to get multiplexing options: {}", e))
-    })?;
+/// Commands that run another command with elevated privileges.
+const PRIVILEGE_ESCALATION_COMMANDS: [&str; 2] = ["sudo", "doas"];
 
-    // Build SSH command with multiplexing enabled
-    let mut cmd = Command::new("ssh");
-    cmd.arg(host);
+/// Check whether a command escalates privileges, either because the command
+/// itself is sudo or doas (by basename) or because one of its arguments is.
+pub(crate) fn uses_privilege_escalation(cmd: &str, args: &[String]) -> bool {
+    let basename = cmd.rsplit('/').next().unwrap_or(cmd);
+    PRIVILEGE_ESCALATION_COMMANDS.contains(&basename)
+        || args
+            .iter()
+            .any(|arg| PRIVILEGE_ESCALATION_COMMANDS.contains(&arg.as_str()))
+}
 
-    // Always append StrictHostKeyChecking=yes to ensure SSH fails instead of prompting interactively
-    cmd.args(["-o", "StrictHostKeyChecking=yes"]);
+/// Build the `env KEY=VALUE ...` words that set `env` for a remote command,
+/// or nothing if no variables are given.
+fn env_prefix(env: Option<&[String]>) -> Result<Vec<String>, CallToolError> {
+    let Some(env) = env.filter(|env| !env.is_empty()) else {
+        return Ok(vec![]);
+    };
 
-    // Add multiplexing options first to ensure they take precedence
-    for opt in &multiplexing_opts {
-        cmd.args(["-o", opt]);
+    let mut prefix = vec!["env".to_string()];
+    for var in env {
+        let (key, value) = var.split_once('=').ok_or_else(|| {
+            CallToolError::from_message(format!(
+                "Invalid environment variable {:?}: expected KEY=VALUE",
+                var
+            ))
+        })?;
+        let valid_key = key
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_key {
+            return Err(CallToolError::from_message(format!(
+                "Invalid environment variable name: {:?}",
+                key
+            )));
+        }
+        prefix.push(format!("{}={}", key, value));
     }
+    Ok(prefix)
+}
 
-    // Add user-provided options last
-    if let Some(opts) = options {
-        for opt in opts {
-            cmd.args(["-o", opt]);
-        }
+/// The sudo flag running the command as `user`, if set. The user must be a
+/// plain user name, so that it can't be taken for another flag or for sudo's
+/// `#uid` syntax.
+fn run_as_flag(user: Option<&str>) -> Result<Vec<String>, CallToolError> {
+    let Some(user) = user else {
+        return Ok(Vec::new());
+    };
+    let valid = user.len() <= 32
+        && user
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && user
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+    if !valid {
+        return Err(CallToolError::from_message(format!(
+            "Invalid run_as user: {:?}",
+            user
+        )));
     }
+    Ok(vec!["-u".to_string(), user.to_string()])
+}
 
-    // Add command and arguments
-    cmd.arg(command).args(args);
+/// Wrap a command to run as `<shell> -c '<command>'`, quoting each word of the
+/// command so the shell runs it literally. Without a shell, the command is
+/// returned unchanged.
+fn with_remote_shell(
+    shell: Option<&str>,
+    command: Vec<String>,
+) -> Result<Vec<String>, CallToolError> {
+    let Some(shell) = shell else {
+        return Ok(command);
+    };
+    if shell.is_empty() || shell.starts_with('-') || shell.chars().any(char::is_whitespace) {
+        return Err(CallToolError::from_message(format!(
+            "Invalid remote shell: {:?}",
+            shell
+        )));
+    }
 
-    let command_future = cmd.output();
+    let command_line = command
+        .iter()
+        .map(|word| super::shell_quote(word))
+        .collect::<Vec<String>>()
+        .join(" ");
+    Ok(vec![shell.to_string(), "-c".to_string(), command_line])
+}
 
-    let result = if timeout_seconds == 0 {
-        // No timeout - run indefinitely
-        command_future.await
-    } else {
-        // Apply timeout
-        let timeout_duration = Duration::from_secs(timeout_seconds);
-        match timeout(timeout_duration, command_future).await {
+/// Wrap a command to run as `sh -c 'exec "$@" 2>&1' sh <command>` when
+/// `merge` is set, so that its stderr goes to stdout. The words of the command
+/// are passed as arguments to sh, so they are still run literally.
+fn with_merged_stderr(merge: bool, command: Vec<String>) -> Vec<String> {
+    if !merge {
+        return command;
+    }
+    ["sh", "-c", "exec \"$@\" 2>&1", "sh"]
+        .into_iter()
+        .map(str::to_string)
+        .chain(command)
+        .collect()
+}
+
+/// Add the stdout of a command's result parsed as `parse_as`, if set, as
+/// `parsed`, or a `parse_warning` saying why it couldn't be parsed. Dry runs
+/// and binary output, which have no stdout text, are left alone.
+fn with_parsed_output(mut result: CallToolResult, parse_as: Option<&str>) -> CallToolResult {
+    let Some(parse_as) = parse_as else {
+        return result;
+    };
+    let Some(structured_content) = result.structured_content.as_mut() else {
+        return result;
+    };
+    let Some(stdout) = structured_content.get("stdout").and_then(|v| v.as_str()) else {
+        return result;
+    };
+    match super::parse_output::parse(parse_as, stdout) {
+        Ok(parsed) => {
+            structured_content.insert("parsed".to_string(), parsed);
+        }
+        Err(warning) => {
+            tracing::warn!("{}", warning);
+            structured_content.insert("parse_warning".to_string(), warning.into());
+        }
+    }
+    result
+}
+
+/// Run `run` for each host, on at most `max_concurrency` hosts at a time,
+/// returning each host's result in the order the hosts were given, however
+/// long each took. Errors are returned as messages, which can cross tasks.
+async fn fan_out<F, Fut>(
+    remote_hosts: &[String],
+    max_concurrency: usize,
+    run: F,
+) -> Vec<(String, Result<CallToolResult, String>)>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<CallToolResult, String>> + Send + 'static,
+{
+    let semaphore = Arc::new(Semaphore::new(max_concurrency));
+    let tasks = remote_hosts
+        .iter()
+        .map(|remote_host| {
+            let semaphore = semaphore.clone();
+            let call = run(remote_host.clone());
+            tokio::spawn(
+                async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    call.await
+                }
+                .in_current_span(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for (remote_host, task) in remote_hosts.iter().zip(tasks) {
+        let result = match task.await {
             Ok(result) => result,
-            Err(_) => {
-                return Err(CallToolError::from_message(format!(
-                    "SSH command timed out after {} seconds",
-                    timeout_seconds
-                )));
+            Err(e) => Err(format!("Failed to run command: {}", e)),
+        };
+        results.push((remote_host.clone(), result));
+    }
+    results
+}
+
+/// Combine the results of a command run on several hosts into one, holding
+/// for each host its status code and output, or the error that stopped it.
+/// `succeeded` is set when the command exited with status 0 on every host,
+/// and `failed_hosts` counts the hosts where it didn't.
+fn fan_out_result(results: Vec<(String, Result<CallToolResult, String>)>) -> CallToolResult {
+    let mut failed_hosts = 0;
+    let hosts = results
+        .into_iter()
+        .map(|(remote_host, result)| {
+            let mut host = serde_json::Map::new();
+            host.insert("host".to_string(), remote_host.into());
+            match result {
+                Ok(result) => {
+                    if !crate::audit::succeeded(&result) {
+                        failed_hosts += 1;
+                    }
+                    host.extend(result.structured_content.unwrap_or_default())
+                }
+                Err(error) => {
+                    failed_hosts += 1;
+                    host.insert("status_code".to_string(), serde_json::Value::Null);
+                    host.insert("stdout".to_string(), "".into());
+                    host.insert("stderr".to_string(), "".into());
+                    host.insert("error_kind".to_string(), super::error_kind(&error).into());
+                    host.insert("error".to_string(), error.into());
+                }
             }
+            serde_json::Value::Object(host)
+        })
+        .collect::<Vec<serde_json::Value>>();
+
+    let text = serde_json::to_string_pretty(&hosts).unwrap_or_default();
+    let mut structured_content = serde_json::Map::new();
+    structured_content.insert("succeeded".to_string(), (failed_hosts == 0).into());
+    structured_content.insert("failed_hosts".to_string(), failed_hosts.into());
+    structured_content.insert("results".to_string(), hosts.into());
+    CallToolResult::text_content(vec![TextContent::from(text)])
+        .with_structured_content(structured_content)
+}
+
+/// Run a command on a remote POSIX compatible system (Linux, BSD, macOS) system
+/// via SSH, with `cmd` built by [`ssh_command`] or [`mosh_command`]. The
+/// command isn't recorded in the span since it carries SSHPASS when a password
+/// is set.
+#[tracing::instrument(skip_all, fields(remote_host = %connection.remote_host))]
+pub(crate) async fn exec_ssh(
+    connection: &SshConnectionParams,
+    mut cmd: Command,
+    timeout_seconds: u64,
+    idle_timeout_seconds: Option<u64>,
+    stream: bool,
+    max_output_bytes: Option<u64>,
+    dry_run: bool,
+) -> Result<CallToolResult, CallToolError> {
+    tracing::trace!(command = %super::display_command(&cmd), "Executing SSH command");
+
+    if dry_run {
+        return Ok(super::dry_run_result(&cmd, &connection.remote_host, None));
+    }
+
+    let (output, timing) = super::Timing::measure(run_ssh_until_timeout(
+        connection,
+        &mut cmd,
+        timeout_seconds,
+        idle_timeout_seconds,
+        stream,
+    ))
+    .await;
+    let output = output?;
+    let mut result = super::result_from_output(&output.output, max_output_bytes, Some(timing));
+    if output.timed_out {
+        let structured_content = result.structured_content.get_or_insert_default();
+        structured_content.insert("timed_out".to_string(), serde_json::Value::Bool(true));
+        if output.idle {
+            structured_content.insert("idle_timed_out".to_string(), serde_json::Value::Bool(true));
         }
-    };
+    }
+    Ok(result)
+}
 
-    match result {
-        Ok(output) => {
-            // The command executed successfully. This doesn't mean it
-            // succeeded, so output is returned as a tool call result.
-            // If the status code is 255 or None, an error is returned.
-            let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-            let status_code = output.status.code();
-
-            match status_code {
-                Some(255) => Err(CallToolError::from_message(format!(
-                    "SSH command failed unexpectedly: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                ))),
-                None => Err(CallToolError::from_message(
-                    "SSH command unexpectedly terminated",
-                )),
-                _ => Ok(
-                    CallToolResult::text_content(vec![TextContent::from(stdout.clone())])
-                        .with_structured_content(super::map_from_output(
-                            stdout,
-                            stderr,
-                            status_code,
-                        )),
-                ),
-            }
+/// Build the ssh command that runs `command` with `args` on the remote host.
+///
+/// ssh joins the command and arguments with spaces into a command line that
+/// the remote shell parses again, so each is shell-quoted to be delivered
+/// literally, without word splitting or expansion.
+pub(crate) fn ssh_command(
+    connection: &SshConnectionParams,
+    command: &str,
+    args: &[&str],
+) -> Result<Command, CallToolError> {
+    // Build SSH options (host key checking, multiplexing, jump host and
+    // user-provided options)
+    let ssh_args = connection.ssh_args().map_err(super::ssh_options_error)?;
+
+    let ssh_program = connection
+        .ssh_program()
+        .map_err(|e| CallToolError::from_message(e.to_string()))?;
+    let mut cmd = connection
+        .command(&ssh_program)
+        .map_err(|e| CallToolError::from_message(e.to_string()))?;
+    let remote_env = connection
+        .remote_env()
+        .map_err(|e| CallToolError::from_message(e.to_string()))?;
+    let destination =
+        super::ssh_destination(&connection.remote_host).map_err(super::ssh_options_error)?;
+    cmd.args(&ssh_args);
+    if connection.allocate_tty {
+        // A single -t is ignored since stdin isn't a terminal
+        cmd.arg("-tt");
+    }
+    cmd.arg("--").arg(destination);
+
+    // Add command and arguments
+    cmd.args(remote_env.iter().map(|word| super::shell_quote(word)))
+        .arg(super::shell_quote(command))
+        .args(args.iter().map(|arg| super::shell_quote(arg)));
+    Ok(cmd)
+}
+
+/// Build the mosh command that runs `command` with `args` on the remote host.
+///
+/// mosh starts its session over ssh, given the same arguments as
+/// [`ssh_command`], then talks to mosh-server over UDP. mosh-server executes
+/// the command directly rather than through a shell, so unlike with ssh the
+/// command and arguments aren't quoted. mosh has no way to pass the password
+/// to sshpass, so password authentication is rejected.
+pub(crate) fn mosh_command(
+    connection: &SshConnectionParams,
+    command: &str,
+    args: &[&str],
+) -> Result<Command, CallToolError> {
+    if connection.password.is_some() {
+        return Err(CallToolError::from_message(
+            "use_mosh does not support password authentication; use a private key or ssh-agent",
+        ));
+    }
+    let ssh_args = connection.ssh_args().map_err(super::ssh_options_error)?;
+
+    let ssh_program = connection
+        .ssh_program()
+        .map_err(|e| CallToolError::from_message(e.to_string()))?;
+
+    // mosh splits --ssh into words as a shell would
+    let ssh = std::iter::once(super::shell_quote(&ssh_program))
+        .chain(ssh_args.iter().map(|arg| super::shell_quote(arg)))
+        .collect::<Vec<String>>()
+        .join(" ");
+    let remote_env = connection
+        .remote_env()
+        .map_err(|e| CallToolError::from_message(e.to_string()))?;
+    let destination =
+        super::ssh_destination(&connection.remote_host).map_err(super::ssh_options_error)?;
+
+    let mut cmd = Command::new("mosh");
+    cmd.arg(format!("--ssh={}", ssh))
+        .arg("--")
+        .arg(destination)
+        .args(&remote_env)
+        .arg(command)
+        .args(args);
+    Ok(cmd)
+}
+
+/// Run an ssh command, returning its output. Failing to connect (exit status
+/// 255), being terminated by a signal and timing out are errors; any other
+/// exit status is the remote command's and is returned with the output.
+///
+/// Connection failures and failures to spawn ssh are retried up to
+/// `connection.retries` times with exponential backoff. Timeouts and commands
+/// that ran and exited with a non-zero status are never retried.
+pub(crate) async fn run_ssh(
+    connection: &SshConnectionParams,
+    cmd: &mut Command,
+    timeout_seconds: u64,
+    stream: bool,
+) -> Result<Output, CallToolError> {
+    let output = run_ssh_until_timeout(connection, cmd, timeout_seconds, None, stream).await?;
+    if output.timed_out {
+        return Err(CallToolError::from_message(format!(
+            "SSH command timed out after {} seconds",
+            timeout_seconds
+        )));
+    }
+    Ok(output.output)
+}
+
+/// Run an ssh command like [`run_ssh`], except that a command that times out,
+/// or prints nothing for `idle_timeout_seconds`, is stopped and returned with
+/// the output it printed until then.
+async fn run_ssh_until_timeout(
+    connection: &SshConnectionParams,
+    cmd: &mut Command,
+    timeout_seconds: u64,
+    idle_timeout_seconds: Option<u64>,
+    stream: bool,
+) -> Result<TimedOutput, CallToolError> {
+    super::require_installed(&[&cmd.as_std().get_program().to_string_lossy()])?;
+    let command_line = super::display_command(cmd);
+    let mut attempt = 0;
+    loop {
+        let error = match super::output_with_idle_timeout(
+            cmd,
+            timeout_seconds,
+            idle_timeout_seconds,
+            stream,
+        )
+        .await
+        {
+            Ok(output) if output.timed_out => return Ok(output),
+            Ok(output) => match connection_failure(&output.output)? {
+                Some(error) => error,
+                None => return Ok(output),
+            },
+            Err(err) => format!("Failed to execute remote SSH command: {}", err),
+        };
+
+        if attempt >= connection.retries {
+            return Err(with_command_line(error, &command_line));
         }
-        Err(err) => Err(CallToolError::from_message(format!(
-            "Failed to execute remote SSH command: {}",
-            err
+        attempt += 1;
+
+        let delay = retry_delay(connection.retry_backoff_ms, attempt);
+        tracing::warn!(
+            attempt,
+            retries = connection.retries,
+            delay_ms = delay.as_millis() as u64,
+            error = %error,
+            "Retrying SSH command"
+        );
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Run an ssh command with `input` written to its stdin, returning its output.
+/// Exit statuses are handled as in [`run_ssh`], but since the input can't be
+/// replayed, connection failures are never retried.
+pub(crate) async fn run_ssh_with_stdin(
+    cmd: &mut Command,
+    input: &[u8],
+    timeout_seconds: u64,
+) -> Result<Output, CallToolError> {
+    let output = run_ssh_with_stdin_until_timeout(cmd, input, timeout_seconds).await?;
+    if output.timed_out {
+        return Err(CallToolError::from_message(format!(
+            "SSH command timed out after {} seconds",
+            timeout_seconds
+        )));
+    }
+    Ok(output.output)
+}
+
+/// Run an ssh command like [`run_ssh_with_stdin`], except that a command that
+/// times out is stopped and returned with the output it printed until then.
+pub(crate) async fn run_ssh_with_stdin_until_timeout(
+    cmd: &mut Command,
+    input: &[u8],
+    timeout_seconds: u64,
+) -> Result<TimedOutput, CallToolError> {
+    super::require_installed(&[&cmd.as_std().get_program().to_string_lossy()])?;
+    let output = super::output_with_input_timeout(cmd, input, timeout_seconds)
+        .await
+        .map_err(|err| {
+            with_command_line(
+                format!("Failed to execute remote SSH command: {}", err),
+                &super::display_command(cmd),
+            )
+        })?;
+    if output.timed_out {
+        return Ok(output);
+    }
+    match connection_failure(&output.output)? {
+        Some(error) => Err(with_command_line(error, &super::display_command(cmd))),
+        None => Ok(output),
+    }
+}
+
+/// Classify how an ssh invocation exited, shared by the runners. Returns the
+/// error message if ssh failed to connect (exit status 255), an error if it
+/// was terminated by a signal, and None if the remote command ran. That
+/// doesn't mean it succeeded, so its output is returned as a tool call result
+/// whatever its exit status.
+fn connection_failure(output: &Output) -> Result<Option<String>, CallToolError> {
+    match output.status.code() {
+        Some(255) => Ok(Some(format!(
+            "SSH command failed unexpectedly: {}",
+            String::from_utf8_lossy(&output.stderr)
         ))),
+        None => Err(signal_error(output)),
+        _ => Ok(None),
     }
 }
 
+/// Build an error for a failed ssh invocation, showing the command line that
+/// was run so that a wrong host, user or option can be spotted.
+fn with_command_line(error: String, command_line: &str) -> CallToolError {
+    CallToolError::from_message(format!("{}\nCommand: {}", error.trim_end(), command_line))
+}
+
+/// The error for an ssh command that was terminated by a signal.
+fn signal_error(output: &Output) -> CallToolError {
+    let message = match super::exit_signal(&output.status) {
+        (Some(signal), _) => format!("SSH command unexpectedly terminated by signal {}", signal),
+        (None, _) => "SSH command unexpectedly terminated".to_string(),
+    };
+    CallToolError::from_message(message)
+}
+
+/// Delay before retry number `attempt` (starting at 1): the backoff doubled
+/// for each earlier retry.
+fn retry_delay(backoff_ms: u64, attempt: u32) -> Duration {
+    let factor = 1u64 << (attempt - 1).min(16);
+    Duration::from_millis(backoff_ms.saturating_mul(factor))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tools::{Secret, TestPath};
 
     #[tokio::test]
     async fn test_run_ssh_command_rejects_sudo() {
         let cmd = RunSSHCommand {
-            remote_host: "localhost".to_string(),
+            remote_host: Some("localhost".to_string()),
             cmd: "sudo".to_string(),
             args: vec!["ls".to_string()],
             timeout_seconds: Some(1),
             options: None,
+            ..Default::default()
         };
 
         let result = cmd.call_tool().await;
@@ -212,6 +836,640 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("sudo"));
     }
 
+    #[test]
+    fn test_run_ssh_sudo_command_run_as() {
+        let cmd = RunSSHSudoCommand {
+            remote_host: "db".to_string(),
+            cmd: "psql".to_string(),
+            args: vec!["-c".to_string(), "select 1".to_string()],
+            run_as: Some("postgres".to_string()),
+            env: Some(vec!["PGDATABASE=app".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            cmd.remote_command().unwrap(),
+            [
+                "sudo",
+                "-u",
+                "postgres",
+                "env",
+                "PGDATABASE=app",
+                "psql",
+                "-c",
+                "select 1"
+            ]
+        );
+
+        let cmd = RunSSHSudoCommand {
+            run_as: None,
+            ..cmd
+        };
+        assert_eq!(
+            cmd.remote_command().unwrap()[..3],
+            ["sudo", "env", "PGDATABASE=app"]
+        );
+    }
+
+    #[test]
+    fn test_run_as_flag_rejects_invalid_users() {
+        assert!(run_as_flag(None).unwrap().is_empty());
+        assert_eq!(run_as_flag(Some("www-data")).unwrap(), ["-u", "www-data"]);
+        for user in [
+            "",
+            "-s",
+            "#0",
+            "root postgres",
+            "post;gres",
+            "2fast",
+            &"a".repeat(33),
+        ] {
+            let err = run_as_flag(Some(user)).unwrap_err();
+            assert!(err.to_string().contains("Invalid run_as user"), "{}", user);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_ssh_sudo_command_dry_run() {
+        let cmd = RunSSHSudoCommand {
+            remote_host: "db".to_string(),
+            cmd: "systemctl".to_string(),
+            args: vec!["restart".to_string(), "nginx".to_string()],
+            port: Some(2222),
+            dry_run: Some(true),
+            ..Default::default()
+        };
+
+        let result = cmd.call_tool().await.unwrap();
+        let content = result.structured_content.unwrap();
+        assert_eq!(content["command"], "ssh");
+        assert_eq!(content["remote_host"], "db");
+        let args: Vec<&str> = content["args"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|arg| arg.as_str().unwrap())
+            .collect();
+        assert!(args.windows(2).any(|pair| pair == ["-p", "2222"]));
+        assert!(args.ends_with(&["db", "sudo", "systemctl", "restart", "nginx"]));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_ssh_command_rejects_unlisted_ssh_binary() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // An ssh that leaves a marker behind if it is ever run
+        let dir = TestPath::dir("fake_ssh");
+        let (fake_ssh, marker) = (dir.join("ssh"), dir.join("ran"));
+        std::fs::write(
+            &fake_ssh,
+            format!("#!/bin/sh\ntouch '{}'\n", marker.display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&fake_ssh, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let cmd = RunSSHCommand {
+            remote_host: Some("db".to_string()),
+            cmd: "uptime".to_string(),
+            reuse_connection: Some(false),
+            ssh_binary: Some(fake_ssh.to_string_lossy().into_owned()),
+            ..Default::default()
+        };
+        assert!(
+            cmd.describe_effective_command()
+                .ends_with(&format!(", using {}", fake_ssh.display()))
+        );
+        let error = cmd.call_tool().await.unwrap_err().to_string();
+        assert!(
+            error.contains("MCP_LINUX_SSH_ALLOWED_SSH_BINARIES"),
+            "{}",
+            error
+        );
+        assert!(!marker.exists());
+    }
+
+    #[tokio::test]
+    async fn test_run_ssh_with_stdin_reads_output_while_writing() {
+        // More than the pipes hold, echoed back by cat
+        let input = vec![b'x'; 1024 * 1024];
+        let mut cmd = Command::new("cat");
+        let output = run_ssh_with_stdin(&mut cmd, &input, 10).await.unwrap();
+        assert_eq!(output.stdout.len(), input.len());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_ssh_with_stdin_kills_timed_out_command() {
+        let marker = TestPath::new("stdin_timeout");
+
+        // Reads its input, then writes once the timeout has passed
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg("cat > /dev/null; sleep 2; touch \"$1\"")
+            .arg("sh")
+            .arg(marker.as_os_str());
+        let error = run_ssh_with_stdin(&mut cmd, b"content", 1)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("timed out after 1 seconds"));
+
+        tokio::time::sleep(Duration::from_secs(3)).await;
+        assert!(!marker.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_ssh_with_stdin_until_timeout_keeps_output() {
+        // Echoes its input, then runs past the timeout
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("cat; sleep 5");
+        let output = run_ssh_with_stdin_until_timeout(&mut cmd, b"partial", 1)
+            .await
+            .unwrap();
+        assert!(output.timed_out);
+        assert_eq!(output.output.stdout, b"partial");
+    }
+
+    #[tokio::test]
+    async fn test_run_ssh_retries_connection_failures() {
+        let marker = TestPath::new("retry");
+
+        // Fails like an ssh connection failure the first time, then succeeds
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg("if [ -e \"$1\" ]; then echo ok; else touch \"$1\"; exit 255; fi")
+            .arg("sh")
+            .arg(marker.as_os_str());
+
+        let connection = SshConnectionParams {
+            retries: 1,
+            retry_backoff_ms: 1,
+            ..Default::default()
+        };
+        let output = run_ssh(&connection, &mut cmd, 5, false).await;
+        assert_eq!(output.unwrap().stdout, b"ok\n");
+    }
+
+    #[tokio::test]
+    async fn test_run_ssh_error_shows_command_line() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo 'Connection refused' >&2; exit 255"]);
+
+        let connection = SshConnectionParams::default();
+        let err = run_ssh(&connection, &mut cmd, 5, false)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Connection refused"));
+        assert!(err.contains("Command: sh -c 'echo '\\''Connection refused'\\'' >&2; exit 255'"));
+    }
+
+    #[test]
+    fn test_display_command_omits_environment() {
+        let mut cmd = Command::new("sshpass");
+        cmd.args(["-e", "ssh", "-i", "/home/me/.ssh/id_ed25519", "db"])
+            .env("SSHPASS", "hunter2");
+        assert_eq!(
+            crate::tools::display_command(&cmd),
+            "sshpass -e ssh -i /home/me/.ssh/id_ed25519 db"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_ssh_does_not_retry_command_failures() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "exit 1"]);
+
+        let connection = SshConnectionParams {
+            retries: 3,
+            retry_backoff_ms: 60_000,
+            ..Default::default()
+        };
+        let output = run_ssh(&connection, &mut cmd, 5, false).await.unwrap();
+        assert_eq!(output.status.code(), Some(1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_connection_failure() {
+        use std::os::unix::process::ExitStatusExt;
+        let output = |status| Output {
+            status: std::process::ExitStatus::from_raw(status),
+            stdout: Vec::new(),
+            stderr: b"Connection refused".to_vec(),
+        };
+
+        assert_eq!(connection_failure(&output(0)).unwrap(), None);
+        assert_eq!(connection_failure(&output(1 << 8)).unwrap(), None);
+        assert_eq!(
+            connection_failure(&output(255 << 8)).unwrap().as_deref(),
+            Some("SSH command failed unexpectedly: Connection refused")
+        );
+        // Terminated by SIGKILL
+        assert!(connection_failure(&output(9)).is_err());
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(100, 1), Duration::from_millis(100));
+        assert_eq!(retry_delay(100, 2), Duration::from_millis(200));
+        assert_eq!(retry_delay(100, 4), Duration::from_millis(800));
+    }
+
+    #[test]
+    fn test_ssh_command_quotes_remote_arguments() {
+        let connection = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            ..Default::default()
+        };
+        let args = ["a b", "$HOME", "it's", "$(id); rm -rf /", "plain"];
+        let cmd = ssh_command(&connection, "printf", &args).unwrap();
+        let words: Vec<String> = cmd
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .skip_while(|arg| arg != "example.com")
+            .skip(1)
+            .collect();
+        assert_eq!(
+            words,
+            [
+                "printf",
+                "'a b'",
+                "'$HOME'",
+                r"'it'\''s'",
+                "'$(id); rm -rf /'",
+                "plain"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_ssh_command_allocate_tty() {
+        let ssh_args = |allocate_tty| async move {
+            let cmd = RunSSHCommand {
+                remote_host: Some("db".to_string()),
+                cmd: "top".to_string(),
+                allocate_tty,
+                dry_run: Some(true),
+                ..Default::default()
+            };
+            let result = cmd.call_tool().await.unwrap();
+            let content = result.structured_content.unwrap();
+            content["args"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|arg| arg.as_str().unwrap().to_string())
+                .collect::<Vec<String>>()
+        };
+
+        let args = ssh_args(Some(true)).await;
+        assert!(args.ends_with(&[
+            "-tt".to_string(),
+            "--".to_string(),
+            "db".to_string(),
+            "top".to_string()
+        ]));
+        assert!(!ssh_args(None).await.iter().any(|arg| arg.starts_with("-t")));
+    }
+
+    #[test]
+    fn test_with_parsed_output() {
+        let result = |stdout: &str| {
+            let mut structured_content = serde_json::Map::new();
+            structured_content.insert("stdout".to_string(), stdout.into());
+            CallToolResult::text_content(vec![]).with_structured_content(structured_content)
+        };
+        let stdout = "  PID TTY          TIME CMD\n    1 ?        00:00:12 systemd\n";
+
+        let content = with_parsed_output(result(stdout), Some("ps"))
+            .structured_content
+            .unwrap();
+        assert_eq!(content["stdout"], stdout);
+        assert_eq!(content["parsed"][0]["pid"], 1);
+        assert_eq!(content["parsed"][0]["cmd"], "systemd");
+
+        let content = with_parsed_output(result(stdout), Some("lsblk"))
+            .structured_content
+            .unwrap();
+        assert_eq!(content["stdout"], stdout);
+        assert!(content.get("parsed").is_none());
+        assert!(
+            content["parse_warning"]
+                .as_str()
+                .unwrap()
+                .contains("Unknown parse_as value")
+        );
+
+        let content = with_parsed_output(result(stdout), None)
+            .structured_content
+            .unwrap();
+        assert!(content.get("parsed").is_none());
+        assert!(content.get("parse_warning").is_none());
+    }
+
+    #[test]
+    fn test_ssh_command_ipv6_host() {
+        let connection = SshConnectionParams {
+            remote_host: "admin@[fe80::1]".to_string(),
+            ..Default::default()
+        };
+        let cmd = ssh_command(&connection, "uptime", &[]).unwrap();
+        let args: Vec<String> = cmd
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert!(args.ends_with(&["admin@fe80::1".to_string(), "uptime".to_string()]));
+    }
+
+    #[test]
+    fn test_ssh_command_remote_tmp_dir() {
+        let mut connection = SshConnectionParams {
+            remote_host: "example.com".to_string(),
+            remote_tmp_dir: Some("/data/tmp dir".to_string()),
+            ..Default::default()
+        };
+        let cmd = ssh_command(&connection, "patch", &["-p1"]).unwrap();
+        let args: Vec<String> = cmd
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert!(args.ends_with(&[
+            "example.com".to_string(),
+            "env".to_string(),
+            "'TMPDIR=/data/tmp dir'".to_string(),
+            "patch".to_string(),
+            "-p1".to_string(),
+        ]));
+
+        connection.remote_tmp_dir = Some("tmp".to_string());
+        let err = ssh_command(&connection, "patch", &[]).unwrap_err();
+        assert!(err.to_string().contains("Invalid remote_tmp_dir"));
+    }
+
+    #[test]
+    fn test_mosh_command() {
+        let connection = SshConnectionParams {
+            remote_host: "admin@field-unit".to_string(),
+            port: Some(2222),
+            ..Default::default()
+        };
+        let cmd = mosh_command(&connection, "echo", &["a b", "$HOME"]).unwrap();
+        assert_eq!(cmd.as_std().get_program(), "mosh");
+        let args: Vec<String> = cmd
+            .as_std()
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        assert!(args[0].starts_with("--ssh=ssh "));
+        assert!(args[0].contains("-p 2222"));
+        // mosh-server doesn't run the command through a shell
+        assert_eq!(
+            args[1..],
+            ["--", "admin@field-unit", "echo", "a b", "$HOME"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exec_ssh_span_omits_password() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        /// Records the fields of every new span
+        #[derive(Clone, Default)]
+        struct SpanFields(Arc<std::sync::Mutex<String>>);
+
+        impl tracing::field::Visit for SpanFields {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                let mut fields = self.0.lock().unwrap();
+                fields.push_str(&format!("{}={:?} ", field.name(), value));
+            }
+        }
+
+        impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanFields {
+            fn on_new_span(
+                &self,
+                attrs: &tracing::span::Attributes<'_>,
+                _: &tracing::span::Id,
+                _: tracing_subscriber::layer::Context<'_, S>,
+            ) {
+                attrs.record(&mut self.clone());
+            }
+        }
+
+        let fields = SpanFields::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(fields.clone()));
+
+        let connection = SshConnectionParams {
+            remote_host: "db".to_string(),
+            password: Some(Secret("hunter2".to_string())),
+            ..Default::default()
+        };
+        let mut cmd = Command::new("ssh");
+        connection.apply_password(&mut cmd);
+        cmd.arg("db").arg("uptime");
+        exec_ssh(&connection, cmd, 5, None, false, None, true)
+            .await
+            .unwrap();
+
+        let fields = fields.0.lock().unwrap();
+        assert!(fields.contains("remote_host=db"));
+        assert!(!fields.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_mosh_command_rejects_password() {
+        let connection = SshConnectionParams {
+            remote_host: "field-unit".to_string(),
+            password: Some(Secret("secret".to_string())),
+            ..Default::default()
+        };
+        assert!(mosh_command(&connection, "uptime", &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_quoted_arguments_survive_the_shell() {
+        // Run the command line the remote shell would see through a local shell
+        let args = ["a b", "$HOME", "it's", "\"quoted\"", "$(id)", "*"];
+        let command_line = std::iter::once("printf '%s\\n'".to_string())
+            .chain(args.iter().map(|arg| super::super::shell_quote(arg)))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&command_line)
+            .output()
+            .await
+            .unwrap();
+        let lines: Vec<String> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        assert_eq!(lines, args);
+    }
+
+    #[test]
+    fn test_with_remote_shell() {
+        let command = vec!["echo".to_string(), "a b".to_string()];
+        assert_eq!(with_remote_shell(None, command.clone()).unwrap(), command);
+        assert_eq!(
+            with_remote_shell(Some("/bin/bash"), command.clone()).unwrap(),
+            ["/bin/bash", "-c", "echo 'a b'"]
+        );
+        assert!(with_remote_shell(Some("bash -x"), command.clone()).is_err());
+        assert!(with_remote_shell(Some(""), command).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_with_merged_stderr() {
+        let command = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "echo out; echo err >&2; echo 'more out'".to_string(),
+        ];
+        assert_eq!(with_merged_stderr(false, command.clone()), command);
+
+        // Run the words through a local shell, as the remote host would
+        let command_line = with_merged_stderr(true, command)
+            .iter()
+            .map(|word| super::super::shell_quote(word))
+            .collect::<Vec<String>>()
+            .join(" ");
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&command_line)
+            .output()
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "out\nerr\nmore out\n"
+        );
+        assert!(output.stderr.is_empty());
+    }
+
+    #[test]
+    fn test_env_prefix() {
+        assert!(env_prefix(None).unwrap().is_empty());
+        assert_eq!(
+            env_prefix(Some(
+                &[
+                    "DEBIAN_FRONTEND=noninteractive".to_string(),
+                    "GREETING=hello world".to_string(),
+                ][..]
+            ))
+            .unwrap(),
+            [
+                "env",
+                "DEBIAN_FRONTEND=noninteractive",
+                "GREETING=hello world"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_env_prefix_rejects_invalid_entries() {
+        assert!(env_prefix(Some(&["NOEQUALS".to_string()][..])).is_err());
+        assert!(env_prefix(Some(&["1BAD=value".to_string()][..])).is_err());
+        assert!(env_prefix(Some(&["BAD KEY=value".to_string()][..])).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_captures_full_output() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo one; echo two >&2; echo three"]);
+
+        let output = run_ssh(&SshConnectionParams::default(), &mut cmd, 5, true)
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"one\nthree\n");
+        assert_eq!(output.stderr, b"two\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_ssh_timeout_keeps_partial_output() {
+        let connection = SshConnectionParams::default();
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo partial; sleep 10"]);
+        let output = run_ssh_until_timeout(&connection, &mut cmd, 1, None, false)
+            .await
+            .unwrap();
+        assert!(output.timed_out);
+        assert_eq!(output.output.stdout, b"partial\n");
+
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo partial; sleep 10"]);
+        let err = run_ssh(&connection, &mut cmd, 1, false).await.unwrap_err();
+        assert!(err.to_string().contains("timed out"));
+    }
+
+    #[test]
+    fn test_uses_privilege_escalation_allows_similar_names() {
+        assert!(!uses_privilege_escalation("sudoku", &[]));
+        assert!(!uses_privilege_escalation(
+            "ls",
+            &["/opt/sudoku/bin".to_string()]
+        ));
+    }
+
+    #[test]
+    fn test_uses_privilege_escalation_blocks_sudo() {
+        assert!(uses_privilege_escalation("sudo", &["ls".to_string()]));
+        assert!(uses_privilege_escalation("/usr/bin/sudo", &[]));
+        assert!(uses_privilege_escalation("doas", &[]));
+        assert!(uses_privilege_escalation(
+            "env",
+            &["sudo".to_string(), "ls".to_string()]
+        ));
+    }
+
+    #[test]
+    fn test_describe_effective_command() {
+        let cmd = RunSSHSudoCommand {
+            remote_host: "prod-db".to_string(),
+            cmd: "apt".to_string(),
+            args: vec![
+                "remove".to_string(),
+                "--purge".to_string(),
+                "nginx".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            cmd.describe_effective_command(),
+            "sudo apt remove --purge nginx on prod-db"
+        );
+
+        let cmd = RunSSHCommand {
+            remote_host: Some("prod-db".to_string()),
+            cmd: "grep".to_string(),
+            args: vec!["error level".to_string(), "/var/log/app.log".to_string()],
+            env: Some(vec!["LANG=C".to_string()]),
+            jump_host: Some("bastion".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            cmd.describe_effective_command(),
+            "env LANG=C grep 'error level' /var/log/app.log on prod-db via bastion"
+        );
+
+        let cmd = RunSSHCommand {
+            remote_hosts: Some(vec!["web1".to_string(), "web2".to_string()]),
+            cmd: "uptime".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(cmd.describe_effective_command(), "uptime on web1, web2");
+    }
+
     #[test]
     fn test_run_ssh_sudo_command_struct_creation() {
         let cmd = RunSSHSudoCommand {
@@ -220,9 +1478,92 @@ mod tests {
             args: vec!["update".to_string()],
             timeout_seconds: Some(60),
             options: None,
+            ..Default::default()
         };
 
         assert_eq!(cmd.remote_host, "localhost");
         assert_eq!(cmd.cmd, "apt");
     }
+
+    #[tokio::test]
+    async fn test_fan_out_keeps_host_order() {
+        let hosts = ["slow", "broken", "fast"].map(String::from);
+        let results = fan_out(&hosts, 3, |host| async move {
+            match host.as_str() {
+                "slow" => tokio::time::sleep(Duration::from_millis(50)).await,
+                "broken" => return Err("Connection refused".to_string()),
+                _ => {}
+            }
+            let output = std::process::Command::new("echo")
+                .arg(&host)
+                .output()
+                .unwrap();
+            Ok(super::super::result_from_output(&output, None, None))
+        })
+        .await;
+
+        let result = fan_out_result(results);
+        assert!(!crate::audit::succeeded(&result));
+        let content = result.structured_content.unwrap();
+        assert_eq!(content["succeeded"], false);
+        assert_eq!(content["failed_hosts"], 1);
+        let results = content["results"].as_array().unwrap();
+        let hosts: Vec<&str> = results
+            .iter()
+            .map(|result| result["host"].as_str().unwrap())
+            .collect();
+        assert_eq!(hosts, ["slow", "broken", "fast"]);
+
+        // A failure on one host doesn't stop the others
+        assert_eq!(results[0]["status_code"], 0);
+        assert_eq!(results[0]["stdout"], "slow\n");
+        assert_eq!(results[1]["status_code"], serde_json::Value::Null);
+        assert_eq!(results[1]["error"], "Connection refused");
+        assert_eq!(results[1]["error_kind"], "connection_refused");
+        assert_eq!(results[2]["stdout"], "fast\n");
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_limits_concurrency() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let most_running = Arc::new(AtomicUsize::new(0));
+        let hosts: Vec<String> = (0..6).map(|i| format!("host{}", i)).collect();
+        let results = fan_out(&hosts, 2, |_| {
+            let (running, most_running) = (running.clone(), most_running.clone());
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most_running.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok(CallToolResult::text_content(vec![]))
+            }
+        })
+        .await;
+
+        assert_eq!(results.len(), 6);
+        assert_eq!(most_running.load(Ordering::SeqCst), 2);
+
+        let content = fan_out_result(results).structured_content.unwrap();
+        assert_eq!(content["succeeded"], true);
+        assert_eq!(content["failed_hosts"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_run_ssh_command_requires_one_host_parameter() {
+        let cmd = RunSSHCommand {
+            remote_host: Some("web1".to_string()),
+            remote_hosts: Some(vec!["web2".to_string()]),
+            cmd: "uptime".to_string(),
+            ..Default::default()
+        };
+        assert!(cmd.call_tool().await.is_err());
+
+        let cmd = RunSSHCommand {
+            cmd: "uptime".to_string(),
+            ..Default::default()
+        };
+        assert!(cmd.call_tool().await.is_err());
+    }
 }
@@ -0,0 +1,476 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+/// Number of processes returned when the call doesn't set top
+const DEFAULT_TOP: u32 = 10;
+
+/// Script printing disk usage, memory, load and the busiest processes as
+/// sections introduced by "--- <name>" lines. Memory comes from free -b on
+/// Linux, and from vm_stat and the hw.memsize sysctl on macOS. df -k makes
+/// BSD and macOS report 1024-byte blocks like Linux. $1 is the number of
+/// processes to print.
+const SNAPSHOT_SCRIPT: &str = r#"echo '--- df'
+df -P -k 2>/dev/null
+echo '--- memory'
+if command -v free >/dev/null 2>&1; then
+    free -b
+elif command -v vm_stat >/dev/null 2>&1; then
+    vm_stat
+    printf 'memsize: %s\n' "$(sysctl -n hw.memsize)"
+fi
+echo '--- uptime'
+uptime
+echo '--- ps'
+ps -A -o pid= -o pcpu= -o pmem= -o comm= | sort -k2 -rn | head -n "$1"
+true"#;
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "system_snapshot",
+        description = "Take a snapshot of resource usage on a remote POSIX compatible system (Linux, BSD, macOS) in one SSH round trip: \
+        disk usage of each filesystem (df), memory (free, or vm_stat on macOS), the 1, 5 and 15 minute load averages (uptime) \
+        and the processes using the most CPU (ps). Returns them parsed as disks, memory, load and top_processes. \
+        Use this to start triaging a slow or misbehaving host.",
+        title = "System Snapshot"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct SystemSnapshot {
+        /// The host to take the snapshot of. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Number of processes to return, busiest first. Defaults to 10.
+        pub top: Option<u32>,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Number of times to retry when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+/// Resource usage parsed from the snapshot script's output. Parts the host
+/// couldn't report are left empty.
+#[derive(Debug, Default, Clone, PartialEq, ::serde::Serialize)]
+struct Snapshot {
+    disks: Vec<Disk>,
+    memory: Option<Memory>,
+    load: Option<[f64; 3]>,
+    top_processes: Vec<Process>,
+}
+
+/// A filesystem as reported by df -P -k
+#[derive(Debug, Clone, PartialEq, Eq, ::serde::Serialize)]
+struct Disk {
+    filesystem: String,
+    mount: String,
+    size_bytes: u64,
+    used_bytes: u64,
+    available_bytes: u64,
+    use_percent: u8,
+}
+
+/// Memory and swap usage in bytes. available is the memory that can be used
+/// without swapping, including reclaimable caches.
+#[derive(Debug, Default, Clone, PartialEq, Eq, ::serde::Serialize)]
+struct Memory {
+    total_bytes: u64,
+    used_bytes: u64,
+    free_bytes: u64,
+    available_bytes: Option<u64>,
+    swap_total_bytes: Option<u64>,
+    swap_used_bytes: Option<u64>,
+}
+
+/// A process as reported by ps, with its CPU and memory use in percent
+#[derive(Debug, Clone, PartialEq, ::serde::Serialize)]
+struct Process {
+    pid: u32,
+    cpu_percent: f64,
+    memory_percent: f64,
+    command: String,
+}
+
+impl SystemSnapshot {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let top = self.top.unwrap_or(DEFAULT_TOP);
+        if top == 0 {
+            return Err(CallToolError::from_message("top must be greater than 0"));
+        }
+
+        let connection = self.connection();
+        let top_arg = top.to_string();
+        let mut cmd = super::ssh::ssh_command(
+            &connection,
+            "sh",
+            &["-c", SNAPSHOT_SCRIPT, "sh", top_arg.as_str()],
+        )?;
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(&cmd, &self.remote_host, None));
+        }
+
+        let output = super::ssh::run_ssh(&connection, &mut cmd, timeout_seconds, false).await?;
+        if !output.status.success() {
+            return Err(CallToolError::from_message(format!(
+                "Failed to take a snapshot of {}: {}",
+                self.remote_host,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let snapshot = parse_snapshot(&String::from_utf8_lossy(&output.stdout));
+        let structured_content = match serde_json::to_value(&snapshot) {
+            Ok(serde_json::Value::Object(map)) => map,
+            _ => serde_json::Map::new(),
+        };
+        Ok(
+            CallToolResult::text_content(vec![TextContent::from(summary(&snapshot))])
+                .with_structured_content(structured_content),
+        )
+    }
+
+    /// Describe the snapshot, e.g. "take a snapshot of disk, memory, load and
+    /// process usage on prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        format!(
+            "take a snapshot of disk, memory, load and process usage {}",
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// Split the script's output into the sections introduced by "--- <name>"
+/// lines and parse each of them
+fn parse_snapshot(output: &str) -> Snapshot {
+    let mut sections: Vec<(&str, Vec<&str>)> = Vec::new();
+    for line in output.lines() {
+        match (line.strip_prefix("--- "), sections.last_mut()) {
+            (Some(name), _) => sections.push((name.trim(), Vec::new())),
+            (None, Some((_, lines))) => lines.push(line),
+            (None, None) => {}
+        }
+    }
+
+    let mut snapshot = Snapshot::default();
+    for (name, lines) in sections {
+        let body = lines.join("\n");
+        match name {
+            "df" => snapshot.disks = lines.iter().filter_map(|l| parse_df_line(l)).collect(),
+            "memory" => snapshot.memory = parse_free(&body).or_else(|| parse_vm_stat(&body)),
+            "uptime" => snapshot.load = parse_load(&body),
+            "ps" => {
+                snapshot.top_processes = lines.iter().filter_map(|l| parse_ps_line(l)).collect()
+            }
+            _ => {}
+        }
+    }
+    snapshot
+}
+
+/// Parse a line of df -P -k output. The capacity column is found from the
+/// right of the numbers, so that filesystem names and mount points
+/// containing spaces, such as macOS's "map auto_home", are kept whole.
+fn parse_df_line(line: &str) -> Option<Disk> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let capacity = (4..fields.len()).find(|&i| {
+        fields[i].ends_with('%') && fields[i - 3..i].iter().all(|f| f.parse::<u64>().is_ok())
+    })?;
+    let kib = |field: &str| field.parse::<u64>().ok().map(|kib| kib * 1024);
+    Some(Disk {
+        filesystem: fields[..capacity - 3].join(" "),
+        mount: fields[capacity + 1..].join(" "),
+        size_bytes: kib(fields[capacity - 3])?,
+        used_bytes: kib(fields[capacity - 2])?,
+        available_bytes: kib(fields[capacity - 1])?,
+        use_percent: fields[capacity].trim_end_matches('%').parse().ok()?,
+    })
+}
+
+/// Parse the output of free -b, whose columns are named by its header.
+/// Versions of procps before 3.3.10 don't have the available column.
+fn parse_free(output: &str) -> Option<Memory> {
+    let mut lines = output.lines();
+    let header: Vec<&str> = lines.next()?.split_whitespace().collect();
+    let mut memory: Option<Memory> = None;
+    for line in lines {
+        let mut fields = line.split_whitespace();
+        let Some(label) = fields.next() else {
+            continue;
+        };
+        let values: Vec<u64> = fields.filter_map(|f| f.parse().ok()).collect();
+        let column = |name: &str| {
+            header
+                .iter()
+                .position(|h| *h == name)
+                .and_then(|i| values.get(i).copied())
+        };
+        match label {
+            "Mem:" => {
+                memory = Some(Memory {
+                    total_bytes: column("total")?,
+                    used_bytes: column("used")?,
+                    free_bytes: column("free")?,
+                    available_bytes: column("available"),
+                    ..Default::default()
+                })
+            }
+            "Swap:" => {
+                if let Some(memory) = memory.as_mut() {
+                    memory.swap_total_bytes = column("total");
+                    memory.swap_used_bytes = column("used");
+                }
+            }
+            _ => {}
+        }
+    }
+    memory
+}
+
+/// Parse the output of macOS's vm_stat, followed by a "memsize: <bytes>"
+/// line. Memory that is free, inactive or speculative can be used without
+/// swapping, so it is counted as available, and the rest as used.
+fn parse_vm_stat(output: &str) -> Option<Memory> {
+    let page_size: u64 = output
+        .split("page size of ")
+        .nth(1)?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()?;
+    let value = |name: &str| -> Option<u64> {
+        output.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            (key.trim() == name)
+                .then(|| value.trim().trim_end_matches('.').parse().ok())
+                .flatten()
+        })
+    };
+    let total_bytes = value("memsize")?;
+    let pages = |name: &str| value(name).unwrap_or(0) * page_size;
+    let free_bytes = pages("Pages free") + pages("Pages speculative");
+    let available_bytes = (free_bytes + pages("Pages inactive")).min(total_bytes);
+    Some(Memory {
+        total_bytes,
+        used_bytes: total_bytes - available_bytes,
+        free_bytes,
+        available_bytes: Some(available_bytes),
+        ..Default::default()
+    })
+}
+
+/// Parse the load averages out of uptime's output: "load average: 0.52, 0.58,
+/// 0.59" on Linux and "load averages: 1.52 1.60 1.73" on macOS.
+fn parse_load(output: &str) -> Option<[f64; 3]> {
+    let (_, averages) = output.split_once("load average")?;
+    let (_, averages) = averages.split_once(':')?;
+    let loads: Vec<f64> = averages
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter_map(|load| load.parse().ok())
+        .collect();
+    Some([*loads.first()?, *loads.get(1)?, *loads.get(2)?])
+}
+
+/// Parse a line of `ps -o pid= -o pcpu= -o pmem= -o comm=` output
+fn parse_ps_line(line: &str) -> Option<Process> {
+    let mut fields = line.split_whitespace();
+    let pid = fields.next()?.parse().ok()?;
+    let cpu_percent = fields.next()?.parse().ok()?;
+    let memory_percent = fields.next()?.parse().ok()?;
+    let command = fields.collect::<Vec<_>>().join(" ");
+    Some(Process {
+        pid,
+        cpu_percent,
+        memory_percent,
+        command,
+    })
+}
+
+/// Summarize a snapshot in a few lines: the load, memory, fullest disk and
+/// busiest process.
+fn summary(snapshot: &Snapshot) -> String {
+    const GIB: f64 = (1u64 << 30) as f64;
+    let mut lines = Vec::new();
+    if let Some([one, five, fifteen]) = snapshot.load {
+        lines.push(format!("load: {:.2} {:.2} {:.2}", one, five, fifteen));
+    }
+    if let Some(memory) = &snapshot.memory {
+        lines.push(format!(
+            "memory: {:.1} GiB available of {:.1} GiB",
+            memory.available_bytes.unwrap_or(memory.free_bytes) as f64 / GIB,
+            memory.total_bytes as f64 / GIB
+        ));
+    }
+    if let Some(disk) = snapshot.disks.iter().max_by_key(|disk| disk.use_percent) {
+        lines.push(format!(
+            "fullest disk: {} at {}% ({:.1} GiB available)",
+            disk.mount,
+            disk.use_percent,
+            disk.available_bytes as f64 / GIB
+        ));
+    }
+    if let Some(process) = snapshot.top_processes.first() {
+        lines.push(format!(
+            "busiest process: {} (pid {}) at {:.1}% CPU",
+            process.command, process.pid, process.cpu_percent
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::tools::run_script;
+
+    #[test]
+    fn test_parse_snapshot_linux() {
+        let output = "--- df\n\
+            Filesystem     1024-blocks     Used Available Capacity Mounted on\n\
+            /dev/sda1         41152736 30454320   8585264      79% /\n\
+            tmpfs              2013604        0   2013604       0% /dev/shm\n\
+            --- memory\n\
+            \x20              total        used        free      shared  buff/cache   available\n\
+            Mem:      8242147328  2137489408  1263759360    12345678  4840898560  5812006912\n\
+            Swap:     2147479552    10485760  2136993792\n\
+            --- uptime\n\
+            \x2014:02:11 up 12 days,  3:04,  2 users,  load average: 0.52, 0.58, 0.59\n\
+            --- ps\n\
+            \x20 1234 97.5  2.1 java\n\
+            \x20  987  3.0  0.4 nginx\n";
+        let snapshot = parse_snapshot(output);
+
+        assert_eq!(snapshot.disks.len(), 2);
+        assert_eq!(
+            snapshot.disks[0],
+            Disk {
+                filesystem: "/dev/sda1".to_string(),
+                mount: "/".to_string(),
+                size_bytes: 41152736 * 1024,
+                used_bytes: 30454320 * 1024,
+                available_bytes: 8585264 * 1024,
+                use_percent: 79,
+            }
+        );
+        assert_eq!(
+            snapshot.memory,
+            Some(Memory {
+                total_bytes: 8242147328,
+                used_bytes: 2137489408,
+                free_bytes: 1263759360,
+                available_bytes: Some(5812006912),
+                swap_total_bytes: Some(2147479552),
+                swap_used_bytes: Some(10485760),
+            })
+        );
+        assert_eq!(snapshot.load, Some([0.52, 0.58, 0.59]));
+        assert_eq!(
+            snapshot.top_processes[0],
+            Process {
+                pid: 1234,
+                cpu_percent: 97.5,
+                memory_percent: 2.1,
+                command: "java".to_string(),
+            }
+        );
+        assert_eq!(snapshot.top_processes.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_snapshot_macos() {
+        let output = "--- df\n\
+            Filesystem     1024-blocks      Used Available Capacity  Mounted on\n\
+            /dev/disk3s1s1   482797652  10009536 219548028     5%    /\n\
+            map auto_home            0         0         0   100%    /System/Volumes/Data/home\n\
+            --- memory\n\
+            Mach Virtual Memory Statistics: (page size of 16384 bytes)\n\
+            Pages free:                               10000.\n\
+            Pages active:                            200000.\n\
+            Pages inactive:                          100000.\n\
+            Pages speculative:                         5000.\n\
+            Pages wired down:                         80000.\n\
+            memsize: 17179869184\n\
+            --- uptime\n\
+            10:01  up 3 days, 18:22, 2 users, load averages: 1.52 1.60 1.73\n\
+            --- ps\n";
+        let snapshot = parse_snapshot(output);
+
+        assert_eq!(snapshot.disks[1].filesystem, "map auto_home");
+        assert_eq!(snapshot.disks[1].mount, "/System/Volumes/Data/home");
+        assert_eq!(snapshot.disks[1].use_percent, 100);
+        let memory = snapshot.memory.unwrap();
+        assert_eq!(memory.total_bytes, 17179869184);
+        assert_eq!(memory.free_bytes, 15000 * 16384);
+        assert_eq!(memory.available_bytes, Some(115000 * 16384));
+        assert_eq!(memory.used_bytes, 17179869184 - 115000 * 16384);
+        assert_eq!(memory.swap_total_bytes, None);
+        assert_eq!(snapshot.load, Some([1.52, 1.60, 1.73]));
+        assert!(snapshot.top_processes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_snapshot_missing_sections() {
+        assert_eq!(parse_snapshot(""), Snapshot::default());
+        assert_eq!(parse_free("no memory here"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_snapshot_script() {
+        let output = run_script(SNAPSHOT_SCRIPT, &["3"], b"", None);
+        assert!(output.status.success());
+
+        let snapshot = parse_snapshot(&String::from_utf8_lossy(&output.stdout));
+        assert!(!snapshot.disks.is_empty());
+        assert!(snapshot.load.is_some());
+        assert!(!snapshot.top_processes.is_empty());
+        assert!(snapshot.top_processes.len() <= 3);
+    }
+
+    #[test]
+    fn test_summary() {
+        let snapshot = Snapshot {
+            load: Some([0.5, 0.25, 0.1]),
+            disks: vec![Disk {
+                filesystem: "/dev/sda1".to_string(),
+                mount: "/".to_string(),
+                size_bytes: 10 << 30,
+                used_bytes: 9 << 30,
+                available_bytes: 1 << 30,
+                use_percent: 90,
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            summary(&snapshot),
+            "load: 0.50 0.25 0.10\nfullest disk: / at 90% (1.0 GiB available)"
+        );
+    }
+
+    #[test]
+    fn test_describe_effective_command() {
+        let tool = SystemSnapshot {
+            remote_host: "prod-db".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            tool.describe_effective_command(),
+            "take a snapshot of disk, memory, load and process usage on prod-db"
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, schema_utils::CallToolError},
+};
+
+/// Properties read with `systemctl show` to describe a unit's state.
+const SHOW_PROPERTIES: &str = "ActiveState,SubState,UnitFileState,MainPID,MemoryCurrent";
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "systemctl",
+        description = "Manage a systemd service on a remote Linux system: start, stop, restart, reload, enable, disable, status, is-active or is-enabled. Actions that change state run with sudo. status and is-active also return the unit's state as structured data: active, sub_state, enabled, pid and memory.",
+        title = "Systemctl"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct Systemctl {
+        /// The action to perform: start, stop, restart, reload, enable, disable, status, is-active or is-enabled.
+        pub action: String,
+        /// The unit to act on, e.g. nginx or nginx.service.
+        pub unit: String,
+        /// The host to manage the unit on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Number of times to retry when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+/// A systemctl action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Start,
+    Stop,
+    Restart,
+    Reload,
+    Enable,
+    Disable,
+    Status,
+    IsActive,
+    IsEnabled,
+}
+
+impl Action {
+    fn parse(action: &str) -> Result<Self, CallToolError> {
+        match action {
+            "start" => Ok(Action::Start),
+            "stop" => Ok(Action::Stop),
+            "restart" => Ok(Action::Restart),
+            "reload" => Ok(Action::Reload),
+            "enable" => Ok(Action::Enable),
+            "disable" => Ok(Action::Disable),
+            "status" => Ok(Action::Status),
+            "is-active" => Ok(Action::IsActive),
+            "is-enabled" => Ok(Action::IsEnabled),
+            other => Err(CallToolError::from_message(format!(
+                "Invalid action: {} (expected start, stop, restart, reload, enable, disable, status, is-active or is-enabled)",
+                other
+            ))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Start => "start",
+            Action::Stop => "stop",
+            Action::Restart => "restart",
+            Action::Reload => "reload",
+            Action::Enable => "enable",
+            Action::Disable => "disable",
+            Action::Status => "status",
+            Action::IsActive => "is-active",
+            Action::IsEnabled => "is-enabled",
+        }
+    }
+
+    /// Whether the action changes the unit's state and so needs sudo.
+    fn mutates(&self) -> bool {
+        !matches!(self, Action::Status | Action::IsActive | Action::IsEnabled)
+    }
+
+    /// Whether the result includes the unit's state from `systemctl show`.
+    fn reports_state(&self) -> bool {
+        matches!(self, Action::Status | Action::IsActive)
+    }
+}
+
+impl Systemctl {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let action = Action::parse(&self.action)?;
+        validate_unit(&self.unit)?;
+        let connection = self.connection();
+
+        let mut cmd = super::ssh::ssh_command(
+            &connection,
+            if action.mutates() {
+                "sudo"
+            } else {
+                "systemctl"
+            },
+            &systemctl_args(action, &self.unit),
+        )?;
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(&cmd, &self.remote_host, None));
+        }
+
+        // systemctl exits non-zero for inactive units, so the output is
+        // returned whatever the status code
+        let output = super::ssh::run_ssh(&connection, &mut cmd, timeout_seconds, false).await?;
+        let mut result = super::result_from_output(&output, None, None);
+
+        let mut state = serde_json::Map::new();
+        if action.reports_state() {
+            let mut cmd = super::ssh::ssh_command(
+                &connection,
+                "systemctl",
+                &[
+                    "show",
+                    "--property",
+                    SHOW_PROPERTIES,
+                    "--",
+                    self.unit.as_str(),
+                ],
+            )?;
+            let output = super::ssh::run_ssh(&connection, &mut cmd, timeout_seconds, false).await?;
+            if output.status.success() {
+                state = parse_show(&String::from_utf8_lossy(&output.stdout));
+            }
+        }
+
+        let structured_content = result.structured_content.get_or_insert_default();
+        structured_content.insert("action".to_string(), action.name().into());
+        structured_content.insert("unit".to_string(), self.unit.as_str().into());
+        structured_content.extend(state);
+        Ok(result)
+    }
+
+    /// Describe the command that will run, e.g. "sudo systemctl restart --
+    /// nginx on prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        let command = match Action::parse(&self.action) {
+            Ok(action) if action.mutates() => std::iter::once("sudo")
+                .chain(systemctl_args(action, &self.unit))
+                .collect(),
+            Ok(action) => std::iter::once("systemctl")
+                .chain(systemctl_args(action, &self.unit))
+                .collect(),
+            Err(_) => vec!["systemctl", self.action.as_str(), self.unit.as_str()],
+        };
+        format!(
+            "{} {}",
+            super::command_line(&command),
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// Reject unit names that systemctl would parse as options.
+fn validate_unit(unit: &str) -> Result<(), CallToolError> {
+    if unit.is_empty() || unit.starts_with('-') || unit.chars().any(char::is_whitespace) {
+        return Err(CallToolError::from_message(format!(
+            "Invalid unit: {:?}",
+            unit
+        )));
+    }
+    Ok(())
+}
+
+/// Build the arguments after the program: `systemctl <action> -- <unit>`
+/// under sudo for actions that change state, otherwise `<action> -- <unit>`.
+fn systemctl_args(action: Action, unit: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    if action.mutates() {
+        args.push("systemctl");
+    }
+    if action == Action::Status {
+        args.push("--no-pager");
+    }
+    args.extend([action.name(), "--", unit]);
+    args
+}
+
+/// Parse `systemctl show` output into the unit's state. Properties systemd
+/// reports as unset are omitted.
+fn parse_show(output: &str) -> serde_json::Map<String, serde_json::Value> {
+    let mut state = serde_json::Map::new();
+    for (key, value) in output.lines().filter_map(|line| line.split_once('=')) {
+        let value = value.trim();
+        let (name, value) = match key {
+            "ActiveState" => ("active", serde_json::Value::from(value == "active")),
+            "SubState" => ("sub_state", value.into()),
+            "UnitFileState" if !value.is_empty() => {
+                ("enabled", serde_json::Value::from(value == "enabled"))
+            }
+            "MainPID" => match value.parse::<u32>() {
+                Ok(pid) if pid != 0 => ("pid", pid.into()),
+                _ => continue,
+            },
+            "MemoryCurrent" => match value.parse::<u64>() {
+                // [not set] and u64::MAX both mean unknown
+                Ok(memory) if memory != u64::MAX => ("memory", memory.into()),
+                _ => continue,
+            },
+            _ => continue,
+        };
+        state.insert(name.to_string(), value);
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_parse() {
+        assert_eq!(Action::parse("restart").unwrap(), Action::Restart);
+        assert_eq!(Action::parse("is-active").unwrap(), Action::IsActive);
+        assert!(Action::parse("mask").is_err());
+        assert!(Action::parse("Restart").is_err());
+        assert!(Action::parse("").is_err());
+    }
+
+    #[test]
+    fn test_action_mutates() {
+        assert!(Action::Start.mutates());
+        assert!(Action::Disable.mutates());
+        assert!(!Action::Status.mutates());
+        assert!(!Action::IsEnabled.mutates());
+    }
+
+    #[test]
+    fn test_systemctl_args() {
+        assert_eq!(
+            systemctl_args(Action::Restart, "nginx"),
+            ["systemctl", "restart", "--", "nginx"]
+        );
+        assert_eq!(
+            systemctl_args(Action::Status, "nginx"),
+            ["--no-pager", "status", "--", "nginx"]
+        );
+    }
+
+    #[test]
+    fn test_validate_unit() {
+        assert!(validate_unit("nginx.service").is_ok());
+        assert!(validate_unit("getty@tty1.service").is_ok());
+        assert!(validate_unit("").is_err());
+        assert!(validate_unit("--now").is_err());
+        assert!(validate_unit("nginx; reboot").is_err());
+    }
+
+    #[test]
+    fn test_parse_show() {
+        let state = parse_show(
+            "ActiveState=active\nSubState=running\nUnitFileState=enabled\nMainPID=1234\nMemoryCurrent=10485760\n",
+        );
+        assert_eq!(state["active"], true);
+        assert_eq!(state["sub_state"], "running");
+        assert_eq!(state["enabled"], true);
+        assert_eq!(state["pid"], 1234);
+        assert_eq!(state["memory"], 10485760);
+    }
+
+    #[test]
+    fn test_parse_show_inactive() {
+        let state = parse_show(
+            "ActiveState=inactive\nSubState=dead\nUnitFileState=disabled\nMainPID=0\nMemoryCurrent=[not set]\n",
+        );
+        assert_eq!(state["active"], false);
+        assert_eq!(state["enabled"], false);
+        assert!(!state.contains_key("pid"));
+        assert!(!state.contains_key("memory"));
+    }
+}
@@ -0,0 +1,238 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+/// Number of lines returned when the call doesn't set lines.
+const DEFAULT_LINES: u64 = 100;
+
+/// Cap on lines
+const MAX_LINES: u64 = 10000;
+
+/// Exit statuses of the tail script
+const TAIL_MISSING: i32 = 3;
+const TAIL_NOT_READABLE: i32 = 4;
+
+/// Script printing the last $2 lines of the file $1, keeping only the lines
+/// containing the fixed string $3 if it isn't empty. grep exits with 1 when
+/// no line matches, which isn't an error here.
+const TAIL_SCRIPT: &str = r#"[ -f "$1" ] || exit 3
+[ -r "$1" ] || exit 4
+if [ -n "$3" ]; then
+    tail -n "$2" -- "$1" | { grep -F -e "$3" || [ $? -eq 1 ]; }
+else
+    tail -n "$2" -- "$1"
+fi"#;
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "tail_file",
+        description = "Return the last lines of a file on a remote POSIX compatible system (Linux, BSD, macOS), as with tail -n. \
+        Optionally keep only the lines containing grep_filter, matched as a fixed string rather than a regular expression. \
+        Use this to look at recent log entries without downloading the whole file.",
+        title = "Tail File"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct TailFile {
+        /// The path of the file on the remote machine.
+        pub remote_path: String,
+        /// Number of lines to return from the end of the file, at most 10000. Defaults to 100.
+        pub lines: Option<u64>,
+        /// Only return the lines, among the last ones, that contain this text. It is matched with grep -F, as a fixed string.
+        pub grep_filter: Option<String>,
+        /// The host to read the file on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Number of times to retry when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+impl TailFile {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let lines = self.lines();
+        if lines == 0 {
+            return Err(CallToolError::from_message("lines must be greater than 0"));
+        }
+
+        let connection = self.connection();
+        let lines_arg = lines.to_string();
+        let mut cmd = super::ssh::ssh_command(
+            &connection,
+            "sh",
+            &[
+                "-c",
+                TAIL_SCRIPT,
+                "sh",
+                self.remote_path.as_str(),
+                lines_arg.as_str(),
+                self.grep_filter.as_deref().unwrap_or(""),
+            ],
+        )?;
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(&cmd, &self.remote_host, None));
+        }
+
+        let output = super::ssh::run_ssh(&connection, &mut cmd, timeout_seconds, false).await?;
+        match output.status.code() {
+            Some(0) => {}
+            Some(TAIL_MISSING) => {
+                return Err(CallToolError::from_message(format!(
+                    "File not found: {}",
+                    self.remote_path
+                )));
+            }
+            Some(TAIL_NOT_READABLE) => {
+                return Err(CallToolError::from_message(format!(
+                    "File is not readable: {}",
+                    self.remote_path
+                )));
+            }
+            _ => {
+                return Err(CallToolError::from_message(format!(
+                    "Failed to tail {}: {}",
+                    self.remote_path,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+        }
+
+        // Long lines can still make the output large, so it's capped like
+        // that of commands
+        let (stdout, truncated) =
+            super::truncate_output(&output.stdout, super::DEFAULT_MAX_OUTPUT_BYTES);
+        let stdout = String::from_utf8_lossy(&stdout);
+        let tail = split_lines(&stdout);
+
+        let mut structured_content = serde_json::Map::new();
+        structured_content.insert("path".to_string(), self.remote_path.as_str().into());
+        structured_content.insert("count".to_string(), tail.len().into());
+        structured_content.insert("lines".to_string(), tail.into());
+        if truncated {
+            structured_content.insert("truncated".to_string(), true.into());
+        }
+
+        Ok(
+            CallToolResult::text_content(vec![TextContent::from(stdout.into_owned())])
+                .with_structured_content(structured_content),
+        )
+    }
+
+    /// Describe the tail, e.g. "tail -n 100 /var/log/syslog | grep -F error
+    /// on prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        let lines = self.lines().to_string();
+        let mut command =
+            super::command_line(&["tail", "-n", lines.as_str(), self.remote_path.as_str()]);
+        if let Some(filter) = self.grep_filter.as_deref().filter(|f| !f.is_empty()) {
+            command.push_str(" | ");
+            command.push_str(&super::command_line(&["grep", "-F", filter]));
+        }
+        format!(
+            "{} {}",
+            command,
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        )
+    }
+
+    /// The number of lines to return: lines, capped at MAX_LINES
+    fn lines(&self) -> u64 {
+        self.lines.unwrap_or(DEFAULT_LINES).min(MAX_LINES)
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// Split tail's output into lines, without their line endings.
+fn split_lines(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::tools::{TestPath, run_script};
+
+    #[test]
+    fn test_split_lines() {
+        assert_eq!(
+            split_lines("one\ntwo\r\nthree"),
+            vec!["one", "two", "three"]
+        );
+        assert_eq!(split_lines("one\n\nthree\n"), vec!["one", "", "three"]);
+        assert!(split_lines("").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_tail_script() {
+        let dir = TestPath::dir("tail_file");
+        let file = dir.join("app.log");
+        std::fs::write(&file, "start\nerror: one\nok\nerror: .*\nend\n").unwrap();
+        let tail = |path: &str, lines: &str, filter: &str| {
+            run_script(TAIL_SCRIPT, &[path, lines, filter], b"", Some(&dir))
+        };
+
+        let output = tail(file.to_str().unwrap(), "2", "");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"error: .*\nend\n");
+
+        // The filter is a fixed string, not a regular expression
+        let output = tail(file.to_str().unwrap(), "10", ".*");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"error: .*\n");
+
+        let output = tail(file.to_str().unwrap(), "10", "missing");
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+
+        let output = tail("/nonexistent/file", "10", "");
+        assert_eq!(output.status.code(), Some(TAIL_MISSING));
+
+        // A relative path looking like an option is still read as a path
+        std::fs::write(dir.join("-n"), "one\ntwo\n").unwrap();
+        assert_eq!(tail("-n", "1", "").stdout, b"two\n");
+    }
+
+    #[test]
+    fn test_describe_effective_command() {
+        let tool = TailFile {
+            remote_path: "/var/log/syslog".to_string(),
+            grep_filter: Some("out of memory".to_string()),
+            remote_host: "prod-db".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            tool.describe_effective_command(),
+            "tail -n 100 /var/log/syslog | grep -F 'out of memory' on prod-db"
+        );
+
+        let tool = TailFile {
+            lines: Some(1_000_000),
+            ..tool
+        };
+        assert!(
+            tool.describe_effective_command()
+                .starts_with("tail -n 10000 /var/log/syslog")
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use super::{CommonConnection, SshConnectionParams};
+use expand_tilde::expand_tilde;
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+use tokio::process::Command;
+
+/// Exit statuses of the checksum script
+const CHECKSUM_MISSING: i32 = 3;
+const CHECKSUM_NOT_READABLE: i32 = 4;
+const CHECKSUM_NO_TOOL: i32 = 5;
+
+/// Script printing the SHA-256 of the file $1 with the first of sha256sum
+/// (GNU coreutils, busybox), shasum (macOS, Perl) and openssl that is
+/// installed. The file is read from stdin so that its name never appears in
+/// the output, which is then only the digest and a placeholder for the name.
+/// The same script computes the local and the remote checksum.
+const CHECKSUM_SCRIPT: &str = r#"[ -f "$1" ] || exit 3
+[ -r "$1" ] || exit 4
+if command -v sha256sum >/dev/null 2>&1; then
+    sha256sum < "$1"
+elif command -v shasum >/dev/null 2>&1; then
+    shasum -a 256 < "$1"
+elif command -v openssl >/dev/null 2>&1; then
+    openssl dgst -sha256 < "$1"
+else
+    exit 5
+fi"#;
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "verify_checksum",
+        description = "Check that a file on a remote POSIX compatible system (Linux, BSD, macOS) has the same content as a local file, by comparing their SHA-256 checksums. \
+        Use this after copy_file to make sure the bytes arrived intact. \
+        The remote checksum is computed with sha256sum, shasum -a 256 or openssl dgst, whichever is installed. Returns match, local and remote.",
+        title = "Verify Checksum"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct VerifyChecksum {
+        /// The path of the file on the local machine.
+        pub local_path: String,
+        /// The path of the file on the remote machine.
+        pub remote_path: String,
+        /// The host holding the remote file. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for computing each checksum. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Number of times to retry when ssh fails to connect (exit status 255), such as on connection timeouts. A command that ran and exited with any other status is never retried. Defaults to 0.
+        pub retries: Option<u32>,
+        /// Delay in milliseconds before the first retry, doubled for each further retry. Defaults to 500.
+        pub retry_backoff_ms: Option<u64>,
+        /// Return the command that would be run on the remote host instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+impl VerifyChecksum {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        let timeout_seconds = super::timeout_seconds(self.timeout_seconds);
+        let local_path = expand_tilde(&self.local_path).map_err(|e| {
+            CallToolError::from_message(format!("Failed to expand local path: {}", e))
+        })?;
+        let local_path = local_path.to_string_lossy().into_owned();
+
+        let connection = self.connection();
+        let mut remote_cmd = super::ssh::ssh_command(
+            &connection,
+            "sh",
+            &["-c", CHECKSUM_SCRIPT, "sh", self.remote_path.as_str()],
+        )?;
+
+        if self.dry_run.unwrap_or(false) {
+            return Ok(super::dry_run_result(&remote_cmd, &self.remote_host, None));
+        }
+
+        let mut local_cmd = Command::new("sh");
+        local_cmd.args(["-c", CHECKSUM_SCRIPT, "sh", local_path.as_str()]);
+        let local = super::output_with_timeout(&mut local_cmd, timeout_seconds, false)
+            .await
+            .map_err(|e| {
+                CallToolError::from_message(format!("Failed to compute the local checksum: {}", e))
+            })?;
+        if local.timed_out {
+            return Err(CallToolError::from_message(format!(
+                "Computing the local checksum timed out after {} seconds",
+                timeout_seconds
+            )));
+        }
+        let local = checksum_from_output(&local.output, "local", &local_path)?;
+
+        let remote =
+            super::ssh::run_ssh(&connection, &mut remote_cmd, timeout_seconds, false).await?;
+        let remote = checksum_from_output(&remote, "remote", &self.remote_path)?;
+
+        let matches = local == remote;
+        let text = if matches {
+            format!("SHA-256 checksums match: {}", local)
+        } else {
+            format!(
+                "SHA-256 checksums differ: {} is {} locally, {} is {} on {}",
+                local_path, local, self.remote_path, remote, self.remote_host
+            )
+        };
+
+        let mut structured_content = serde_json::Map::new();
+        structured_content.insert("match".to_string(), matches.into());
+        structured_content.insert("local".to_string(), local.into());
+        structured_content.insert("remote".to_string(), remote.into());
+        Ok(CallToolResult::text_content(vec![TextContent::from(text)])
+            .with_structured_content(structured_content))
+    }
+
+    /// Describe the comparison, e.g. "compare the SHA-256 of ./app.tar.gz with
+    /// /srv/app.tar.gz on prod-db"
+    pub fn describe_effective_command(&self) -> String {
+        format!(
+            "compare the SHA-256 of {} with {} {}",
+            super::shell_quote(&self.local_path),
+            super::shell_quote(&self.remote_path),
+            super::on_host(&self.remote_host, self.jump_host.as_deref())
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        SshConnectionParams {
+            retries: self.retries.unwrap_or(0),
+            retry_backoff_ms: self.retry_backoff_ms.unwrap_or(500),
+            ..self.common_connection(&self.remote_host)
+        }
+    }
+}
+
+/// Read the checksum printed by the checksum script run on the `side`
+/// ("local" or "remote") copy of `path`, or the reason it failed.
+fn checksum_from_output(
+    output: &std::process::Output,
+    side: &str,
+    path: &str,
+) -> Result<String, CallToolError> {
+    match output.status.code() {
+        Some(0) => {}
+        Some(CHECKSUM_MISSING) => {
+            return Err(CallToolError::from_message(format!(
+                "File not found ({}): {}",
+                side, path
+            )));
+        }
+        Some(CHECKSUM_NOT_READABLE) => {
+            return Err(CallToolError::from_message(format!(
+                "File is not readable ({}): {}",
+                side, path
+            )));
+        }
+        Some(CHECKSUM_NO_TOOL) => {
+            return Err(CallToolError::from_message(format!(
+                "No SHA-256 program found on the {} machine: install sha256sum, shasum or openssl",
+                side
+            )));
+        }
+        _ => {
+            return Err(CallToolError::from_message(format!(
+                "Failed to compute the {} checksum of {}: {}",
+                side,
+                path,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+    }
+
+    parse_checksum(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+        CallToolError::from_message(format!(
+            "Unexpected output computing the {} checksum of {}: {}",
+            side,
+            path,
+            String::from_utf8_lossy(&output.stdout).trim()
+        ))
+    })
+}
+
+/// Find the SHA-256 digest in the output of sha256sum or shasum ("<digest>
+/// -") or openssl dgst ("SHA2-256(stdin)= <digest>"), in lowercase.
+fn parse_checksum(output: &str) -> Option<String> {
+    output
+        .split(|c: char| c.is_whitespace() || c == '=')
+        .find(|word| word.len() == 64 && word.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(str::to_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::tools::{TestPath, run_script};
+
+    /// SHA-256 of "hello\n"
+    const HELLO_SHA256: &str = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+
+    #[test]
+    fn test_parse_checksum() {
+        assert_eq!(
+            parse_checksum(&format!("{}  -\n", HELLO_SHA256)).as_deref(),
+            Some(HELLO_SHA256)
+        );
+        assert_eq!(
+            parse_checksum(&format!(
+                "SHA2-256(stdin)= {}\n",
+                HELLO_SHA256.to_uppercase()
+            ))
+            .as_deref(),
+            Some(HELLO_SHA256)
+        );
+        assert_eq!(
+            parse_checksum(&format!("(stdin)={}\n", HELLO_SHA256)).as_deref(),
+            Some(HELLO_SHA256)
+        );
+        assert_eq!(parse_checksum("sha256sum: -: I/O error\n"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_checksum_script() {
+        let file = TestPath::new("verify_checksum");
+        std::fs::write(&file, "hello\n").unwrap();
+        let checksum = |path: &str| run_script(CHECKSUM_SCRIPT, &[path], b"", None);
+
+        let output = checksum(file.to_str().unwrap());
+        assert_eq!(
+            checksum_from_output(&output, "local", "hello").unwrap(),
+            HELLO_SHA256
+        );
+
+        let output = checksum("/nonexistent/file");
+        assert_eq!(output.status.code(), Some(CHECKSUM_MISSING));
+        let error = checksum_from_output(&output, "remote", "/nonexistent/file").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("File not found (remote): /nonexistent/file")
+        );
+    }
+
+    #[test]
+    fn test_describe_effective_command() {
+        let tool = VerifyChecksum {
+            local_path: "./app.tar.gz".to_string(),
+            remote_path: "/srv/app releases/app.tar.gz".to_string(),
+            remote_host: "prod-db".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            tool.describe_effective_command(),
+            "compare the SHA-256 of ./app.tar.gz with '/srv/app releases/app.tar.gz' on prod-db"
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use super::{CommonConnection, SshConnectionParams};
+use rust_mcp_sdk::{
+    macros::{JsonSchema, mcp_tool},
+    schema::{CallToolResult, TextContent, schema_utils::CallToolError},
+};
+
+/// Script writing stdin to the file $1 atomically: the content goes to a
+/// temporary file in the same directory, which is renamed over the file once
+/// complete, so readers see either the old or the new content. The mode is $2,
+/// or else the existing file's mode (its target's, for a symlink), or 644 for
+/// a new file. Prints the size of the written file.
+const WRITE_SCRIPT: &str = r#"set -e
+tmp=$(mktemp "$(dirname -- "$1")/.mcp_write.XXXXXX")
+trap 'rm -f "$tmp"' EXIT
+cat > "$tmp"
+mode=$2
+if [ -z "$mode" ]; then
+    mode=$(stat -L -c %a -- "$1" 2>/dev/null || stat -L -f %Lp -- "$1" 2>/dev/null || echo 644)
+fi
+chmod -- "$mode" "$tmp"
+mv -f -- "$tmp" "$1"
+wc -c < "$1""#;
+
+super::ssh_tool! {
+    #[mcp_tool(
+        name = "write_file",
+        description = "Write content to a file on a remote POSIX compatible system (Linux, BSD, macOS) atomically. \
+        The content is written to a temporary file in the same directory, which then replaces the file, \
+        so the file is never left partially written. Use this instead of echo or tee to write configuration files.",
+        title = "Write File"
+    )]
+    #[derive(Debug, Default, ::serde::Serialize, ::serde::Deserialize, JsonSchema)]
+    pub struct WriteFile {
+        /// The content to write to the file.
+        pub content: String,
+        /// The path of the file on the remote machine. The directory must exist.
+        pub remote_path: String,
+        /// File mode in octal, e.g. 644 or 0600. Defaults to the mode of the existing file, or 644 for a new file.
+        pub mode: Option<String>,
+        /// The host to write the file on. Can be a host alias from ~/.ssh/config, a hostname, or an IP address.
+        pub remote_host: String,
+        /// Timeout in seconds for the command execution. Defaults to 30 seconds unless the server configures another default. Set to 0 to disable timeout, unless the server sets a maximum.
+        pub timeout_seconds: Option<u64>,
+        /// Return the command that would be run instead of running it. Defaults to false.
+        pub dry_run: Option<bool>,
+    }
+}
+
+impl WriteFile {
+    #[tracing::instrument(skip(self), fields(params = %super::redacted(self)))]
+    pub async fn call_tool(&self) -> Result<CallToolResult, CallToolError> {
+        write(
+            &self.connection(),
+            &self.remote_path,
+            self.mode.as_deref(),
+            &self.content,
+            self.timeout_seconds,
+            self.dry_run.unwrap_or(false),
+        )
+        .await
+    }
+
+    /// Describe the write, e.g. "replace /etc/app.conf on prod-db with 12
+    /// bytes of content, mode 600"
+    pub fn describe_effective_command(&self) -> String {
+        describe_write(
+            &self.connection(),
+            &self.remote_path,
+            self.mode.as_deref(),
+            &self.content,
+        )
+    }
+
+    fn connection(&self) -> SshConnectionParams {
+        self.common_connection(&self.remote_host)
+    }
+}
+
+/// Write `content` to `remote_path` on the connection's host atomically, with
+/// `mode` or else the existing file's mode. Shared by write_file and
+/// render_and_write.
+pub(crate) async fn write(
+    connection: &SshConnectionParams,
+    remote_path: &str,
+    mode: Option<&str>,
+    content: &str,
+    timeout_seconds: Option<u64>,
+    dry_run: bool,
+) -> Result<CallToolResult, CallToolError> {
+    let timeout_seconds = super::timeout_seconds(timeout_seconds);
+
+    let mode = mode.unwrap_or_default();
+    if !mode.is_empty() && !is_octal_mode(mode) {
+        return Err(CallToolError::from_message(format!(
+            "Invalid mode: {:?} (expected an octal mode such as 644)",
+            mode
+        )));
+    }
+
+    let mut cmd = super::ssh::ssh_command(
+        connection,
+        "sh",
+        &["-c", WRITE_SCRIPT, "sh", remote_path, mode],
+    )?;
+
+    if dry_run {
+        return Ok(super::dry_run_result(
+            &cmd,
+            &connection.remote_host,
+            Some(content),
+        ));
+    }
+
+    let output =
+        super::ssh::run_ssh_with_stdin(&mut cmd, content.as_bytes(), timeout_seconds).await?;
+    if !output.status.success() {
+        return Err(CallToolError::from_message(format!(
+            "Failed to write {}: {}",
+            remote_path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    // wc -c prints the size of the file that was written
+    let bytes = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<u64>()
+        .unwrap_or(content.len() as u64);
+
+    let mut structured_content = serde_json::Map::new();
+    structured_content.insert("path".to_string(), serde_json::Value::from(remote_path));
+    structured_content.insert("bytes".to_string(), serde_json::Value::from(bytes));
+
+    Ok(CallToolResult::text_content(vec![TextContent::from(format!(
+        "Wrote {} bytes to {}",
+        bytes, remote_path
+    ))])
+    .with_structured_content(structured_content))
+}
+
+/// Describe a [`write`] for the judge
+pub(crate) fn describe_write(
+    connection: &SshConnectionParams,
+    remote_path: &str,
+    mode: Option<&str>,
+    content: &str,
+) -> String {
+    let mut description = format!(
+        "replace {} {} with {} bytes of content",
+        super::shell_quote(remote_path),
+        super::on_host(&connection.remote_host, connection.jump_host.as_deref()),
+        content.len()
+    );
+    if let Some(mode) = mode.filter(|mode| !mode.is_empty()) {
+        description.push_str(&format!(", mode {}", mode));
+    }
+    description
+}
+
+/// Whether `mode` is a numeric file mode such as 644 or 0600.
+fn is_octal_mode(mode: &str) -> bool {
+    (3..=4).contains(&mode.len()) && mode.chars().all(|c| ('0'..='7').contains(&c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::tools::{TestPath, run_script};
+
+    #[test]
+    fn test_is_octal_mode() {
+        assert!(is_octal_mode("644"));
+        assert!(is_octal_mode("0600"));
+        assert!(!is_octal_mode("u+x"));
+        assert!(!is_octal_mode("888"));
+        assert!(!is_octal_mode("64"));
+        assert!(!is_octal_mode("644; rm"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_script() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TestPath::dir("write_file");
+        let path = dir.join("app.conf");
+        let write = |content: &str, mode: &str| {
+            run_script(
+                WRITE_SCRIPT,
+                &[path.to_str().unwrap(), mode],
+                content.as_bytes(),
+                None,
+            )
+        };
+
+        let output = write("key = value\n", "600");
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "12");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "key = value\n");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+
+        // Without a mode, the existing file's mode is kept
+        let output = write("key = other\n", "");
+        assert!(output.status.success());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "key = other\n");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+
+        // No temporary files are left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        // A relative path starting with - isn't taken for an option
+        let output = run_script(WRITE_SCRIPT, &["-app.conf", "640"], b"a = 1\n", Some(&dir));
+        assert!(output.status.success());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("-app.conf")).unwrap(),
+            "a = 1\n"
+        );
+
+        // A symlink gets its target's mode, not the link's own 777
+        let link = dir.join("link.conf");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+        let output = run_script(
+            WRITE_SCRIPT,
+            &[link.to_str().unwrap(), ""],
+            b"key = linked\n",
+            None,
+        );
+        assert!(output.status.success());
+        assert_eq!(std::fs::read_to_string(&link).unwrap(), "key = linked\n");
+        let mode = std::fs::metadata(&link).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+
+    #[tokio::test]
+    async fn test_write_file_rejects_invalid_mode() {
+        let tool = WriteFile {
+            content: "content".to_string(),
+            remote_path: "/etc/app.conf".to_string(),
+            mode: Some("u+rwx".to_string()),
+            remote_host: "example.com".to_string(),
+            ..Default::default()
+        };
+        let err = tool.call_tool().await.unwrap_err();
+        assert!(err.to_string().contains("Invalid mode"));
+    }
+}